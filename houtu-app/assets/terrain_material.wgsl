@@ -5,7 +5,9 @@
 #ifdef QUANTIZATION_BITS12
 struct Vertex {
     @location(0) compressed0: vec4<f32>,
-    // @location(1) compressed1: f32,
+    #ifdef HAS_VERTEX_NORMALS
+    @location(3) normal: vec3<f32>,
+    #endif
 };
 
 #else
@@ -13,6 +15,9 @@ struct Vertex {
     @location(0) position: vec4<f32>,
     @location(1) uv: vec2<f32>,
     @location(2) web_mercator_t: f32,
+    #ifdef HAS_VERTEX_NORMALS
+    @location(3) normal: vec3<f32>,
+    #endif
 };
 #endif
 struct VertexUniform {
@@ -32,6 +37,10 @@ struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) height: f32,
     @location(1) texture_coordinates: vec3<f32>,
+    @location(2) position_wc: vec3<f32>,
+    #ifdef HAS_VERTEX_NORMALS
+    @location(3) normal: vec3<f32>,
+    #endif
 };
 
 @vertex
@@ -61,6 +70,10 @@ fn vertex(in: Vertex) -> VertexOutput {
     out.position = clip_position;
     out.texture_coordinates = texture_coordinates;
     out.height = height;
+    out.position_wc = position;
+    #ifdef HAS_VERTEX_NORMALS
+    out.normal = in.normal;
+    #endif
     return out;
 }
 
@@ -68,6 +81,10 @@ fn vertex(in: Vertex) -> VertexOutput {
 struct FragmentInput {
     @location(0) height: f32,
     @location(1) texture_coordinates: vec3<f32>,
+    @location(2) position_wc: vec3<f32>,
+    #ifdef HAS_VERTEX_NORMALS
+    @location(3) normal: vec3<f32>,
+    #endif
 }
 
 struct TerrainMaterialUniform {
@@ -75,7 +92,8 @@ struct TerrainMaterialUniform {
     coordinate_rectangle: vec4<f32>,
     use_web_mercator_t: f32,
     alpha: f32,
-    apply_day_night_alpha: f32,
+    night_alpha: f32,
+    day_alpha: f32,
     brightness: f32,
     contrast: f32,
     hue: f32,
@@ -83,8 +101,13 @@ struct TerrainMaterialUniform {
     one_over_gamma: f32,
 };
 struct StateUniform {
-    texture_num: i32
- 
+    texture_num: i32,
+    time: f32,
+    water_mask_translation_and_scale: vec4<f32>,
+    camera_position: vec3<f32>,
+    lambert_diffuse_multiplier: f32,
+    sun_direction_wc: vec3<f32>,
+    vertex_shadow_darkness: f32,
 }
 
 @group(1) @binding(0) var texture_array: texture_2d_array<f32>;
@@ -92,10 +115,24 @@ struct StateUniform {
 @group(1) @binding(2) var<storage,read> terrain_material_uniforms: array<TerrainMaterialUniform>;
 @group(1) @binding(3) var<uniform> state_uniform: StateUniform;
 @group(1) @binding(4) var<uniform> vertex_uniform: VertexUniform;
+@group(1) @binding(5) var water_mask_texture: texture_2d<f32>;
+
+// WGS84椭球半径平方的倒数
+const ONE_OVER_RADII_SQUARED = vec3<f32>(2.458172257647332e-14, 2.458172257647332e-14, 2.4747391015697002e-14);
+const OCEAN_FREQUENCY: f32 = 800.0;
+const OCEAN_AMPLITUDE: f32 = 0.08;
+const OCEAN_SHININESS: f32 = 50.0;
+const OCEAN_SPECULAR_INTENSITY: f32 = 0.5;
 
 @fragment
 fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     var final_color = vec4<f32>(0.0);
+    let ellipsoid_normal = normalize(in.position_wc * ONE_OVER_RADII_SQUARED);
+    // 0表示白天，1表示夜晚，中间是晨昏线附近的过渡
+    var night_blend = 0.0;
+    #ifdef ENABLE_LIGHTING
+    night_blend = 1.0 - clamp(dot(ellipsoid_normal, state_uniform.sun_direction_wc) * 5.0, 0.0, 1.0);
+    #endif
     var i: i32 = 0;
     loop{
         if i >= state_uniform.texture_num {
@@ -118,12 +155,12 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
 
         var texture_night_alpha = 1.0;
         #ifdef APPLY_DAY_NIGHT_ALPHA
-        texture_night_alpha = u.apply_day_night_alpha;
+        texture_night_alpha = u.night_alpha;
         #endif
 
         var texture_day_alpha = 1.0;
         #ifdef APPLY_DAY_NIGHT_ALPHA
-        texture_day_alpha = u.apply_day_night_alpha;
+        texture_day_alpha = u.day_alpha;
         #endif
 
         var texture_brightness = 0.0;
@@ -159,6 +196,10 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
         alpha_multiplier = step(vec2<f32>(0.0), texture_coordinate_rectangle.zw - tile_texture_coordinates);
         texture_alpha = texture_alpha * alpha_multiplier.x * alpha_multiplier.y;
 
+        #ifdef APPLY_DAY_NIGHT_ALPHA
+        texture_alpha = texture_alpha * mix(texture_day_alpha, texture_night_alpha, night_blend);
+        #endif
+
         let translation = translation_and_scale.xy;
         let scale = translation_and_scale.zw;
         let texture_coordinates = tile_texture_coordinates * scale + translation;
@@ -190,10 +231,44 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
         final_color = vec4<f32>(out_color, max(out_alpha, 0.0));
         i++;
     }
+    #ifdef SHOW_REFLECTIVE_OCEAN
+    let water_mask_translation_and_scale = state_uniform.water_mask_translation_and_scale;
+    let water_mask_texture_coordinates = clamp(in.texture_coordinates.xy, vec2<f32>(0.0), vec2<f32>(1.0)) * water_mask_translation_and_scale.zw + water_mask_translation_and_scale.xy;
+    let mask = textureSample(water_mask_texture, my_sampler, water_mask_texture_coordinates).r;
+    if mask > 0.0 {
+        final_color = compute_water_color(in.position_wc, water_mask_texture_coordinates, final_color, mask);
+    }
+    #endif
+    #ifdef ENABLE_LIGHTING
+    let sun_direction = state_uniform.sun_direction_wc;
+    #ifdef HAS_VERTEX_NORMALS
+    let diffuse_intensity = clamp(max(dot(normalize(in.normal), sun_direction), 0.0) * state_uniform.lambert_diffuse_multiplier + state_uniform.vertex_shadow_darkness, 0.0, 1.0);
+    #else
+    let diffuse_intensity = clamp(max(dot(ellipsoid_normal, sun_direction), 0.0) * 5.0 + 0.3, 0.0, 1.0);
+    #endif
+    final_color = vec4<f32>(final_color.rgb * diffuse_intensity, final_color.a);
+    #endif
     return final_color;
     // return vec4<f32>(1.0);
 }
 
+// 用几组随时间移动的正弦波扰动椭球面法线，以相机为光源计算海面的高光
+fn compute_water_color(position_wc: vec3<f32>, texture_coordinates: vec2<f32>, image_color: vec4<f32>, mask: f32) -> vec4<f32> {
+    let normal = normalize(position_wc * ONE_OVER_RADII_SQUARED);
+    let east = normalize(vec3<f32>(-normal.y, normal.x, 0.0) + vec3<f32>(1e-6, 0.0, 0.0));
+    let north = cross(normal, east);
+
+    let time = state_uniform.time;
+    let p = texture_coordinates * OCEAN_FREQUENCY;
+    let slope_x = cos(p.x + time * 0.8) + 0.5 * cos((p.x + p.y) * 1.7 + time * 1.3);
+    let slope_y = cos(p.y * 1.3 - time * 0.6) + 0.5 * cos((p.y - p.x) * 2.3 + time * 1.1);
+    let wave_normal = normalize(normal + (east * slope_x + north * slope_y) * OCEAN_AMPLITUDE);
+
+    let to_eye = normalize(state_uniform.camera_position - position_wc);
+    let specular = pow(max(dot(reflect(-to_eye, wave_normal), to_eye), 0.0), OCEAN_SHININESS);
+    let color = image_color.rgb + vec3<f32>(specular * OCEAN_SPECULAR_INTENSITY);
+    return vec4<f32>(mix(image_color.rgb, color, mask), image_color.a);
+}
 fn czm_hue(rgb: vec3<f32>, adjustment: f32) -> vec3<f32> {
     let toYIQ = mat3x3(0.299, 0.587, 0.114, 0.595716, -0.274453, -0.321263, 0.211456, -0.522591, 0.311135);
     let toRGB = mat3x3(1.0, 0.9563, 0.6210, 1.0, -0.2721, -0.6474, 1.0, -1.107, 1.7046);
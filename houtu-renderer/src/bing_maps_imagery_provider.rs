@@ -1,8 +1,35 @@
-use houtu_scene::GeographicTilingScheme;
+use std::io;
 
-use super::quadtree::{credit::Credit, imagery_provider::ImageryProvider};
-#[derive(PartialEq)]
+use bevy::prelude::*;
+use houtu_jobs::{AsyncReturn, Context, Job};
+use houtu_scene::{Rectangle, TilingScheme, WebMercatorTilingScheme};
+use serde::Deserialize;
+
+use crate::{
+    quadtree::{
+        credit::Credit,
+        imagery_provider::{ImageryProvider, PickFeaturesJob},
+        tile_key::TileKey,
+    },
+    resource::{self, fetch_text},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Resource(#[from] resource::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("bing maps authentication failed: {0}")]
+    Authentication(String),
+    #[error("bing maps metadata does not contain any imagery resource")]
+    NoImageryMetadata,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum BingMapsStyle {
+    #[default]
     Aerial = 0,
     AerialWithLabels = 1,
     AerialWithLabelsOnDemand = 2,
@@ -14,18 +41,422 @@ pub enum BingMapsStyle {
     OrdnanceSurvey = 9,
     CollinsBart = 10,
 }
+impl BingMapsStyle {
+    /// 请求元数据时使用的imagerySet名称
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            Self::Aerial => "Aerial",
+            Self::AerialWithLabels => "AerialWithLabels",
+            Self::AerialWithLabelsOnDemand => "AerialWithLabelsOnDemand",
+            Self::Road => "Road",
+            Self::RoadOnDemand => "RoadOnDemand",
+            Self::CanvasDark => "CanvasDark",
+            Self::CanvasLight => "CanvasLight",
+            Self::CanvasGray => "CanvasGray",
+            Self::OrdnanceSurvey => "OrdnanceSurvey",
+            Self::CollinsBart => "CollinsBart",
+        };
+    }
+}
+/// [Imagery Metadata](https://learn.microsoft.com/en-us/bingmaps/rest-services/imagery/get-imagery-metadata)的响应
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BingMapsMetadata {
+    #[serde(default)]
+    pub authentication_result_code: Option<String>,
+    #[serde(default)]
+    pub brand_logo_uri: Option<String>,
+    #[serde(default)]
+    pub resource_sets: Vec<BingMapsResourceSet>,
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BingMapsResourceSet {
+    #[serde(default)]
+    pub resources: Vec<BingMapsImageryResource>,
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BingMapsImageryResource {
+    pub image_url: String,
+    #[serde(default)]
+    pub image_url_subdomains: Vec<String>,
+    #[serde(default = "default_tile_size")]
+    pub image_width: u32,
+    #[serde(default = "default_tile_size")]
+    pub image_height: u32,
+    #[serde(default)]
+    pub zoom_min: Option<u32>,
+    #[serde(default)]
+    pub zoom_max: Option<u32>,
+    #[serde(default)]
+    pub imagery_providers: Vec<BingMapsAttribution>,
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BingMapsAttribution {
+    pub attribution: String,
+    #[serde(default)]
+    pub coverage_areas: Vec<BingMapsCoverageArea>,
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BingMapsCoverageArea {
+    /// 南、西、北、东，单位是度
+    pub bbox: [f64; 4],
+    pub zoom_min: u32,
+    pub zoom_max: u32,
+}
+fn default_tile_size() -> u32 {
+    256
+}
+impl BingMapsMetadata {
+    pub fn from_str(json: &str) -> Result<Self, Error> {
+        return Ok(serde_json::from_str(json)?);
+    }
+}
+pub struct BingMapsImageryProviderOptions {
+    pub url: String,
+    pub key: String,
+    pub map_style: BingMapsStyle,
+    /// 标注的语言，比如`zh-Hans`
+    pub culture: String,
+}
+impl Default for BingMapsImageryProviderOptions {
+    fn default() -> Self {
+        Self {
+            url: "https://dev.virtualearth.net".to_string(),
+            key: "".to_string(),
+            map_style: BingMapsStyle::default(),
+            culture: "".to_string(),
+        }
+    }
+}
+/// 覆盖范围和层级范围内才显示的版权信息
+struct CoverageCredit {
+    credit: Credit,
+    /// 弧度
+    rectangle: Rectangle,
+    zoom_min: u32,
+    zoom_max: u32,
+}
+/// Bing Maps影像服务，瓦片用quadkey编号，版权信息随瓦片的位置和层级变化
 pub struct BingMapsImageryProvider {
-    pub tiling_scheme: GeographicTilingScheme,
+    pub tiling_scheme: Box<dyn TilingScheme>,
+    pub rectangle: Rectangle,
     pub map_style: BingMapsStyle,
+    pub culture: String,
+    /// Bing Maps的logo
     pub credit: Credit,
     pub tile_width: u32,
     pub tile_height: u32,
     pub maximum_level: u32,
     pub image_url_template: String,
-    pub image_url_subdomains: Vec<u8>,
+    pub image_url_subdomains: Vec<String>,
+    coverage_credits: Vec<CoverageCredit>,
+}
+impl BingMapsImageryProvider {
+    pub fn get_metadata_url(options: &BingMapsImageryProviderOptions) -> String {
+        let mut url = format!(
+            "{}/REST/v1/Imagery/Metadata/{}?incl=ImageryProviders&key={}&uriScheme=https",
+            options.url.trim_end_matches('/'),
+            options.map_style.as_str(),
+            options.key
+        );
+        if !options.culture.is_empty() {
+            url = format!("{}&culture={}", url, options.culture);
+        }
+        return url;
+    }
+    /// 读取元数据并创建影像服务，在bevy中一般用[`LoadBingMapsImageryJob`]
+    pub async fn from_url(options: BingMapsImageryProviderOptions) -> Result<Self, Error> {
+        let json = fetch_text(&Self::get_metadata_url(&options), &[]).await?;
+        return Self::from_metadata(&options, BingMapsMetadata::from_str(&json)?);
+    }
+    pub fn from_metadata(
+        options: &BingMapsImageryProviderOptions,
+        metadata: BingMapsMetadata,
+    ) -> Result<Self, Error> {
+        if let Some(code) = metadata.authentication_result_code.as_ref() {
+            if code != "ValidCredentials" {
+                return Err(Error::Authentication(code.clone()));
+            }
+        }
+        let resource = metadata
+            .resource_sets
+            .into_iter()
+            .flat_map(|x| x.resources)
+            .next()
+            .ok_or(Error::NoImageryMetadata)?;
+        // Bing Maps的第1级有2x2个瓦片，对应这里的第0级
+        let mut tiling_scheme = WebMercatorTilingScheme::default();
+        tiling_scheme.number_of_level_zero_tiles_x = 2;
+        tiling_scheme.number_of_level_zero_tiles_y = 2;
+        let rectangle = tiling_scheme.get_rectangle();
+        let coverage_credits = resource
+            .imagery_providers
+            .iter()
+            .flat_map(|provider| {
+                let credit = Credit::new(&provider.attribution);
+                provider
+                    .coverage_areas
+                    .iter()
+                    .map(move |area| CoverageCredit {
+                        credit: credit.clone(),
+                        rectangle: Rectangle::new(
+                            area.bbox[1].to_radians(),
+                            area.bbox[0].to_radians(),
+                            area.bbox[3].to_radians(),
+                            area.bbox[2].to_radians(),
+                        ),
+                        zoom_min: area.zoom_min,
+                        zoom_max: area.zoom_max,
+                    })
+            })
+            .collect();
+        let logo = metadata
+            .brand_logo_uri
+            .map(|x| format!("<img src=\"{}\" title=\"Bing Imagery\"/>", x))
+            .unwrap_or_default();
+        return Ok(Self {
+            tiling_scheme: Box::new(tiling_scheme),
+            rectangle: rectangle,
+            map_style: options.map_style,
+            culture: options.culture.clone(),
+            credit: Credit::new(&logo),
+            tile_width: resource.image_width,
+            tile_height: resource.image_height,
+            maximum_level: resource.zoom_max.unwrap_or(21).saturating_sub(1),
+            image_url_template: resource.image_url.replace("http://", "https://"),
+            image_url_subdomains: resource.image_url_subdomains,
+            coverage_credits: coverage_credits,
+        });
+    }
+    pub fn get_subdomain(&self, key: &TileKey) -> &str {
+        if self.image_url_subdomains.is_empty() {
+            return "";
+        }
+        let index = (key.x + key.y + key.level) as usize % self.image_url_subdomains.len();
+        return self.image_url_subdomains[index].as_str();
+    }
+    pub fn build_url(&self, key: &TileKey) -> String {
+        return self
+            .image_url_template
+            .replace("{quadkey}", &tile_x_y_to_quad_key(key.x, key.y, key.level))
+            .replace("{subdomain}", self.get_subdomain(key))
+            .replace("{culture}", &self.culture);
+    }
+}
+/// 瓦片坐标转为quadkey，第0级的quadkey有一位数字
+pub fn tile_x_y_to_quad_key(x: u32, y: u32, level: u32) -> String {
+    let mut quad_key = String::with_capacity(level as usize + 1);
+    for i in (0..=level).rev() {
+        let bitmask = 1 << i;
+        let mut digit = 0;
+        if x & bitmask != 0 {
+            digit |= 1;
+        }
+        if y & bitmask != 0 {
+            digit |= 2;
+        }
+        quad_key.push(char::from(b'0' + digit));
+    }
+    return quad_key;
 }
-impl BingMapsImageryProvider {}
+/// quadkey转为瓦片坐标，quadkey为空或者包含0到3以外的字符时返回`None`
+pub fn quad_key_to_tile_x_y(quad_key: &str) -> Option<TileKey> {
+    if quad_key.is_empty() {
+        return None;
+    }
+    let level = quad_key.len() as u32 - 1;
+    let mut x = 0;
+    let mut y = 0;
+    for (i, digit) in quad_key.bytes().enumerate() {
+        let bitmask = 1 << (level - i as u32);
+        match digit {
+            b'0' => {}
+            b'1' => x |= bitmask,
+            b'2' => y |= bitmask,
+            b'3' => {
+                x |= bitmask;
+                y |= bitmask;
+            }
+            _ => return None,
+        }
+    }
+    return Some(TileKey::new(x, y, level));
+}
+impl ImageryProvider for BingMapsImageryProvider {
+    fn get_maximum_level(&self) -> u32 {
+        self.maximum_level
+    }
+    fn get_minimum_level(&self) -> u32 {
+        0
+    }
+    fn get_ready(&self) -> bool {
+        true
+    }
+    fn get_rectangle(&self) -> &Rectangle {
+        &self.rectangle
+    }
+    fn get_tile_credits(&self, key: &TileKey) -> Option<Vec<Credit>> {
+        // Bing Maps的层级从1开始
+        let level = key.level + 1;
+        let rectangle = self
+            .tiling_scheme
+            .tile_x_y_to_rectange(key.x, key.y, key.level);
+        let mut credits: Vec<Credit> = vec![];
+        for coverage in self.coverage_credits.iter() {
+            if level < coverage.zoom_min || level > coverage.zoom_max {
+                continue;
+            }
+            if rectangle.simple_intersection(&coverage.rectangle).is_none() {
+                continue;
+            }
+            // 同一个版权信息可能有多个覆盖范围
+            if credits.iter().all(|x| x.id != coverage.credit.id) {
+                credits.push(coverage.credit.clone());
+            }
+        }
+        return Some(credits);
+    }
+    fn get_tile_height(&self) -> u32 {
+        self.tile_height
+    }
+    fn get_tile_width(&self) -> u32 {
+        self.tile_width
+    }
+    fn get_tiling_scheme(&self) -> &Box<dyn TilingScheme> {
+        &self.tiling_scheme
+    }
+    fn load_image(&self, _url: String) {}
+    fn pick_features(
+        &self,
+        _key: &TileKey,
+        _longitude: f64,
+        _latitude: f64,
+    ) -> Option<PickFeaturesJob> {
+        None
+    }
+    fn request_image(&self, key: &TileKey, asset_server: &AssetServer) -> Option<Handle<Image>> {
+        let url = self.build_url(key);
+        // 地址以`?g=...`结尾，bevy无法从中得到扩展名，在末尾加一个服务会忽略的参数
+        let Some((path, _)) = url.split_once('?') else {
+            return Some(asset_server.load(url));
+        };
+        let extension = path.rsplit_once('.').map(|x| x.1).unwrap_or("jpeg");
+        return Some(asset_server.load(format!("{}&.{}", url, extension)));
+    }
+}
+pub struct LoadBingMapsImageryJob {
+    pub options: BingMapsImageryProviderOptions,
+}
+impl Job for LoadBingMapsImageryJob {
+    type Outcome = Result<BingMapsImageryProvider, Error>;
+    fn name(&self) -> String {
+        format!("load bing maps metadata {}", self.options.map_style.as_str())
+    }
+    fn perform(self, _context: Context) -> AsyncReturn<Self::Outcome> {
+        Box::pin(async move {
+            let fetch = BingMapsImageryProvider::from_url(self.options);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(fetch)
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                fetch.await
+            }
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-// impl ImageryProvider for BingMapsImageryProvider {
-//     fn get_maximum_level(&self) -> u32 {}
-// }
+    fn create_provider() -> BingMapsImageryProvider {
+        let metadata =
+            BingMapsMetadata::from_str(include_str!("../assets/bing-maps-metadata.json")).unwrap();
+        let options = BingMapsImageryProviderOptions {
+            culture: "zh-Hans".to_string(),
+            ..Default::default()
+        };
+        return BingMapsImageryProvider::from_metadata(&options, metadata).unwrap();
+    }
+    fn credit_htmls(provider: &BingMapsImageryProvider, key: &TileKey) -> Vec<String> {
+        return provider
+            .get_tile_credits(key)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.html)
+            .collect();
+    }
+    #[test]
+    fn test_quad_key() {
+        assert!(tile_x_y_to_quad_key(0, 0, 0) == "0");
+        assert!(tile_x_y_to_quad_key(1, 1, 0) == "3");
+        // https://learn.microsoft.com/en-us/bingmaps/articles/bing-maps-tile-system 中的例子
+        assert!(tile_x_y_to_quad_key(3, 5, 2) == "213");
+        assert!(quad_key_to_tile_x_y("213") == Some(TileKey::new(3, 5, 2)));
+        for level in 0..5 {
+            let count = 2 << level;
+            for x in (0..count).step_by(3) {
+                for y in (0..count).step_by(5) {
+                    let quad_key = tile_x_y_to_quad_key(x, y, level);
+                    assert!(quad_key.len() == level as usize + 1);
+                    assert!(quad_key_to_tile_x_y(&quad_key) == Some(TileKey::new(x, y, level)));
+                }
+            }
+        }
+        assert!(quad_key_to_tile_x_y("").is_none());
+        assert!(quad_key_to_tile_x_y("014").is_none());
+    }
+    #[test]
+    fn test_metadata() {
+        let provider = create_provider();
+        assert!(provider.maximum_level == 19);
+        assert!(provider.tile_width == 256);
+        assert!(provider.image_url_subdomains.len() == 4);
+        assert!(provider.tiling_scheme.get_number_of_x_tiles_at_level(0) == 2);
+        assert!(provider.credit.html.contains("logo_powered_by.png"));
+        let url = provider.build_url(&TileKey::new(3, 5, 2));
+        assert!(url == "https://ecn.t2.tiles.virtualearth.net/tiles/a213.jpeg?g=13579&mkt=zh-Hans");
+    }
+    #[test]
+    fn test_authentication_failed() {
+        let metadata = BingMapsMetadata::from_str(
+            r#"{"authenticationResultCode":"InvalidCredentials","resourceSets":[]}"#,
+        )
+        .unwrap();
+        let result =
+            BingMapsImageryProvider::from_metadata(&BingMapsImageryProviderOptions::default(), metadata);
+        assert!(matches!(result, Err(Error::Authentication(_))));
+    }
+    #[test]
+    fn test_tile_credits() {
+        let provider = create_provider();
+        // 第0级对应Bing Maps的第1级
+        let credits = credit_htmls(&provider, &TileKey::new(0, 0, 0));
+        assert!(credits == vec!["© 2023 Microsoft Corporation", "© 2023 Earthstar Geographics SIO"]);
+        // 第13级(Bing Maps第14级)，阿拉斯加，落在Maxar的第二个覆盖范围内
+        let level = 13;
+        let position = houtu_scene::Cartographic::from_degrees(-150.0, 60.0, 0.0);
+        let tile = provider
+            .tiling_scheme
+            .position_to_tile_x_y(&position, level)
+            .unwrap();
+        let credits = credit_htmls(&provider, &TileKey::new(tile.x, tile.y, level));
+        assert!(credits == vec!["© 2023 Microsoft Corporation", "© 2023 Maxar"]);
+        // 同一层级的亚洲没有Maxar
+        let position = houtu_scene::Cartographic::from_degrees(110.0, 30.0, 0.0);
+        let tile = provider
+            .tiling_scheme
+            .position_to_tile_x_y(&position, level)
+            .unwrap();
+        let credits = credit_htmls(&provider, &TileKey::new(tile.x, tile.y, level));
+        assert!(credits == vec!["© 2023 Microsoft Corporation"]);
+    }
+}
@@ -0,0 +1,406 @@
+use std::f64::consts::FRAC_PI_2;
+
+use bevy::{
+    math::{DMat4, DVec2, DVec3},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use houtu_scene::{Cartographic, Ellipsoid, HeadingPitchRoll, Transforms, EPSILON3};
+
+use super::{
+    camera_event_aggregator::{ControlEvent, ControlEventData},
+    globe_camra::{GlobeCamera, LookAtTransformOffset, SetViewOrientation},
+};
+
+/// 当前使用的相机控制方式，运行时修改该资源即可切换
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraControllerMode {
+    /// 默认的地球漫游(pan_orbit_camera)
+    #[default]
+    Globe,
+    FirstPerson,
+    Orbit,
+    TopDown,
+}
+pub trait CameraController: Resource {
+    /// 切换到该模式时调用，从相机当前状态初始化
+    fn activate(&mut self, camera: &mut GlobeCamera);
+    fn handle_event(&mut self, camera: &mut GlobeCamera, event: &ControlEvent, window_size: &DVec2);
+    /// 每帧调用一次，把控制器状态写回相机
+    fn update(&mut self, camera: &mut GlobeCamera, delta_seconds: f64);
+}
+
+/// 第一人称：贴着地面行走，视点高度为地面高度加上碰撞高度
+#[derive(Resource, Debug, Clone)]
+pub struct FirstPersonController {
+    /// 视点离地高度(米)
+    pub collision_height: f64,
+    /// 脚下的地面高度，没有地形时为0
+    pub ground_height: f64,
+    /// 滚轮每单位前进的距离(米)
+    pub move_rate: f64,
+    /// 拖动每像素转动的角度(弧度)
+    pub look_factor: f64,
+    pub position: Cartographic,
+    pub heading: f64,
+    pub pitch: f64,
+}
+impl Default for FirstPersonController {
+    fn default() -> Self {
+        Self {
+            collision_height: 1.8,
+            ground_height: 0.0,
+            move_rate: 20.0,
+            look_factor: 0.002,
+            position: Cartographic::default(),
+            heading: 0.0,
+            pitch: 0.0,
+        }
+    }
+}
+impl CameraController for FirstPersonController {
+    fn activate(&mut self, camera: &mut GlobeCamera) {
+        self.position = camera.get_position_cartographic();
+        self.heading = camera.get_heading();
+        self.pitch = 0.0;
+    }
+    fn handle_event(
+        &mut self,
+        _camera: &mut GlobeCamera,
+        event: &ControlEvent,
+        _window_size: &DVec2,
+    ) {
+        match event {
+            ControlEvent::Spin(data) | ControlEvent::Look(data) => {
+                let delta = movement_delta(data);
+                self.heading += delta.x * self.look_factor;
+                self.pitch = clamp_pitch(self.pitch - delta.y * self.look_factor);
+            }
+            ControlEvent::Tilt(data) | ControlEvent::Translate(data) => {
+                // 拖动平移：上下前进后退，左右横移
+                let delta = movement_delta(data);
+                let forward = delta.y * self.move_rate * 0.1;
+                let right = -delta.x * self.move_rate * 0.1;
+                self.position = move_on_surface(&self.position, self.heading, forward, right);
+            }
+            ControlEvent::Zoom(data) => {
+                let forward = movement_delta(data).y * self.move_rate;
+                self.position = move_on_surface(&self.position, self.heading, forward, 0.0);
+            }
+        }
+    }
+    fn update(&mut self, camera: &mut GlobeCamera, _delta_seconds: f64) {
+        self.position.height = self.ground_height + self.collision_height;
+        set_camera_view(
+            camera,
+            &self.position,
+            HeadingPitchRoll::new(self.heading, self.pitch, 0.0),
+        );
+    }
+}
+
+/// 环绕一个固定的ECEF点观察
+#[derive(Resource, Debug, Clone)]
+pub struct OrbitController {
+    pub center: DVec3,
+    pub heading: f64,
+    pub pitch: f64,
+    /// 相机到中心点的距离(米)
+    pub range: f64,
+    pub minimum_range: f64,
+    /// 拖动每像素转动的角度(弧度)
+    pub rotate_factor: f64,
+    pub zoom_factor: f64,
+}
+impl Default for OrbitController {
+    fn default() -> Self {
+        Self {
+            center: DVec3::ZERO,
+            heading: 0.0,
+            pitch: -FRAC_PI_2 / 2.0,
+            range: 1000.0,
+            minimum_range: 1.0,
+            rotate_factor: 0.005,
+            zoom_factor: 0.5,
+        }
+    }
+}
+impl OrbitController {
+    pub fn new(center: DVec3, heading: f64, pitch: f64, range: f64) -> Self {
+        Self {
+            center,
+            heading,
+            pitch,
+            range,
+            ..Default::default()
+        }
+    }
+}
+impl CameraController for OrbitController {
+    fn activate(&mut self, camera: &mut GlobeCamera) {
+        // 未指定中心点时环绕屏幕中心对应的地面点
+        if self.center != DVec3::ZERO {
+            return;
+        }
+        let window_size = DVec2::new(camera.viewport.width, camera.viewport.height);
+        if let Some(center) = camera.pick_ellipsoid(&(window_size / 2.0), &window_size) {
+            self.range = center.distance(camera.get_position_wc());
+            self.center = center;
+            self.heading = camera.get_heading();
+        }
+    }
+    fn handle_event(
+        &mut self,
+        _camera: &mut GlobeCamera,
+        event: &ControlEvent,
+        _window_size: &DVec2,
+    ) {
+        match event {
+            ControlEvent::Spin(data) | ControlEvent::Tilt(data) | ControlEvent::Look(data) => {
+                let delta = movement_delta(data);
+                self.heading -= delta.x * self.rotate_factor;
+                self.pitch = clamp_pitch(self.pitch + delta.y * self.rotate_factor);
+            }
+            ControlEvent::Zoom(data) => {
+                self.range = zoom_distance(
+                    self.range,
+                    movement_delta(data).y * self.zoom_factor,
+                    self.minimum_range,
+                );
+            }
+            // 中心点固定，不能平移
+            ControlEvent::Translate(_) => {}
+        }
+    }
+    fn update(&mut self, camera: &mut GlobeCamera, _delta_seconds: f64) {
+        let transform = Transforms::eastNorthUpToFixedFrame(&self.center, None);
+        // offset里的roll作为range使用
+        camera.look_at_transform(
+            &transform,
+            Some(LookAtTransformOffset::HeadingPitchRoll(
+                HeadingPitchRoll::new(self.heading, self.pitch, self.range),
+            )),
+        );
+    }
+}
+
+/// 垂直向下看的二维平移缩放
+#[derive(Resource, Debug, Clone)]
+pub struct TopDownController {
+    pub position: Cartographic,
+    pub minimum_height: f64,
+    pub maximum_height: f64,
+    pub zoom_factor: f64,
+}
+impl Default for TopDownController {
+    fn default() -> Self {
+        Self {
+            position: Cartographic::default(),
+            minimum_height: 100.0,
+            maximum_height: 40000000.0,
+            zoom_factor: 0.5,
+        }
+    }
+}
+impl CameraController for TopDownController {
+    fn activate(&mut self, camera: &mut GlobeCamera) {
+        self.position = camera.get_position_cartographic();
+        self.position.height = self
+            .position
+            .height
+            .clamp(self.minimum_height, self.maximum_height);
+    }
+    fn handle_event(
+        &mut self,
+        camera: &mut GlobeCamera,
+        event: &ControlEvent,
+        window_size: &DVec2,
+    ) {
+        match event {
+            ControlEvent::Spin(data) | ControlEvent::Tilt(data) | ControlEvent::Translate(data) => {
+                // 地面跟着鼠标走
+                let delta = movement_delta(data);
+                let scale = meters_per_pixel(
+                    self.position.height,
+                    camera.frustum.get_fovy(),
+                    window_size.y,
+                );
+                let height = self.position.height;
+                self.position =
+                    move_on_surface(&self.position, 0.0, delta.y * scale, -delta.x * scale);
+                self.position.height = height;
+            }
+            ControlEvent::Zoom(data) => {
+                self.position.height = zoom_distance(
+                    self.position.height,
+                    movement_delta(data).y * self.zoom_factor,
+                    self.minimum_height,
+                )
+                .min(self.maximum_height);
+            }
+            ControlEvent::Look(_) => {}
+        }
+    }
+    fn update(&mut self, camera: &mut GlobeCamera, _delta_seconds: f64) {
+        set_camera_view(
+            camera,
+            &self.position,
+            HeadingPitchRoll::new(0.0, -FRAC_PI_2, 0.0),
+        );
+    }
+}
+
+fn movement_delta(data: &ControlEventData) -> DVec2 {
+    return data.movement.end_position - data.movement.start_position;
+}
+/// 避免正好垂直时heading无法确定
+fn clamp_pitch(pitch: f64) -> f64 {
+    return pitch.clamp(-FRAC_PI_2 + EPSILON3, FRAC_PI_2 - EPSILON3);
+}
+/// amount为正时靠近，每次最多缩短一半
+fn zoom_distance(distance: f64, amount: f64, minimum: f64) -> f64 {
+    let factor = 1.0 - amount.clamp(-1.0, 0.5);
+    return (distance * factor).max(minimum);
+}
+/// 透视投影下高度为height时，屏幕中心一个像素对应的地面距离
+pub fn meters_per_pixel(height: f64, fovy: f64, window_height: f64) -> f64 {
+    if window_height <= 0.0 {
+        return 0.0;
+    }
+    return 2.0 * height * (fovy / 2.0).tan() / window_height;
+}
+/// 在当地东北天坐标系中沿heading方向前进forward米、向右移动right米，保持高度不变
+pub fn move_on_surface(
+    position: &Cartographic,
+    heading: f64,
+    forward: f64,
+    right: f64,
+) -> Cartographic {
+    let ellipsoid = Ellipsoid::WGS84;
+    let origin = ellipsoid.cartographic_to_cartesian(position);
+    let enu = Transforms::eastNorthUpToFixedFrame(&origin, None);
+    let east = enu.col(0).truncate();
+    let north = enu.col(1).truncate();
+    let (sin, cos) = heading.sin_cos();
+    let offset = east * (forward * sin + right * cos) + north * (forward * cos - right * sin);
+    let Some(mut result) = ellipsoid.cartesian_to_cartographic(&(origin + offset)) else {
+        return position.clone();
+    };
+    result.height = position.height;
+    return result;
+}
+fn set_camera_view(camera: &mut GlobeCamera, position: &Cartographic, hpr: HeadingPitchRoll) {
+    let destination = Ellipsoid::WGS84.cartographic_to_cartesian(position);
+    camera.set_view(
+        Some(destination),
+        Some(SetViewOrientation::HeadingPitchRoll(hpr)),
+        Some(DMat4::IDENTITY),
+        None,
+    );
+}
+
+/// 模式切换时初始化新的控制器；离开环绕模式时恢复世界坐标系
+pub fn camera_controller_mode_system(
+    mode: Res<CameraControllerMode>,
+    mut first_person: ResMut<FirstPersonController>,
+    mut orbit: ResMut<OrbitController>,
+    mut top_down: ResMut<TopDownController>,
+    mut cameras: Query<&mut GlobeCamera>,
+) {
+    if !mode.is_changed() {
+        return;
+    }
+    for mut globe_camera in &mut cameras {
+        globe_camera._setTransform(&DMat4::IDENTITY);
+        globe_camera.cancel_flight();
+        match *mode {
+            CameraControllerMode::Globe => {}
+            CameraControllerMode::FirstPerson => first_person.activate(&mut globe_camera),
+            CameraControllerMode::Orbit => orbit.activate(&mut globe_camera),
+            CameraControllerMode::TopDown => top_down.activate(&mut globe_camera),
+        }
+    }
+}
+pub fn camera_controller_system<C: CameraController>(
+    time: Res<Time>,
+    primary_query: Query<&Window, With<PrimaryWindow>>,
+    mut controller: ResMut<C>,
+    mut control_event_reader: EventReader<ControlEvent>,
+    mut cameras: Query<(&mut GlobeCamera, &mut Transform)>,
+) {
+    let Ok(primary) = primary_query.get_single() else {
+        return;
+    };
+    let window_size = DVec2::new(primary.width() as f64, primary.height() as f64);
+    for (mut globe_camera, mut transform) in &mut cameras {
+        for event in control_event_reader.iter() {
+            controller.handle_event(&mut globe_camera, event, &window_size);
+        }
+        controller.update(&mut globe_camera, time.delta_seconds_f64());
+        globe_camera.update_camera_matrix(&mut transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use houtu_scene::{equals_epsilon, EPSILON6, EPSILON7};
+
+    use super::*;
+
+    #[test]
+    fn test_move_on_surface() {
+        let start = Cartographic::from_degrees(120.0, 30.0, 50.0);
+        let north = move_on_surface(&start, 0.0, 1000.0, 0.0);
+        assert!(north.latitude > start.latitude);
+        assert!(equals_epsilon(
+            north.longitude,
+            start.longitude,
+            None,
+            Some(EPSILON7)
+        ));
+        assert!(equals_epsilon(north.height, 50.0, None, Some(EPSILON6)));
+        // heading为90度时前进即向东
+        let east = move_on_surface(&start, FRAC_PI_2, 1000.0, 0.0);
+        assert!(east.longitude > start.longitude);
+        assert!(equals_epsilon(
+            east.latitude,
+            start.latitude,
+            None,
+            Some(EPSILON7)
+        ));
+        // 向右移动等价于heading加90度前进
+        let right = move_on_surface(&start, 0.0, 0.0, 1000.0);
+        assert!(equals_epsilon(
+            right.longitude,
+            east.longitude,
+            None,
+            Some(EPSILON7)
+        ));
+        assert!(equals_epsilon(
+            right.latitude,
+            east.latitude,
+            None,
+            Some(EPSILON7)
+        ));
+    }
+    #[test]
+    fn test_meters_per_pixel() {
+        // 90度视角时屏幕高度对应2倍高度
+        let value = meters_per_pixel(500.0, FRAC_PI_2, 1000.0);
+        assert!(equals_epsilon(value, 1.0, None, Some(EPSILON6)));
+        assert!(meters_per_pixel(500.0, FRAC_PI_2, 0.0) == 0.0);
+    }
+    #[test]
+    fn test_zoom_distance() {
+        assert!(zoom_distance(1000.0, 0.25, 1.0) == 750.0);
+        assert!(zoom_distance(1000.0, 10.0, 1.0) == 500.0);
+        assert!(zoom_distance(1000.0, -0.5, 1.0) == 1500.0);
+        assert!(zoom_distance(1.5, 0.5, 1.0) == 1.0);
+    }
+    #[test]
+    fn test_clamp_pitch() {
+        assert!(clamp_pitch(-10.0) > -FRAC_PI_2);
+        assert!(clamp_pitch(10.0) < FRAC_PI_2);
+        assert!(clamp_pitch(0.3) == 0.3);
+    }
+}
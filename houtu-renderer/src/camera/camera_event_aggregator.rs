@@ -3,6 +3,7 @@ use bevy::{
     input::{
         mouse::{MouseScrollUnit, MouseWheel},
         prelude::*,
+        touch::Touches,
     },
     math::DVec2,
     prelude::*,
@@ -14,11 +15,13 @@ use bevy_egui::EguiSet;
 use houtu_scene::{Cartesian2, EPSILON14};
 
 use super::egui::{self, EguiWantsFocus};
+use super::input_map::{CameraAction, CameraInput, CameraInputMap, InputBinding, KeyModifier};
 pub struct Plugin;
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ScreenSpaceEventHandlerPlugin);
         app.add_event::<ControlEvent>();
+        app.init_resource::<CameraInputMap>();
         app.add_systems(Update, (default_input_map, maintain_inertia_system));
         app.insert_resource(Aggregator::default());
 
@@ -32,7 +35,6 @@ impl bevy::app::Plugin for Plugin {
         app.insert_resource(ReleaseTimeWrap::default());
 
         app.insert_resource(MovementStateWrap::default());
-        app.add_systems(Startup, setup);
     }
 }
 
@@ -57,43 +59,42 @@ impl Movement {
     }
 }
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct UpdateWrap(HashMap<&'static str, bool>);
+pub struct UpdateWrap(HashMap<InputBinding, bool>);
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct IsDownWrap(HashMap<&'static str, bool>);
+pub struct IsDownWrap(HashMap<InputBinding, bool>);
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct EventStartPositionWrap(HashMap<&'static str, DVec2>);
+pub struct EventStartPositionWrap(HashMap<InputBinding, DVec2>);
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct MovementWrap(HashMap<&'static str, Movement>);
+pub struct MovementWrap(HashMap<InputBinding, Movement>);
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct LastMovementWrap(HashMap<&'static str, LastMovement>);
+pub struct LastMovementWrap(HashMap<InputBinding, LastMovement>);
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct PressTimetWrap(HashMap<&'static str, f64>);
+pub struct PressTimetWrap(HashMap<InputBinding, f64>);
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct ReleaseTimeWrap(HashMap<&'static str, f64>);
+pub struct ReleaseTimeWrap(HashMap<InputBinding, f64>);
 #[derive(Resource, Default)]
 pub struct Aggregator {
     _current_mouse_position: DVec2,
     _buttons_down: u32,
     _event_start_position: DVec2,
+    /// 按下时的修饰键，松开时用来找到对应的绑定
+    _down_bindings: HashMap<CameraInput, InputBinding>,
 }
 impl Aggregator {
     pub fn get_start_mouse_position(
         &self,
-        type_name: &'static str,
+        binding: &InputBinding,
         event_start_position_wrap: &EventStartPositionWrap,
     ) -> DVec2 {
-        if type_name == WHEEL {
+        if binding.input == CameraInput::Wheel {
             return self._current_mouse_position.clone();
         }
-        return event_start_position_wrap.get(type_name).unwrap().clone();
+        return event_start_position_wrap
+            .get(binding)
+            .cloned()
+            .unwrap_or(self._current_mouse_position);
     }
 }
-const WHEEL: &'static str = "WHEEL";
-const LEFT_DRAG: &'static str = "LEFT_DRAG";
-const RIGHT_DRAG: &'static str = "RIGHT_DRAG";
-const MIDDLE_DRAG: &'static str = "MIDDLE_DRAG";
-const PINCH: &'static str = "PINCH";
-const CAMERA_EVENT_TYPE: [&'static str; 4] = [WHEEL, LEFT_DRAG, RIGHT_DRAG, MIDDLE_DRAG];
 #[derive(Debug)]
 pub struct ControlEventData {
     pub movement: MovementState,
@@ -105,18 +106,75 @@ pub enum ControlEvent {
     Tilt(ControlEventData),
     Spin(ControlEventData),
     Zoom(ControlEventData),
+    Translate(ControlEventData),
+    Look(ControlEventData),
 }
-fn setup(
-    mut update_wrap: ResMut<UpdateWrap>,
-    // mut movement_wrap: ResMut<MovementWrap>,
-    // mut last_movement_wrap: ResMut<LastMovementWrap>,
+impl ControlEvent {
+    pub fn new(action: CameraAction, data: ControlEventData) -> Self {
+        return match action {
+            CameraAction::Rotate => ControlEvent::Spin(data),
+            CameraAction::Translate => ControlEvent::Translate(data),
+            CameraAction::Zoom => ControlEvent::Zoom(data),
+            CameraAction::Tilt => ControlEvent::Tilt(data),
+            CameraAction::Look => ControlEvent::Look(data),
+        };
+    }
+}
+fn press(
+    binding: InputBinding,
+    position: &DVec2,
+    cur_time: f64,
+    aggregator: &mut Aggregator,
+    is_down_wrap: &mut IsDownWrap,
+    event_start_position_wrap: &mut EventStartPositionWrap,
+    last_movement_wrap: &mut LastMovementWrap,
+    press_time_wrap: &mut PressTimetWrap,
 ) {
-    for type_name in CAMERA_EVENT_TYPE {
-        update_wrap.insert(type_name, true);
+    last_movement_wrap.entry(binding).or_default().valid = false;
+    aggregator._buttons_down += 1;
+    aggregator._down_bindings.insert(binding.input, binding);
+    is_down_wrap.insert(binding, true);
+    press_time_wrap.insert(binding, cur_time);
+    event_start_position_wrap.insert(binding, position.clone());
+}
+fn release(
+    input: CameraInput,
+    cur_time: f64,
+    aggregator: &mut Aggregator,
+    is_down_wrap: &mut IsDownWrap,
+    release_time_wrap: &mut ReleaseTimeWrap,
+) {
+    let Some(binding) = aggregator._down_bindings.remove(&input) else {
+        return;
+    };
+    aggregator._buttons_down = aggregator._buttons_down.saturating_sub(1);
+    is_down_wrap.insert(binding, false);
+    release_time_wrap.insert(binding, cur_time);
+}
+fn update_movement(
+    binding: InputBinding,
+    event: &Movement,
+    update_wrap: &mut UpdateWrap,
+    movement_wrap: &mut MovementWrap,
+    last_movement_wrap: &mut LastMovementWrap,
+) {
+    let movement = movement_wrap.entry(binding).or_default();
+    let last_movement = last_movement_wrap.entry(binding).or_default();
+    if update_wrap.get(&binding) == Some(&false) {
+        movement.end_position = event.end_position.clone();
+    } else {
+        last_movement.start_position = movement.start_position.clone();
+        last_movement.end_position = movement.end_position.clone();
+        last_movement.valid = true;
+
+        movement.start_position = event.start_position.clone();
+        movement.end_position = event.end_position.clone();
+        update_wrap.insert(binding, false);
     }
 }
 pub fn default_input_map(
     time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
     mut update_wrap: ResMut<UpdateWrap>,
     mut is_down_wrap: ResMut<IsDownWrap>,
     mut event_start_position_wrap: ResMut<EventStartPositionWrap>,
@@ -128,135 +186,87 @@ pub fn default_input_map(
     mut mouse_event_reader: EventReader<MouseEvent>,
 ) {
     let cur_time = time.elapsed_seconds_f64();
+    let modifier = KeyModifier::current(&keys);
     for event in mouse_event_reader.iter() {
-        match event {
+        let (input, position) = match event {
             MouseEvent::Wheel(delta) => {
-                update_wrap.insert(WHEEL, true);
-                let movement = match movement_wrap.get_mut(WHEEL) {
-                    None => {
-                        let v = Movement::default();
-                        movement_wrap.insert(WHEEL, v);
-                        movement_wrap.get_mut(WHEEL).unwrap()
-                    }
-                    Some(v) => v,
-                };
-                let last_movement = match last_movement_wrap.get_mut(WHEEL) {
-                    None => {
-                        let v = LastMovement::default();
-                        last_movement_wrap.insert(WHEEL, v);
-                        last_movement_wrap.get_mut(WHEEL).unwrap()
-                    }
-                    Some(v) => v,
+                let binding = InputBinding {
+                    input: CameraInput::Wheel,
+                    modifier,
                 };
+                let movement = movement_wrap.entry(binding).or_default();
+                let last_movement = last_movement_wrap.entry(binding).or_default();
                 let arc_length = 7.5 * delta.to_radians();
-                press_time_wrap.insert(WHEEL, cur_time);
-                release_time_wrap.insert(WHEEL, cur_time);
+                press_time_wrap.insert(binding, cur_time);
+                release_time_wrap.insert(binding, cur_time);
                 movement.end_position.x = 0.0;
                 movement.end_position.y = arc_length;
                 last_movement.end_position = movement.end_position.clone();
                 last_movement.valid = true;
-                update_wrap.insert(WHEEL, false);
+                update_wrap.insert(binding, false);
+                continue;
             }
             MouseEvent::MouseMove(mouse_movemet_event) => {
-                for type_name in CAMERA_EVENT_TYPE {
-                    let movement = match movement_wrap.get_mut(type_name) {
-                        None => {
-                            let v = Movement::default();
-                            movement_wrap.insert(type_name, v);
-                            movement_wrap.get_mut(type_name).unwrap()
-                        }
-                        Some(v) => v,
-                    };
-                    let last_movement = match last_movement_wrap.get_mut(type_name) {
-                        None => {
-                            let v = LastMovement::default();
-                            last_movement_wrap.insert(type_name, v);
-                            last_movement_wrap.get_mut(type_name).unwrap()
-                        }
-                        Some(v) => v,
-                    };
-                    if let Some(v) = is_down_wrap.get(type_name) {
-                        if !v {
-                            continue;
-                        }
-                        if !update_wrap.get(type_name).unwrap() {
-                            movement.end_position = mouse_movemet_event.end_position.clone();
-                        } else {
-                            last_movement.start_position = movement.start_position.clone();
-                            last_movement.end_position = movement.end_position.clone();
-                            last_movement.valid = true;
-
-                            movement.start_position = mouse_movemet_event.start_position.clone();
-                            movement.end_position = mouse_movemet_event.end_position.clone();
-                            update_wrap.insert(type_name, false);
-                        }
-                    }
+                let down: Vec<InputBinding> = aggregator
+                    ._down_bindings
+                    .values()
+                    .filter(|v| v.input != CameraInput::Pinch)
+                    .cloned()
+                    .collect();
+                for binding in down {
+                    update_movement(
+                        binding,
+                        mouse_movemet_event,
+                        &mut update_wrap,
+                        &mut movement_wrap,
+                        &mut last_movement_wrap,
+                    );
                 }
                 aggregator._current_mouse_position = mouse_movemet_event.end_position.clone();
+                continue;
             }
-            MouseEvent::LeftDown(p) => {
-                let last_movement = match last_movement_wrap.get_mut(LEFT_DRAG) {
-                    None => {
-                        let v = LastMovement::default();
-                        last_movement_wrap.insert(LEFT_DRAG, v);
-                        last_movement_wrap.get_mut(LEFT_DRAG).unwrap()
-                    }
-                    Some(v) => v,
-                };
-                aggregator._buttons_down += 1;
-                last_movement.valid = false;
-                is_down_wrap.insert(LEFT_DRAG, true);
-                press_time_wrap.insert(LEFT_DRAG, cur_time);
-                event_start_position_wrap.insert(LEFT_DRAG, p.clone());
-                // println!("left down");
-            }
-            MouseEvent::LeftUp(_p) => {
-                aggregator._buttons_down = (aggregator._buttons_down - 1).max(0);
-                is_down_wrap.insert(LEFT_DRAG, false);
-                release_time_wrap.insert(LEFT_DRAG, cur_time);
-                // println!("left up");
-            }
-            MouseEvent::RightDown(p) => {
-                let last_movement = match last_movement_wrap.get_mut(RIGHT_DRAG) {
-                    None => {
-                        let v = LastMovement::default();
-                        last_movement_wrap.insert(RIGHT_DRAG, v);
-                        last_movement_wrap.get_mut(RIGHT_DRAG).unwrap()
-                    }
-                    Some(v) => v,
-                };
-                aggregator._buttons_down += 1;
-                last_movement.valid = false;
-                is_down_wrap.insert(RIGHT_DRAG, true);
-                press_time_wrap.insert(RIGHT_DRAG, cur_time);
-                event_start_position_wrap.insert(RIGHT_DRAG, p.clone());
-            }
-            MouseEvent::RightUp(_p) => {
-                aggregator._buttons_down = (aggregator._buttons_down - 1).max(0);
-                is_down_wrap.insert(RIGHT_DRAG, false);
-                release_time_wrap.insert(RIGHT_DRAG, cur_time);
-            }
-            MouseEvent::MiddleDown(p) => {
-                let last_movement = match last_movement_wrap.get_mut(MIDDLE_DRAG) {
-                    None => {
-                        let v = LastMovement::default();
-                        last_movement_wrap.insert(MIDDLE_DRAG, v);
-                        last_movement_wrap.get_mut(MIDDLE_DRAG).unwrap()
-                    }
-                    Some(v) => v,
-                };
-                aggregator._buttons_down += 1;
-                last_movement.valid = false;
-                is_down_wrap.insert(MIDDLE_DRAG, true);
-                press_time_wrap.insert(MIDDLE_DRAG, cur_time);
-                event_start_position_wrap.insert(MIDDLE_DRAG, p.clone());
-            }
-            MouseEvent::MiddleUp(_p) => {
-                aggregator._buttons_down = (aggregator._buttons_down - 1).max(0);
-                is_down_wrap.insert(MIDDLE_DRAG, false);
-                release_time_wrap.insert(MIDDLE_DRAG, cur_time);
+            MouseEvent::PinchMove(movement) => {
+                if let Some(binding) = aggregator._down_bindings.get(&CameraInput::Pinch).cloned() {
+                    update_movement(
+                        binding,
+                        movement,
+                        &mut update_wrap,
+                        &mut movement_wrap,
+                        &mut last_movement_wrap,
+                    );
+                }
+                continue;
             }
-            _ => {}
+            MouseEvent::LeftDown(p) => (CameraInput::LeftDrag, Some(p)),
+            MouseEvent::RightDown(p) => (CameraInput::RightDrag, Some(p)),
+            MouseEvent::MiddleDown(p) => (CameraInput::MiddleDrag, Some(p)),
+            MouseEvent::PinchStart(p) => (CameraInput::Pinch, Some(p)),
+            MouseEvent::LeftUp(_p) => (CameraInput::LeftDrag, None),
+            MouseEvent::RightUp(_p) => (CameraInput::RightDrag, None),
+            MouseEvent::MiddleUp(_p) => (CameraInput::MiddleDrag, None),
+            MouseEvent::PinchEnd(_movement) => (CameraInput::Pinch, None),
+            _ => continue,
+        };
+        if let Some(position) = position {
+            let binding = InputBinding { input, modifier };
+            press(
+                binding,
+                position,
+                cur_time,
+                &mut aggregator,
+                &mut is_down_wrap,
+                &mut event_start_position_wrap,
+                &mut last_movement_wrap,
+                &mut press_time_wrap,
+            );
+        } else {
+            release(
+                input,
+                cur_time,
+                &mut aggregator,
+                &mut is_down_wrap,
+                &mut release_time_wrap,
+            );
         }
     }
 }
@@ -268,7 +278,7 @@ pub struct MovementState {
     pub inertiaEnabled: bool,
 }
 #[derive(Default, Debug, Resource, Deref, DerefMut)]
-pub struct MovementStateWrap(HashMap<&'static str, MovementState>);
+pub struct MovementStateWrap(HashMap<CameraAction, MovementState>);
 
 pub fn maintain_inertia_system(
     mut control_event_writer: EventWriter<ControlEvent>,
@@ -279,138 +289,73 @@ pub fn maintain_inertia_system(
     press_time_wrap: ResMut<PressTimetWrap>,
     release_time_wrap: ResMut<ReleaseTimeWrap>,
     aggregator: ResMut<Aggregator>,
-    _mouse_event_reader: EventReader<MouseEvent>,
+    input_map: Res<CameraInputMap>,
     mut movement_state_wrap: ResMut<MovementStateWrap>,
     is_down_wrap: ResMut<IsDownWrap>,
     time: Res<Time>,
 ) {
-    for type_name in CAMERA_EVENT_TYPE {
+    for binding in input_map.bindings() {
+        let Some(action) = input_map.action_for(&binding) else {
+            continue;
+        };
         //isMoving
-        if !update_wrap.get(type_name).unwrap() {
+        if update_wrap.get(&binding) == Some(&false) {
             let start_position =
-                aggregator.get_start_mouse_position(type_name, &event_start_position_wrap);
-            if let Some(movement) = movement_wrap.get(type_name) {
-                match type_name {
-                    WHEEL => {
-                        control_event_writer.send(ControlEvent::Zoom(ControlEventData {
-                            movement: movement.into_state(false),
-                            start_position: start_position,
-                        }));
-                        activate_inertia(&mut movement_state_wrap, "_lastInertiaZoomMovement");
-                    }
-                    LEFT_DRAG => {
-                        control_event_writer.send(ControlEvent::Spin(ControlEventData {
-                            movement: movement.into_state(false),
-                            start_position: start_position,
-                        }));
-                        activate_inertia(&mut movement_state_wrap, "_lastInertiaSpinMovement");
-                    }
-                    MIDDLE_DRAG => {
-                        control_event_writer.send(ControlEvent::Tilt(ControlEventData {
-                            movement: movement.into_state(false),
-                            start_position: start_position,
-                        }));
-                        activate_inertia(&mut movement_state_wrap, "_lastInertiaTiltMovement");
-                    }
-                    _ => {}
-                }
+                aggregator.get_start_mouse_position(&binding, &event_start_position_wrap);
+            if let Some(movement) = movement_wrap.get(&binding) {
+                control_event_writer.send(ControlEvent::new(
+                    action,
+                    ControlEventData {
+                        movement: movement.into_state(false),
+                        start_position: start_position,
+                    },
+                ));
+                activate_inertia(&mut movement_state_wrap, action);
             }
-        } else {
-            match type_name {
-                WHEEL => {
-                    if maintain_inertia(
-                        &mut movement_state_wrap,
-                        type_name,
-                        "_lastInertiaZoomMovement",
-                        0.9,
-                        &press_time_wrap,
-                        &release_time_wrap,
-                        &last_movement_wrap,
-                        &is_down_wrap,
-                        &time,
-                    ) {
-                        let start_position = aggregator
-                            .get_start_mouse_position(type_name, &event_start_position_wrap);
-                        let movement = movement_state_wrap.get("_lastInertiaZoomMovement").unwrap();
-                        control_event_writer.send(ControlEvent::Zoom(ControlEventData {
-                            movement: movement.clone(),
-                            start_position: start_position,
-                        }))
-                    }
-                }
-                LEFT_DRAG => {
-                    if maintain_inertia(
-                        &mut movement_state_wrap,
-                        type_name,
-                        "_lastInertiaSpinMovement",
-                        0.9,
-                        &press_time_wrap,
-                        &release_time_wrap,
-                        &last_movement_wrap,
-                        &is_down_wrap,
-                        &time,
-                    ) {
-                        // info!("start spin inertia");
-                        let start_position = aggregator
-                            .get_start_mouse_position(type_name, &event_start_position_wrap);
-                        let movement = movement_state_wrap.get("_lastInertiaSpinMovement").unwrap();
-                        control_event_writer.send(ControlEvent::Spin(ControlEventData {
-                            movement: movement.clone(),
-                            start_position: start_position,
-                        }))
-                    }
-                }
-                MIDDLE_DRAG => {
-                    if maintain_inertia(
-                        &mut movement_state_wrap,
-                        type_name,
-                        "_lastInertiaTiltMovement",
-                        0.9,
-                        &press_time_wrap,
-                        &release_time_wrap,
-                        &last_movement_wrap,
-                        &is_down_wrap,
-                        &time,
-                    ) {
-                        let start_position = aggregator
-                            .get_start_mouse_position(type_name, &event_start_position_wrap);
-                        let movement = movement_state_wrap.get("_lastInertiaTiltMovement").unwrap();
-                        control_event_writer.send(ControlEvent::Tilt(ControlEventData {
-                            movement: movement.clone(),
-                            start_position: start_position,
-                        }))
-                    }
-                }
-                _ => {}
+        } else if action != CameraAction::Look {
+            // look没有惯性
+            if maintain_inertia(
+                &mut movement_state_wrap,
+                &binding,
+                action,
+                0.9,
+                &press_time_wrap,
+                &release_time_wrap,
+                &last_movement_wrap,
+                &is_down_wrap,
+                &time,
+            ) {
+                let start_position =
+                    aggregator.get_start_mouse_position(&binding, &event_start_position_wrap);
+                let movement = movement_state_wrap.get(&action).unwrap();
+                control_event_writer.send(ControlEvent::new(
+                    action,
+                    ControlEventData {
+                        movement: movement.clone(),
+                        start_position: start_position,
+                    },
+                ))
             }
         }
         //重置状态
-        update_wrap.insert(type_name, true);
+        update_wrap.insert(binding, true);
     }
 }
-fn activate_inertia(
-    movement_state_wrap: &mut ResMut<MovementStateWrap>,
-    last_movement_name: &'static str,
-) {
-    if let Some(movement_state) = movement_state_wrap.get_mut(last_movement_name) {
+fn activate_inertia(movement_state_wrap: &mut ResMut<MovementStateWrap>, action: CameraAction) {
+    if let Some(movement_state) = movement_state_wrap.get_mut(&action) {
         movement_state.inertiaEnabled = true;
     }
-    let last_movement_name_list: Vec<&'static str>;
-    if last_movement_name == "_lastInertiaZoomMovement" {
-        last_movement_name_list = [
-            "_lastInertiaSpinMovement",
-            "_lastInertiaTranslateMovement",
-            "_lastInertiaTiltMovement",
-        ]
-        .into();
-    } else if last_movement_name == "_lastInertiaTiltMovement" {
-        last_movement_name_list =
-            ["_lastInertiaSpinMovement", "_lastInertiaTranslateMovement"].into();
-    } else {
-        last_movement_name_list = Vec::new();
-    }
-    for last_movement_name in last_movement_name_list {
-        if let Some(movement_state) = movement_state_wrap.get_mut(last_movement_name) {
+    let disabled_actions: &[CameraAction] = match action {
+        CameraAction::Zoom => &[
+            CameraAction::Rotate,
+            CameraAction::Translate,
+            CameraAction::Tilt,
+        ],
+        CameraAction::Tilt => &[CameraAction::Rotate, CameraAction::Translate],
+        _ => &[],
+    };
+    for disabled_action in disabled_actions {
+        if let Some(movement_state) = movement_state_wrap.get_mut(disabled_action) {
             movement_state.inertiaEnabled = false;
         }
     }
@@ -418,8 +363,8 @@ fn activate_inertia(
 const INERTIA_MAX_CLICK_TIME_THRESHOLD: f64 = 0.4;
 fn maintain_inertia(
     movement_state_wrap: &mut ResMut<MovementStateWrap>,
-    type_name: &'static str,
-    last_movement_name: &'static str,
+    binding: &InputBinding,
+    action: CameraAction,
     inertia_constant: f64,
     press_time_wrap: &ResMut<PressTimetWrap>,
     release_time_wrap: &ResMut<ReleaseTimeWrap>,
@@ -427,17 +372,10 @@ fn maintain_inertia(
     is_down_wrap: &ResMut<IsDownWrap>,
     time: &Res<Time>,
 ) -> bool {
-    let movement_state = match movement_state_wrap.get_mut(last_movement_name) {
-        None => {
-            let v = MovementState::default();
-            movement_state_wrap.insert(last_movement_name, v);
-            movement_state_wrap.get_mut(last_movement_name).unwrap()
-        }
-        Some(v) => v,
-    };
+    let movement_state = movement_state_wrap.entry(action).or_default();
 
-    let ts = press_time_wrap.get(type_name);
-    let tr = release_time_wrap.get(type_name);
+    let ts = press_time_wrap.get(binding);
+    let tr = release_time_wrap.get(binding);
     if ts.is_none() || tr.is_none() {
         return false;
     }
@@ -445,20 +383,14 @@ fn maintain_inertia(
     let tr = tr.unwrap();
 
     let threshold = tr - ts;
-    // if last_movement_name=="_lastInertiaSpinMovement"{
-    //     info!("threshold {}",threshold);
-    // }
     //如果按键释放事件和点击事件之间的时间差在0.4秒内才会保持惯性，滚轮缩放时，阈值=0，所以会保持惯性，而spin和tilt大于0.4，一般不会保持惯性，除非很快的拉动地球才会。
     if threshold < INERTIA_MAX_CLICK_TIME_THRESHOLD {
         let now = time.elapsed_seconds_f64();
         //随时间增加，从1无限接近于0
         let d = decay(now - tr, inertia_constant);
 
-        let last_movement = last_movement_wrap.get(type_name);
+        let last_movement = last_movement_wrap.get(binding);
         if last_movement.is_none() || !movement_state.inertiaEnabled {
-            // if last_movement_name=="_lastInertiaSpinMovement"{
-            //     info!("spin inertia over 0");
-            // }
             return false;
         }
         let last_movement = last_movement.unwrap();
@@ -467,9 +399,6 @@ fn maintain_inertia(
             Some(EPSILON14),
             None,
         ) {
-            // if last_movement_name=="_lastInertiaSpinMovement"{
-            //     info!("spin inertia over 1");
-            // }
             return false;
         }
         //不清楚为什么乘以0.5,可能想减小动作幅度
@@ -478,13 +407,6 @@ fn maintain_inertia(
         motion.y = (last_movement.end_position.y - last_movement.start_position.y) * 0.5;
 
         movement_state.start_position = last_movement.start_position.clone();
-        // println!(
-        //     "startPositin={:?},end_position={:?},motion={:?}",
-        //     last_movement.start_position.clone(),
-        //     last_movement.end_position.clone(),
-        //     motion.clone() * d
-        // );
-        // movement_state.end_position = motion * (d as f64);
         movement_state.end_position =
             movement_state.start_position + motion.multiply_by_scalar(d as f64);
 
@@ -498,12 +420,9 @@ fn maintain_inertia(
                 .distance(movement_state.end_position)
                 < 0.5
         {
-            // if last_movement_name=="_lastInertiaSpinMovement"{
-            //     info!("spin inertia over 2");
-            // }
             return false;
         }
-        let down = is_down_wrap.get(type_name);
+        let down = is_down_wrap.get(binding);
 
         if down == Some(&false) || down == None {
             //可以保持惯性，更新相机
@@ -526,7 +445,7 @@ impl bevy::prelude::Plugin for ScreenSpaceEventHandlerPlugin {
         app.add_event::<MouseEvent>();
         app.add_systems(
             Update,
-            screen_space_event_hanlder_system.in_set(PanOrbitCameraSystemSet),
+            (screen_space_event_hanlder_system, touch_pinch_system).in_set(PanOrbitCameraSystemSet),
         );
         {
             app.init_resource::<EguiWantsFocus>()
@@ -589,6 +508,7 @@ pub struct ScreenSpaceEventHandler {
     _is_pinching: bool,
     _pinching_position: DVec2,
     _click_pixel_to_lerance: f64,
+    _touch_pinch_distance: Option<f64>,
 }
 impl Default for ScreenSpaceEventHandler {
     fn default() -> Self {
@@ -599,11 +519,12 @@ impl Default for ScreenSpaceEventHandler {
             _primary_position: DVec2::ZERO,
             _click_pixel_to_lerance: 5.0,
             _is_pinching: false,
+            _touch_pinch_distance: None,
         }
     }
 }
 
-fn decay(time: f64, coefficient: f64) -> f64 {
+pub fn decay(time: f64, coefficient: f64) -> f64 {
     if time < 0. {
         return 0.0;
     }
@@ -614,7 +535,10 @@ fn decay(time: f64, coefficient: f64) -> f64 {
 #[derive(Event)]
 pub enum MouseEvent {
     MouseMove(Movement),
-    PinchStart(Movement),
+    /// 两指按下时的中点
+    PinchStart(DVec2),
+    /// y为两指距离的变化
+    PinchMove(Movement),
     PinchEnd(Movement),
     Wheel(f64),
     LeftDown(DVec2),
@@ -645,7 +569,7 @@ pub fn screen_space_event_hanlder_system(
         let Some(raw_position) = window.cursor_position() else {
             return;
         };
-        let Some((_left_top,_)) = camera.physical_viewport_rect() else {
+        let Some((_left_top, _)) = camera.physical_viewport_rect() else {
             return;
         };
         let position = DVec2::new(raw_position.x as f64, raw_position.y as f64);
@@ -765,6 +689,46 @@ pub fn screen_space_event_hanlder_system(
         }
     }
 }
+/// 两指触摸时把两指距离的变化作为缩放输入
+pub fn touch_pinch_system(
+    mut events: EventWriter<MouseEvent>,
+    touches: Res<Touches>,
+    mut screen_space_event_hanlder: ResMut<ScreenSpaceEventHandler>,
+) {
+    let pressed: Vec<DVec2> = touches
+        .iter()
+        .map(|v| {
+            let p = v.position();
+            DVec2::new(p.x as f64, p.y as f64)
+        })
+        .collect();
+    if pressed.len() != 2 {
+        if screen_space_event_hanlder
+            ._touch_pinch_distance
+            .take()
+            .is_some()
+        {
+            events.send(MouseEvent::PinchEnd(Movement::default()));
+        }
+        return;
+    }
+    // 与Cesium一致，距离乘以0.25
+    let distance = pressed[0].distance(pressed[1]) * 0.25;
+    match screen_space_event_hanlder._touch_pinch_distance {
+        None => {
+            events.send(MouseEvent::PinchStart((pressed[0] + pressed[1]) / 2.0));
+        }
+        Some(previous) => {
+            if previous != distance {
+                events.send(MouseEvent::PinchMove(Movement {
+                    start_position: DVec2::new(0.0, previous),
+                    end_position: DVec2::new(0.0, distance),
+                }));
+            }
+        }
+    }
+    screen_space_event_hanlder._touch_pinch_distance = Some(distance);
+}
 fn check_pixel_tolerance(
     start_position: &DVec2,
     end_position: &DVec2,
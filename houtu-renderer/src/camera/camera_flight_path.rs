@@ -0,0 +1,394 @@
+use std::f64::consts::PI;
+
+use bevy::{math::DVec3, prelude::*};
+use houtu_scene::{
+    lerp, nagetive_pi_to_pi, Cartographic, Ellipsoid, EllipsoidGeodesic, HeadingPitchRoll,
+    Rectangle,
+};
+
+use super::{
+    camera_event_aggregator::ControlEvent,
+    globe_camra::{GlobeCamera, SetViewOrientation},
+};
+
+pub enum FlyToDestination {
+    Cartographic(Cartographic),
+    /// 飞到能看到整个矩形的位置
+    Rectangle(Rectangle),
+}
+pub struct FlyToOptions {
+    pub destination: FlyToDestination,
+    /// 默认朝北垂直向下看
+    pub orientation: Option<HeadingPitchRoll>,
+    /// 飞行时间(秒)，默认根据距离计算，最长3秒
+    pub duration: Option<f64>,
+    /// 飞行中的最高高度，默认根据起点和终点的距离计算
+    pub maximum_height: Option<f64>,
+}
+impl FlyToOptions {
+    pub fn new(destination: FlyToDestination) -> Self {
+        Self {
+            destination,
+            orientation: None,
+            duration: None,
+            maximum_height: None,
+        }
+    }
+}
+/// 飞行正常结束
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlyToComplete {
+    pub entity: Entity,
+    pub flight_id: u64,
+}
+/// 飞行被新的飞行、`cancel_flight`或者用户操作相机打断
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlyToCancelled {
+    pub entity: Entity,
+    pub flight_id: u64,
+}
+/// 沿椭球面上的大圆弧飞行，高度先升后降
+pub struct CameraFlight {
+    pub id: u64,
+    pub duration: f64,
+    pub elapsed: f64,
+    start: Cartographic,
+    end: Cartographic,
+    start_orientation: HeadingPitchRoll,
+    end_orientation: HeadingPitchRoll,
+    /// 起点和终点几乎重合或者几乎相对时为None，此时按经纬度线性插值
+    geodesic: Option<EllipsoidGeodesic>,
+    altitude: Option<f64>,
+    /// 从高处下降时用CUBIC_OUT，否则用QUINTIC_IN_OUT
+    descending: bool,
+}
+impl CameraFlight {
+    pub fn new(
+        id: u64,
+        start: Cartographic,
+        start_orientation: HeadingPitchRoll,
+        end: Cartographic,
+        end_orientation: HeadingPitchRoll,
+        duration: f64,
+        altitude: Option<f64>,
+    ) -> Self {
+        let ellipsoid = Ellipsoid::WGS84;
+        let start_normal = ellipsoid.cartographic_to_cartesian(&start).normalize();
+        let end_normal = ellipsoid.cartographic_to_cartesian(&end).normalize();
+        let angle = start_normal.angle_between(end_normal);
+        // EllipsoidGeodesic无法处理相对的两个点
+        let geodesic = if angle > 1e-9 && PI - angle > 0.0125 {
+            let mut geodesic = EllipsoidGeodesic::default();
+            geodesic.setEndPoints(start, end);
+            Some(geodesic)
+        } else {
+            None
+        };
+        let max_height = start.height.max(end.height);
+        Self {
+            id,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            start,
+            end,
+            start_orientation,
+            end_orientation,
+            geodesic,
+            altitude: altitude.filter(|x| *x > max_height),
+            descending: start.height > end.height && start.height > 11500.0,
+        }
+    }
+    pub fn is_finished(&self) -> bool {
+        return self.elapsed >= self.duration;
+    }
+    /// 已经飞行的时间占总时间的比例，经过缓动函数处理
+    pub fn get_fraction(&self) -> f64 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        let t = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        return if self.descending {
+            cubic_out(t)
+        } else {
+            quintic_in_out(t)
+        };
+    }
+    /// 当前时刻相机的位置和姿态
+    pub fn evaluate(&mut self) -> (Cartographic, HeadingPitchRoll) {
+        let t = self.get_fraction();
+        if t >= 1.0 {
+            return (self.end, self.end_orientation);
+        }
+        let mut position = match self.geodesic.as_mut() {
+            Some(geodesic) => geodesic.interpolateUsingFraction(t),
+            None => Cartographic::from_radians(
+                self.start.longitude
+                    + nagetive_pi_to_pi(self.end.longitude - self.start.longitude) * t,
+                lerp(self.start.latitude, self.end.latitude, t),
+                0.0,
+            ),
+        };
+        position.height = flight_height(self.start.height, self.end.height, self.altitude, t);
+        let orientation = HeadingPitchRoll::new(
+            lerp_angle(
+                self.start_orientation.heading,
+                self.end_orientation.heading,
+                t,
+            ),
+            lerp(self.start_orientation.pitch, self.end_orientation.pitch, t),
+            lerp_angle(self.start_orientation.roll, self.end_orientation.roll, t),
+        );
+        return (position, orientation);
+    }
+}
+/// 飞行的最高高度，参考Cesium的CameraFlightPath，和起点终点在屏幕上的距离有关
+pub fn compute_flight_altitude(camera: &mut GlobeCamera, destination: &DVec3) -> f64 {
+    let diff = camera.get_position_wc() - *destination;
+    let up = camera.get_up_wc();
+    let right = camera.get_right_wc();
+    let vertical_distance = (up * diff.dot(up)).length();
+    let horizontal_distance = (right * diff.dot(right)).length();
+    let near = camera.frustum.near;
+    let top = near * (0.5 * camera.frustum.get_fovy()).tan();
+    let right = camera.frustum.aspect_ratio * top;
+    let altitude = (vertical_distance * near / right).max(horizontal_distance * near / top);
+    return (altitude * 0.2).min(1000000000.0);
+}
+/// 默认的飞行时间，距离越远越长，最长3秒
+pub fn compute_flight_duration(start: &DVec3, end: &DVec3) -> f64 {
+    let duration = (start.distance(*end) / 1000000.0).ceil() + 2.0;
+    return duration.min(3.0);
+}
+/// 高于起点和终点的`altitude`时先升到这个高度再下降，否则在两个高度之间线性插值
+pub fn flight_height(start_height: f64, end_height: f64, altitude: Option<f64>, t: f64) -> f64 {
+    let Some(altitude) = altitude.filter(|x| *x > start_height.max(end_height)) else {
+        return lerp(start_height, end_height, t);
+    };
+    let power = 8.0;
+    let factor = 1000000.0;
+    let s = -((altitude - start_height) * factor).powf(1.0 / power);
+    let e = ((altitude - end_height) * factor).powf(1.0 / power);
+    let x = t * (e - s) + s;
+    return -x.powf(power) / factor + altitude;
+}
+fn lerp_angle(start: f64, end: f64, t: f64) -> f64 {
+    return start + nagetive_pi_to_pi(end - start) * t;
+}
+fn cubic_out(t: f64) -> f64 {
+    return 1.0 - (1.0 - t).powi(3);
+}
+fn quintic_in_out(t: f64) -> f64 {
+    if t < 0.5 {
+        return 16.0 * t.powi(5);
+    }
+    return 1.0 - (-2.0 * t + 2.0).powi(5) / 2.0;
+}
+impl GlobeCamera {
+    /// 动画飞到目标位置，返回的编号和[`FlyToComplete`]、[`FlyToCancelled`]事件中的一致
+    pub fn fly_to(&mut self, options: FlyToOptions) -> u64 {
+        self.cancel_flight();
+        let ellipsoid = Ellipsoid::WGS84;
+        let end = match options.destination {
+            FlyToDestination::Cartographic(v) => v,
+            FlyToDestination::Rectangle(rectangle) => self
+                .rectangle_camera_position_3d(&rectangle, None)
+                .and_then(|x| ellipsoid.cartesian_to_cartographic(&x))
+                .unwrap_or(rectangle.center()),
+        };
+        let destination = ellipsoid.cartographic_to_cartesian(&end);
+        let start_position = self.get_position_wc();
+        let start = self.get_position_cartographic();
+        let start_orientation =
+            HeadingPitchRoll::new(self.get_heading(), self.get_pitch(), self.get_roll());
+        let end_orientation =
+            options
+                .orientation
+                .unwrap_or(HeadingPitchRoll::new(0.0, -PI / 2.0, 0.0));
+        let duration = options
+            .duration
+            .unwrap_or(compute_flight_duration(&start_position, &destination));
+        let altitude = options
+            .maximum_height
+            .unwrap_or(compute_flight_altitude(self, &destination));
+        self._next_flight_id += 1;
+        let id = self._next_flight_id;
+        self._flight = Some(CameraFlight::new(
+            id,
+            start,
+            start_orientation,
+            end,
+            end_orientation,
+            duration,
+            Some(altitude),
+        ));
+        return id;
+    }
+    pub fn cancel_flight(&mut self) {
+        if let Some(flight) = self._flight.take() {
+            self._cancelled_flights.push(flight.id);
+        }
+    }
+    pub fn is_flying(&self) -> bool {
+        return self._flight.is_some();
+    }
+}
+pub fn camera_flight_system(
+    time: Res<Time>,
+    mut control_event_reader: EventReader<ControlEvent>,
+    mut cameras: Query<(Entity, &mut GlobeCamera, &mut Transform)>,
+    mut complete_writer: EventWriter<FlyToComplete>,
+    mut cancelled_writer: EventWriter<FlyToCancelled>,
+) {
+    // 用户操作相机时取消飞行
+    let interrupted = control_event_reader.iter().count() > 0;
+    for (entity, mut globe_camera, mut transform) in &mut cameras {
+        if interrupted {
+            globe_camera.cancel_flight();
+        }
+        for flight_id in std::mem::take(&mut globe_camera._cancelled_flights) {
+            cancelled_writer.send(FlyToCancelled { entity, flight_id });
+        }
+        let Some(mut flight) = globe_camera._flight.take() else {
+            continue;
+        };
+        flight.elapsed += time.delta_seconds_f64();
+        let (position, orientation) = flight.evaluate();
+        let destination = Ellipsoid::WGS84.cartographic_to_cartesian(&position);
+        globe_camera.set_view(
+            Some(destination),
+            Some(SetViewOrientation::HeadingPitchRoll(orientation)),
+            None,
+            None,
+        );
+        globe_camera.update_camera_matrix(&mut transform);
+        if flight.is_finished() {
+            complete_writer.send(FlyToComplete {
+                entity,
+                flight_id: flight.id,
+            });
+        } else {
+            globe_camera._flight = Some(flight);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use houtu_scene::{equals_epsilon, EPSILON10, EPSILON6};
+
+    use super::*;
+
+    #[test]
+    fn test_flight_height() {
+        // 最高高度低于起点时线性插值
+        assert!(flight_height(1000.0, 3000.0, Some(500.0), 0.5) == 2000.0);
+        assert!(flight_height(1000.0, 3000.0, None, 0.25) == 1500.0);
+        let altitude = Some(1000000.0);
+        assert!(equals_epsilon(
+            flight_height(1000.0, 2000.0, altitude, 0.0),
+            1000.0,
+            None,
+            Some(EPSILON6)
+        ));
+        assert!(equals_epsilon(
+            flight_height(1000.0, 2000.0, altitude, 1.0),
+            2000.0,
+            None,
+            Some(EPSILON6)
+        ));
+        let middle = flight_height(1000.0, 2000.0, altitude, 0.5);
+        assert!(middle > 2000.0 && middle <= 1000000.0);
+    }
+    #[test]
+    fn test_easing() {
+        for easing in [cubic_out, quintic_in_out] {
+            assert!(easing(0.0) == 0.0);
+            assert!(easing(1.0) == 1.0);
+            assert!(easing(0.3) < easing(0.6));
+        }
+        assert!(quintic_in_out(0.5) == 0.5);
+    }
+    #[test]
+    fn test_duration() {
+        let start = DVec3::new(7000000.0, 0.0, 0.0);
+        assert!(compute_flight_duration(&start, &start) == 2.0);
+        assert!(compute_flight_duration(&start, &DVec3::new(0.0, 7000000.0, 0.0)) == 3.0);
+    }
+    #[test]
+    fn test_flight() {
+        let start = Cartographic::from_degrees(0.0, 0.0, 10000.0);
+        let end = Cartographic::from_degrees(90.0, 0.0, 1000.0);
+        let start_orientation = HeadingPitchRoll::new(6.0, -0.5, 0.0);
+        let end_orientation = HeadingPitchRoll::new(0.2, -FRAC_PI_2, 0.0);
+        let mut flight = CameraFlight::new(
+            1,
+            start,
+            start_orientation,
+            end,
+            end_orientation,
+            2.0,
+            Some(2000000.0),
+        );
+        let (position, orientation) = flight.evaluate();
+        assert!(equals_epsilon(
+            position.longitude,
+            0.0,
+            None,
+            Some(EPSILON10)
+        ));
+        assert!(equals_epsilon(
+            position.height,
+            10000.0,
+            None,
+            Some(EPSILON6)
+        ));
+        assert!(orientation == start_orientation);
+        flight.elapsed = 1.0;
+        let (position, orientation) = flight.evaluate();
+        // 沿赤道飞行，中间点在经度45度，高度升高
+        assert!(equals_epsilon(
+            position.longitude,
+            45.0_f64.to_radians(),
+            None,
+            Some(EPSILON6)
+        ));
+        assert!(equals_epsilon(position.latitude, 0.0, None, Some(EPSILON6)));
+        assert!(position.height > 10000.0);
+        // 航向沿最短方向从6.0转到0.2，经过2PI
+        let expected = 6.0 + nagetive_pi_to_pi(0.2 - 6.0) * 0.5;
+        assert!(equals_epsilon(
+            orientation.heading,
+            expected,
+            None,
+            Some(EPSILON10)
+        ));
+        assert!(!flight.is_finished());
+        flight.elapsed = 2.5;
+        let (position, orientation) = flight.evaluate();
+        assert!(flight.is_finished());
+        assert!(position == end);
+        assert!(equals_epsilon(
+            orientation.pitch,
+            -FRAC_PI_2,
+            None,
+            Some(EPSILON10)
+        ));
+    }
+    #[test]
+    fn test_flight_to_antipode() {
+        let start = Cartographic::from_degrees(0.0, 0.0, 1000.0);
+        let end = Cartographic::from_degrees(180.0, 0.0, 1000.0);
+        let orientation = HeadingPitchRoll::default();
+        let mut flight = CameraFlight::new(1, start, orientation, end, orientation, 1.0, None);
+        flight.elapsed = 0.5;
+        let (position, _) = flight.evaluate();
+        assert!(equals_epsilon(
+            position.longitude.abs(),
+            FRAC_PI_2,
+            None,
+            Some(EPSILON6)
+        ));
+        assert!(position.height == 1000.0);
+    }
+}
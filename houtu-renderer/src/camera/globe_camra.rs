@@ -1,4 +1,5 @@
 use super::camera_event_aggregator;
+use super::camera_flight_path::CameraFlight;
 
 use bevy::math::{DMat3, DMat4, DQuat, DVec2, DVec3, DVec4};
 use bevy::prelude::*;
@@ -74,6 +75,10 @@ pub struct GlobeCamera {
     pub constrained_axis: Option<DVec3>,
 
     pub viewport: BoundingRectangle,
+
+    pub _flight: Option<CameraFlight>,
+    pub _cancelled_flights: Vec<u64>,
+    pub _next_flight_id: u64,
 }
 
 impl Default for GlobeCamera {
@@ -122,11 +127,14 @@ impl Default for GlobeCamera {
             inited: false,
             constrained_axis: None,
             viewport: BoundingRectangle::new(),
+            _flight: None,
+            _cancelled_flights: vec![],
+            _next_flight_id: 0,
         };
         return me;
     }
 }
-fn globe_camera_setup_system(
+pub(crate) fn globe_camera_setup_system(
     mut query: Query<
         (
             &mut GlobeCamera,
@@ -243,6 +251,15 @@ impl GlobeCamera {
         self.update_members();
         return self._viewMatrix;
     }
+    /// 与bevy主渲染通道一致的反向Z无穷远投影矩阵，近处深度为1，无穷远处为0，配合GreaterEqual深度测试
+    pub fn get_reverse_z_projection_matrix(&mut self) -> DMat4 {
+        let fovy = self.frustum.get_fovy();
+        return DMat4::perspective_infinite_reverse_rh(
+            fovy,
+            self.frustum.aspect_ratio,
+            self.frustum.near,
+        );
+    }
     pub fn get_inverse_view_matrix(&mut self) -> DMat4 {
         self.update_members();
         return self._invViewMatrix;
@@ -0,0 +1,63 @@
+use bevy::{math::DVec2, prelude::*, window::PrimaryWindow};
+
+use crate::quadtree::quadtree_primitive::{GlobePickResult, QuadtreePrimitive};
+
+use super::{camera_event_aggregator::MouseEvent, globe_camra::GlobeCamera};
+
+impl GlobeCamera {
+    /// 拾取屏幕位置对应的已渲染地形，没有地形数据时不会退回到椭球面
+    pub fn pick_globe(
+        &mut self,
+        window_position: &DVec2,
+        window_size: &DVec2,
+        primitive: &QuadtreePrimitive,
+    ) -> Option<GlobePickResult> {
+        let ray = self.getPickRay(window_position, window_size);
+        return primitive.pick(&ray);
+    }
+}
+/// 鼠标点击到地形上时发送
+#[derive(Event, Debug, Clone)]
+pub struct GlobeClickEvent {
+    pub camera: Entity,
+    pub button: MouseButton,
+    pub window_position: DVec2,
+    pub result: GlobePickResult,
+}
+pub fn globe_click_system(
+    primitive: Option<Res<QuadtreePrimitive>>,
+    primary_query: Query<&Window, With<PrimaryWindow>>,
+    mut mouse_event_reader: EventReader<MouseEvent>,
+    mut cameras: Query<(Entity, &mut GlobeCamera)>,
+    mut click_writer: EventWriter<GlobeClickEvent>,
+) {
+    let Some(primitive) = primitive else {
+        return;
+    };
+    let Ok(primary) = primary_query.get_single() else {
+        return;
+    };
+    let window_size = DVec2 {
+        x: primary.width() as f64,
+        y: primary.height() as f64,
+    };
+    for event in mouse_event_reader.iter() {
+        let (button, window_position) = match event {
+            MouseEvent::LeftClick(position) => (MouseButton::Left, position),
+            MouseEvent::RightClick(position) => (MouseButton::Right, position),
+            MouseEvent::MiddleClick(position) => (MouseButton::Middle, position),
+            _ => continue,
+        };
+        for (entity, mut globe_camera) in &mut cameras {
+            if let Some(result) = globe_camera.pick_globe(window_position, &window_size, &primitive)
+            {
+                click_writer.send(GlobeClickEvent {
+                    camera: entity,
+                    button,
+                    window_position: *window_position,
+                    result,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 可以绑定到相机操作的输入
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CameraInput {
+    LeftDrag,
+    RightDrag,
+    MiddleDrag,
+    Wheel,
+    /// 双指缩放，移动量为两指距离的变化
+    Pinch,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KeyModifier {
+    Shift,
+    Ctrl,
+    Alt,
+}
+impl KeyModifier {
+    pub fn keys(&self) -> [KeyCode; 2] {
+        return match self {
+            KeyModifier::Shift => [KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            KeyModifier::Ctrl => [KeyCode::ControlLeft, KeyCode::ControlRight],
+            KeyModifier::Alt => [KeyCode::AltLeft, KeyCode::AltRight],
+        };
+    }
+    /// 当前按下的修饰键，同时按下多个时按Shift、Ctrl、Alt的顺序取第一个
+    pub fn current(keys: &Input<KeyCode>) -> Option<Self> {
+        return [KeyModifier::Shift, KeyModifier::Ctrl, KeyModifier::Alt]
+            .into_iter()
+            .find(|v| keys.any_pressed(v.keys()));
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InputBinding {
+    pub input: CameraInput,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modifier: Option<KeyModifier>,
+}
+impl InputBinding {
+    pub fn new(input: CameraInput) -> Self {
+        Self {
+            input,
+            modifier: None,
+        }
+    }
+    pub fn with_modifier(input: CameraInput, modifier: KeyModifier) -> Self {
+        Self {
+            input,
+            modifier: Some(modifier),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraAction {
+    /// 旋转地球，对应ControlEvent::Spin
+    Rotate,
+    Translate,
+    Zoom,
+    Tilt,
+    /// 相机原地转动视线
+    Look,
+}
+impl CameraAction {
+    pub const ALL: [CameraAction; 5] = [
+        CameraAction::Look,
+        CameraAction::Tilt,
+        CameraAction::Rotate,
+        CameraAction::Translate,
+        CameraAction::Zoom,
+    ];
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionBindings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub bindings: Vec<InputBinding>,
+}
+fn default_enabled() -> bool {
+    return true;
+}
+impl ActionBindings {
+    pub fn new(bindings: Vec<InputBinding>) -> Self {
+        Self {
+            enabled: true,
+            bindings,
+        }
+    }
+}
+/// 鼠标、修饰键和触摸手势到相机操作的映射，可以序列化保存用户的设置
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraInputMap {
+    pub rotate: ActionBindings,
+    pub translate: ActionBindings,
+    pub zoom: ActionBindings,
+    pub tilt: ActionBindings,
+    pub look: ActionBindings,
+}
+impl Default for CameraInputMap {
+    /// 与Cesium的ScreenSpaceCameraController在3D模式下一致，只是双指手势只用于缩放，不用于倾斜
+    fn default() -> Self {
+        use CameraInput::*;
+        Self {
+            rotate: ActionBindings::new(vec![InputBinding::new(LeftDrag)]),
+            translate: ActionBindings::new(vec![]),
+            zoom: ActionBindings::new(vec![
+                InputBinding::new(RightDrag),
+                InputBinding::new(Wheel),
+                InputBinding::new(Pinch),
+            ]),
+            tilt: ActionBindings::new(vec![
+                InputBinding::new(MiddleDrag),
+                InputBinding::with_modifier(LeftDrag, KeyModifier::Ctrl),
+                InputBinding::with_modifier(RightDrag, KeyModifier::Ctrl),
+            ]),
+            look: ActionBindings::new(vec![InputBinding::with_modifier(
+                LeftDrag,
+                KeyModifier::Shift,
+            )]),
+        }
+    }
+}
+impl CameraInputMap {
+    /// CAD软件习惯：中键平移，Shift+中键旋转，Ctrl+中键倾斜
+    pub fn cad() -> Self {
+        use CameraInput::*;
+        Self {
+            rotate: ActionBindings::new(vec![InputBinding::with_modifier(
+                MiddleDrag,
+                KeyModifier::Shift,
+            )]),
+            translate: ActionBindings::new(vec![InputBinding::new(MiddleDrag)]),
+            zoom: ActionBindings::new(vec![InputBinding::new(Wheel), InputBinding::new(Pinch)]),
+            tilt: ActionBindings::new(vec![InputBinding::with_modifier(
+                MiddleDrag,
+                KeyModifier::Ctrl,
+            )]),
+            look: ActionBindings::new(vec![InputBinding::new(RightDrag)]),
+        }
+    }
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        return serde_json::from_str(json);
+    }
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        return serde_json::to_string_pretty(self);
+    }
+    pub fn get(&self, action: CameraAction) -> &ActionBindings {
+        return match action {
+            CameraAction::Rotate => &self.rotate,
+            CameraAction::Translate => &self.translate,
+            CameraAction::Zoom => &self.zoom,
+            CameraAction::Tilt => &self.tilt,
+            CameraAction::Look => &self.look,
+        };
+    }
+    pub fn get_mut(&mut self, action: CameraAction) -> &mut ActionBindings {
+        return match action {
+            CameraAction::Rotate => &mut self.rotate,
+            CameraAction::Translate => &mut self.translate,
+            CameraAction::Zoom => &mut self.zoom,
+            CameraAction::Tilt => &mut self.tilt,
+            CameraAction::Look => &mut self.look,
+        };
+    }
+    pub fn set_enabled(&mut self, action: CameraAction, enabled: bool) {
+        self.get_mut(action).enabled = enabled;
+    }
+    pub fn is_enabled(&self, action: CameraAction) -> bool {
+        return self.get(action).enabled;
+    }
+    /// 输入触发的操作，同一个输入绑定了多个操作时按CameraAction::ALL的顺序取第一个启用的
+    pub fn action_for(&self, binding: &InputBinding) -> Option<CameraAction> {
+        return CameraAction::ALL.into_iter().find(|action| {
+            let v = self.get(*action);
+            v.enabled && v.bindings.contains(binding)
+        });
+    }
+    /// 所有启用的操作用到的输入，不重复
+    pub fn bindings(&self) -> Vec<InputBinding> {
+        let mut result: Vec<InputBinding> = vec![];
+        for action in CameraAction::ALL {
+            let v = self.get(action);
+            if !v.enabled {
+                continue;
+            }
+            for binding in v.bindings.iter() {
+                if !result.contains(binding) {
+                    result.push(*binding);
+                }
+            }
+        }
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bindings() {
+        let map = CameraInputMap::default();
+        assert_eq!(
+            map.action_for(&InputBinding::new(CameraInput::LeftDrag)),
+            Some(CameraAction::Rotate)
+        );
+        assert_eq!(
+            map.action_for(&InputBinding::with_modifier(
+                CameraInput::LeftDrag,
+                KeyModifier::Shift
+            )),
+            Some(CameraAction::Look)
+        );
+        assert_eq!(
+            map.action_for(&InputBinding::new(CameraInput::Pinch)),
+            Some(CameraAction::Zoom)
+        );
+        assert_eq!(
+            map.action_for(&InputBinding::new(CameraInput::RightDrag)),
+            Some(CameraAction::Zoom)
+        );
+        assert_eq!(
+            map.action_for(&InputBinding::with_modifier(
+                CameraInput::RightDrag,
+                KeyModifier::Ctrl
+            )),
+            Some(CameraAction::Tilt)
+        );
+        assert_eq!(map.bindings().len(), 8);
+    }
+    #[test]
+    fn test_disable_action() {
+        let mut map = CameraInputMap::default();
+        map.set_enabled(CameraAction::Tilt, false);
+        assert!(!map.is_enabled(CameraAction::Tilt));
+        assert_eq!(
+            map.action_for(&InputBinding::new(CameraInput::MiddleDrag)),
+            None
+        );
+        assert!(!map
+            .bindings()
+            .contains(&InputBinding::new(CameraInput::MiddleDrag)));
+    }
+    #[test]
+    fn test_json() {
+        let map = CameraInputMap::cad();
+        let json = map.to_json().unwrap();
+        assert_eq!(CameraInputMap::from_json(&json).unwrap(), map);
+
+        let json = r#"{
+            "rotate": {"bindings": [{"input": "RIGHT_DRAG", "modifier": "ALT"}]},
+            "translate": {"enabled": false, "bindings": [{"input": "LEFT_DRAG"}]},
+            "zoom": {"bindings": [{"input": "WHEEL"}]},
+            "tilt": {},
+            "look": {}
+        }"#;
+        let map = CameraInputMap::from_json(json).unwrap();
+        assert_eq!(
+            map.action_for(&InputBinding::with_modifier(
+                CameraInput::RightDrag,
+                KeyModifier::Alt
+            )),
+            Some(CameraAction::Rotate)
+        );
+        assert_eq!(
+            map.action_for(&InputBinding::new(CameraInput::LeftDrag)),
+            None
+        );
+        assert!(map.tilt.enabled && map.tilt.bindings.is_empty());
+    }
+}
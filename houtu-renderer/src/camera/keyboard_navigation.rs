@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use houtu_scene::{Ellipsoid, EPSILON2};
+
+use super::{camera_event_aggregator::decay, globe_camra::GlobeCamera};
+
+/// 键盘或手柄的导航输入，每个分量在[-1,1]之间
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NavigationInput {
+    /// 前进后退
+    pub forward: f64,
+    /// 左右横移
+    pub right: f64,
+    /// 升高降低
+    pub up: f64,
+    /// 向右转为正
+    pub look: f64,
+    /// 拉近为正
+    pub zoom: f64,
+}
+impl NavigationInput {
+    /// W/S/↑/↓前进后退，A/D横移，←/→转向，Q/E升降，+/-缩放
+    pub fn from_keys(keys: &Input<KeyCode>) -> Self {
+        let axis = |positive: &[KeyCode], negative: &[KeyCode]| -> f64 {
+            let mut value = 0.0;
+            if keys.any_pressed(positive.iter().cloned()) {
+                value += 1.0;
+            }
+            if keys.any_pressed(negative.iter().cloned()) {
+                value -= 1.0;
+            }
+            return value;
+        };
+        return Self {
+            forward: axis(&[KeyCode::W, KeyCode::Up], &[KeyCode::S, KeyCode::Down]),
+            right: axis(&[KeyCode::D], &[KeyCode::A]),
+            up: axis(&[KeyCode::E], &[KeyCode::Q]),
+            look: axis(&[KeyCode::Right], &[KeyCode::Left]),
+            zoom: axis(
+                &[KeyCode::Equals, KeyCode::NumpadAdd],
+                &[KeyCode::Minus, KeyCode::NumpadSubtract],
+            ),
+        };
+    }
+    /// 左摇杆移动，右摇杆左右转向、上下缩放，左右扳机升降
+    pub fn from_gamepad(
+        gamepad: Gamepad,
+        axes: &Axis<GamepadAxis>,
+        buttons: &Input<GamepadButton>,
+        dead_zone: f64,
+    ) -> Self {
+        let axis = |axis_type: GamepadAxisType| -> f64 {
+            let value = axes
+                .get(GamepadAxis::new(gamepad, axis_type))
+                .unwrap_or(0.0) as f64;
+            return apply_dead_zone(value, dead_zone);
+        };
+        let mut up = 0.0;
+        if buttons.pressed(GamepadButton::new(
+            gamepad,
+            GamepadButtonType::RightTrigger2,
+        )) {
+            up += 1.0;
+        }
+        if buttons.pressed(GamepadButton::new(gamepad, GamepadButtonType::LeftTrigger2)) {
+            up -= 1.0;
+        }
+        return Self {
+            forward: axis(GamepadAxisType::LeftStickY),
+            right: axis(GamepadAxisType::LeftStickX),
+            up,
+            look: axis(GamepadAxisType::RightStickX),
+            zoom: axis(GamepadAxisType::RightStickY),
+        };
+    }
+    pub fn is_zero(&self) -> bool {
+        return self.magnitude() == 0.0;
+    }
+    pub fn magnitude(&self) -> f64 {
+        return self
+            .forward
+            .abs()
+            .max(self.right.abs())
+            .max(self.up.abs())
+            .max(self.look.abs())
+            .max(self.zoom.abs());
+    }
+    fn add(&self, other: &Self) -> Self {
+        Self {
+            forward: (self.forward + other.forward).clamp(-1.0, 1.0),
+            right: (self.right + other.right).clamp(-1.0, 1.0),
+            up: (self.up + other.up).clamp(-1.0, 1.0),
+            look: (self.look + other.look).clamp(-1.0, 1.0),
+            zoom: (self.zoom + other.zoom).clamp(-1.0, 1.0),
+        }
+    }
+    fn scale(&self, factor: f64) -> Self {
+        Self {
+            forward: self.forward * factor,
+            right: self.right * factor,
+            up: self.up * factor,
+            look: self.look * factor,
+            zoom: self.zoom * factor,
+        }
+    }
+}
+/// 摇杆死区，死区外重新映射到[0,1]
+pub fn apply_dead_zone(value: f64, dead_zone: f64) -> f64 {
+    if value.abs() <= dead_zone {
+        return 0.0;
+    }
+    return value.signum() * (value.abs() - dead_zone) / (1.0 - dead_zone);
+}
+/// 每秒移动的距离，随离椭球面的高度线性增长
+pub fn navigation_speed(height: f64, move_rate_factor: f64, minimum_speed: f64) -> f64 {
+    return (height.abs() * move_rate_factor).max(minimum_speed);
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct KeyboardNavigation {
+    pub enabled: bool,
+    /// 每秒移动高度的倍数
+    pub move_rate_factor: f64,
+    /// 最小移动速度(米/秒)
+    pub minimum_speed: f64,
+    /// 转向速度(弧度/秒)
+    pub look_rate: f64,
+    /// 每秒缩放高度的倍数
+    pub zoom_rate_factor: f64,
+    /// 松开按键后的惯性，与鼠标惯性使用相同的衰减函数
+    pub inertia: f64,
+    pub gamepad_dead_zone: f64,
+    _last_input: NavigationInput,
+    _release_time: Option<f64>,
+}
+impl Default for KeyboardNavigation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            move_rate_factor: 0.5,
+            minimum_speed: 1.0,
+            look_rate: 0.8,
+            zoom_rate_factor: 1.0,
+            inertia: 0.9,
+            gamepad_dead_zone: 0.15,
+            _last_input: NavigationInput::default(),
+            _release_time: None,
+        }
+    }
+}
+impl KeyboardNavigation {
+    /// 有输入时直接使用；没有输入时按松开后经过的时间衰减上一次的输入
+    pub fn update_input(&mut self, input: NavigationInput, now: f64) -> NavigationInput {
+        if !input.is_zero() {
+            self._last_input = input;
+            self._release_time = None;
+            return input;
+        }
+        if self._last_input.is_zero() {
+            return input;
+        }
+        let release_time = *self._release_time.get_or_insert(now);
+        let result = self
+            ._last_input
+            .scale(decay(now - release_time, self.inertia));
+        if result.magnitude() < EPSILON2 {
+            self._last_input = NavigationInput::default();
+            self._release_time = None;
+            return NavigationInput::default();
+        }
+        return result;
+    }
+}
+pub fn keyboard_navigation_system(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut navigation: ResMut<KeyboardNavigation>,
+    mut cameras: Query<(&mut GlobeCamera, &mut Transform)>,
+) {
+    if !navigation.enabled {
+        return;
+    }
+    let mut input = NavigationInput::from_keys(&keys);
+    for gamepad in gamepads.iter() {
+        input = input.add(&NavigationInput::from_gamepad(
+            gamepad,
+            &gamepad_axes,
+            &gamepad_buttons,
+            navigation.gamepad_dead_zone,
+        ));
+    }
+    let input = navigation.update_input(input, time.elapsed_seconds_f64());
+    if input.is_zero() {
+        return;
+    }
+    let delta_seconds = time.delta_seconds_f64();
+    for (mut globe_camera, mut transform) in &mut cameras {
+        if globe_camera.is_flying() {
+            continue;
+        }
+        let position = globe_camera.get_position_wc();
+        let Some(cartographic) = Ellipsoid::WGS84.cartesian_to_cartographic(&position) else {
+            continue;
+        };
+        let distance = navigation_speed(
+            cartographic.height,
+            navigation.move_rate_factor,
+            navigation.minimum_speed,
+        ) * delta_seconds;
+        let Some(normal) = Ellipsoid::WGS84.geodetic_surface_normal(&position) else {
+            continue;
+        };
+        // 水平方向前进，垂直向下看时沿up方向
+        let mut forward = globe_camera.direction - normal * globe_camera.direction.dot(normal);
+        if forward.length_squared() < EPSILON2 {
+            forward = globe_camera.up - normal * globe_camera.up.dot(normal);
+        }
+        let forward = forward.normalize_or_zero();
+        let right = forward.cross(normal).normalize_or_zero();
+
+        if input.forward != 0.0 {
+            globe_camera.move_direction(&forward, input.forward * distance);
+        }
+        if input.right != 0.0 {
+            globe_camera.move_direction(&right, input.right * distance);
+        }
+        if input.up != 0.0 {
+            globe_camera.move_direction(&normal, input.up * distance);
+        }
+        if input.zoom > 0.0 {
+            globe_camera.zoom_in(Some(
+                input.zoom
+                    * cartographic.height.abs()
+                    * navigation.zoom_rate_factor
+                    * delta_seconds,
+            ));
+        } else if input.zoom < 0.0 {
+            globe_camera.zoom_out(Some(
+                -input.zoom
+                    * cartographic.height.abs()
+                    * navigation.zoom_rate_factor
+                    * delta_seconds,
+            ));
+        }
+        let angle = input.look * navigation.look_rate * delta_seconds;
+        if angle > 0.0 {
+            globe_camera.look_right(Some(angle));
+        } else if angle < 0.0 {
+            globe_camera.look_left(Some(-angle));
+        }
+        globe_camera.update_camera_matrix(&mut transform);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_keys() {
+        let mut keys = Input::<KeyCode>::default();
+        keys.press(KeyCode::W);
+        keys.press(KeyCode::A);
+        keys.press(KeyCode::Q);
+        keys.press(KeyCode::Right);
+        let input = NavigationInput::from_keys(&keys);
+        assert_eq!(input.forward, 1.0);
+        assert_eq!(input.right, -1.0);
+        assert_eq!(input.up, -1.0);
+        assert_eq!(input.look, 1.0);
+        assert_eq!(input.zoom, 0.0);
+        // 同时按下相反方向的键互相抵消
+        keys.press(KeyCode::Down);
+        assert_eq!(NavigationInput::from_keys(&keys).forward, 0.0);
+    }
+    #[test]
+    fn test_dead_zone() {
+        assert_eq!(apply_dead_zone(0.1, 0.2), 0.0);
+        assert_eq!(apply_dead_zone(1.0, 0.2), 1.0);
+        assert_eq!(apply_dead_zone(-1.0, 0.2), -1.0);
+        assert!((apply_dead_zone(0.6, 0.2) - 0.5).abs() < 1e-12);
+    }
+    #[test]
+    fn test_navigation_speed() {
+        assert_eq!(navigation_speed(1000.0, 0.5, 1.0), 500.0);
+        assert_eq!(navigation_speed(0.5, 0.5, 1.0), 1.0);
+    }
+    #[test]
+    fn test_inertia() {
+        let mut navigation = KeyboardNavigation::default();
+        let input = NavigationInput {
+            forward: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(navigation.update_input(input, 0.0), input);
+        // 松开的那一帧保持原速度，之后逐渐衰减
+        let released = navigation.update_input(NavigationInput::default(), 1.0);
+        assert_eq!(released.forward, 1.0);
+        let later = navigation.update_input(NavigationInput::default(), 1.1);
+        assert!(later.forward > 0.0 && later.forward < 1.0);
+        let stopped = navigation.update_input(NavigationInput::default(), 10.0);
+        assert!(stopped.is_zero());
+        assert!(navigation
+            .update_input(NavigationInput::default(), 11.0)
+            .is_zero());
+    }
+}
@@ -6,15 +6,43 @@ use std::f64::{
 use bevy::{
     math::{DMat4, DVec2, DVec3},
     prelude::*,
+    transform::TransformSystem,
     window::PrimaryWindow,
 };
+mod camera_controller;
 mod camera_event_aggregator;
+mod camera_flight_path;
 mod egui;
 mod globe_camra;
+mod globe_pick;
+mod input_map;
+mod keyboard_navigation;
 mod pan_orbit;
-use self::{globe_camra::CameraControlPlugin, pan_orbit::pan_orbit_camera};
+mod terrain_collision;
+mod view_state;
+use self::{
+    camera_controller::{camera_controller_mode_system, camera_controller_system},
+    camera_flight_path::camera_flight_system,
+    egui::EguiWantsFocus,
+    globe_camra::CameraControlPlugin,
+    globe_pick::globe_click_system,
+    keyboard_navigation::keyboard_navigation_system,
+    pan_orbit::pan_orbit_camera,
+    terrain_collision::terrain_collision_system,
+};
+pub use camera_controller::{
+    CameraController, CameraControllerMode, FirstPersonController, OrbitController,
+    TopDownController,
+};
 pub use camera_event_aggregator::MouseEvent;
+pub use camera_flight_path::{FlyToCancelled, FlyToComplete, FlyToDestination, FlyToOptions};
 pub use globe_camra::GlobeCamera;
+pub use globe_pick::GlobeClickEvent;
+pub use input_map::{
+    ActionBindings, CameraAction, CameraInput, CameraInputMap, InputBinding, KeyModifier,
+};
+pub use keyboard_navigation::{KeyboardNavigation, NavigationInput};
+pub use view_state::{CameraViewState, CameraViewStateError};
 
 use houtu_scene::{Projection, *};
 
@@ -24,8 +52,58 @@ impl bevy::app::Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Msaa::default())
             .add_plugins(CameraControlPlugin)
-            .add_systems(Startup,setup)
-            .add_systems(Update, pan_orbit_camera);
+            .add_systems(Startup, setup)
+            .add_event::<FlyToComplete>()
+            .add_event::<FlyToCancelled>()
+            .add_event::<GlobeClickEvent>()
+            .init_resource::<CameraControllerMode>()
+            .init_resource::<FirstPersonController>()
+            .init_resource::<OrbitController>()
+            .init_resource::<TopDownController>()
+            .add_systems(
+                Update,
+                camera_controller_mode_system.before(pan_orbit_camera),
+            )
+            .add_systems(
+                Update,
+                pan_orbit_camera.run_if(resource_equals(CameraControllerMode::Globe)),
+            )
+            .add_systems(
+                Update,
+                (
+                    camera_controller_system::<FirstPersonController>
+                        .run_if(resource_equals(CameraControllerMode::FirstPerson)),
+                    camera_controller_system::<OrbitController>
+                        .run_if(resource_equals(CameraControllerMode::Orbit)),
+                    camera_controller_system::<TopDownController>
+                        .run_if(resource_equals(CameraControllerMode::TopDown)),
+                )
+                    .after(camera_controller_mode_system),
+            )
+            .init_resource::<KeyboardNavigation>()
+            .add_systems(
+                Update,
+                keyboard_navigation_system
+                    .after(pan_orbit_camera)
+                    .run_if(resource_equals(CameraControllerMode::Globe))
+                    .run_if(resource_equals(EguiWantsFocus {
+                        prev: false,
+                        curr: false,
+                    })),
+            )
+            .add_systems(Update, camera_flight_system.after(pan_orbit_camera))
+            .add_systems(Update, globe_click_system)
+            .add_systems(
+                PostUpdate,
+                terrain_collision_system.before(TransformSystem::TransformPropagate),
+            );
+        #[cfg(target_arch = "wasm32")]
+        app.add_systems(
+            Update,
+            view_state::url_hash::url_hash_system
+                .after(globe_camra::globe_camera_setup_system)
+                .after(camera_flight_system),
+        );
     }
 }
 impl Default for CameraPlugin {
@@ -109,6 +187,8 @@ pub struct GlobeCameraControl {
     pub _rotate_rate_range_adjustment: f64,
     pub _horizontal_rotation_axis: Option<DVec3>,
     pub _ellipsoid: Ellipsoid,
+    /// 相机下方已渲染地形的高度
+    pub _globe_height: Option<f64>,
 }
 impl Default for GlobeCameraControl {
     fn default() -> Self {
@@ -162,6 +242,7 @@ impl Default for GlobeCameraControl {
             _rotate_factor: 1.0,
             _rotate_rate_range_adjustment: 1.0,
             _horizontal_rotation_axis: None,
+            _globe_height: None,
         }
     }
 }
@@ -1,8 +1,6 @@
 use crate::camera::globe_camra::SetViewOrientation;
 
-use super::camera_event_aggregator::{
-    Aggregator, ControlEvent, EventStartPositionWrap, MovementState,
-};
+use super::camera_event_aggregator::{ControlEvent, MovementState};
 use super::globe_camra::GlobeCamera;
 use super::GlobeCameraControl;
 
@@ -19,8 +17,6 @@ use std::f64::consts::{PI, TAU};
 use std::ops::Neg;
 pub fn pan_orbit_camera(
     primary_query: Query<&Window, With<PrimaryWindow>>,
-    event_start_position_wrap: ResMut<EventStartPositionWrap>,
-    aggregator: ResMut<Aggregator>,
     mut orbit_cameras: Query<(
         Entity,
         &mut Transform,
@@ -58,8 +54,7 @@ pub fn pan_orbit_camera(
             match event {
                 ControlEvent::Zoom(data) => {
                     //zoom3D函数的内容
-                    let start_position =
-                        aggregator.get_start_mouse_position("WHEEL", &event_start_position_wrap);
+                    let start_position = data.start_position;
                     let movement = &data.movement;
                     let mut window_position;
                     if globe_camera_control._camera_underground {
@@ -384,10 +379,10 @@ pub fn pan_orbit_camera(
                     // println!("controlevent zoom {:?}", data);
                 }
 
-                ControlEvent::Spin(data) => {
+                // 三维模式下平移就是拖动地球
+                ControlEvent::Spin(data) | ControlEvent::Translate(data) => {
                     // println!("controlevent spin {:?}", data);
-                    let start_position = aggregator
-                        .get_start_mouse_position("LEFT_DRAG", &event_start_position_wrap);
+                    let start_position = data.start_position;
                     let mut movement = data.movement.clone();
                     spin_3d(
                         &mut globe_camera_control,
@@ -402,8 +397,7 @@ pub fn pan_orbit_camera(
 
                 ControlEvent::Tilt(data) => {
                     // println!("controlevent tilt {:?}", data);
-                    let start_position = aggregator
-                        .get_start_mouse_position("MIDDLE_DRAG", &event_start_position_wrap);
+                    let start_position = data.start_position;
                     let mut movement = data.movement.clone();
                     tilt_3d(
                         &mut globe_camera_control,
@@ -414,6 +408,18 @@ pub fn pan_orbit_camera(
                     );
                     globe_camera.update_camera_matrix(&mut transform);
                 }
+
+                ControlEvent::Look(data) => {
+                    look_3d(
+                        &mut globe_camera_control,
+                        &mut globe_camera,
+                        &data.start_position,
+                        &data.movement,
+                        None,
+                        &window_size,
+                    );
+                    globe_camera.update_camera_matrix(&mut transform);
+                }
             }
         }
     }
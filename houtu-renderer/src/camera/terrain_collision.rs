@@ -0,0 +1,135 @@
+use bevy::{math::DMat4, prelude::*};
+use houtu_scene::{Cartesian3, Ellipsoid};
+
+use crate::quadtree::quadtree_primitive::QuadtreePrimitive;
+
+use super::{
+    camera_controller::{CameraControllerMode, FirstPersonController},
+    globe_camra::GlobeCamera,
+    GlobeCameraControl,
+};
+
+/// 相机需要调整到的高度，不需要调整时返回None
+///
+/// 只有低于minimum_collision_terrain_height时才考虑地形，与Cesium的adjustHeightForTerrain一致
+pub fn clamp_camera_height(
+    height: f64,
+    globe_height: Option<f64>,
+    minimum_zoom_distance: f64,
+    maximum_zoom_distance: f64,
+    minimum_collision_terrain_height: f64,
+) -> Option<f64> {
+    if let Some(globe_height) = globe_height {
+        let minimum = globe_height + minimum_zoom_distance;
+        if height < minimum_collision_terrain_height && height < minimum {
+            return Some(minimum);
+        }
+    }
+    if height > maximum_zoom_distance {
+        return Some(maximum_zoom_distance);
+    }
+    return None;
+}
+fn adjust_height_for_terrain(
+    camera: &mut GlobeCamera,
+    control: &mut GlobeCameraControl,
+    primitive: &QuadtreePrimitive,
+) -> bool {
+    let ellipsoid = Ellipsoid::WGS84;
+    let transform = camera.get_transform();
+    let has_transform = transform != DMat4::IDENTITY;
+    let magnitude = camera.position.magnitude();
+    if has_transform {
+        camera._setTransform(&DMat4::IDENTITY);
+    }
+    let mut height_updated = false;
+    if let Some(mut cartographic) = ellipsoid.cartesian_to_cartographic(&camera.position) {
+        control._globe_height = primitive.get_height(&cartographic);
+        if let Some(height) = clamp_camera_height(
+            cartographic.height,
+            control._globe_height,
+            control.minimum_zoom_distance,
+            control.maximum_zoom_distance,
+            control._minimum_collision_terrain_height,
+        ) {
+            cartographic.height = height;
+            camera.position = ellipsoid.cartographic_to_cartesian(&cartographic);
+            height_updated = true;
+        }
+    }
+    if has_transform {
+        camera._setTransform(&transform);
+        // 环绕模式下保持看向中心点，调整俯仰
+        if height_updated {
+            let position = camera.position.normalize();
+            camera.direction = position.negate();
+            camera.position =
+                position.multiply_by_scalar(magnitude.max(control.minimum_zoom_distance));
+            camera.right = camera.direction.cross(camera.up);
+            camera.up = camera.right.cross(camera.direction);
+        }
+    }
+    return height_updated;
+}
+/// 每帧采样相机下方已渲染地形的高度，相机低于地形时抬高
+pub fn terrain_collision_system(
+    primitive: Option<Res<QuadtreePrimitive>>,
+    mode: Res<CameraControllerMode>,
+    mut first_person: ResMut<FirstPersonController>,
+    mut cameras: Query<(&mut GlobeCamera, &mut GlobeCameraControl, &mut Transform)>,
+) {
+    let Some(primitive) = primitive else {
+        return;
+    };
+    // 第一人称由控制器自己保持离地高度
+    if *mode == CameraControllerMode::FirstPerson {
+        let ground_height = primitive.get_height(&first_person.position).unwrap_or(0.0);
+        if first_person.ground_height != ground_height {
+            first_person.ground_height = ground_height;
+        }
+        return;
+    }
+    for (mut globe_camera, mut globe_camera_control, mut transform) in &mut cameras {
+        if !globe_camera_control.enable_collision_detection {
+            continue;
+        }
+        if adjust_height_for_terrain(&mut globe_camera, &mut globe_camera_control, &primitive) {
+            globe_camera.update_camera_matrix(&mut transform);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp_camera_height() {
+        // 在地形以上
+        assert_eq!(
+            clamp_camera_height(2000.0, Some(1000.0), 1.0, 1e8, 150000.0),
+            None
+        );
+        // 低于地形加最小距离
+        assert_eq!(
+            clamp_camera_height(1000.5, Some(1000.0), 1.0, 1e8, 150000.0),
+            Some(1001.0)
+        );
+        assert_eq!(
+            clamp_camera_height(-50.0, Some(1000.0), 10.0, 1e8, 150000.0),
+            Some(1010.0)
+        );
+        // 没有地形数据时不处理
+        assert_eq!(clamp_camera_height(-50.0, None, 10.0, 1e8, 150000.0), None);
+        // 高于碰撞检测高度时不考虑地形
+        assert_eq!(
+            clamp_camera_height(200000.0, Some(300000.0), 1.0, 1e8, 150000.0),
+            None
+        );
+        // 最大距离
+        assert_eq!(
+            clamp_camera_height(2e8, Some(0.0), 1.0, 1e8, 150000.0),
+            Some(1e8)
+        );
+    }
+}
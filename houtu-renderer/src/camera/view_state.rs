@@ -0,0 +1,264 @@
+use std::{fmt, str::FromStr};
+
+use bevy::math::DMat4;
+use houtu_scene::{Cartographic, Ellipsoid, HeadingPitchRoll};
+use serde::{Deserialize, Serialize};
+
+use super::globe_camra::{GlobeCamera, SetViewOrientation};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum CameraViewStateError {
+    #[error("expected 6 comma separated values, got {0}")]
+    FieldCount(usize),
+    #[error("invalid number: {0}")]
+    InvalidNumber(String),
+    #[error("{0} is out of range")]
+    OutOfRange(&'static str),
+}
+/// 可以分享的相机视角，角度都以度为单位
+///
+/// 短字符串形式为`经度,纬度,高度,heading,pitch,roll`，用于URL的hash
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraViewState {
+    pub longitude: f64,
+    pub latitude: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub heading: f64,
+    #[serde(default = "default_pitch")]
+    pub pitch: f64,
+    #[serde(default)]
+    pub roll: f64,
+}
+fn default_pitch() -> f64 {
+    return -90.0;
+}
+impl CameraViewState {
+    pub fn new(position: &Cartographic, hpr: &HeadingPitchRoll) -> Self {
+        Self {
+            longitude: position.longitude.to_degrees(),
+            latitude: position.latitude.to_degrees(),
+            height: position.height,
+            heading: hpr.heading.to_degrees(),
+            pitch: hpr.pitch.to_degrees(),
+            roll: hpr.roll.to_degrees(),
+        }
+    }
+    pub fn position(&self) -> Cartographic {
+        return Cartographic::from_degrees(self.longitude, self.latitude, self.height);
+    }
+    pub fn heading_pitch_roll(&self) -> HeadingPitchRoll {
+        return HeadingPitchRoll::new(
+            self.heading.to_radians(),
+            self.pitch.to_radians(),
+            self.roll.to_radians(),
+        );
+    }
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        return serde_json::from_str(json);
+    }
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        return serde_json::to_string(self);
+    }
+}
+/// 去掉小数末尾的0，经纬度保留6位小数约为0.1米
+fn format_number(value: f64, decimals: usize) -> String {
+    let text = format!("{:.*}", decimals, value);
+    let text = if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text.as_str()
+    };
+    if text == "-0" {
+        return "0".to_string();
+    }
+    return text.to_string();
+}
+impl fmt::Display for CameraViewState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{}",
+            format_number(self.longitude, 6),
+            format_number(self.latitude, 6),
+            format_number(self.height, 1),
+            format_number(self.heading, 2),
+            format_number(self.pitch, 2),
+            format_number(self.roll, 2)
+        )
+    }
+}
+impl FromStr for CameraViewState {
+    type Err = CameraViewStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .trim()
+            .trim_start_matches('#')
+            .split(',')
+            .map(|v| {
+                v.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| CameraViewStateError::InvalidNumber(v.to_string()))
+            })
+            .collect::<Result<Vec<f64>, CameraViewStateError>>()?;
+        if values.len() != 6 {
+            return Err(CameraViewStateError::FieldCount(values.len()));
+        }
+        if values[0].abs() > 180.0 {
+            return Err(CameraViewStateError::OutOfRange("longitude"));
+        }
+        if values[1].abs() > 90.0 {
+            return Err(CameraViewStateError::OutOfRange("latitude"));
+        }
+        return Ok(Self {
+            longitude: values[0],
+            latitude: values[1],
+            height: values[2],
+            heading: values[3],
+            pitch: values[4],
+            roll: values[5],
+        });
+    }
+}
+impl GlobeCamera {
+    pub fn get_view_state(&mut self) -> CameraViewState {
+        let position = Ellipsoid::WGS84
+            .cartesian_to_cartographic(&self.get_position_wc())
+            .unwrap_or_default();
+        let hpr = HeadingPitchRoll::new(self.get_heading(), self.get_pitch(), self.get_roll());
+        return CameraViewState::new(&position, &hpr);
+    }
+    pub fn set_view_state(&mut self, state: &CameraViewState) {
+        let destination = Ellipsoid::WGS84.cartographic_to_cartesian(&state.position());
+        self.set_view(
+            Some(destination),
+            Some(SetViewOrientation::HeadingPitchRoll(
+                state.heading_pitch_roll(),
+            )),
+            Some(DMat4::IDENTITY),
+            None,
+        );
+    }
+}
+
+/// 启动时从URL的hash恢复视角，之后相机停止移动时把视角写回hash
+#[cfg(target_arch = "wasm32")]
+pub mod url_hash {
+    use bevy::prelude::*;
+    use wasm_bindgen::JsValue;
+
+    use super::CameraViewState;
+    use crate::camera::globe_camra::GlobeCamera;
+
+    /// 写回hash的最小间隔(秒)
+    const SYNC_INTERVAL: f64 = 0.5;
+
+    fn read_hash() -> Option<String> {
+        return web_sys::window()?.location().hash().ok();
+    }
+    fn write_hash(hash: &str) {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        // 用replaceState避免每次移动都产生一条浏览记录
+        if let Ok(history) = window.history() {
+            let _ = history.replace_state_with_url(&JsValue::NULL, "", Some(&format!("#{}", hash)));
+        }
+    }
+    #[derive(Default)]
+    pub struct UrlHashState {
+        hash: String,
+        last_sync: f64,
+    }
+    pub fn url_hash_system(
+        time: Res<Time>,
+        mut state: Local<UrlHashState>,
+        mut cameras: Query<(&mut GlobeCamera, &mut Transform)>,
+    ) {
+        let Ok((mut globe_camera, mut transform)) = cameras.get_single_mut() else {
+            return;
+        };
+        // 相机初始化之前设置的视角会被覆盖
+        if !globe_camera.inited {
+            return;
+        }
+        // 启动或用户修改了地址栏时跳转到hash中的视角
+        if let Some(hash) = read_hash() {
+            let hash = hash.trim_start_matches('#');
+            if !hash.is_empty() && hash != state.hash {
+                state.hash = hash.to_string();
+                match hash.parse::<CameraViewState>() {
+                    Ok(view) => {
+                        globe_camera.cancel_flight();
+                        globe_camera.set_view_state(&view);
+                        globe_camera.update_camera_matrix(&mut transform);
+                    }
+                    Err(error) => warn!("invalid camera view in url hash: {}", error),
+                }
+                return;
+            }
+        }
+        let now = time.elapsed_seconds_f64();
+        if globe_camera.is_flying() || now - state.last_sync < SYNC_INTERVAL {
+            return;
+        }
+        state.last_sync = now;
+        let hash = globe_camera.get_view_state().to_string();
+        if hash != state.hash {
+            write_hash(&hash);
+            state.hash = hash;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string() {
+        let state = CameraViewState {
+            longitude: 116.3912345678,
+            latitude: -39.9,
+            height: 1500.04,
+            heading: 0.0,
+            pitch: -45.5,
+            roll: -0.0001,
+        };
+        let text = state.to_string();
+        assert_eq!(text, "116.391235,-39.9,1500,0,-45.5,0");
+        let parsed: CameraViewState = text.parse().unwrap();
+        assert!((parsed.longitude - state.longitude).abs() < 1e-6);
+        assert_eq!(parsed.pitch, -45.5);
+        assert_eq!("#1,2,3,4,5,6".parse::<CameraViewState>().unwrap().roll, 6.0);
+    }
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            "1,2,3".parse::<CameraViewState>(),
+            Err(CameraViewStateError::FieldCount(3))
+        );
+        assert_eq!(
+            "1,a,3,4,5,6".parse::<CameraViewState>(),
+            Err(CameraViewStateError::InvalidNumber("a".to_string()))
+        );
+        assert_eq!(
+            "1,91,3,4,5,6".parse::<CameraViewState>(),
+            Err(CameraViewStateError::OutOfRange("latitude"))
+        );
+    }
+    #[test]
+    fn test_json() {
+        let state = CameraViewState::from_json(
+            r#"{"longitude": 120.0, "latitude": 30.0, "height": 10000.0}"#,
+        )
+        .unwrap();
+        assert_eq!(state.pitch, -90.0);
+        assert_eq!(state.heading, 0.0);
+        let json = state.to_json().unwrap();
+        assert_eq!(CameraViewState::from_json(&json).unwrap(), state);
+    }
+}
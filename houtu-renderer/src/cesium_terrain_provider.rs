@@ -1,3 +1,576 @@
-pub struct CesiumTerrainProvider{
-    
-}
\ No newline at end of file
+use std::io;
+
+use bevy::log::warn;
+use houtu_jobs::{AsyncReturn, Context, Job};
+use houtu_scene::{
+    get_estimated_level_zero_geometric_error_for_a_heightmap, GeographicTilingScheme,
+    TilingScheme,
+};
+use quantized_mesh_decoder::from_reader;
+use serde::Deserialize;
+
+use crate::{
+    quadtree::{
+        terrain_provider::{
+            RequestTileGeometry, RequestTileGeometryError, RequestTileGeometryJob,
+            TerrainProvider, TerrainTileRequest, TerrainTileResponse,
+        },
+        tile_availability::TileAvailability,
+        tile_key::TileKey,
+    },
+    quantized_mesh_terrain_data::QuantizedMeshTerrainData,
+    resource::{self, append_forward_slash, fetch_bytes, join_url, RequestPermit, RequestThrottle},
+};
+
+/// 同时请求的地形瓦片数量上限
+const MAXIMUM_REQUESTS: u32 = 18;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Resource(#[from] resource::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported terrain format {0}")]
+    UnsupportedFormat(String),
+    #[error("unsupported terrain projection {0}")]
+    UnsupportedProjection(String),
+    #[error("layer.json does not specify any tile url template")]
+    NoTileUrlTemplates,
+}
+/// layer.json中`available`的一项，坐标是按照`scheme`编号的瓦片坐标
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableRange {
+    pub start_x: u32,
+    pub start_y: u32,
+    pub end_x: u32,
+    pub end_y: u32,
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerJson {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default = "default_format")]
+    pub format: String,
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+    #[serde(default)]
+    pub tiles: Vec<String>,
+    #[serde(default)]
+    pub available: Option<Vec<Vec<AvailableRange>>>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub metadata_availability: Option<u32>,
+    #[serde(default)]
+    pub projection: Option<String>,
+    #[serde(default)]
+    pub attribution: Option<String>,
+    #[serde(default)]
+    pub minzoom: Option<u32>,
+    #[serde(default)]
+    pub maxzoom: Option<u32>,
+}
+fn default_format() -> String {
+    "quantized-mesh-1.0".to_string()
+}
+fn default_scheme() -> String {
+    "tms".to_string()
+}
+impl LayerJson {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        return Ok(serde_json::from_slice(bytes)?);
+    }
+}
+pub struct CesiumTerrainProviderOptions {
+    pub url: String,
+    pub request_vertex_normals: bool,
+    pub request_water_mask: bool,
+    pub request_metadata: bool,
+}
+impl Default for CesiumTerrainProviderOptions {
+    fn default() -> Self {
+        Self {
+            url: "".to_string(),
+            request_vertex_normals: false,
+            request_water_mask: false,
+            request_metadata: true,
+        }
+    }
+}
+/// 读取[quantized-mesh](https://github.com/CesiumGS/quantized-mesh)格式的地形瓦片，
+/// `url`可以是http(s)地址，也可以是本地目录
+pub struct CesiumTerrainProvider {
+    pub url: String,
+    pub tiling_scheme: GeographicTilingScheme,
+    pub tile_url_templates: Vec<String>,
+    pub version: String,
+    pub scheme: String,
+    pub attribution: Option<String>,
+    pub has_water_mask: bool,
+    pub has_vertex_normals: bool,
+    pub has_metadata: bool,
+    pub request_vertex_normals: bool,
+    pub request_water_mask: bool,
+    pub request_metadata: bool,
+    pub availability: Option<TileAvailability>,
+    pub metadata_availability: Option<u32>,
+    /// 已经加载过metadata的瓦片，只在`metadata_availability`存在时使用
+    pub availability_tiles_loaded: Option<TileAvailability>,
+    pub ready: bool,
+    level_zero_maximum_geometric_error: f64,
+    throttle: RequestThrottle,
+}
+impl CesiumTerrainProvider {
+    pub fn from_layer_json(
+        options: &CesiumTerrainProviderOptions,
+        layer_json: LayerJson,
+    ) -> Result<Self, Error> {
+        if layer_json.format != "quantized-mesh-1.0" {
+            return Err(Error::UnsupportedFormat(layer_json.format));
+        }
+        if let Some(projection) = layer_json.projection.as_ref() {
+            if projection != "EPSG:4326" {
+                return Err(Error::UnsupportedProjection(projection.clone()));
+            }
+        }
+        if layer_json.tiles.is_empty() {
+            return Err(Error::NoTileUrlTemplates);
+        }
+        let tiling_scheme = GeographicTilingScheme::default();
+        let level_zero_maximum_geometric_error =
+            get_estimated_level_zero_geometric_error_for_a_heightmap(
+                &tiling_scheme.ellipsoid,
+                65,
+                tiling_scheme.get_number_of_x_tiles_at_level(0),
+            );
+        let has_extension = |name: &str| layer_json.extensions.iter().any(|x| x == name);
+        let has_vertex_normals = has_extension("octvertexnormals") || has_extension("vertexnormals");
+        let has_water_mask = has_extension("watermask");
+        let has_metadata = has_extension("metadata");
+
+        let mut availability = None;
+        let mut availability_tiles_loaded = None;
+        if let Some(available) = layer_json.available.as_ref() {
+            let maximum_level = layer_json
+                .maxzoom
+                .map(|x| x + 1)
+                .unwrap_or(available.len() as u32);
+            let mut tile_availability = TileAvailability::new(maximum_level);
+            for (level, ranges) in available.iter().enumerate() {
+                add_available_ranges(
+                    &mut tile_availability,
+                    &tiling_scheme,
+                    level as u32,
+                    ranges,
+                    &layer_json.scheme,
+                );
+            }
+            if layer_json.metadata_availability.is_some() {
+                availability_tiles_loaded = Some(TileAvailability::new(maximum_level));
+            }
+            availability = Some(tile_availability);
+        }
+        return Ok(Self {
+            url: append_forward_slash(&options.url),
+            tiling_scheme,
+            tile_url_templates: layer_json.tiles,
+            version: layer_json.version.unwrap_or("1.0.0".to_string()),
+            scheme: layer_json.scheme,
+            attribution: layer_json.attribution,
+            has_water_mask: has_water_mask,
+            has_vertex_normals: has_vertex_normals,
+            has_metadata: has_metadata,
+            request_vertex_normals: options.request_vertex_normals,
+            request_water_mask: options.request_water_mask,
+            request_metadata: options.request_metadata,
+            availability,
+            metadata_availability: layer_json.metadata_availability,
+            availability_tiles_loaded,
+            ready: true,
+            level_zero_maximum_geometric_error,
+            throttle: RequestThrottle::new(MAXIMUM_REQUESTS),
+        });
+    }
+    /// 读取`{url}/layer.json`并创建地形服务，在bevy中一般用[`LoadLayerJsonJob`]
+    pub async fn from_url(options: CesiumTerrainProviderOptions) -> Result<Self, Error> {
+        let layer_json_url = join_url(&options.url, "layer.json");
+        let bytes = fetch_bytes(&layer_json_url, &[]).await?;
+        let layer_json = LayerJson::from_slice(&bytes)?;
+        return Self::from_layer_json(&options, layer_json);
+    }
+    /// 本次请求需要的扩展，只请求服务端声明支持的扩展
+    pub fn get_requested_extensions(&self) -> Vec<&'static str> {
+        let mut extensions = vec![];
+        if self.request_vertex_normals && self.has_vertex_normals {
+            extensions.push("octvertexnormals");
+        }
+        if self.request_water_mask && self.has_water_mask {
+            extensions.push("watermask");
+        }
+        if self.request_metadata && self.has_metadata {
+            extensions.push("metadata");
+        }
+        return extensions;
+    }
+    pub fn get_accept_header(&self) -> String {
+        let extensions = self.get_requested_extensions();
+        if extensions.is_empty() {
+            return "application/vnd.quantized-mesh,application/octet-stream;q=0.9,*/*;q=0.01"
+                .to_string();
+        }
+        return format!(
+            "application/vnd.quantized-mesh;extensions={},application/octet-stream;q=0.9,*/*;q=0.01",
+            extensions.join("-")
+        );
+    }
+    pub fn build_tile_url(&self, key: &TileKey) -> String {
+        let template =
+            &self.tile_url_templates[(key.x + key.y + key.level) as usize % self.tile_url_templates.len()];
+        let y = if self.scheme == "tms" {
+            self.tiling_scheme.get_number_of_y_tiles_at_level(key.level) - key.y - 1
+        } else {
+            key.y
+        };
+        let relative = template
+            .replace("{z}", &key.level.to_string())
+            .replace("{x}", &key.x.to_string())
+            .replace("{y}", &y.to_string())
+            .replace("{version}", &self.version);
+        return join_url(&self.url, &relative);
+    }
+    /// 每隔`metadata_availability`级，瓦片的metadata扩展中记录了之后几级的可用范围
+    pub fn get_availability_tile(&self, key: &TileKey) -> Option<TileKey> {
+        let availability_levels = self.metadata_availability?;
+        if key.level == 0 || availability_levels == 0 {
+            return None;
+        }
+        let parent_level = if key.level % availability_levels == 0 {
+            key.level - availability_levels
+        } else {
+            (key.level / availability_levels) * availability_levels
+        };
+        let divisor = 1 << (key.level - parent_level);
+        return Some(TileKey::new(key.x / divisor, key.y / divisor, parent_level));
+    }
+    /// 把metadata扩展中的`available`加入到可用范围中
+    pub fn add_metadata_availability(&mut self, key: &TileKey, metadata: &str) -> Result<(), Error> {
+        #[derive(Deserialize)]
+        struct Metadata {
+            #[serde(default)]
+            available: Option<Vec<Vec<AvailableRange>>>,
+        }
+        let metadata: Metadata = serde_json::from_str(metadata)?;
+        if let Some(loaded) = self.availability_tiles_loaded.as_mut() {
+            loaded.add_available_tile_range(key.level, key.x, key.y, key.x, key.y);
+        }
+        let (Some(available), Some(availability)) = (metadata.available, self.availability.as_mut()) else {
+            return Ok(());
+        };
+        for (offset, ranges) in available.iter().enumerate() {
+            add_available_ranges(
+                availability,
+                &self.tiling_scheme,
+                key.level + 1 + offset as u32,
+                ranges,
+                &self.scheme,
+            );
+        }
+        return Ok(());
+    }
+}
+fn add_available_ranges(
+    availability: &mut TileAvailability,
+    tiling_scheme: &GeographicTilingScheme,
+    level: u32,
+    ranges: &Vec<AvailableRange>,
+    scheme: &str,
+) {
+    let y_tiles = tiling_scheme.get_number_of_y_tiles_at_level(level);
+    for range in ranges.iter() {
+        if scheme == "tms" {
+            availability.add_available_tile_range(
+                level,
+                range.start_x,
+                y_tiles - range.end_y - 1,
+                range.end_x,
+                y_tiles - range.start_y - 1,
+            );
+        } else {
+            availability.add_available_tile_range(
+                level,
+                range.start_x,
+                range.start_y,
+                range.end_x,
+                range.end_y,
+            );
+        }
+    }
+}
+impl TerrainProvider for CesiumTerrainProvider {
+    fn get_tiling_scheme(&self) -> &GeographicTilingScheme {
+        return &self.tiling_scheme;
+    }
+    fn get_ready(&self) -> bool {
+        self.ready
+    }
+    fn get_has_water_mask(&self) -> bool {
+        self.has_water_mask && self.request_water_mask
+    }
+    fn get_has_vertex_normals(&self) -> bool {
+        self.has_vertex_normals && self.request_vertex_normals
+    }
+    fn get_availability(&mut self) -> Option<&mut TileAvailability> {
+        return self.availability.as_mut();
+    }
+    fn request_tile_geometry(&self, key: &TileKey) -> RequestTileGeometry {
+        if self.get_tile_data_available(key) == Some(false) {
+            return RequestTileGeometry::NotAvailable;
+        }
+        let Some(permit) = self.throttle.try_acquire() else {
+            return RequestTileGeometry::Throttled;
+        };
+        let child_tile_mask = self
+            .availability
+            .as_ref()
+            .map(|availability| {
+                availability.compute_child_mask_for_tile(key.level, key.x, key.y)
+            })
+            .unwrap_or(15);
+        return RequestTileGeometry::Requested(RequestTileGeometryJob {
+            key: *key,
+            request: Box::new(CesiumTerrainTileRequest {
+                url: self.build_tile_url(key),
+                accept: self.get_accept_header(),
+                key: *key,
+                tiling_scheme: self.tiling_scheme.clone(),
+                skirt_height: self.get_level_maximum_geometric_error(key.level) * 5.0,
+                child_tile_mask,
+                _permit: permit,
+            }),
+        });
+    }
+    fn handle_tile_metadata(&mut self, key: &TileKey, metadata: &str) {
+        if let Err(error) = self.add_metadata_availability(key, metadata) {
+            warn!("invalid metadata in terrain tile {:?}: {}", key, error);
+        }
+    }
+    fn get_level_maximum_geometric_error(&self, level: u32) -> f64 {
+        return self.level_zero_maximum_geometric_error / (1 << level) as f64;
+    }
+    fn load_tile_data_availability(&self, key: &TileKey) -> Option<bool> {
+        let availability_tile = self.get_availability_tile(key)?;
+        let loaded = self.availability_tiles_loaded.as_ref()?;
+        if loaded.is_tile_available(
+            availability_tile.level,
+            availability_tile.x,
+            availability_tile.y,
+        ) {
+            return None;
+        }
+        // 需要先请求availability_tile，才能知道这个瓦片是否可用
+        return Some(false);
+    }
+    fn get_tile_data_available(&self, key: &TileKey) -> Option<bool> {
+        let availability = self.availability.as_ref()?;
+        if key.level > availability.maximum_level {
+            return Some(false);
+        }
+        if availability.is_tile_available(key.level, key.x, key.y) {
+            return Some(true);
+        }
+        if !self.has_metadata {
+            return Some(false);
+        }
+        if self.load_tile_data_availability(key).is_some() {
+            // 可用范围还没加载
+            return None;
+        }
+        return Some(false);
+    }
+}
+pub struct LoadLayerJsonJob {
+    pub options: CesiumTerrainProviderOptions,
+}
+impl Job for LoadLayerJsonJob {
+    type Outcome = Result<CesiumTerrainProvider, Error>;
+    fn name(&self) -> String {
+        format!("load layer.json from {}", self.options.url)
+    }
+    fn perform(self, _context: Context) -> AsyncReturn<Self::Outcome> {
+        Box::pin(async move {
+            let fetch = CesiumTerrainProvider::from_url(self.options);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(fetch)
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                fetch.await
+            }
+        })
+    }
+}
+/// 单个quantized-mesh瓦片的请求，持有`permit`直到请求结束
+struct CesiumTerrainTileRequest {
+    url: String,
+    accept: String,
+    key: TileKey,
+    tiling_scheme: GeographicTilingScheme,
+    skirt_height: f64,
+    child_tile_mask: u32,
+    _permit: RequestPermit,
+}
+impl TerrainTileRequest for CesiumTerrainTileRequest {
+    fn perform(
+        self: Box<Self>,
+    ) -> AsyncReturn<Result<TerrainTileResponse, RequestTileGeometryError>> {
+        Box::pin(async move {
+            let bytes = match fetch_bytes(&self.url, &[("Accept", self.accept.as_str())]).await {
+                Ok(bytes) => bytes,
+                // 服务端没有这个瓦片，和可用范围中不存在一样处理
+                Err(resource::Error::Status(_, 404 | 204)) => {
+                    return Err(RequestTileGeometryError::NotAvailable)
+                }
+                Err(resource::Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+                    return Err(RequestTileGeometryError::NotAvailable)
+                }
+                Err(error) => return Err(RequestTileGeometryError::Failed(error.to_string())),
+            };
+            // 损坏或者不完整的瓦片和其他读取错误一样报告
+            let value = match from_reader(bytes.as_slice()) {
+                Ok(value) => value,
+                Err(error) => return Err(RequestTileGeometryError::Failed(error.to_string())),
+            };
+            let metadata = value.extension.metadata.clone();
+            let data = QuantizedMeshTerrainData::from_data(
+                value,
+                self.key,
+                &self.tiling_scheme,
+                self.skirt_height,
+                self.child_tile_mask,
+            );
+            return Ok(TerrainTileResponse {
+                data: data.into(),
+                metadata,
+            });
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const LAYER_JSON: &str = r#"{
+        "tilejson": "2.1.0",
+        "format": "quantized-mesh-1.0",
+        "version": "1.2.0",
+        "scheme": "tms",
+        "tiles": ["{z}/{x}/{y}.terrain?v={version}"],
+        "extensions": ["octvertexnormals", "watermask", "metadata"],
+        "metadataAvailability": 10,
+        "projection": "EPSG:4326",
+        "available": [
+            [{"startX": 0, "startY": 0, "endX": 1, "endY": 0}],
+            [{"startX": 0, "startY": 0, "endX": 3, "endY": 1}],
+            [{"startX": 0, "startY": 0, "endX": 0, "endY": 0}]
+        ]
+    }"#;
+    fn create_provider(url: &str) -> CesiumTerrainProvider {
+        let layer_json = LayerJson::from_slice(LAYER_JSON.as_bytes()).unwrap();
+        let options = CesiumTerrainProviderOptions {
+            url: url.to_string(),
+            request_vertex_normals: true,
+            ..Default::default()
+        };
+        CesiumTerrainProvider::from_layer_json(&options, layer_json).unwrap()
+    }
+    #[test]
+    fn test_parse_layer_json() {
+        let provider = create_provider("http://localhost/terrain");
+        assert!(provider.has_vertex_normals);
+        assert!(provider.has_water_mask);
+        assert!(provider.has_metadata);
+        assert!(provider.metadata_availability == Some(10));
+        assert!(provider.get_has_vertex_normals());
+        assert!(!provider.get_has_water_mask());
+        assert!(
+            provider.get_accept_header()
+                == "application/vnd.quantized-mesh;extensions=octvertexnormals-metadata,application/octet-stream;q=0.9,*/*;q=0.01"
+        );
+    }
+    #[test]
+    fn test_tile_url_uses_tms_y() {
+        let provider = create_provider("http://localhost/terrain");
+        let url = provider.build_tile_url(&TileKey::new(1, 0, 1));
+        assert!(url == "http://localhost/terrain/1/1/1.terrain?v=1.2.0");
+        let provider = create_provider("file:///data/terrain/");
+        let url = provider.build_tile_url(&TileKey::new(0, 0, 0));
+        assert!(url == "file:///data/terrain/0/0/0.terrain?v=1.2.0");
+    }
+    #[test]
+    fn test_availability_from_layer_json() {
+        let provider = create_provider("http://localhost/terrain");
+        assert!(provider.get_tile_data_available(&TileKey::new(1, 0, 0)) == Some(true));
+        assert!(provider.get_tile_data_available(&TileKey::new(3, 1, 1)) == Some(true));
+        // tms的(0,0,2)在南边，对应tiling scheme中的(0,3,2)
+        assert!(provider.get_tile_data_available(&TileKey::new(0, 3, 2)) == Some(true));
+        // 根瓦片的metadata还没加载，无法确定是否可用
+        assert!(provider.get_tile_data_available(&TileKey::new(0, 0, 2)) == None);
+    }
+    #[test]
+    fn test_metadata_availability() {
+        let mut provider = create_provider("http://localhost/terrain");
+        provider
+            .add_metadata_availability(
+                &TileKey::new(0, 0, 0),
+                r#"{"available": [[], [{"startX": 0, "startY": 2, "endX": 0, "endY": 3}]]}"#,
+            )
+            .unwrap();
+        assert!(provider.get_tile_data_available(&TileKey::new(0, 0, 2)) == Some(true));
+        assert!(provider.get_tile_data_available(&TileKey::new(1, 0, 2)) == Some(false));
+    }
+    #[test]
+    fn test_request_tile_geometry() {
+        let provider = create_provider("http://localhost/terrain");
+        assert!(matches!(
+            provider.request_tile_geometry(&TileKey::new(0, 0, 4)),
+            RequestTileGeometry::NotAvailable
+        ));
+        let mut jobs = vec![];
+        for _ in 0..MAXIMUM_REQUESTS {
+            let request = provider.request_tile_geometry(&TileKey::new(0, 0, 0));
+            assert!(matches!(request, RequestTileGeometry::Requested(_)));
+            jobs.push(request);
+        }
+        assert!(matches!(
+            provider.request_tile_geometry(&TileKey::new(0, 0, 0)),
+            RequestTileGeometry::Throttled
+        ));
+        // 任务结束后释放请求名额
+        jobs.pop();
+        assert!(matches!(
+            provider.request_tile_geometry(&TileKey::new(0, 0, 0)),
+            RequestTileGeometry::Requested(_)
+        ));
+    }
+    #[test]
+    fn test_fetch_local_terrain_tile() {
+        let path = format!(
+            "file://{}/../quantized-mesh-decoder/assets/tile-with-extensions.terrain",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let bytes = pollster::block_on(fetch_bytes(&path, &[])).unwrap();
+        let data = from_reader(bytes.as_slice()).unwrap();
+        assert!(data.extension.vertex_normals.is_some());
+        assert!(data.extension.water_mask.is_some());
+    }
+}
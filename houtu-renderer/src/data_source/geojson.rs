@@ -0,0 +1,536 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use houtu_scene::{Cartographic, PolygonHierarchy};
+use serde_json::{Map, Value};
+
+use super::{default_feature_style, spawn_features, Feature, FeatureGeometry, FeatureStyleFn};
+
+#[derive(thiserror::Error, Debug)]
+pub enum GeoJsonError {
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported type: {0}")]
+    UnsupportedType(String),
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
+    #[error("invalid coordinates")]
+    InvalidCoordinates,
+    #[error("invalid arc index: {0}")]
+    InvalidArc(i64),
+}
+/// GeoJSON或TopoJSON文件中的所有要素
+#[derive(TypeUuid, TypePath, Debug, Clone, Default)]
+#[uuid = "5d8e3a61-2c4b-4f9a-b7e0-91c6d2f4a8b3"]
+pub struct GeoJsonDataSource {
+    pub features: Vec<Feature>,
+}
+impl GeoJsonDataSource {
+    /// 根据type字段区分GeoJSON和TopoJSON
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, GeoJsonError> {
+        let value: Value = serde_json::from_slice(bytes)?;
+        let features = if value.get("type").and_then(|v| v.as_str()) == Some("Topology") {
+            parse_topojson(&value)?
+        } else {
+            parse_geojson(&value)?
+        };
+        return Ok(Self { features });
+    }
+}
+#[derive(Default)]
+pub struct GeoJsonLoader;
+impl AssetLoader for GeoJsonLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let source = GeoJsonDataSource::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(source));
+            Ok(())
+        })
+    }
+    fn extensions(&self) -> &[&str] {
+        &["geojson", "topojson"]
+    }
+}
+/// 加载完成后把要素生成为子实体，数据或样式改变时重新生成
+#[derive(Component, Clone)]
+pub struct GeoJsonLayer {
+    pub source: Handle<GeoJsonDataSource>,
+    /// 为None时使用default_feature_style
+    pub style: Option<FeatureStyleFn>,
+}
+impl GeoJsonLayer {
+    pub fn new(source: Handle<GeoJsonDataSource>) -> Self {
+        Self {
+            source,
+            style: None,
+        }
+    }
+}
+/// 已经生成了要素的图层
+#[derive(Component)]
+pub struct GeoJsonLayerLoaded;
+
+pub fn geojson_layer_system(
+    mut commands: Commands,
+    sources: Res<Assets<GeoJsonDataSource>>,
+    mut source_events: EventReader<AssetEvent<GeoJsonDataSource>>,
+    layers: Query<(
+        Entity,
+        Ref<GeoJsonLayer>,
+        Option<&GeoJsonLayerLoaded>,
+        Option<&Visibility>,
+    )>,
+) {
+    let modified: Vec<Handle<GeoJsonDataSource>> = source_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone_weak()),
+            _ => None,
+        })
+        .collect();
+    for (entity, layer, loaded, visibility) in layers.iter() {
+        if loaded.is_some() && !layer.is_changed() && !modified.contains(&layer.source) {
+            continue;
+        }
+        let Some(source) = sources.get(&layer.source) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        if visibility.is_none() {
+            entity_commands.insert(SpatialBundle::INHERITED_IDENTITY);
+        }
+        entity_commands
+            .despawn_descendants()
+            .insert(GeoJsonLayerLoaded)
+            .with_children(|parent| match &layer.style {
+                Some(style) => spawn_features(parent, &source.features, style.as_ref()),
+                None => spawn_features(parent, &source.features, &default_feature_style),
+            });
+    }
+}
+
+/// 经度、纬度和可选的高度
+fn parse_coordinates(value: &Value) -> Result<[f64; 3], GeoJsonError> {
+    let coordinates = value.as_array().ok_or(GeoJsonError::InvalidCoordinates)?;
+    let get = |i: usize| coordinates.get(i).and_then(|v| v.as_f64());
+    let (Some(x), Some(y)) = (get(0), get(1)) else {
+        return Err(GeoJsonError::InvalidCoordinates);
+    };
+    return Ok([x, y, get(2).unwrap_or(0.0)]);
+}
+fn parse_position(value: &Value) -> Result<Cartographic, GeoJsonError> {
+    let [longitude, latitude, height] = parse_coordinates(value)?;
+    return Ok(Cartographic::from_degrees(longitude, latitude, height));
+}
+fn parse_positions(value: &Value) -> Result<Vec<Cartographic>, GeoJsonError> {
+    let array = value.as_array().ok_or(GeoJsonError::InvalidCoordinates)?;
+    return array.iter().map(parse_position).collect();
+}
+/// 第一个环是外环，其余的是洞
+fn rings_to_polygon(mut rings: Vec<Vec<Cartographic>>) -> Result<PolygonHierarchy, GeoJsonError> {
+    if rings.is_empty() {
+        return Err(GeoJsonError::InvalidCoordinates);
+    }
+    let mut hierarchy = PolygonHierarchy::new(rings.remove(0));
+    hierarchy.holes = rings.into_iter().map(PolygonHierarchy::new).collect();
+    return Ok(hierarchy);
+}
+fn parse_polygon(value: &Value) -> Result<PolygonHierarchy, GeoJsonError> {
+    let rings = value
+        .as_array()
+        .ok_or(GeoJsonError::InvalidCoordinates)?
+        .iter()
+        .map(parse_positions)
+        .collect::<Result<Vec<_>, _>>()?;
+    return rings_to_polygon(rings);
+}
+fn array_field<'a>(value: &'a Value, name: &'static str) -> Result<&'a Vec<Value>, GeoJsonError> {
+    return value
+        .get(name)
+        .and_then(|v| v.as_array())
+        .ok_or(GeoJsonError::MissingField(name));
+}
+fn parse_geometry(geometry: &Value, result: &mut Vec<FeatureGeometry>) -> Result<(), GeoJsonError> {
+    // 要素的geometry可以为null
+    if geometry.is_null() {
+        return Ok(());
+    }
+    let geometry_type = geometry
+        .get("type")
+        .and_then(|v| v.as_str())
+        .ok_or(GeoJsonError::MissingField("type"))?;
+    if geometry_type == "GeometryCollection" {
+        let geometries = array_field(geometry, "geometries")?;
+        for geometry in geometries.iter() {
+            parse_geometry(geometry, result)?;
+        }
+        return Ok(());
+    }
+    let coordinates = geometry
+        .get("coordinates")
+        .ok_or(GeoJsonError::MissingField("coordinates"))?;
+    match geometry_type {
+        "Point" => result.push(FeatureGeometry::Point(parse_position(coordinates)?)),
+        "MultiPoint" => {
+            for position in parse_positions(coordinates)? {
+                result.push(FeatureGeometry::Point(position));
+            }
+        }
+        "LineString" => result.push(FeatureGeometry::LineString(parse_positions(coordinates)?)),
+        "MultiLineString" => {
+            for part in array_field(geometry, "coordinates")?.iter() {
+                result.push(FeatureGeometry::LineString(parse_positions(part)?));
+            }
+        }
+        "Polygon" => result.push(FeatureGeometry::Polygon(parse_polygon(coordinates)?)),
+        "MultiPolygon" => {
+            for part in array_field(geometry, "coordinates")?.iter() {
+                result.push(FeatureGeometry::Polygon(parse_polygon(part)?));
+            }
+        }
+        _ => return Err(GeoJsonError::UnsupportedType(geometry_type.to_string())),
+    }
+    return Ok(());
+}
+fn parse_id(value: &Value) -> Option<String> {
+    return match value.get("id")? {
+        Value::String(v) => Some(v.clone()),
+        Value::Null => None,
+        v => Some(v.to_string()),
+    };
+}
+fn parse_properties(value: &Value) -> Map<String, Value> {
+    return value
+        .get("properties")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
+}
+pub fn parse_geojson(value: &Value) -> Result<Vec<Feature>, GeoJsonError> {
+    let geojson_type = value
+        .get("type")
+        .and_then(|v| v.as_str())
+        .ok_or(GeoJsonError::MissingField("type"))?;
+    let mut result: Vec<Feature> = vec![];
+    match geojson_type {
+        "FeatureCollection" => {
+            let features = array_field(value, "features")?;
+            for feature in features.iter() {
+                result.extend(parse_geojson(feature)?);
+            }
+        }
+        "Feature" => {
+            let mut geometries = vec![];
+            parse_geometry(
+                value.get("geometry").unwrap_or(&Value::Null),
+                &mut geometries,
+            )?;
+            result.push(Feature {
+                id: parse_id(value),
+                properties: parse_properties(value),
+                geometries,
+            });
+        }
+        // 单独的几何对象
+        _ => {
+            let mut geometries = vec![];
+            parse_geometry(value, &mut geometries)?;
+            result.push(Feature {
+                geometries,
+                ..Default::default()
+            });
+        }
+    }
+    return Ok(result);
+}
+
+/// 解码后的弧段，量化的坐标已经还原
+struct Topology {
+    arcs: Vec<Vec<Cartographic>>,
+    /// scale和translate
+    transform: Option<([f64; 2], [f64; 2])>,
+}
+impl Topology {
+    fn new(value: &Value) -> Result<Self, GeoJsonError> {
+        let pair = |v: Option<&Value>| -> Option<[f64; 2]> {
+            let v = v?.as_array()?;
+            return Some([v.get(0)?.as_f64()?, v.get(1)?.as_f64()?]);
+        };
+        let transform = match value.get("transform") {
+            Some(transform) => Some((
+                pair(transform.get("scale")).ok_or(GeoJsonError::MissingField("scale"))?,
+                pair(transform.get("translate")).ok_or(GeoJsonError::MissingField("translate"))?,
+            )),
+            None => None,
+        };
+        let arcs = array_field(value, "arcs")?;
+        let mut topology = Self {
+            arcs: Vec::with_capacity(arcs.len()),
+            transform,
+        };
+        for arc in arcs.iter() {
+            let arc = arc.as_array().ok_or(GeoJsonError::InvalidCoordinates)?;
+            let mut positions: Vec<Cartographic> = Vec::with_capacity(arc.len());
+            // 量化后的弧段是差分编码的
+            let (mut x, mut y) = (0.0, 0.0);
+            for position in arc.iter() {
+                let [dx, dy, height] = parse_coordinates(position)?;
+                let position = match topology.transform {
+                    Some((scale, translate)) => {
+                        x += dx;
+                        y += dy;
+                        [x * scale[0] + translate[0], y * scale[1] + translate[1]]
+                    }
+                    None => [dx, dy],
+                };
+                positions.push(Cartographic::from_degrees(position[0], position[1], height));
+            }
+            topology.arcs.push(positions);
+        }
+        return Ok(topology);
+    }
+    /// 点的坐标是量化的但不是差分编码的
+    fn parse_position(&self, value: &Value) -> Result<Cartographic, GeoJsonError> {
+        let [mut longitude, mut latitude, height] = parse_coordinates(value)?;
+        if let Some((scale, translate)) = self.transform {
+            longitude = longitude * scale[0] + translate[0];
+            latitude = latitude * scale[1] + translate[1];
+        }
+        return Ok(Cartographic::from_degrees(longitude, latitude, height));
+    }
+    /// 拼接弧段，负的下标~i表示反向的第i个弧段，相邻弧段共享端点
+    fn line(&self, indices: &Value) -> Result<Vec<Cartographic>, GeoJsonError> {
+        let indices = indices.as_array().ok_or(GeoJsonError::InvalidCoordinates)?;
+        let mut result: Vec<Cartographic> = vec![];
+        for index in indices.iter() {
+            let index = index.as_i64().ok_or(GeoJsonError::InvalidCoordinates)?;
+            let (i, reversed) = if index < 0 {
+                (!index, true)
+            } else {
+                (index, false)
+            };
+            let arc = self
+                .arcs
+                .get(i as usize)
+                .ok_or(GeoJsonError::InvalidArc(index))?;
+            let mut positions = arc.clone();
+            if reversed {
+                positions.reverse();
+            }
+            if !result.is_empty() && !positions.is_empty() {
+                positions.remove(0);
+            }
+            result.extend(positions);
+        }
+        return Ok(result);
+    }
+    fn polygon(&self, rings: &Value) -> Result<PolygonHierarchy, GeoJsonError> {
+        let rings = rings
+            .as_array()
+            .ok_or(GeoJsonError::InvalidCoordinates)?
+            .iter()
+            .map(|v| self.line(v))
+            .collect::<Result<Vec<_>, _>>()?;
+        return rings_to_polygon(rings);
+    }
+    fn geometry(&self, geometry: &Value, result: &mut Vec<Feature>) -> Result<(), GeoJsonError> {
+        // 没有几何的对象type为null
+        let geometry_type = match geometry.get("type") {
+            Some(Value::Null) => "null",
+            v => v
+                .and_then(|v| v.as_str())
+                .ok_or(GeoJsonError::MissingField("type"))?,
+        };
+        if geometry_type == "GeometryCollection" {
+            let geometries = array_field(geometry, "geometries")?;
+            for geometry in geometries.iter() {
+                self.geometry(geometry, result)?;
+            }
+            return Ok(());
+        }
+        let mut geometries: Vec<FeatureGeometry> = vec![];
+        match geometry_type {
+            "Point" => {
+                let coordinates = geometry
+                    .get("coordinates")
+                    .ok_or(GeoJsonError::MissingField("coordinates"))?;
+                geometries.push(FeatureGeometry::Point(self.parse_position(coordinates)?));
+            }
+            "MultiPoint" => {
+                for position in array_field(geometry, "coordinates")?.iter() {
+                    geometries.push(FeatureGeometry::Point(self.parse_position(position)?));
+                }
+            }
+            "LineString" => {
+                let arcs = geometry
+                    .get("arcs")
+                    .ok_or(GeoJsonError::MissingField("arcs"))?;
+                geometries.push(FeatureGeometry::LineString(self.line(arcs)?));
+            }
+            "MultiLineString" => {
+                for arcs in array_field(geometry, "arcs")?.iter() {
+                    geometries.push(FeatureGeometry::LineString(self.line(arcs)?));
+                }
+            }
+            "Polygon" => {
+                let arcs = geometry
+                    .get("arcs")
+                    .ok_or(GeoJsonError::MissingField("arcs"))?;
+                geometries.push(FeatureGeometry::Polygon(self.polygon(arcs)?));
+            }
+            "MultiPolygon" => {
+                for arcs in array_field(geometry, "arcs")?.iter() {
+                    geometries.push(FeatureGeometry::Polygon(self.polygon(arcs)?));
+                }
+            }
+            "null" => {}
+            _ => return Err(GeoJsonError::UnsupportedType(geometry_type.to_string())),
+        }
+        result.push(Feature {
+            id: parse_id(geometry),
+            properties: parse_properties(geometry),
+            geometries,
+        });
+        return Ok(());
+    }
+}
+pub fn parse_topojson(value: &Value) -> Result<Vec<Feature>, GeoJsonError> {
+    let topology = Topology::new(value)?;
+    let objects = value
+        .get("objects")
+        .and_then(|v| v.as_object())
+        .ok_or(GeoJsonError::MissingField("objects"))?;
+    let mut result: Vec<Feature> = vec![];
+    for object in objects.values() {
+        topology.geometry(object, &mut result)?;
+    }
+    return Ok(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use houtu_scene::EPSILON10;
+    use serde_json::json;
+
+    use super::*;
+
+    fn assert_degrees(position: &Cartographic, longitude: f64, latitude: f64) {
+        assert!(position.equals_epsilon(
+            Cartographic::from_degrees(longitude, latitude, 0.0),
+            EPSILON10
+        ));
+    }
+    #[test]
+    fn test_parse_geojson() {
+        let value = json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "id": 1,
+                    "properties": {"name": "a"},
+                    "geometry": {"type": "MultiPoint", "coordinates": [[120.0, 30.0], [121.0, 31.0, 10.0]]}
+                },
+                {
+                    "type": "Feature",
+                    "properties": null,
+                    "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}
+                },
+                {
+                    "type": "Feature",
+                    "id": "parcel",
+                    "properties": {"zone": "R1"},
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [
+                            [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0]],
+                            [[2.0, 2.0], [2.0, 4.0], [4.0, 4.0], [2.0, 2.0]]
+                        ]
+                    }
+                },
+                {"type": "Feature", "properties": {}, "geometry": null}
+            ]
+        });
+        let features = parse_geojson(&value).unwrap();
+        assert_eq!(features.len(), 4);
+        assert_eq!(features[0].id.as_deref(), Some("1"));
+        assert_eq!(features[0].properties["name"], "a");
+        assert_eq!(features[0].geometries.len(), 2);
+        let FeatureGeometry::Point(point) = &features[0].geometries[1] else {
+            panic!("expect point");
+        };
+        assert_degrees(point, 121.0, 31.0);
+        assert_eq!(point.height, 10.0);
+        assert!(features[1].properties.is_empty());
+        assert!(
+            matches!(&features[1].geometries[0], FeatureGeometry::LineString(v) if v.len() == 2)
+        );
+        let FeatureGeometry::Polygon(polygon) = &features[2].geometries[0] else {
+            panic!("expect polygon");
+        };
+        assert_eq!(polygon.positions.len(), 5);
+        assert_eq!(polygon.holes.len(), 1);
+        assert!(features[3].geometries.is_empty());
+
+        let invalid = json!({"type": "Feature", "geometry": {"type": "Circle", "coordinates": []}});
+        assert!(matches!(
+            parse_geojson(&invalid),
+            Err(GeoJsonError::UnsupportedType(_))
+        ));
+    }
+    #[test]
+    fn test_parse_topojson() {
+        // TopoJSON规范中的例子
+        let bytes = br#"{
+            "type": "Topology",
+            "transform": {"scale": [0.0005, 0.0001], "translate": [100, 0]},
+            "objects": {
+                "example": {
+                    "type": "GeometryCollection",
+                    "geometries": [
+                        {"type": "Point", "properties": {"prop0": "value0"}, "coordinates": [4000, 5000]},
+                        {"type": "LineString", "properties": {"prop0": "value0", "prop1": 0}, "arcs": [0]},
+                        {"type": "Polygon", "properties": {"prop0": "value0", "prop1": {"this": "that"}}, "arcs": [[-2]]}
+                    ]
+                }
+            },
+            "arcs": [
+                [[4000, 0], [1999, 9999], [2000, -9999], [2000, 9999]],
+                [[0, 0], [0, 9999], [2000, 0], [0, -9999], [-2000, 0]]
+            ]
+        }"#;
+        let source = GeoJsonDataSource::from_slice(bytes).unwrap();
+        let features = &source.features;
+        assert_eq!(features.len(), 3);
+        let FeatureGeometry::Point(point) = &features[0].geometries[0] else {
+            panic!("expect point");
+        };
+        assert_degrees(point, 102.0, 0.5);
+        let FeatureGeometry::LineString(line) = &features[1].geometries[0] else {
+            panic!("expect line");
+        };
+        assert_eq!(line.len(), 4);
+        assert_degrees(&line[1], 102.9995, 0.9999);
+        assert_degrees(&line[3], 104.9995, 0.9999);
+        let FeatureGeometry::Polygon(polygon) = &features[2].geometries[0] else {
+            panic!("expect polygon");
+        };
+        // 反向的弧段
+        assert_eq!(polygon.positions.len(), 5);
+        assert_degrees(&polygon.positions[1], 101.0, 0.0);
+        assert_eq!(features[2].properties["prop1"]["this"], "that");
+
+        let invalid = json!({"type": "Topology", "objects": {"a": {"type": "LineString", "arcs": [3]}}, "arcs": []});
+        assert!(matches!(
+            parse_topojson(&invalid),
+            Err(GeoJsonError::InvalidArc(3))
+        ));
+    }
+}
@@ -0,0 +1,826 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::texture::{CompressedImageFormats, ImageType},
+    utils::BoxedFuture,
+};
+use houtu_scene::{Cartographic, PolygonHierarchy, Rectangle};
+use roxmltree::Node;
+use serde_json::{Map, Value};
+
+use super::{spawn_features, Feature, FeatureGeometry, FeatureStyle};
+use crate::{
+    quadtree::{
+        imagery_layer::{ImageryLayer, ImageryLayerId},
+        imagery_layer_storage::ImageryLayerStorage,
+        imagery_storage::ImageryStorage,
+        quadtree_primitive::QuadtreePrimitive,
+    },
+    single_tile_imagery_provider::SingleTileImageryProvider,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum KmlError {
+    #[error("invalid xml: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("invalid kmz: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid utf8: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("kmz has no kml document")]
+    MissingDocument,
+    #[error("invalid coordinates: {0}")]
+    InvalidCoordinates(String),
+}
+/// Style中和绘制有关的部分，没有设置的字段为None，StyleMap只使用normal状态
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KmlStyle {
+    pub icon_color: Option<Color>,
+    pub icon_scale: Option<f32>,
+    pub icon_href: Option<String>,
+    pub line_color: Option<Color>,
+    pub line_width: Option<f32>,
+    pub poly_color: Option<Color>,
+    pub poly_fill: Option<bool>,
+    pub poly_outline: Option<bool>,
+}
+impl KmlStyle {
+    /// other中设置了的字段覆盖self，用于内联样式覆盖共享样式
+    fn merge(mut self, other: &KmlStyle) -> Self {
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field.clone();
+                })*
+            };
+        }
+        merge!(
+            icon_color,
+            icon_scale,
+            icon_href,
+            line_color,
+            line_width,
+            poly_color,
+            poly_fill,
+            poly_outline
+        );
+        return self;
+    }
+    pub fn to_feature_style(&self, marker_image: Option<Handle<Image>>) -> FeatureStyle {
+        let mut style = FeatureStyle::default();
+        if let Some(color) = self.icon_color {
+            style.marker_color = color;
+        }
+        if let Some(scale) = self.icon_scale {
+            style.marker_size *= scale;
+        }
+        style.marker_image = marker_image;
+        if let Some(color) = self.line_color {
+            style.stroke = color;
+        }
+        if let Some(width) = self.line_width {
+            style.stroke_width = width;
+        }
+        if let Some(color) = self.poly_color {
+            style.fill = color;
+        }
+        if self.poly_fill == Some(false) {
+            style.fill.set_a(0.0);
+        }
+        if let Some(outline) = self.poly_outline {
+            style.outline = outline;
+        }
+        return style;
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AltitudeMode {
+    #[default]
+    ClampToGround,
+    RelativeToGround,
+    Absolute,
+}
+impl AltitudeMode {
+    fn parse(value: &str) -> Self {
+        match value {
+            "relativeToGround" | "relativeToSeaFloor" => Self::RelativeToGround,
+            "absolute" => Self::Absolute,
+            _ => Self::ClampToGround,
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KmlPlacemarkDescription {
+    pub feature: Feature,
+    pub style: KmlStyle,
+    pub altitude_mode: AltitudeMode,
+    pub extrude: bool,
+}
+impl KmlPlacemarkDescription {
+    /// 贴地时忽略高度，相对地面时高度加上地形高度，拉伸时从顶面拉伸到地面
+    fn to_placemark(&self, marker_image: Option<Handle<Image>>) -> KmlPlacemark {
+        let mut feature = self.feature.clone();
+        let mut style = self.style.to_feature_style(marker_image);
+        if self.altitude_mode == AltitudeMode::ClampToGround {
+            for geometry in feature.geometries.iter_mut() {
+                match geometry {
+                    FeatureGeometry::Point(position) => position.height = 0.0,
+                    FeatureGeometry::LineString(positions) => {
+                        positions.iter_mut().for_each(|v| v.height = 0.0)
+                    }
+                    FeatureGeometry::Polygon(hierarchy) => clear_heights(hierarchy),
+                }
+            }
+        } else if self.extrude {
+            style.extruded_height = Some(0.0);
+        }
+        style.clamp_to_ground = self.altitude_mode != AltitudeMode::Absolute;
+        style.clamp_lines_to_ground = style.clamp_to_ground;
+        return KmlPlacemark { feature, style };
+    }
+}
+fn clear_heights(hierarchy: &mut PolygonHierarchy) {
+    hierarchy.positions.iter_mut().for_each(|v| v.height = 0.0);
+    hierarchy.holes.iter_mut().for_each(clear_heights);
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct KmlGroundOverlayDescription {
+    pub name: Option<String>,
+    pub href: String,
+    pub rectangle: Rectangle,
+    /// color中的透明度，用作影像图层的alpha
+    pub alpha: f64,
+}
+/// 解析后的KML文档，图片还只是地址
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KmlDocument {
+    pub placemarks: Vec<KmlPlacemarkDescription>,
+    pub ground_overlays: Vec<KmlGroundOverlayDescription>,
+}
+impl KmlDocument {
+    pub fn parse(xml: &str) -> Result<Self, KmlError> {
+        let document = roxmltree::Document::parse(xml)?;
+        let root = document.root_element();
+        let styles = parse_shared_styles(root);
+        let mut result = Self::default();
+        for node in root.descendants().filter(|x| x.is_element()) {
+            match node.tag_name().name() {
+                "Placemark" => result.placemarks.push(parse_placemark(node, &styles)?),
+                "GroundOverlay" => {
+                    if let Some(overlay) = parse_ground_overlay(node) {
+                        result.ground_overlays.push(overlay);
+                    }
+                }
+                _ => {}
+            }
+        }
+        return Ok(result);
+    }
+}
+#[derive(Debug, Clone)]
+pub struct KmlPlacemark {
+    pub feature: Feature,
+    pub style: FeatureStyle,
+}
+#[derive(Debug, Clone)]
+pub struct KmlGroundOverlay {
+    pub name: Option<String>,
+    pub image: Handle<Image>,
+    pub rectangle: Rectangle,
+    pub alpha: f64,
+}
+/// KML或KMZ文件中的地标和地面叠加层，KMZ中的图片作为子资源加载
+#[derive(TypeUuid, TypePath, Debug, Clone, Default)]
+#[uuid = "c3f1a7e4-8b2d-4e6a-9d15-6f0b2a8c4e71"]
+pub struct KmlDataSource {
+    pub placemarks: Vec<KmlPlacemark>,
+    pub ground_overlays: Vec<KmlGroundOverlay>,
+}
+/// 返回压缩包中的第一个kml文件和其余的文件
+pub fn read_kmz(bytes: &[u8]) -> Result<(String, HashMap<String, Vec<u8>>), KmlError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let mut document = None;
+    let mut files = HashMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let mut content = vec![];
+        file.read_to_end(&mut content)?;
+        let name = file.name().to_string();
+        // 规范规定使用第一个kml文件，一般是根目录下的doc.kml
+        if document.is_none() && name.to_lowercase().ends_with(".kml") {
+            document = Some(std::str::from_utf8(&content)?.to_string());
+            continue;
+        }
+        files.insert(name, content);
+    }
+    let document = document.ok_or(KmlError::MissingDocument)?;
+    return Ok((document, files));
+}
+#[derive(Default)]
+pub struct KmlLoader;
+impl AssetLoader for KmlLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            // zip文件以PK开头
+            let (xml, files) = if bytes.starts_with(b"PK") {
+                read_kmz(bytes)?
+            } else {
+                (std::str::from_utf8(bytes)?.to_string(), HashMap::new())
+            };
+            let document = KmlDocument::parse(&xml)?;
+            let mut resolver = ImageResolver {
+                files,
+                images: HashMap::new(),
+                dependencies: vec![],
+            };
+            let placemarks = document
+                .placemarks
+                .iter()
+                .map(|v| {
+                    let image = v
+                        .style
+                        .icon_href
+                        .as_ref()
+                        .and_then(|href| resolver.resolve(load_context, href));
+                    return v.to_placemark(image);
+                })
+                .collect();
+            let ground_overlays = document
+                .ground_overlays
+                .iter()
+                .filter_map(|v| {
+                    let image = resolver.resolve(load_context, &v.href)?;
+                    return Some(KmlGroundOverlay {
+                        name: v.name.clone(),
+                        image,
+                        rectangle: v.rectangle,
+                        alpha: v.alpha,
+                    });
+                })
+                .collect();
+            let source = KmlDataSource {
+                placemarks,
+                ground_overlays,
+            };
+            load_context.set_default_asset(
+                LoadedAsset::new(source).with_dependencies(resolver.dependencies),
+            );
+            Ok(())
+        })
+    }
+    fn extensions(&self) -> &[&str] {
+        &["kml", "kmz"]
+    }
+}
+/// KMZ中的图片解码为子资源，其他图片相对于KML文件加载
+struct ImageResolver {
+    files: HashMap<String, Vec<u8>>,
+    images: HashMap<String, Handle<Image>>,
+    dependencies: Vec<AssetPath<'static>>,
+}
+impl ImageResolver {
+    fn resolve(&mut self, load_context: &mut LoadContext, href: &str) -> Option<Handle<Image>> {
+        let href = href.trim();
+        if href.is_empty() {
+            return None;
+        }
+        if let Some(handle) = self.images.get(href) {
+            return Some(handle.clone());
+        }
+        let name = href.trim_start_matches("./");
+        let handle = if let Some(bytes) = self.files.get(name) {
+            let extension = Path::new(name).extension()?.to_str()?.to_lowercase();
+            let image = match Image::from_buffer(
+                bytes,
+                ImageType::Extension(&extension),
+                CompressedImageFormats::NONE,
+                true,
+            ) {
+                Ok(v) => v,
+                Err(error) => {
+                    warn!("failed to decode {} in kmz: {}", name, error);
+                    return None;
+                }
+            };
+            load_context.set_labeled_asset(name, LoadedAsset::new(image))
+        } else {
+            let path = if href.contains("://") {
+                AssetPath::from(href.to_string())
+            } else {
+                let parent = load_context.path().parent().unwrap_or(Path::new(""));
+                AssetPath::from(parent.join(name))
+            };
+            let handle = load_context.get_handle(path.get_id());
+            self.dependencies.push(path);
+            handle
+        };
+        self.images.insert(href.to_string(), handle.clone());
+        return Some(handle);
+    }
+}
+/// 加载完成后生成地标，地面叠加层添加为影像图层
+#[derive(Component, Clone)]
+pub struct KmlLayer {
+    pub source: Handle<KmlDataSource>,
+}
+impl KmlLayer {
+    pub fn new(source: Handle<KmlDataSource>) -> Self {
+        Self { source }
+    }
+}
+#[derive(Component)]
+pub struct KmlLayerLoaded;
+
+/// 地面叠加层添加的影像图层和当前是否显示
+struct GroundOverlayLayers {
+    ids: Vec<ImageryLayerId>,
+    show: bool,
+}
+impl GroundOverlayLayers {
+    /// 重新加载或删除KmlLayer时，旧的影像图层连同瓦片上的影像一起删除
+    fn remove(
+        &self,
+        primitive: &mut QuadtreePrimitive,
+        imagery_layer_storage: &mut ImageryLayerStorage,
+        imagery_storage: &mut ImageryStorage,
+    ) {
+        for id in self.ids.iter() {
+            primitive.remove_imagery_layer(id, imagery_layer_storage, imagery_storage);
+        }
+    }
+}
+pub fn kml_layer_system(
+    mut commands: Commands,
+    sources: Res<Assets<KmlDataSource>>,
+    mut source_events: EventReader<AssetEvent<KmlDataSource>>,
+    mut removed_layers: RemovedComponents<KmlLayer>,
+    mut primitive: ResMut<QuadtreePrimitive>,
+    mut imagery_layer_storage: ResMut<ImageryLayerStorage>,
+    mut imagery_storage: ResMut<ImageryStorage>,
+    mut overlays: Local<HashMap<Entity, GroundOverlayLayers>>,
+    layers: Query<(
+        Entity,
+        Ref<KmlLayer>,
+        Option<&KmlLayerLoaded>,
+        Option<&Visibility>,
+    )>,
+) {
+    for entity in removed_layers.iter() {
+        if let Some(layers) = overlays.remove(&entity) {
+            layers.remove(
+                &mut primitive,
+                &mut imagery_layer_storage,
+                &mut imagery_storage,
+            );
+        }
+    }
+    let modified: Vec<Handle<KmlDataSource>> = source_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone_weak()),
+            _ => None,
+        })
+        .collect();
+    for (entity, layer, loaded, visibility) in layers.iter() {
+        let show = visibility.map_or(true, |v| *v != Visibility::Hidden);
+        if loaded.is_some() && !layer.is_changed() && !modified.contains(&layer.source) {
+            // 地面叠加层不是子实体，跟随图层实体的Visibility显示或隐藏
+            let Some(layers) = overlays.get_mut(&entity) else {
+                continue;
+            };
+            if layers.show != show {
+                layers.show = show;
+                for id in layers.ids.iter() {
+                    primitive.set_imagery_layer_show(
+                        id,
+                        show,
+                        &mut imagery_layer_storage,
+                        &mut imagery_storage,
+                    );
+                }
+            }
+            continue;
+        }
+        let Some(source) = sources.get(&layer.source) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        if visibility.is_none() {
+            entity_commands.insert(SpatialBundle::INHERITED_IDENTITY);
+        }
+        entity_commands
+            .despawn_descendants()
+            .insert(KmlLayerLoaded)
+            .with_children(|parent| {
+                for placemark in source.placemarks.iter() {
+                    spawn_features(parent, std::slice::from_ref(&placemark.feature), &|_| {
+                        placemark.style.clone()
+                    });
+                }
+            });
+        if let Some(layers) = overlays.remove(&entity) {
+            layers.remove(
+                &mut primitive,
+                &mut imagery_layer_storage,
+                &mut imagery_storage,
+            );
+        }
+        let ids = source
+            .ground_overlays
+            .iter()
+            .map(|overlay| {
+                let provider =
+                    SingleTileImageryProvider::new(overlay.image.clone(), overlay.rectangle);
+                let mut imagery_layer = ImageryLayer::new(Box::new(provider), &mut imagery_storage);
+                imagery_layer.alpha = overlay.alpha;
+                imagery_layer.show = show;
+                return primitive.add_imagery_layer(
+                    imagery_layer,
+                    &mut imagery_layer_storage,
+                    &mut imagery_storage,
+                );
+            })
+            .collect();
+        overlays.insert(entity, GroundOverlayLayers { ids, show });
+    }
+}
+
+fn is_element(node: &Node, name: &str) -> bool {
+    return node.is_element() && node.tag_name().name() == name;
+}
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    return node.children().find(|x| is_element(x, name));
+}
+fn child_text(node: Node, name: &str) -> Option<String> {
+    return child(node, name)
+        .and_then(|x| x.text())
+        .map(|x| x.trim().to_string());
+}
+fn child_number<T: std::str::FromStr>(node: Node, name: &str) -> Option<T> {
+    return child_text(node, name).and_then(|x| x.parse().ok());
+}
+fn child_bool(node: Node, name: &str) -> Option<bool> {
+    return child_text(node, name).map(|x| x == "1" || x == "true");
+}
+/// KML的颜色是aabbggrr顺序的16进制
+pub fn parse_kml_color(value: &str) -> Option<Color> {
+    let value = value.trim().trim_start_matches('#');
+    if value.len() != 8 {
+        return None;
+    }
+    let value = u32::from_str_radix(value, 16).ok()?;
+    let [a, b, g, r] = value.to_be_bytes();
+    return Some(Color::rgba_u8(r, g, b, a));
+}
+fn parse_style(node: Node) -> KmlStyle {
+    let mut style = KmlStyle::default();
+    if let Some(icon_style) = child(node, "IconStyle") {
+        style.icon_color = child_text(icon_style, "color").and_then(|v| parse_kml_color(&v));
+        style.icon_scale = child_number(icon_style, "scale");
+        style.icon_href = child(icon_style, "Icon").and_then(|v| child_text(v, "href"));
+    }
+    if let Some(line_style) = child(node, "LineStyle") {
+        style.line_color = child_text(line_style, "color").and_then(|v| parse_kml_color(&v));
+        style.line_width = child_number(line_style, "width");
+    }
+    if let Some(poly_style) = child(node, "PolyStyle") {
+        style.poly_color = child_text(poly_style, "color").and_then(|v| parse_kml_color(&v));
+        style.poly_fill = child_bool(poly_style, "fill");
+        style.poly_outline = child_bool(poly_style, "outline");
+    }
+    return style;
+}
+/// 收集所有带id的Style，StyleMap解析为normal状态对应的样式
+fn parse_shared_styles(root: Node) -> HashMap<String, KmlStyle> {
+    let mut styles = HashMap::new();
+    for node in root.descendants().filter(|x| is_element(x, "Style")) {
+        if let Some(id) = node.attribute("id") {
+            styles.insert(id.to_string(), parse_style(node));
+        }
+    }
+    for node in root.descendants().filter(|x| is_element(x, "StyleMap")) {
+        let Some(id) = node.attribute("id") else {
+            continue;
+        };
+        let normal = node.children().find(|pair| {
+            is_element(pair, "Pair") && child_text(*pair, "key").as_deref() == Some("normal")
+        });
+        let Some(normal) = normal else {
+            continue;
+        };
+        let style = if let Some(style) = child(normal, "Style") {
+            parse_style(style)
+        } else if let Some(url) = child_text(normal, "styleUrl") {
+            let Some(style) = styles.get(url.trim_start_matches('#')) else {
+                continue;
+            };
+            style.clone()
+        } else {
+            continue;
+        };
+        styles.insert(id.to_string(), style);
+    }
+    return styles;
+}
+fn parse_coordinates(node: Node) -> Result<Vec<Cartographic>, KmlError> {
+    let text = child_text(node, "coordinates").unwrap_or_default();
+    let mut positions = vec![];
+    for tuple in text.split_whitespace() {
+        let values: Vec<f64> = tuple
+            .split(',')
+            .map(|v| v.parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| KmlError::InvalidCoordinates(tuple.to_string()))?;
+        if values.len() < 2 {
+            return Err(KmlError::InvalidCoordinates(tuple.to_string()));
+        }
+        positions.push(Cartographic::from_degrees(
+            values[0],
+            values[1],
+            values.get(2).copied().unwrap_or(0.0),
+        ));
+    }
+    return Ok(positions);
+}
+/// 闭合的环去掉重复的最后一个点
+fn parse_ring(node: Node) -> Result<Vec<Cartographic>, KmlError> {
+    let mut positions = parse_coordinates(node)?;
+    if positions.len() > 1 && positions.first() == positions.last() {
+        positions.pop();
+    }
+    return Ok(positions);
+}
+fn parse_boundary(node: Node) -> Result<Vec<Cartographic>, KmlError> {
+    return match child(node, "LinearRing") {
+        Some(ring) => parse_ring(ring),
+        None => Ok(vec![]),
+    };
+}
+/// 解析几何并返回第一个几何的高度模式和是否拉伸
+fn parse_geometry(
+    node: Node,
+    result: &mut Vec<FeatureGeometry>,
+    mode: &mut Option<(AltitudeMode, bool)>,
+) -> Result<(), KmlError> {
+    let name = node.tag_name().name();
+    if name == "MultiGeometry" {
+        for child in node.children().filter(|x| x.is_element()) {
+            parse_geometry(child, result, mode)?;
+        }
+        return Ok(());
+    }
+    let geometry = match name {
+        "Point" => parse_coordinates(node)?
+            .first()
+            .map(|v| FeatureGeometry::Point(*v)),
+        "LineString" => Some(FeatureGeometry::LineString(parse_coordinates(node)?)),
+        "LinearRing" => Some(FeatureGeometry::Polygon(PolygonHierarchy::new(parse_ring(
+            node,
+        )?))),
+        "Polygon" => {
+            let outer = child(node, "outerBoundaryIs")
+                .map(parse_boundary)
+                .transpose()?
+                .unwrap_or_default();
+            let mut hierarchy = PolygonHierarchy::new(outer);
+            for inner in node.children().filter(|x| is_element(x, "innerBoundaryIs")) {
+                hierarchy
+                    .holes
+                    .push(PolygonHierarchy::new(parse_boundary(inner)?));
+            }
+            Some(FeatureGeometry::Polygon(hierarchy))
+        }
+        _ => None,
+    };
+    if let Some(geometry) = geometry {
+        if mode.is_none() {
+            let altitude_mode = child_text(node, "altitudeMode")
+                .map(|v| AltitudeMode::parse(&v))
+                .unwrap_or_default();
+            *mode = Some((altitude_mode, child_bool(node, "extrude").unwrap_or(false)));
+        }
+        result.push(geometry);
+    }
+    return Ok(());
+}
+/// 名字、描述和ExtendedData都放到属性中
+fn parse_properties(node: Node) -> Map<String, Value> {
+    let mut properties = Map::new();
+    for name in ["name", "description"] {
+        if let Some(value) = child_text(node, name) {
+            properties.insert(name.to_string(), Value::String(value));
+        }
+    }
+    let Some(extended_data) = child(node, "ExtendedData") else {
+        return properties;
+    };
+    for data in extended_data.descendants().filter(|x| x.is_element()) {
+        let value = match data.tag_name().name() {
+            "Data" => child_text(data, "value"),
+            "SimpleData" => data.text().map(|v| v.trim().to_string()),
+            _ => continue,
+        };
+        let Some(name) = data.attribute("name") else {
+            continue;
+        };
+        properties.insert(name.to_string(), value.map_or(Value::Null, Value::String));
+    }
+    return properties;
+}
+fn parse_placemark(
+    node: Node,
+    styles: &HashMap<String, KmlStyle>,
+) -> Result<KmlPlacemarkDescription, KmlError> {
+    let mut style = child_text(node, "styleUrl")
+        .and_then(|url| styles.get(url.trim_start_matches('#')).cloned())
+        .unwrap_or_default();
+    if let Some(inline) = child(node, "Style") {
+        style = style.merge(&parse_style(inline));
+    }
+    let mut geometries = vec![];
+    let mut mode = None;
+    for child in node.children().filter(|x| x.is_element()) {
+        parse_geometry(child, &mut geometries, &mut mode)?;
+    }
+    let (altitude_mode, extrude) = mode.unwrap_or_default();
+    return Ok(KmlPlacemarkDescription {
+        feature: Feature {
+            id: node.attribute("id").map(|v| v.to_string()),
+            properties: parse_properties(node),
+            geometries,
+        },
+        style,
+        altitude_mode,
+        extrude,
+    });
+}
+fn parse_ground_overlay(node: Node) -> Option<KmlGroundOverlayDescription> {
+    let href = child(node, "Icon").and_then(|v| child_text(v, "href"))?;
+    let lat_lon_box = child(node, "LatLonBox")?;
+    let get = |name: &str| child_number::<f64>(lat_lon_box, name).map(|v| v.to_radians());
+    let rectangle = Rectangle::new(get("west")?, get("south")?, get("east")?, get("north")?);
+    let alpha = child_text(node, "color")
+        .and_then(|v| parse_kml_color(&v))
+        .map_or(1.0, |v| v.a() as f64);
+    return Some(KmlGroundOverlayDescription {
+        name: child_text(node, "name"),
+        href,
+        rectangle,
+        alpha,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const KML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">
+  <Document>
+    <Style id="red">
+      <LineStyle><color>ff0000ff</color><width>3</width></LineStyle>
+      <PolyStyle><color>7f00ff00</color><outline>0</outline></PolyStyle>
+    </Style>
+    <StyleMap id="redMap">
+      <Pair><key>normal</key><styleUrl>#red</styleUrl></Pair>
+      <Pair><key>highlight</key><Style><LineStyle><width>6</width></LineStyle></Style></Pair>
+    </StyleMap>
+    <Folder>
+      <Placemark id="p1">
+        <name>Tower</name>
+        <ExtendedData><Data name="height"><value>120</value></Data></ExtendedData>
+        <Point><coordinates>120.5,30.25,10</coordinates></Point>
+      </Placemark>
+      <Placemark>
+        <styleUrl>#redMap</styleUrl>
+        <Style><LineStyle><width>5</width></LineStyle></Style>
+        <MultiGeometry>
+          <LineString>
+            <altitudeMode>absolute</altitudeMode>
+            <coordinates>120,30,100 121,31,100</coordinates>
+          </LineString>
+          <Polygon>
+            <outerBoundaryIs><LinearRing><coordinates>
+              120,30 121,30 121,31 120,31 120,30
+            </coordinates></LinearRing></outerBoundaryIs>
+            <innerBoundaryIs><LinearRing><coordinates>
+              120.4,30.4 120.6,30.4 120.6,30.6 120.4,30.4
+            </coordinates></LinearRing></innerBoundaryIs>
+          </Polygon>
+        </MultiGeometry>
+      </Placemark>
+    </Folder>
+    <GroundOverlay>
+      <name>Scan</name>
+      <color>80ffffff</color>
+      <Icon><href>files/scan.png</href></Icon>
+      <LatLonBox><north>31</north><south>30</south><east>121</east><west>120</west></LatLonBox>
+    </GroundOverlay>
+  </Document>
+</kml>"#;
+
+    #[test]
+    fn test_parse_kml() {
+        let document = KmlDocument::parse(KML).unwrap();
+        assert_eq!(document.placemarks.len(), 2);
+
+        let tower = &document.placemarks[0];
+        assert_eq!(tower.feature.id.as_deref(), Some("p1"));
+        assert_eq!(tower.feature.properties["name"], Value::from("Tower"));
+        assert_eq!(tower.feature.properties["height"], Value::from("120"));
+        assert_eq!(tower.altitude_mode, AltitudeMode::ClampToGround);
+        // 默认贴地，高度被忽略
+        let placemark = tower.to_placemark(None);
+        assert!(placemark.style.clamp_to_ground);
+        assert_eq!(
+            placemark.feature.geometries,
+            vec![FeatureGeometry::Point(Cartographic::from_degrees(
+                120.5, 30.25, 0.0
+            ))]
+        );
+
+        let shape = &document.placemarks[1];
+        assert_eq!(shape.altitude_mode, AltitudeMode::Absolute);
+        assert_eq!(shape.style.line_color, Some(Color::rgba_u8(255, 0, 0, 255)));
+        // 内联样式覆盖StyleMap中normal状态的样式
+        assert_eq!(shape.style.line_width, Some(5.0));
+        assert_eq!(shape.feature.geometries.len(), 2);
+        let FeatureGeometry::Polygon(hierarchy) = &shape.feature.geometries[1] else {
+            panic!("expect polygon");
+        };
+        assert_eq!(hierarchy.positions.len(), 4);
+        assert_eq!(hierarchy.holes.len(), 1);
+        assert_eq!(hierarchy.holes[0].positions.len(), 3);
+        let style = shape.to_placemark(None).style;
+        assert!(!style.outline);
+        assert!(!style.clamp_to_ground);
+        assert!(!style.clamp_lines_to_ground);
+        assert_eq!(style.fill, Color::rgba_u8(0, 255, 0, 127));
+
+        assert_eq!(document.ground_overlays.len(), 1);
+        let overlay = &document.ground_overlays[0];
+        assert_eq!(overlay.href, "files/scan.png");
+        assert!((overlay.rectangle.west - 120f64.to_radians()).abs() < 1e-12);
+        assert!((overlay.rectangle.north - 31f64.to_radians()).abs() < 1e-12);
+        assert!((overlay.alpha - 128.0 / 255.0).abs() < 1e-6);
+    }
+    #[test]
+    fn test_relative_to_ground() {
+        let description = KmlPlacemarkDescription {
+            feature: Feature {
+                geometries: vec![FeatureGeometry::LineString(vec![
+                    Cartographic::from_degrees(120.0, 30.0, 100.0),
+                    Cartographic::from_degrees(121.0, 31.0, 50.0),
+                ])],
+                ..Default::default()
+            },
+            altitude_mode: AltitudeMode::RelativeToGround,
+            extrude: true,
+            ..Default::default()
+        };
+        // 保留高度，绘制时加上地形高度
+        let placemark = description.to_placemark(None);
+        assert!(placemark.style.clamp_to_ground);
+        assert_eq!(placemark.style.extruded_height, Some(0.0));
+        assert_eq!(placemark.feature, description.feature);
+    }
+    #[test]
+    fn test_read_kmz() {
+        let mut buffer = Cursor::new(vec![]);
+        let mut writer = zip::ZipWriter::new(&mut buffer);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("files/scan.png", options).unwrap();
+        writer.write_all(&[1, 2, 3]).unwrap();
+        writer.start_file("doc.kml", options).unwrap();
+        writer.write_all(KML.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let (xml, files) = read_kmz(buffer.get_ref()).unwrap();
+        assert_eq!(xml, KML);
+        assert_eq!(files["files/scan.png"], vec![1, 2, 3]);
+        assert!(matches!(
+            read_kmz(&[b'P', b'K', 0, 0]),
+            Err(KmlError::Zip(_))
+        ));
+    }
+}
@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use houtu_scene::{ArcType, Cartographic, PolygonHierarchy};
+use serde_json::{Map, Value};
+
+use crate::primitive::{Billboard, PointPrimitive, Polygon, Polyline};
+
+mod geojson;
+mod kml;
+mod pick;
+pub use geojson::{GeoJsonDataSource, GeoJsonError, GeoJsonLayer};
+pub use kml::{KmlDataSource, KmlError, KmlLayer};
+pub use pick::FeaturePickEvent;
+
+/// 要素的一个几何部分，多点、多线、多面拆成多个
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeatureGeometry {
+    Point(Cartographic),
+    LineString(Vec<Cartographic>),
+    Polygon(PolygonHierarchy),
+}
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Feature {
+    pub id: Option<String>,
+    pub properties: Map<String, Value>,
+    pub geometries: Vec<FeatureGeometry>,
+}
+/// 附加在生成的实体上，拾取后可以查到原始要素的属性
+#[derive(Component, Debug, Clone, PartialEq, Default)]
+pub struct FeatureProperties {
+    pub id: Option<String>,
+    pub properties: Map<String, Value>,
+}
+#[derive(Debug, Clone)]
+pub struct FeatureStyle {
+    pub marker_color: Color,
+    pub marker_size: f32,
+    /// 设置后点用图片显示
+    pub marker_image: Option<Handle<Image>>,
+    pub stroke: Color,
+    pub stroke_width: f32,
+    pub fill: Color,
+    /// 多边形是否绘制轮廓线
+    pub outline: bool,
+    /// 贴地，高度都相对于已加载的地形
+    pub clamp_to_ground: bool,
+    /// 线贴地，默认开启，线一般是地面上的路径
+    pub clamp_lines_to_ground: bool,
+    pub extruded_height: Option<f64>,
+}
+impl Default for FeatureStyle {
+    fn default() -> Self {
+        Self {
+            marker_color: Color::rgb(0.25, 0.41, 0.88),
+            marker_size: 10.0,
+            marker_image: None,
+            stroke: Color::YELLOW,
+            stroke_width: 2.0,
+            fill: Color::rgba(1.0, 1.0, 0.0, 0.5),
+            outline: true,
+            clamp_to_ground: false,
+            clamp_lines_to_ground: true,
+            extruded_height: None,
+        }
+    }
+}
+fn parse_color(value: Option<&Value>) -> Option<Color> {
+    let value = value?.as_str()?;
+    let value = value.strip_prefix('#').unwrap_or(value);
+    // simplestyle允许3位的简写
+    if value.len() == 3 {
+        let value: String = value.chars().flat_map(|c| [c, c]).collect();
+        return Color::hex(value).ok();
+    }
+    return Color::hex(value).ok();
+}
+impl FeatureStyle {
+    /// 用属性中的simplestyle-spec字段覆盖，与Cesium的GeoJsonDataSource相同
+    pub fn with_simplestyle(mut self, properties: &Map<String, Value>) -> Self {
+        if let Some(color) = parse_color(properties.get("marker-color")) {
+            self.marker_color = color;
+        }
+        if let Some(size) = properties.get("marker-size").and_then(|v| v.as_str()) {
+            self.marker_size = match size {
+                "small" => 6.0,
+                "large" => 14.0,
+                _ => 10.0,
+            };
+        }
+        if let Some(color) = parse_color(properties.get("stroke")) {
+            self.stroke = color;
+        }
+        if let Some(opacity) = properties.get("stroke-opacity").and_then(|v| v.as_f64()) {
+            self.stroke.set_a(opacity as f32);
+        }
+        if let Some(width) = properties.get("stroke-width").and_then(|v| v.as_f64()) {
+            self.stroke_width = width as f32;
+        }
+        if let Some(color) = parse_color(properties.get("fill")) {
+            self.fill = color.with_a(self.fill.a());
+        }
+        if let Some(opacity) = properties.get("fill-opacity").and_then(|v| v.as_f64()) {
+            self.fill.set_a(opacity as f32);
+        }
+        return self;
+    }
+}
+/// 根据要素返回样式，用户可以根据属性分类设色
+pub type FeatureStyleFn = Arc<dyn Fn(&Feature) -> FeatureStyle + Send + Sync>;
+
+/// 默认样式只读取simplestyle-spec字段
+pub fn default_feature_style(feature: &Feature) -> FeatureStyle {
+    return FeatureStyle::default().with_simplestyle(&feature.properties);
+}
+/// 每个几何部分生成一个子实体，都带有要素的属性
+pub fn spawn_features(
+    parent: &mut ChildBuilder,
+    features: &[Feature],
+    style: &dyn Fn(&Feature) -> FeatureStyle,
+) {
+    for feature in features.iter() {
+        let style = style(feature);
+        let properties = FeatureProperties {
+            id: feature.id.clone(),
+            properties: feature.properties.clone(),
+        };
+        for geometry in feature.geometries.iter() {
+            let mut entity = parent.spawn((properties.clone(), SpatialBundle::INHERITED_IDENTITY));
+            match geometry {
+                FeatureGeometry::Point(position) => match &style.marker_image {
+                    Some(image) => {
+                        entity.insert(Billboard {
+                            size: Some(Vec2::splat(style.marker_size * 3.0)),
+                            color: style.marker_color,
+                            clamp_to_ground: style.clamp_to_ground,
+                            ..Billboard::new(*position, image.clone())
+                        });
+                    }
+                    None => {
+                        entity.insert(PointPrimitive {
+                            pixel_size: style.marker_size,
+                            color: style.marker_color,
+                            outline_color: Color::WHITE,
+                            outline_width: 1.0,
+                            clamp_to_ground: style.clamp_to_ground,
+                            ..PointPrimitive::new(*position)
+                        });
+                    }
+                },
+                FeatureGeometry::LineString(positions) => {
+                    entity.insert(Polyline {
+                        width: style.stroke_width,
+                        color: style.stroke,
+                        arc_type: ArcType::Geodesic,
+                        clamp_to_ground: style.clamp_to_ground || style.clamp_lines_to_ground,
+                        ..Polyline::new(positions.clone())
+                    });
+                }
+                FeatureGeometry::Polygon(hierarchy) => {
+                    let has_height = hierarchy.positions.iter().any(|v| v.height != 0.0);
+                    entity.insert(Polygon {
+                        per_position_height: has_height,
+                        clamp_to_ground: style.clamp_to_ground,
+                        extruded_height: style.extruded_height,
+                        fill: style.fill.a() > 0.0,
+                        fill_color: style.fill,
+                        outline: style.outline,
+                        outline_color: style.stroke,
+                        outline_width: style.stroke_width,
+                        ..Polygon::new(hierarchy.clone())
+                    });
+                }
+            }
+        }
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<GeoJsonDataSource>()
+            .init_asset_loader::<geojson::GeoJsonLoader>()
+            .add_asset::<KmlDataSource>()
+            .init_asset_loader::<kml::KmlLoader>()
+            .add_event::<FeaturePickEvent>()
+            .add_systems(
+                Update,
+                (
+                    geojson::geojson_layer_system,
+                    kml::kml_layer_system,
+                    pick::feature_pick_system,
+                ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_simplestyle() {
+        let properties = json!({
+            "stroke": "#ff0000",
+            "stroke-width": 4,
+            "fill": "#0f0",
+            "fill-opacity": 0.25,
+        });
+        let style = FeatureStyle::default().with_simplestyle(properties.as_object().unwrap());
+        assert_eq!(style.stroke, Color::rgb(1.0, 0.0, 0.0));
+        assert_eq!(style.stroke_width, 4.0);
+        assert_eq!(style.fill, Color::rgba(0.0, 1.0, 0.0, 0.25));
+        assert_eq!(style.marker_size, FeatureStyle::default().marker_size);
+        assert!(style.clamp_lines_to_ground);
+    }
+}
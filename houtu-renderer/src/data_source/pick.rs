@@ -0,0 +1,254 @@
+use bevy::{
+    math::{DMat4, DVec3},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use houtu_scene::{
+    ArcType, Cartographic, Ellipsoid, EllipsoidalOccluder, PolygonHierarchy, PolylinePipeline,
+};
+
+use super::FeatureProperties;
+use crate::{
+    camera::{GlobeCamera, GlobeClickEvent},
+    primitive::{world_to_window_coordinates, Billboard, PointPrimitive, Polygon, Polyline},
+    quadtree::quadtree_primitive::QuadtreePrimitive,
+};
+
+/// 点和线的拾取容差(像素)
+const PICK_TOLERANCE: f32 = 5.0;
+
+/// 左键点击拾取到要素时发送，可以通过properties读取原始要素的属性
+#[derive(Event, Debug, Clone)]
+pub struct FeaturePickEvent {
+    pub entity: Entity,
+    pub properties: FeatureProperties,
+    /// 点击到的地形位置
+    pub position: Cartographic,
+}
+/// 点到线段的距离
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    if length_squared == 0.0 {
+        return point.distance(start);
+    }
+    let t = ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0);
+    return point.distance(start + segment * t);
+}
+/// 射线法判断经纬度是否在环内，按经纬度平面计算
+fn ring_contains(ring: &[Cartographic], position: &Cartographic) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let (a, b) = (&ring[i], &ring[j]);
+        if (a.latitude > position.latitude) != (b.latitude > position.latitude) {
+            let longitude = a.longitude
+                + (position.latitude - a.latitude) / (b.latitude - a.latitude)
+                    * (b.longitude - a.longitude);
+            if position.longitude < longitude {
+                inside = !inside;
+            }
+        }
+        j = i;
+    }
+    return inside;
+}
+/// 在外环内且不在洞内，洞中的岛是洞的洞
+fn hierarchy_contains(hierarchy: &PolygonHierarchy, position: &Cartographic) -> bool {
+    return ring_contains(&hierarchy.positions, position)
+        && !hierarchy
+            .holes
+            .iter()
+            .any(|hole| hierarchy_contains(hole, position));
+}
+struct PickContext<'a> {
+    primitive: Option<&'a QuadtreePrimitive>,
+    occluder: EllipsoidalOccluder,
+    view_projection: DMat4,
+    window_size: Vec2,
+}
+impl PickContext<'_> {
+    fn to_cartesian(&self, position: &Cartographic, clamp_to_ground: bool) -> DVec3 {
+        let mut position = *position;
+        if clamp_to_ground {
+            position.height += self
+                .primitive
+                .and_then(|v| v.get_height(&position))
+                .unwrap_or(0.0);
+        }
+        return Ellipsoid::WGS84.cartographic_to_cartesian(&position);
+    }
+    fn to_window(&self, position: &DVec3) -> Option<Vec2> {
+        if !self.occluder.is_point_visible(*position) {
+            return None;
+        }
+        return world_to_window_coordinates(&self.view_projection, position, self.window_size);
+    }
+    /// 到点的屏幕距离减去点的半径
+    fn marker_distance(
+        &self,
+        position: &Cartographic,
+        clamp_to_ground: bool,
+        radius: f32,
+        click: Vec2,
+    ) -> Option<f32> {
+        let window_position = self.to_window(&self.to_cartesian(position, clamp_to_ground))?;
+        return Some((window_position.distance(click) - radius).max(0.0));
+    }
+    /// 到折线的屏幕距离，按和绘制时相同的方式加密
+    fn polyline_distance(&self, polyline: &Polyline, click: Vec2) -> Option<f32> {
+        let arc_type = match (polyline.clamp_to_ground, polyline.arc_type) {
+            (true, ArcType::None) => ArcType::Geodesic,
+            (_, v) => v,
+        };
+        let points: Vec<Option<Vec2>> = PolylinePipeline::generate_cartographic_arc(
+            &polyline.positions,
+            arc_type,
+            polyline.granularity,
+            None,
+        )
+        .iter()
+        .map(|v| self.to_window(&self.to_cartesian(v, polyline.clamp_to_ground)))
+        .collect();
+        return points
+            .windows(2)
+            .filter_map(|pair| match (pair[0], pair[1]) {
+                (Some(start), Some(end)) => Some(distance_to_segment(click, start, end)),
+                _ => None,
+            })
+            .reduce(f32::min);
+    }
+}
+/// 左键点击地形时拾取要素，点和线按屏幕距离，面按点击位置是否在面内，优先点、其次线、最后面
+pub fn feature_pick_system(
+    primitive: Option<Res<QuadtreePrimitive>>,
+    primary_query: Query<&Window, With<PrimaryWindow>>,
+    mut click_reader: EventReader<GlobeClickEvent>,
+    mut cameras: Query<&mut GlobeCamera>,
+    mut pick_writer: EventWriter<FeaturePickEvent>,
+    features: Query<(
+        Entity,
+        &FeatureProperties,
+        Option<&PointPrimitive>,
+        Option<&Billboard>,
+        Option<&Polyline>,
+        Option<&Polygon>,
+        Option<&ComputedVisibility>,
+    )>,
+) {
+    let Ok(window) = primary_query.get_single() else {
+        return;
+    };
+    let Ok(mut globe_camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let ellipsoid = Ellipsoid::WGS84;
+    let mut occluder = EllipsoidalOccluder::new(&ellipsoid);
+    occluder.set_camera_position(globe_camera.get_position_wc());
+    let projection = *globe_camera.frustum.get_projection_matrix();
+    let context = PickContext {
+        primitive: primitive.as_deref(),
+        occluder,
+        view_projection: projection * globe_camera.get_view_matrix(),
+        window_size: Vec2::new(window.width(), window.height()),
+    };
+    for event in click_reader.iter() {
+        if event.button != MouseButton::Left {
+            continue;
+        }
+        let click = event.window_position.as_vec2();
+        let position = &event.result.cartographic;
+        // (优先级, 屏幕距离)，越小越优先
+        let mut picked: Option<((u8, f32), Entity, &FeatureProperties)> = None;
+        for (entity, properties, point, billboard, polyline, polygon, visibility) in features.iter()
+        {
+            if visibility.map_or(false, |v| !v.is_visible()) {
+                continue;
+            }
+            let score = if let Some(point) = point {
+                let radius = point.pixel_size * 0.5 + point.outline_width;
+                context
+                    .marker_distance(&point.position, point.clamp_to_ground, radius, click)
+                    .map(|v| (0, v))
+            } else if let Some(billboard) = billboard {
+                let radius =
+                    billboard.size.map_or(16.0, |v| v.max_element() * 0.5) * billboard.scale;
+                context
+                    .marker_distance(
+                        &billboard.position,
+                        billboard.clamp_to_ground,
+                        radius,
+                        click,
+                    )
+                    .map(|v| (0, v))
+            } else if let Some(polyline) = polyline {
+                context.polyline_distance(polyline, click).map(|v| (1, v))
+            } else if let Some(polygon) = polygon {
+                hierarchy_contains(&polygon.hierarchy, position).then_some((2, 0.0))
+            } else {
+                None
+            };
+            let Some(score) = score else {
+                continue;
+            };
+            if score.0 < 2 && score.1 > PICK_TOLERANCE {
+                continue;
+            }
+            if picked.as_ref().map_or(true, |v| score < v.0) {
+                picked = Some((score, entity, properties));
+            }
+        }
+        if let Some((_, entity, properties)) = picked {
+            pick_writer.send(FeaturePickEvent {
+                entity,
+                properties: properties.clone(),
+                position: *position,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_to_segment() {
+        let start = Vec2::new(0.0, 0.0);
+        let end = Vec2::new(10.0, 0.0);
+        assert_eq!(distance_to_segment(Vec2::new(5.0, 3.0), start, end), 3.0);
+        assert_eq!(distance_to_segment(Vec2::new(-4.0, 3.0), start, end), 5.0);
+        assert_eq!(
+            distance_to_segment(Vec2::new(1.0, 1.0), start, start),
+            2f32.sqrt()
+        );
+    }
+    #[test]
+    fn test_hierarchy_contains() {
+        let ring = |west: f64, south: f64, east: f64, north: f64| -> Vec<Cartographic> {
+            return vec![
+                Cartographic::from_degrees(west, south, 0.0),
+                Cartographic::from_degrees(east, south, 0.0),
+                Cartographic::from_degrees(east, north, 0.0),
+                Cartographic::from_degrees(west, north, 0.0),
+            ];
+        };
+        let mut hole = PolygonHierarchy::new(ring(2.0, 2.0, 8.0, 8.0));
+        hole.holes
+            .push(PolygonHierarchy::new(ring(4.0, 4.0, 6.0, 6.0)));
+        let mut hierarchy = PolygonHierarchy::new(ring(0.0, 0.0, 10.0, 10.0));
+        hierarchy.holes.push(hole);
+        let contains = |longitude: f64, latitude: f64| -> bool {
+            return hierarchy_contains(
+                &hierarchy,
+                &Cartographic::from_degrees(longitude, latitude, 0.0),
+            );
+        };
+        assert!(contains(1.0, 1.0));
+        // 在洞中
+        assert!(!contains(3.0, 3.0));
+        // 在洞中的岛上
+        assert!(contains(5.0, 5.0));
+        assert!(!contains(11.0, 5.0));
+    }
+}
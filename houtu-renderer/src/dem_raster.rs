@@ -0,0 +1,500 @@
+use std::io::Cursor;
+
+use houtu_scene::Rectangle;
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    tags::Tag,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Tiff(#[from] tiff::TiffError),
+    #[error("unsupported dem format {0}")]
+    UnsupportedFormat(String),
+    #[error("invalid dem: {0}")]
+    Invalid(String),
+    #[error("only geographic (EPSG:4326) rasters are supported")]
+    UnsupportedProjection,
+}
+/// 规则格网的高程数据，第0行在北边。
+///
+/// `rectangle`是四个角上采样点的经纬度(弧度)，不是像元的外边界，
+/// `samples`是文件中的原始值，还没有乘以`height_scale`和加上`height_offset`
+#[derive(Debug, Clone)]
+pub struct DemRaster {
+    pub width: u32,
+    pub height: u32,
+    pub rectangle: Rectangle,
+    pub samples: Vec<f32>,
+    pub no_data: Option<f32>,
+}
+impl DemRaster {
+    pub fn new(
+        width: u32,
+        height: u32,
+        rectangle: Rectangle,
+        samples: Vec<f32>,
+        no_data: Option<f32>,
+    ) -> Result<Self, Error> {
+        if width < 2 || height < 2 {
+            return Err(Error::Invalid(format!(
+                "raster must have at least 2x2 samples, got {}x{}",
+                width, height
+            )));
+        }
+        if samples.len() != (width * height) as usize {
+            return Err(Error::Invalid(format!(
+                "expected {} samples, got {}",
+                width * height,
+                samples.len()
+            )));
+        }
+        return Ok(Self {
+            width,
+            height,
+            rectangle,
+            samples,
+            no_data,
+        });
+    }
+    /// 根据文件扩展名选择解析方式，`.bil`需要同名的`.hdr`头文件
+    pub fn from_bytes(
+        name: &str,
+        bytes: &[u8],
+        header: Option<&str>,
+        is_big_endian: bool,
+    ) -> Result<Self, Error> {
+        let extension = get_extension(name);
+        match extension.as_str() {
+            "tif" | "tiff" => Self::from_geotiff(bytes),
+            "hgt" => Self::from_hgt(name, bytes),
+            "bil" => {
+                let Some(header) = header else {
+                    return Err(Error::Invalid(format!("missing .hdr file for {}", name)));
+                };
+                Self::from_bil(header, bytes, is_big_endian)
+            }
+            _ => Err(Error::UnsupportedFormat(extension)),
+        }
+    }
+    /// SRTM的`.hgt`文件，文件名(如`N37W122.hgt`)是西南角的经纬度，
+    /// 数据是大端序的i16，`-32768`表示无数据
+    pub fn from_hgt(name: &str, bytes: &[u8]) -> Result<Self, Error> {
+        let (longitude, latitude) = parse_hgt_name(name)
+            .ok_or_else(|| Error::Invalid(format!("can not parse hgt file name {}", name)))?;
+        let size = ((bytes.len() / 2) as f64).sqrt() as u32;
+        if (size * size * 2) as usize != bytes.len() {
+            return Err(Error::Invalid(format!(
+                "hgt file {} is not a square grid of i16",
+                name
+            )));
+        }
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|x| i16::from_be_bytes([x[0], x[1]]) as f32)
+            .collect();
+        let rectangle = Rectangle::new(
+            longitude.to_radians(),
+            latitude.to_radians(),
+            (longitude + 1.0).to_radians(),
+            (latitude + 1.0).to_radians(),
+        );
+        return Self::new(size, size, rectangle, samples, Some(-32768.0));
+    }
+    /// ESRI BIL格式，只读取第一个波段。
+    /// 头文件中没有`BYTEORDER`时按`is_big_endian`读取
+    pub fn from_bil(header: &str, bytes: &[u8], is_big_endian: bool) -> Result<Self, Error> {
+        let header = BilHeader::parse(header, is_big_endian)?;
+        let bytes_per_sample = (header.nbits / 8) as usize;
+        let row_bytes = header.ncols as usize * bytes_per_sample;
+        let band_row_bytes = row_bytes * header.nbands as usize;
+        if bytes.len() < band_row_bytes * header.nrows as usize {
+            return Err(Error::Invalid(format!(
+                "bil file is too short, expected {} bytes, got {}",
+                band_row_bytes * header.nrows as usize,
+                bytes.len()
+            )));
+        }
+        let mut samples = Vec::with_capacity((header.ncols * header.nrows) as usize);
+        for row in 0..header.nrows as usize {
+            let start = row * band_row_bytes;
+            for value in bytes[start..start + row_bytes].chunks_exact(bytes_per_sample) {
+                samples.push(header.read_sample(value)?);
+            }
+        }
+        let rectangle = Rectangle::new(
+            header.ulxmap.to_radians(),
+            (header.ulymap - header.ydim * (header.nrows - 1) as f64).to_radians(),
+            (header.ulxmap + header.xdim * (header.ncols - 1) as f64).to_radians(),
+            header.ulymap.to_radians(),
+        );
+        return Self::new(header.ncols, header.nrows, rectangle, samples, header.nodata);
+    }
+    /// 只支持地理坐标系、通过`ModelPixelScale`和`ModelTiepoint`定位的GeoTIFF，
+    /// 多波段时只读取第一个波段
+    pub fn from_geotiff(bytes: &[u8]) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(Cursor::new(bytes))?;
+        let (width, height) = decoder.dimensions()?;
+        let mut pixel_is_point = false;
+        if let Ok(directory) = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag) {
+            for key in directory.chunks_exact(4).skip(1) {
+                match (key[0], key[3]) {
+                    // GTModelTypeGeoKey, 1是投影坐标系
+                    (1024, 1) => return Err(Error::UnsupportedProjection),
+                    // GTRasterTypeGeoKey, 2是RasterPixelIsPoint
+                    (1025, value) => pixel_is_point = value == 2,
+                    // ProjectedCSTypeGeoKey
+                    (3072, _) => return Err(Error::UnsupportedProjection),
+                    _ => {}
+                }
+            }
+        }
+        let scale = decoder
+            .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+            .map_err(|_| Error::Invalid("missing ModelPixelScaleTag".to_string()))?;
+        let tiepoint = decoder
+            .get_tag_f64_vec(Tag::ModelTiepointTag)
+            .map_err(|_| Error::Invalid("missing ModelTiepointTag".to_string()))?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err(Error::Invalid("invalid georeference tags".to_string()));
+        }
+        let no_data = decoder
+            .get_tag_ascii_string(Tag::GdalNodata)
+            .ok()
+            .and_then(|x| x.trim_matches(char::from(0)).trim().parse::<f32>().ok());
+        let samples = match decoder.read_image()? {
+            DecodingResult::U8(v) => v.into_iter().map(|x| x as f32).collect::<Vec<f32>>(),
+            DecodingResult::U16(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::U32(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::U64(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I8(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I16(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I32(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I64(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::F32(v) => v,
+            DecodingResult::F64(v) => v.into_iter().map(|x| x as f32).collect(),
+        };
+        let samples_per_pixel = samples.len() / (width * height) as usize;
+        let samples = if samples_per_pixel > 1 {
+            samples.into_iter().step_by(samples_per_pixel).collect()
+        } else {
+            samples
+        };
+        let (scale_x, scale_y) = (scale[0], scale[1]);
+        // RasterPixelIsArea时tiepoint是像元的左上角，采样点在像元中心
+        let half_pixel = if pixel_is_point { 0.0 } else { 0.5 };
+        let west = tiepoint[3] + (half_pixel - tiepoint[0]) * scale_x;
+        let north = tiepoint[4] - (half_pixel - tiepoint[1]) * scale_y;
+        let rectangle = Rectangle::new(
+            west.to_radians(),
+            (north - scale_y * (height - 1) as f64).to_radians(),
+            (west + scale_x * (width - 1) as f64).to_radians(),
+            north.to_radians(),
+        );
+        return Self::new(width, height, rectangle, samples, no_data);
+    }
+    /// 采样点之间的最小间距(弧度)
+    pub fn get_sample_spacing(&self) -> f64 {
+        let x = self.rectangle.compute_width() / (self.width - 1) as f64;
+        let y = self.rectangle.compute_height() / (self.height - 1) as f64;
+        return x.min(y);
+    }
+    fn get_sample(&self, column: u32, row: u32) -> Option<f32> {
+        let value = self.samples[(row * self.width + column) as usize];
+        if Some(value) == self.no_data || value.is_nan() {
+            return None;
+        }
+        return Some(value);
+    }
+    /// 双线性插值得到经纬度(弧度)处的原始值，超出范围或者周围都是无数据时返回None
+    pub fn interpolate(&self, longitude: f64, latitude: f64) -> Option<f64> {
+        let rectangle = &self.rectangle;
+        let x = (longitude - rectangle.west) / rectangle.compute_width() * (self.width - 1) as f64;
+        let y =
+            (rectangle.north - latitude) / rectangle.compute_height() * (self.height - 1) as f64;
+        const EPSILON: f64 = 1e-9;
+        if x < -EPSILON
+            || y < -EPSILON
+            || x > (self.width - 1) as f64 + EPSILON
+            || y > (self.height - 1) as f64 + EPSILON
+        {
+            return None;
+        }
+        let x = x.clamp(0.0, (self.width - 1) as f64);
+        let y = y.clamp(0.0, (self.height - 1) as f64);
+        let column = (x.floor() as u32).min(self.width - 2);
+        let row = (y.floor() as u32).min(self.height - 2);
+        let dx = x - column as f64;
+        let dy = y - row as f64;
+
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        for (c, r, weight) in [
+            (column, row, (1.0 - dx) * (1.0 - dy)),
+            (column + 1, row, dx * (1.0 - dy)),
+            (column, row + 1, (1.0 - dx) * dy),
+            (column + 1, row + 1, dx * dy),
+        ] {
+            if weight <= 0.0 {
+                continue;
+            }
+            // 无数据的采样点不参与插值
+            if let Some(value) = self.get_sample(c, r) {
+                sum += value as f64 * weight;
+                weight_sum += weight;
+            }
+        }
+        if weight_sum <= 0.0 {
+            return None;
+        }
+        return Some(sum / weight_sum);
+    }
+}
+fn get_extension(name: &str) -> String {
+    let path = name.split(['?', '#']).next().unwrap_or(name);
+    return path
+        .rsplit('.')
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+}
+fn parse_hgt_name(name: &str) -> Option<(f64, f64)> {
+    let path = name.split(['?', '#']).next()?;
+    let file_name = path.rsplit(['/', '\\']).next()?.to_ascii_uppercase();
+    let file_name = file_name.as_bytes();
+    if file_name.len() < 7 {
+        return None;
+    }
+    let latitude: f64 = std::str::from_utf8(&file_name[1..3]).ok()?.parse().ok()?;
+    let longitude: f64 = std::str::from_utf8(&file_name[4..7]).ok()?.parse().ok()?;
+    let latitude = match file_name[0] {
+        b'N' => latitude,
+        b'S' => -latitude,
+        _ => return None,
+    };
+    let longitude = match file_name[3] {
+        b'E' => longitude,
+        b'W' => -longitude,
+        _ => return None,
+    };
+    return Some((longitude, latitude));
+}
+struct BilHeader {
+    nrows: u32,
+    ncols: u32,
+    nbands: u32,
+    nbits: u32,
+    is_big_endian: bool,
+    is_float: bool,
+    is_signed: bool,
+    ulxmap: f64,
+    ulymap: f64,
+    xdim: f64,
+    ydim: f64,
+    nodata: Option<f32>,
+}
+impl BilHeader {
+    fn parse(text: &str, is_big_endian: bool) -> Result<Self, Error> {
+        let mut header = BilHeader {
+            nrows: 0,
+            ncols: 0,
+            nbands: 1,
+            nbits: 8,
+            is_big_endian,
+            is_float: false,
+            is_signed: false,
+            ulxmap: f64::NAN,
+            ulymap: f64::NAN,
+            xdim: f64::NAN,
+            ydim: f64::NAN,
+            nodata: None,
+        };
+        let invalid = |key: &str, value: &str| Error::Invalid(format!("invalid {} {}", key, value));
+        for line in text.lines() {
+            let mut parts = line.split_whitespace();
+            let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let key = key.to_ascii_uppercase();
+            match key.as_str() {
+                "NROWS" => header.nrows = value.parse().map_err(|_| invalid(&key, value))?,
+                "NCOLS" => header.ncols = value.parse().map_err(|_| invalid(&key, value))?,
+                "NBANDS" => header.nbands = value.parse().map_err(|_| invalid(&key, value))?,
+                "NBITS" => header.nbits = value.parse().map_err(|_| invalid(&key, value))?,
+                "BYTEORDER" => header.is_big_endian = value.eq_ignore_ascii_case("M"),
+                "PIXELTYPE" => {
+                    header.is_float = value.eq_ignore_ascii_case("FLOAT");
+                    header.is_signed = value.eq_ignore_ascii_case("SIGNEDINT");
+                }
+                "LAYOUT" => {
+                    if !value.eq_ignore_ascii_case("BIL") {
+                        return Err(Error::UnsupportedFormat(value.to_string()));
+                    }
+                }
+                "ULXMAP" => header.ulxmap = value.parse().map_err(|_| invalid(&key, value))?,
+                "ULYMAP" => header.ulymap = value.parse().map_err(|_| invalid(&key, value))?,
+                "XDIM" => header.xdim = value.parse().map_err(|_| invalid(&key, value))?,
+                "YDIM" => header.ydim = value.parse().map_err(|_| invalid(&key, value))?,
+                "NODATA" => header.nodata = value.parse().ok(),
+                _ => {}
+            }
+        }
+        if header.nrows == 0 || header.ncols == 0 {
+            return Err(Error::Invalid("missing NROWS or NCOLS".to_string()));
+        }
+        if header.ulxmap.is_nan()
+            || header.ulymap.is_nan()
+            || header.xdim.is_nan()
+            || header.ydim.is_nan()
+        {
+            return Err(Error::Invalid(
+                "missing ULXMAP, ULYMAP, XDIM or YDIM".to_string(),
+            ));
+        }
+        // 没有PIXELTYPE时，高程数据一般是有符号整数
+        if !header.is_float && !text.to_ascii_uppercase().contains("PIXELTYPE") {
+            header.is_signed = true;
+        }
+        match (header.nbits, header.is_float) {
+            (8 | 16 | 32, false) | (32 | 64, true) => {}
+            _ => {
+                return Err(Error::UnsupportedFormat(format!(
+                    "{} bits {}",
+                    header.nbits,
+                    if header.is_float { "float" } else { "integer" }
+                )))
+            }
+        }
+        return Ok(header);
+    }
+    fn read_sample(&self, bytes: &[u8]) -> Result<f32, Error> {
+        let mut buffer = [0u8; 8];
+        let len = bytes.len();
+        buffer[..len].copy_from_slice(bytes);
+        if self.is_big_endian {
+            buffer[..len].reverse();
+        }
+        let value = match (len, self.is_float, self.is_signed) {
+            (1, false, false) => buffer[0] as f32,
+            (1, false, true) => buffer[0] as i8 as f32,
+            (2, false, false) => u16::from_le_bytes([buffer[0], buffer[1]]) as f32,
+            (2, false, true) => i16::from_le_bytes([buffer[0], buffer[1]]) as f32,
+            (4, false, false) => {
+                u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f32
+            }
+            (4, false, true) => {
+                i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f32
+            }
+            (4, true, _) => f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]),
+            (8, true, _) => f64::from_le_bytes(buffer) as f32,
+            _ => return Err(Error::UnsupportedFormat(format!("{} bytes sample", len))),
+        };
+        return Ok(value);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use houtu_scene::{equals_epsilon, EPSILON12};
+
+    #[test]
+    fn test_parse_hgt() {
+        let mut bytes = vec![];
+        for value in [1i16, 2, 3, -32768, 5, 6, 7, 8, 9] {
+            bytes.extend(value.to_be_bytes());
+        }
+        let raster = DemRaster::from_bytes("data/S01W002.hgt", &bytes, None, false).unwrap();
+        assert!(raster.width == 3 && raster.height == 3);
+        assert!(raster.rectangle.west == (-2.0f64).to_radians());
+        assert!(raster.rectangle.south == (-1.0f64).to_radians());
+        assert!(raster.rectangle.east == (-1.0f64).to_radians());
+        assert!(raster.rectangle.north == 0.0);
+        // 西北角
+        let value = raster
+            .interpolate(raster.rectangle.west, raster.rectangle.north)
+            .unwrap();
+        assert!(value == 1.0);
+        // 无数据的点不参与插值
+        let value = raster
+            .interpolate((-2.0f64).to_radians(), (-0.75f64).to_radians())
+            .unwrap();
+        assert!(value == 7.0);
+        assert!(raster.interpolate(0.1, 0.0).is_none());
+    }
+    #[test]
+    fn test_parse_bil() {
+        let header = "LAYOUT BIL\nNROWS 2\nNCOLS 2\nNBANDS 1\nNBITS 16\nULXMAP 10\nULYMAP 20\nXDIM 1\nYDIM 1\nNODATA -9999\n";
+        let mut bytes = vec![];
+        for value in [100i16, 200, 300, 400] {
+            bytes.extend(value.to_be_bytes());
+        }
+        let raster = DemRaster::from_bytes("dem.bil", &bytes, Some(header), true).unwrap();
+        assert!(raster.no_data == Some(-9999.0));
+        assert!(equals_epsilon(
+            raster.rectangle.south,
+            19.0f64.to_radians(),
+            Some(EPSILON12),
+            None
+        ));
+        assert!(equals_epsilon(
+            raster.rectangle.east,
+            11.0f64.to_radians(),
+            Some(EPSILON12),
+            None
+        ));
+        let center = raster
+            .interpolate(10.5f64.to_radians(), 19.5f64.to_radians())
+            .unwrap();
+        assert!(equals_epsilon(center, 250.0, Some(EPSILON12), None));
+    }
+    #[test]
+    fn test_parse_geotiff() {
+        use tiff::encoder::{colortype, TiffEncoder};
+        let mut bytes = Cursor::new(vec![]);
+        {
+            let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+            let mut image = encoder.new_image::<colortype::Gray32Float>(3, 2).unwrap();
+            image
+                .encoder()
+                .write_tag(Tag::ModelPixelScaleTag, &[0.5f64, 0.5, 0.0][..])
+                .unwrap();
+            image
+                .encoder()
+                .write_tag(Tag::ModelTiepointTag, &[0.0f64, 0.0, 0.0, 100.0, 30.0, 0.0][..])
+                .unwrap();
+            image
+                .encoder()
+                .write_tag(Tag::GeoKeyDirectoryTag, &[1u16, 1, 0, 2, 1024, 0, 1, 2, 1025, 0, 1, 1][..])
+                .unwrap();
+            image
+                .write_data(&[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0])
+                .unwrap();
+        }
+        let raster = DemRaster::from_geotiff(bytes.get_ref()).unwrap();
+        assert!(raster.width == 3 && raster.height == 2);
+        // RasterPixelIsArea，第一个采样点在像元中心
+        assert!(equals_epsilon(
+            raster.rectangle.west,
+            100.25f64.to_radians(),
+            Some(EPSILON12),
+            None
+        ));
+        assert!(equals_epsilon(
+            raster.rectangle.north,
+            29.75f64.to_radians(),
+            Some(EPSILON12),
+            None
+        ));
+        assert!(equals_epsilon(
+            raster.rectangle.east,
+            101.25f64.to_radians(),
+            Some(EPSILON12),
+            None
+        ));
+        let value = raster
+            .interpolate(raster.rectangle.east, raster.rectangle.south)
+            .unwrap();
+        assert!(value == 6.0);
+    }
+}
@@ -0,0 +1,378 @@
+use std::{io, sync::Arc};
+
+use houtu_jobs::{AsyncReturn, Context, Job};
+use houtu_scene::{
+    get_estimated_level_zero_geometric_error_for_a_heightmap, lerp, GeographicTilingScheme,
+    HeightmapTerrainData, HeightmapTerrainStructure, Rectangle, TilingScheme,
+};
+
+use crate::{
+    dem_raster::{self, DemRaster},
+    quadtree::{
+        terrain_provider::{
+            RequestTileGeometry, RequestTileGeometryError, RequestTileGeometryJob,
+            TerrainProvider, TerrainTileRequest, TerrainTileResponse,
+        },
+        tile_availability::TileAvailability,
+        tile_key::TileKey,
+    },
+    resource::{self, fetch_bytes, fetch_text},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Resource(#[from] resource::Error),
+    #[error("{0}")]
+    Dem(#[from] dem_raster::Error),
+    #[error("no dem file is specified")]
+    NoRasters,
+}
+pub struct HeightmapTerrainProviderOptions {
+    /// `.tif`/`.tiff`、`.hgt`或`.bil`文件，可以是本地路径也可以是http(s)地址
+    pub urls: Vec<String>,
+    /// 每个瓦片在经纬度方向上的采样点数
+    pub tile_width: u32,
+    /// `height_scale`和`height_offset`把文件中的原始值换算成米，
+    /// `is_big_endian`是`.hdr`中没有`BYTEORDER`时`.bil`的字节序
+    pub structure: HeightmapTerrainStructure,
+    /// 生成顶点法线，开启光照时需要
+    pub request_vertex_normals: bool,
+}
+impl Default for HeightmapTerrainProviderOptions {
+    fn default() -> Self {
+        Self {
+            urls: vec![],
+            tile_width: 65,
+            structure: HeightmapTerrainStructure::default(),
+            request_vertex_normals: false,
+        }
+    }
+}
+/// 把本地的DEM文件按需切成[`GeographicTilingScheme`]的高度图瓦片
+pub struct HeightmapTerrainProvider {
+    pub tiling_scheme: GeographicTilingScheme,
+    pub rasters: Arc<Vec<DemRaster>>,
+    pub tile_width: u32,
+    pub structure: HeightmapTerrainStructure,
+    /// 采样间距不再比DEM精细的层级，更深的层级从父瓦片上采样
+    pub maximum_level: u32,
+    pub ready: bool,
+    pub request_vertex_normals: bool,
+    level_zero_maximum_geometric_error: f64,
+}
+impl HeightmapTerrainProvider {
+    pub fn new(options: &HeightmapTerrainProviderOptions, rasters: Vec<DemRaster>) -> Self {
+        let tiling_scheme = GeographicTilingScheme::default();
+        let level_zero_maximum_geometric_error =
+            get_estimated_level_zero_geometric_error_for_a_heightmap(
+                &tiling_scheme.ellipsoid,
+                options.tile_width,
+                tiling_scheme.get_number_of_x_tiles_at_level(0),
+            );
+        let sample_spacing = rasters
+            .iter()
+            .map(|x| x.get_sample_spacing())
+            .fold(f64::MAX, f64::min);
+        let mut maximum_level = 0;
+        while maximum_level < 24 {
+            let tile_spacing = tiling_scheme.rectangle.compute_width()
+                / tiling_scheme.get_number_of_x_tiles_at_level(maximum_level) as f64
+                / (options.tile_width - 1) as f64;
+            if tile_spacing <= sample_spacing {
+                break;
+            }
+            maximum_level += 1;
+        }
+        return Self {
+            tiling_scheme,
+            rasters: Arc::new(rasters),
+            tile_width: options.tile_width,
+            structure: options.structure,
+            maximum_level,
+            ready: true,
+            request_vertex_normals: options.request_vertex_normals,
+            level_zero_maximum_geometric_error,
+        };
+    }
+    /// 读取所有DEM文件并创建地形服务，在bevy中一般用[`LoadHeightmapTerrainJob`]
+    pub async fn from_urls(options: HeightmapTerrainProviderOptions) -> Result<Self, Error> {
+        if options.urls.is_empty() {
+            return Err(Error::NoRasters);
+        }
+        let mut rasters = vec![];
+        for url in options.urls.iter() {
+            let bytes = fetch_bytes(url, &[]).await?;
+            let header = if url.to_ascii_lowercase().ends_with(".bil") {
+                let header_url = format!("{}.hdr", &url[..url.len() - 4]);
+                Some(fetch_text(&header_url, &[]).await?)
+            } else {
+                None
+            };
+            rasters.push(DemRaster::from_bytes(
+                url,
+                &bytes,
+                header.as_deref(),
+                options.structure.is_big_endian,
+            )?);
+        }
+        return Ok(Self::new(&options, rasters));
+    }
+    fn intersects(&self, key: &TileKey) -> bool {
+        let rectangle = self
+            .tiling_scheme
+            .tile_x_y_to_rectange(key.x, key.y, key.level);
+        return self
+            .rasters
+            .iter()
+            .any(|x| x.rectangle.simple_intersection(&rectangle).is_some());
+    }
+    fn compute_child_tile_mask(&self, key: &TileKey) -> i32 {
+        let mut mask = 0;
+        // 和HeightmapTerrainData::is_child_available中的位对应
+        for (bit, (x, y)) in [
+            (key.x * 2, key.y * 2 + 1),
+            (key.x * 2 + 1, key.y * 2 + 1),
+            (key.x * 2, key.y * 2),
+            (key.x * 2 + 1, key.y * 2),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if self.get_tile_data_available(&TileKey::new(x, y, key.level + 1)) == Some(true) {
+                mask |= 1 << bit;
+            }
+        }
+        return mask;
+    }
+}
+impl TerrainProvider for HeightmapTerrainProvider {
+    fn get_tiling_scheme(&self) -> &GeographicTilingScheme {
+        return &self.tiling_scheme;
+    }
+    fn get_ready(&self) -> bool {
+        self.ready
+    }
+    fn get_has_water_mask(&self) -> bool {
+        false
+    }
+    /// 请求法线时高度图生成网格会根据相邻格网点计算法线
+    fn get_has_vertex_normals(&self) -> bool {
+        self.request_vertex_normals
+    }
+    fn get_availability(&mut self) -> Option<&mut TileAvailability> {
+        return None;
+    }
+    fn request_tile_geometry(&self, key: &TileKey) -> RequestTileGeometry {
+        if self.get_tile_data_available(key) != Some(true) {
+            return RequestTileGeometry::NotAvailable;
+        }
+        return RequestTileGeometry::Requested(RequestTileGeometryJob {
+            key: *key,
+            request: Box::new(HeightmapTerrainTileRequest {
+                rasters: self.rasters.clone(),
+                rectangle: self
+                    .tiling_scheme
+                    .tile_x_y_to_rectange(key.x, key.y, key.level),
+                width: self.tile_width,
+                structure: self.structure,
+                child_tile_mask: self.compute_child_tile_mask(key),
+                include_vertex_normals: self.request_vertex_normals,
+            }),
+        });
+    }
+    fn handle_tile_metadata(&mut self, _key: &TileKey, _metadata: &str) {}
+    fn get_level_maximum_geometric_error(&self, level: u32) -> f64 {
+        return self.level_zero_maximum_geometric_error / (1 << level) as f64;
+    }
+    fn load_tile_data_availability(&self, _key: &TileKey) -> Option<bool> {
+        return None;
+    }
+    fn get_tile_data_available(&self, key: &TileKey) -> Option<bool> {
+        if key.level > self.maximum_level {
+            return Some(false);
+        }
+        // 根瓦片即使没有DEM覆盖也要有数据，其他瓦片没有覆盖时从父瓦片上采样
+        return Some(key.level == 0 || self.intersects(key));
+    }
+}
+/// 在任务中从DEM上采样出一个瓦片
+struct HeightmapTerrainTileRequest {
+    rasters: Arc<Vec<DemRaster>>,
+    rectangle: Rectangle,
+    width: u32,
+    structure: HeightmapTerrainStructure,
+    child_tile_mask: i32,
+    include_vertex_normals: bool,
+}
+impl HeightmapTerrainTileRequest {
+    fn create_terrain_data(&self) -> HeightmapTerrainData {
+        let width = self.width;
+        // 没有DEM覆盖的地方高度为0
+        let fill_value = (-self.structure.height_offset / self.structure.height_scale) as f32;
+        let mut buffer = Vec::with_capacity((width * width) as usize);
+        for j in 0..width {
+            let latitude = lerp(
+                self.rectangle.north,
+                self.rectangle.south,
+                j as f64 / (width - 1) as f64,
+            );
+            for i in 0..width {
+                let longitude = lerp(
+                    self.rectangle.west,
+                    self.rectangle.east,
+                    i as f64 / (width - 1) as f64,
+                );
+                let value = self
+                    .rasters
+                    .iter()
+                    .find_map(|x| x.interpolate(longitude, latitude))
+                    .map(|x| x as f32)
+                    .unwrap_or(fill_value);
+                buffer.push(value);
+            }
+        }
+        let mut data = HeightmapTerrainData::new(
+            buffer,
+            width,
+            width,
+            Some(self.child_tile_mask),
+            None,
+            Some(self.structure),
+            None,
+            None,
+            None,
+            None,
+        );
+        data._include_vertex_normals = self.include_vertex_normals;
+        return data;
+    }
+}
+impl TerrainTileRequest for HeightmapTerrainTileRequest {
+    fn perform(
+        self: Box<Self>,
+    ) -> AsyncReturn<Result<TerrainTileResponse, RequestTileGeometryError>> {
+        Box::pin(async move {
+            Ok(TerrainTileResponse {
+                data: self.create_terrain_data().into(),
+                metadata: None,
+            })
+        })
+    }
+}
+pub struct LoadHeightmapTerrainJob {
+    pub options: HeightmapTerrainProviderOptions,
+}
+impl Job for LoadHeightmapTerrainJob {
+    type Outcome = Result<HeightmapTerrainProvider, Error>;
+    fn name(&self) -> String {
+        format!("load dem files {:?}", self.options.urls)
+    }
+    fn perform(self, _context: Context) -> AsyncReturn<Self::Outcome> {
+        Box::pin(async move {
+            let fetch = HeightmapTerrainProvider::from_urls(self.options);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(fetch)
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                fetch.await
+            }
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::quadtree::terrain_data::TerrainData;
+    use houtu_scene::{equals_epsilon, EPSILON9};
+
+    /// 覆盖经度[0,90]、纬度[0,90]，正好是第1级的(2,0)瓦片
+    fn create_provider() -> HeightmapTerrainProvider {
+        let raster = DemRaster::new(
+            3,
+            3,
+            Rectangle::new(0.0, 0.0, FRAC_PI_2, FRAC_PI_2),
+            vec![0.0, 5.0, 10.0, 10.0, 15.0, 20.0, 20.0, 25.0, 30.0],
+            None,
+        )
+        .unwrap();
+        let options = HeightmapTerrainProviderOptions {
+            tile_width: 3,
+            structure: HeightmapTerrainStructure {
+                height_scale: 2.0,
+                height_offset: -100.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        HeightmapTerrainProvider::new(&options, vec![raster])
+    }
+    fn request(provider: &HeightmapTerrainProvider, key: TileKey) -> HeightmapTerrainData {
+        let RequestTileGeometry::Requested(job) = provider.request_tile_geometry(&key) else {
+            panic!("tile {:?} should be requested", key);
+        };
+        let response = pollster::block_on(job.request.perform()).unwrap();
+        let TerrainData::Heightmap(data) = response.data else {
+            panic!("expected heightmap terrain data");
+        };
+        data
+    }
+    #[test]
+    fn test_request_vertex_normals() {
+        let mut provider = create_provider();
+        // 默认不生成法线
+        assert!(!provider.get_has_vertex_normals());
+        assert!(!request(&provider, TileKey::new(2, 0, 1))._include_vertex_normals);
+        provider.request_vertex_normals = true;
+        assert!(provider.get_has_vertex_normals());
+        assert!(request(&provider, TileKey::new(2, 0, 1))._include_vertex_normals);
+    }
+    #[test]
+    fn test_maximum_level() {
+        let provider = create_provider();
+        // 第1级瓦片宽90度，3个采样点的间距是45度，和DEM一样
+        assert!(provider.maximum_level == 1);
+        assert!(provider.get_tile_data_available(&TileKey::new(0, 0, 2)) == Some(false));
+        assert!(matches!(
+            provider.request_tile_geometry(&TileKey::new(4, 0, 2)),
+            RequestTileGeometry::NotAvailable
+        ));
+    }
+    #[test]
+    fn test_tile_availability() {
+        let provider = create_provider();
+        assert!(provider.get_tile_data_available(&TileKey::new(0, 0, 0)) == Some(true));
+        assert!(provider.get_tile_data_available(&TileKey::new(2, 0, 1)) == Some(true));
+        assert!(provider.get_tile_data_available(&TileKey::new(0, 1, 1)) == Some(false));
+        // 只有东边根瓦片的西北子瓦片有数据
+        assert!(provider.compute_child_tile_mask(&TileKey::new(1, 0, 0)) == 1 << 2);
+        assert!(provider.compute_child_tile_mask(&TileKey::new(0, 0, 0)) == 0);
+    }
+    #[test]
+    fn test_create_tile_from_raster() {
+        let provider = create_provider();
+        let data = request(&provider, TileKey::new(2, 0, 1));
+        assert!(data._width == 3 && data._height == 3);
+        assert!(data._structure.height_scale == 2.0);
+        for (index, expected) in [(0, 0.0), (2, 10.0), (4, 15.0), (8, 30.0)] {
+            assert!(equals_epsilon(
+                data._buffer[index] as f64,
+                expected,
+                Some(EPSILON9),
+                None
+            ));
+        }
+        // 没有DEM覆盖的地方，原始值换算后高度为0
+        let data = request(&provider, TileKey::new(0, 0, 0));
+        assert!(data._buffer[0] == 50.0);
+    }
+}
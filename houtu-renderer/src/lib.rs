@@ -13,14 +13,26 @@ mod camera;
 mod globe;
 
 mod bing_maps_imagery_provider;
+mod data_source;
 mod helpers;
 mod image;
+mod measure;
+mod mvt_imagery_provider;
+mod mvt_style;
+mod mvt_tile;
+mod primitive;
 mod quadtree;
 mod render;
+mod single_tile_imagery_provider;
 mod wmts_imagery_provider;
+mod wmts_capabilities;
+mod web_map_service_imagery_provider;
 mod xyz_imagery_provider;
 mod quantized_mesh_terrain_data;
 mod cesium_terrain_provider;
+mod resource;
+mod dem_raster;
+mod heightmap_terrain_provider;
 // use plugins::quadtree;
 #[derive(Clone, Copy, Component, PartialEq, Eq)]
 pub enum RenderEntityType {
@@ -56,6 +68,10 @@ impl Plugin for RendererPlugin {
                 camera::CameraPlugin,
                 quadtree::Plugin,
                 render::Plugin,
+                measure::Plugin,
+                primitive::Plugin,
+                data_source::Plugin,
+                mvt_imagery_provider::Plugin,
             )); //bevy_egui的插件会让wasm下canavas显示变成灰色，暂时先不用。
         #[cfg(not(target_arch = "wasm32"))]
         app.add_plugins(WorldInspectorPlugin::new());
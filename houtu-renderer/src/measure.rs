@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy_egui::{egui, EguiContexts};
+use houtu_scene::{
+    geodesic_bearing, geodesic_polygon_area, polygon_perimeter, polyline_length, Cartographic,
+};
+
+use crate::{
+    camera::GlobeClickEvent,
+    primitive::{Label, Polyline},
+};
+
+/// 开关测量工具的按键
+const TOGGLE_KEY: KeyCode = KeyCode::M;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeasureMode {
+    #[default]
+    None,
+    Distance,
+    Area,
+}
+/// 在地球上点击测量距离和面积，按M键开关，左键添加点，右键结束，结束后再点击开始新的测量
+#[derive(Resource, Debug, Default)]
+pub struct MeasureTool {
+    pub mode: MeasureMode,
+    pub positions: Vec<Cartographic>,
+    pub finished: bool,
+}
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MeasureResult {
+    /// 距离模式为折线长度，面积模式为周长
+    pub length: Option<f64>,
+    pub area: Option<f64>,
+    /// 最后一段的起点方位角(弧度)
+    pub bearing: Option<f64>,
+}
+impl MeasureTool {
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.finished = false;
+    }
+    pub fn result(&self) -> MeasureResult {
+        let mut result = MeasureResult::default();
+        let n = self.positions.len();
+        if n >= 2 {
+            result.bearing = geodesic_bearing(&self.positions[n - 2], &self.positions[n - 1])
+                .map(|(start, _)| start);
+        }
+        match self.mode {
+            MeasureMode::None => {}
+            MeasureMode::Distance => {
+                result.length = polyline_length(&self.positions);
+            }
+            MeasureMode::Area => {
+                result.length = polygon_perimeter(&self.positions);
+                if n >= 3 {
+                    result.area = Some(geodesic_polygon_area(&self.positions));
+                }
+            }
+        }
+        return result;
+    }
+    /// 测量结果的文字，每项一行
+    pub fn result_lines(&self) -> Vec<String> {
+        let result = self.result();
+        let mut lines = vec![];
+        if let Some(length) = result.length {
+            let name = if self.mode == MeasureMode::Area {
+                "周长"
+            } else {
+                "距离"
+            };
+            lines.push(format!("{}: {}", name, format_distance(length)));
+        }
+        if let Some(area) = result.area {
+            lines.push(format!("面积: {}", format_area(area)));
+        }
+        if let Some(bearing) = result.bearing {
+            lines.push(format!("方位角: {:.2}°", bearing.to_degrees()));
+        }
+        return lines;
+    }
+}
+pub fn format_distance(meters: f64) -> String {
+    if meters >= 1000.0 {
+        return format!("{:.2} km", meters / 1000.0);
+    }
+    return format!("{:.1} m", meters);
+}
+pub fn format_area(square_meters: f64) -> String {
+    if square_meters >= 1e6 {
+        return format!("{:.2} km²", square_meters / 1e6);
+    }
+    return format!("{:.1} m²", square_meters);
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeasureTool>().add_systems(
+            Update,
+            (
+                measure_toggle_system,
+                measure_click_system,
+                measure_draw_system,
+            )
+                .chain(),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(Update, measure_ui_system);
+    }
+}
+fn measure_toggle_system(keys: Res<Input<KeyCode>>, mut tool: ResMut<MeasureTool>) {
+    if !keys.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+    tool.mode = if tool.mode == MeasureMode::None {
+        MeasureMode::Distance
+    } else {
+        MeasureMode::None
+    };
+    tool.clear();
+}
+fn measure_click_system(
+    mut tool: ResMut<MeasureTool>,
+    mut click_reader: EventReader<GlobeClickEvent>,
+) {
+    for event in click_reader.iter() {
+        if tool.mode == MeasureMode::None {
+            continue;
+        }
+        match event.button {
+            MouseButton::Left => {
+                if tool.finished {
+                    tool.clear();
+                }
+                tool.positions.push(event.result.cartographic);
+            }
+            MouseButton::Right => {
+                tool.finished = true;
+            }
+            _ => {}
+        }
+    }
+}
+/// 测量改变时重新生成贴地的路径和结果标注
+fn measure_draw_system(
+    mut commands: Commands,
+    tool: Res<MeasureTool>,
+    mut entities: Local<Vec<Entity>>,
+) {
+    if !tool.is_changed() {
+        return;
+    }
+    for entity in entities.drain(..) {
+        commands.entity(entity).despawn_recursive();
+    }
+    if tool.mode == MeasureMode::None {
+        return;
+    }
+    let Some(last) = tool.positions.last() else {
+        return;
+    };
+    if tool.positions.len() >= 2 {
+        let mut positions = tool.positions.clone();
+        if tool.mode == MeasureMode::Area && positions.len() >= 3 {
+            positions.push(positions[0]);
+        }
+        let path = Polyline {
+            positions,
+            width: 3.0,
+            color: Color::YELLOW,
+            clamp_to_ground: true,
+            ..Default::default()
+        };
+        entities.push(commands.spawn(path).id());
+    }
+    let lines = tool.result_lines();
+    if !lines.is_empty() {
+        let label = Label {
+            background_color: Some(Color::rgba(0.0, 0.0, 0.0, 0.6)),
+            // 放在最后一个点的上方
+            pixel_offset: Vec2::new(0.0, -16.0 * lines.len() as f32),
+            ..Label::new(*last, lines.join("\n"))
+        };
+        entities.push(commands.spawn(label).id());
+    }
+}
+#[cfg(not(target_arch = "wasm32"))]
+fn measure_ui_system(mut contexts: EguiContexts, mut tool: ResMut<MeasureTool>) {
+    if tool.mode == MeasureMode::None {
+        return;
+    }
+    let mut open = true;
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("测量")
+        .open(&mut open)
+        .default_pos([20.0, 100.0])
+        .resizable(false)
+        .auto_sized()
+        .show(ctx, |ui| {
+            let mut mode = tool.mode;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut mode, MeasureMode::None, "关闭");
+                ui.radio_value(&mut mode, MeasureMode::Distance, "距离");
+                ui.radio_value(&mut mode, MeasureMode::Area, "面积");
+            });
+            if mode != tool.mode {
+                tool.mode = mode;
+                tool.clear();
+            }
+            if tool.mode == MeasureMode::None {
+                return;
+            }
+            ui.label(format!("点数: {}", tool.positions.len()));
+            for line in tool.result_lines() {
+                ui.label(line);
+            }
+            if ui.button("清除").clicked() {
+                tool.clear();
+            }
+        });
+    if !open {
+        tool.mode = MeasureMode::None;
+        tool.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result() {
+        let mut tool = MeasureTool {
+            mode: MeasureMode::Area,
+            ..Default::default()
+        };
+        tool.positions = vec![
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(0.01, 0.0, 0.0),
+        ];
+        let result = tool.result();
+        assert!(result.area.is_none());
+        assert!((result.bearing.unwrap().to_degrees() - 90.0).abs() < 1e-9);
+        tool.positions
+            .push(Cartographic::from_degrees(0.01, 0.01, 0.0));
+        assert!(tool.result().area.unwrap() > 0.0);
+        let lines = tool.result_lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("周长: "));
+        assert!(lines[1].starts_with("面积: "));
+        assert_eq!(format_distance(1234.5), "1.23 km");
+        assert_eq!(format_distance(12.34), "12.3 m");
+        assert_eq!(format_area(2.5e6), "2.50 km²");
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::BoxedFuture,
+};
+use houtu_scene::{Rectangle, TilingScheme, WebMercatorTilingScheme};
+use new_string_template::template::Template;
+
+use crate::{
+    mvt_style::MvtStyle,
+    mvt_tile::MvtTile,
+    quadtree::{
+        credit::Credit,
+        imagery_provider::{ImageryProvider, PickFeaturesJob},
+        tile_key::TileKey,
+    },
+    resource::is_local_url,
+};
+
+static NEXT_PROVIDER_ID: AtomicU32 = AtomicU32::new(0);
+
+/// 瓦片地址对应的样式和图片大小，由ImageryProvider在请求前登记，MvtLoader加载时取出
+#[derive(Debug, Clone)]
+struct MvtTileRequest {
+    provider_id: u32,
+    style: Arc<MvtStyle>,
+    width: u32,
+    height: u32,
+}
+/// AssetLoader只能拿到地址，通过这个表把样式传给它，键是带有提供者编号的资源路径
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MvtRequests(Arc<Mutex<HashMap<String, MvtTileRequest>>>);
+
+/// 请求{z}/{x}/{y}格式的矢量瓦片(.pbf/.mvt)，按样式绘制成图片后和栅格瓦片一样重投影和贴到地形上
+pub struct MvtImageryProvider {
+    pub tiling_scheme: Box<dyn TilingScheme>,
+    pub rectangle: Rectangle,
+    pub url: &'static str,
+    pub subdomains: Option<Vec<&'static str>>,
+    pub minimum_level: u32,
+    pub maximum_level: u32,
+    pub ready: bool,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub style: Arc<MvtStyle>,
+    id: u32,
+    requests: MvtRequests,
+}
+impl MvtImageryProvider {
+    pub fn new(url: &'static str, style: MvtStyle, requests: &MvtRequests) -> Self {
+        let tiling_scheme = Box::new(WebMercatorTilingScheme::default());
+        let rectangle = tiling_scheme.get_rectangle();
+        Self {
+            tiling_scheme,
+            rectangle,
+            url,
+            subdomains: None,
+            minimum_level: 0,
+            maximum_level: 14,
+            ready: true,
+            tile_width: 256,
+            tile_height: 256,
+            style: Arc::new(style),
+            id: NEXT_PROVIDER_ID.fetch_add(1, Ordering::Relaxed),
+            requests: requests.clone(),
+        }
+    }
+    pub fn get_subdomain(&self, key: &TileKey) -> Option<&'static str> {
+        self.subdomains
+            .as_ref()
+            .and_then(|subs| Some(subs[(key.y + key.x + key.level) as usize % subs.len()]))
+    }
+    pub fn build_url(&self, key: &TileKey) -> Option<String> {
+        let template = Template::new(self.url);
+        let mut args = HashMap::new();
+        if let Some(subdomain) = self.get_subdomain(key) {
+            args.insert("s", subdomain);
+        }
+        let level = key.level.to_string();
+        let x = key.x.to_string();
+        let y = key.y.to_string();
+        args.insert("z", level.as_str());
+        args.insert("x", x.as_str());
+        args.insert("y", y.as_str());
+        return template.render(&args).ok();
+    }
+    /// 加载用的资源路径。相同地址的资源只会加载一次，网络地址加上提供者的编号，
+    /// 不同样式的提供者得到各自的图片；本地文件不能带查询参数，直接使用地址
+    pub fn asset_path(&self, key: &TileKey) -> Option<String> {
+        let url = self.build_url(key)?;
+        if is_local_url(&url) {
+            return Some(url);
+        }
+        let separator = if url.contains('?') { "&" } else { "?" };
+        // bevy根据扩展名选择AssetLoader，末尾加一个服务会忽略的参数
+        return Some(format!("{}{}mvt_style={}&.pbf", url, separator, self.id));
+    }
+}
+impl Drop for MvtImageryProvider {
+    /// 删除还没有被加载的请求
+    fn drop(&mut self) {
+        if let Ok(mut requests) = self.requests.0.lock() {
+            requests.retain(|_, v| v.provider_id != self.id);
+        }
+    }
+}
+impl ImageryProvider for MvtImageryProvider {
+    fn get_maximum_level(&self) -> u32 {
+        self.maximum_level
+    }
+    fn get_minimum_level(&self) -> u32 {
+        self.minimum_level
+    }
+    fn get_ready(&self) -> bool {
+        self.ready
+    }
+    fn get_rectangle(&self) -> &Rectangle {
+        &self.rectangle
+    }
+    fn get_tile_credits(&self, _key: &TileKey) -> Option<Vec<Credit>> {
+        None
+    }
+    fn get_tile_height(&self) -> u32 {
+        self.tile_height
+    }
+    fn get_tile_width(&self) -> u32 {
+        self.tile_width
+    }
+    fn get_tiling_scheme(&self) -> &Box<dyn TilingScheme> {
+        &self.tiling_scheme
+    }
+    fn load_image(&self, _url: String) {}
+    fn pick_features(
+        &self,
+        _key: &TileKey,
+        _longitude: f64,
+        _latitude: f64,
+    ) -> Option<PickFeaturesJob> {
+        None
+    }
+    fn request_image(&self, key: &TileKey, asset_server: &AssetServer) -> Option<Handle<Image>> {
+        let Some(path) = self.asset_path(key) else {
+            warn!("extected a tile url");
+            return None;
+        };
+        // 已经加载或正在加载的资源不会再交给MvtLoader，登记了也取不走
+        let state = asset_server.get_load_state(path.as_str());
+        if !matches!(state, LoadState::Loaded | LoadState::Loading) {
+            self.requests.0.lock().unwrap().insert(
+                path.clone(),
+                MvtTileRequest {
+                    provider_id: self.id,
+                    style: self.style.clone(),
+                    width: self.tile_width,
+                    height: self.tile_height,
+                },
+            );
+        }
+        return Some(asset_server.load(path));
+    }
+}
+/// 把矢量瓦片解码并绘制成图片，没有登记样式时使用MvtStyle::fallback
+pub struct MvtLoader {
+    requests: MvtRequests,
+}
+impl FromWorld for MvtLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            requests: world
+                .get_resource_or_insert_with(MvtRequests::default)
+                .clone(),
+        }
+    }
+}
+impl AssetLoader for MvtLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let url = load_context.path().to_string_lossy().to_string();
+            let request = self.requests.0.lock().unwrap().remove(&url);
+            let tile = MvtTile::from_slice(bytes)?;
+            let (style, width, height) = match request {
+                Some(v) => (v.style, v.width, v.height),
+                None => (Arc::new(MvtStyle::fallback(&tile)), 256, 256),
+            };
+            let image = Image::new(
+                Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                style.rasterize(&tile, width, height),
+                TextureFormat::Rgba8UnormSrgb,
+            );
+            load_context.set_default_asset(LoadedAsset::new(image));
+            Ok(())
+        })
+    }
+    fn extensions(&self) -> &[&str] {
+        &["pbf", "mvt"]
+    }
+}
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MvtRequests>()
+            .init_asset_loader::<MvtLoader>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_url() {
+        let requests = MvtRequests::default();
+        let mut provider = MvtImageryProvider::new(
+            "https://{s}.tiles.example.com/v1/{z}/{x}/{y}.pbf",
+            MvtStyle::default(),
+            &requests,
+        );
+        provider.subdomains = Some(vec!["a", "b"]);
+        let key = TileKey::new(3, 2, 1);
+        assert_eq!(
+            provider.build_url(&key).unwrap(),
+            "https://a.tiles.example.com/v1/1/3/2.pbf"
+        );
+        provider.url = "https://tiles.example.com/{z}/{x}/{y}.pbf?key=abc";
+        assert_eq!(
+            provider.build_url(&key).unwrap(),
+            "https://tiles.example.com/1/3/2.pbf?key=abc"
+        );
+    }
+    #[test]
+    fn test_asset_path() {
+        let requests = MvtRequests::default();
+        let url = "https://tiles.example.com/{z}/{x}/{y}.pbf";
+        let first = MvtImageryProvider::new(url, MvtStyle::default(), &requests);
+        let second = MvtImageryProvider::new(url, MvtStyle::default(), &requests);
+        let key = TileKey::new(3, 2, 1);
+        // 相同地址不同提供者的资源路径不同，都以.pbf结尾
+        let path = first.asset_path(&key).unwrap();
+        assert_eq!(
+            path,
+            format!(
+                "https://tiles.example.com/1/3/2.pbf?mvt_style={}&.pbf",
+                first.id
+            )
+        );
+        assert_ne!(path, second.asset_path(&key).unwrap());
+        let local = MvtImageryProvider::new(
+            "/data/tiles/{z}/{x}/{y}.pbf",
+            MvtStyle::default(),
+            &requests,
+        );
+        assert_eq!(local.asset_path(&key).unwrap(), "/data/tiles/1/3/2.pbf");
+
+        // 删除提供者时清理没有被取走的请求
+        requests.0.lock().unwrap().insert(
+            path,
+            MvtTileRequest {
+                provider_id: first.id,
+                style: first.style.clone(),
+                width: 256,
+                height: 256,
+            },
+        );
+        drop(first);
+        assert!(requests.0.lock().unwrap().is_empty());
+    }
+}
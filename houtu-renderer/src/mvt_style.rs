@@ -0,0 +1,512 @@
+use bevy::{math::Vec2, prelude::Color};
+use serde_json::Value;
+
+use crate::mvt_tile::{MvtFeature, MvtGeometryType, MvtTile};
+
+/// 要素过滤条件，参考Mapbox样式规范中的旧版filter
+#[derive(Debug, Clone, PartialEq)]
+pub enum MvtFilter {
+    All(Vec<MvtFilter>),
+    Any(Vec<MvtFilter>),
+    Not(Box<MvtFilter>),
+    Has(String),
+    Eq(String, Value),
+    In(String, Vec<Value>),
+    Lt(String, f64),
+    Gt(String, f64),
+    GeometryType(MvtGeometryType),
+}
+/// 整数和浮点数的属性按数值比较
+fn value_eq(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+impl MvtFilter {
+    pub fn eq(key: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self::Eq(key.into(), value.into())
+    }
+    pub fn evaluate(&self, feature: &MvtFeature) -> bool {
+        let properties = &feature.properties;
+        let number = |key: &String| properties.get(key).and_then(|v| v.as_f64());
+        match self {
+            Self::All(filters) => filters.iter().all(|v| v.evaluate(feature)),
+            Self::Any(filters) => filters.iter().any(|v| v.evaluate(feature)),
+            Self::Not(filter) => !filter.evaluate(feature),
+            Self::Has(key) => properties.contains_key(key),
+            Self::Eq(key, value) => properties.get(key).map_or(false, |v| value_eq(v, value)),
+            Self::In(key, values) => properties
+                .get(key)
+                .map_or(false, |v| values.iter().any(|value| value_eq(v, value))),
+            Self::Lt(key, value) => number(key).map_or(false, |v| v < *value),
+            Self::Gt(key, value) => number(key).map_or(false, |v| v > *value),
+            Self::GeometryType(geometry_type) => feature.geometry_type == *geometry_type,
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum MvtPaint {
+    /// 填充面要素
+    Fill {
+        color: Color,
+        outline_color: Option<Color>,
+    },
+    /// 绘制线要素和面要素的边界，宽度为像素
+    Line { color: Color, width: f32 },
+    /// 绘制点要素，半径为像素
+    Circle {
+        color: Color,
+        radius: f32,
+        stroke_color: Option<Color>,
+        stroke_width: f32,
+    },
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct MvtStyleLayer {
+    /// 瓦片中图层的名字
+    pub source_layer: String,
+    pub filter: Option<MvtFilter>,
+    pub paint: MvtPaint,
+}
+impl MvtStyleLayer {
+    pub fn new(source_layer: impl Into<String>, paint: MvtPaint) -> Self {
+        Self {
+            source_layer: source_layer.into(),
+            filter: None,
+            paint,
+        }
+    }
+    pub fn with_filter(mut self, filter: MvtFilter) -> Self {
+        self.filter = Some(filter);
+        return self;
+    }
+}
+/// 按顺序绘制，后面的图层在上面
+#[derive(Debug, Clone, PartialEq)]
+pub struct MvtStyle {
+    pub background: Color,
+    pub layers: Vec<MvtStyleLayer>,
+}
+impl Default for MvtStyle {
+    fn default() -> Self {
+        Self {
+            background: Color::NONE,
+            layers: vec![],
+        }
+    }
+}
+impl MvtStyle {
+    /// 没有配置样式时，所有图层都用同一种颜色绘制
+    pub fn fallback(tile: &MvtTile) -> Self {
+        let color = Color::rgb(0.2, 0.4, 0.8);
+        let mut layers = vec![];
+        for layer in tile.layers.iter() {
+            layers.push(MvtStyleLayer::new(
+                layer.name.clone(),
+                MvtPaint::Fill {
+                    color: color.with_a(0.3),
+                    outline_color: None,
+                },
+            ));
+            layers.push(MvtStyleLayer::new(
+                layer.name.clone(),
+                MvtPaint::Line { color, width: 1.0 },
+            ));
+            layers.push(MvtStyleLayer::new(
+                layer.name.clone(),
+                MvtPaint::Circle {
+                    color,
+                    radius: 3.0,
+                    stroke_color: None,
+                    stroke_width: 0.0,
+                },
+            ));
+        }
+        return Self {
+            background: Color::NONE,
+            layers,
+        };
+    }
+    /// 把瓦片绘制为RGBA8(sRGB)的像素
+    pub fn rasterize(&self, tile: &MvtTile, width: u32, height: u32) -> Vec<u8> {
+        let mut canvas = Canvas::new(width, height);
+        canvas.clear(self.background);
+        for style_layer in self.layers.iter() {
+            let Some(layer) = tile.get_layer(&style_layer.source_layer) else {
+                continue;
+            };
+            let scale = Vec2::new(width as f32, height as f32) / layer.extent as f32;
+            for feature in layer.features.iter() {
+                if !style_layer
+                    .filter
+                    .as_ref()
+                    .map_or(true, |v| v.evaluate(feature))
+                {
+                    continue;
+                }
+                let parts: Vec<Vec<Vec2>> = feature
+                    .geometry
+                    .iter()
+                    .map(|part| part.iter().map(|v| v.as_vec2() * scale).collect())
+                    .collect();
+                draw_feature(
+                    &mut canvas,
+                    &style_layer.paint,
+                    feature.geometry_type,
+                    &parts,
+                );
+            }
+        }
+        return canvas.pixels;
+    }
+}
+fn draw_feature(
+    canvas: &mut Canvas,
+    paint: &MvtPaint,
+    geometry_type: MvtGeometryType,
+    parts: &[Vec<Vec2>],
+) {
+    match (paint, geometry_type) {
+        (
+            MvtPaint::Fill {
+                color,
+                outline_color,
+            },
+            MvtGeometryType::Polygon,
+        ) => {
+            canvas.fill_polygon(parts);
+            canvas.composite(*color);
+            if let Some(outline_color) = outline_color {
+                for ring in parts.iter() {
+                    canvas.stroke(ring, 1.0, true);
+                }
+                canvas.composite(*outline_color);
+            }
+        }
+        (
+            MvtPaint::Line { color, width },
+            MvtGeometryType::LineString | MvtGeometryType::Polygon,
+        ) => {
+            let closed = geometry_type == MvtGeometryType::Polygon;
+            for part in parts.iter() {
+                canvas.stroke(part, *width, closed);
+            }
+            canvas.composite(*color);
+        }
+        (
+            MvtPaint::Circle {
+                color,
+                radius,
+                stroke_color,
+                stroke_width,
+            },
+            MvtGeometryType::Point,
+        ) => {
+            for point in parts.iter().flatten() {
+                canvas.circle(*point, *radius);
+            }
+            canvas.composite(*color);
+            if let (Some(stroke_color), true) = (stroke_color, *stroke_width > 0.0) {
+                for point in parts.iter().flatten() {
+                    canvas.ring(*point, *radius, *stroke_width);
+                }
+                canvas.composite(*stroke_color);
+            }
+        }
+        _ => {}
+    }
+}
+/// 填充时每个像素行的子扫描线数
+const FILL_SUBSAMPLES: u32 = 4;
+
+/// 先把一个要素的覆盖率画到mask上再一次性混合，半透明的线在转折处不会重叠变深
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    mask: Vec<f32>,
+    /// mask中被修改过的范围(min_x, min_y, max_x, max_y)
+    dirty: Option<(u32, u32, u32, u32)>,
+}
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        Self {
+            width,
+            height,
+            pixels: vec![0; len * 4],
+            mask: vec![0.0; len],
+            dirty: None,
+        }
+    }
+    fn clear(&mut self, color: Color) {
+        let color = color.as_rgba_u8();
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+    /// 把浮点范围裁剪到画布内的像素范围
+    fn pixel_range(&self, min: Vec2, max: Vec2) -> Option<(u32, u32, u32, u32)> {
+        let min_x = min.x.floor().max(0.0);
+        let min_y = min.y.floor().max(0.0);
+        let max_x = max.x.ceil().min(self.width as f32 - 1.0);
+        let max_y = max.y.ceil().min(self.height as f32 - 1.0);
+        if min_x > max_x || min_y > max_y {
+            return None;
+        }
+        return Some((min_x as u32, min_y as u32, max_x as u32, max_y as u32));
+    }
+    fn mark_dirty(&mut self, range: (u32, u32, u32, u32)) {
+        self.dirty = Some(match self.dirty {
+            Some(v) => (
+                v.0.min(range.0),
+                v.1.min(range.1),
+                v.2.max(range.2),
+                v.3.max(range.3),
+            ),
+            None => range,
+        });
+    }
+    /// 对range内的每个像素中心计算覆盖率
+    fn cover(&mut self, min: Vec2, max: Vec2, coverage: impl Fn(Vec2) -> f32) {
+        let Some(range) = self.pixel_range(min, max) else {
+            return;
+        };
+        self.mark_dirty(range);
+        for y in range.1..=range.3 {
+            for x in range.0..=range.2 {
+                let value = coverage(Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+                if value > 0.0 {
+                    let mask = &mut self.mask[(y * self.width + x) as usize];
+                    *mask = mask.max(value.min(1.0));
+                }
+            }
+        }
+    }
+    /// 奇偶规则扫描线填充，洞不需要区分环的方向。每行取多条子扫描线，
+    /// 按区间和像素的重叠长度累加覆盖率，边缘得到抗锯齿
+    fn fill_polygon(&mut self, rings: &[Vec<Vec2>]) {
+        let points = rings.iter().flatten();
+        let min = points.clone().fold(Vec2::MAX, |a, b| a.min(*b));
+        let max = points.fold(Vec2::MIN, |a, b| a.max(*b));
+        let Some(range) = self.pixel_range(min, max) else {
+            return;
+        };
+        self.mark_dirty(range);
+        let weight = 1.0 / FILL_SUBSAMPLES as f32;
+        let mut crossings = vec![];
+        let mut row = vec![0.0; (range.2 - range.0 + 1) as usize];
+        for y in range.1..=range.3 {
+            row.fill(0.0);
+            for sample in 0..FILL_SUBSAMPLES {
+                let sample_y = y as f32 + (sample as f32 + 0.5) * weight;
+                crossings.clear();
+                for ring in rings.iter() {
+                    for i in 0..ring.len() {
+                        let a = ring[i];
+                        let b = ring[(i + 1) % ring.len()];
+                        if (a.y <= sample_y) != (b.y <= sample_y) {
+                            crossings.push(a.x + (sample_y - a.y) / (b.y - a.y) * (b.x - a.x));
+                        }
+                    }
+                }
+                crossings.sort_by(|a, b| a.total_cmp(b));
+                for pair in crossings.chunks_exact(2) {
+                    let start = pair[0].max(range.0 as f32);
+                    let end = pair[1].min(range.2 as f32 + 1.0);
+                    if start >= end {
+                        continue;
+                    }
+                    for x in start.floor() as u32..end.ceil() as u32 {
+                        let overlap = end.min(x as f32 + 1.0) - start.max(x as f32);
+                        row[(x - range.0) as usize] += overlap.max(0.0) * weight;
+                    }
+                }
+            }
+            let offset = (y * self.width + range.0) as usize;
+            for (mask, value) in self.mask[offset..offset + row.len()]
+                .iter_mut()
+                .zip(row.iter())
+            {
+                if *value > 0.0 {
+                    *mask = mask.max(value.min(1.0));
+                }
+            }
+        }
+    }
+    /// 线宽两侧各留半个像素做抗锯齿
+    fn stroke(&mut self, points: &[Vec2], width: f32, closed: bool) {
+        if points.len() < 2 {
+            return;
+        }
+        let half_width = width * 0.5;
+        let count = if closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+        for i in 0..count {
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            let padding = Vec2::splat(half_width + 1.0);
+            self.cover(a.min(b) - padding, a.max(b) + padding, |p| {
+                let ab = b - a;
+                let t = if ab == Vec2::ZERO {
+                    0.0
+                } else {
+                    ((p - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+                };
+                return half_width + 0.5 - p.distance(a + ab * t);
+            });
+        }
+    }
+    fn circle(&mut self, center: Vec2, radius: f32) {
+        let padding = Vec2::splat(radius + 1.0);
+        self.cover(center - padding, center + padding, |p| {
+            return radius + 0.5 - p.distance(center);
+        });
+    }
+    fn ring(&mut self, center: Vec2, radius: f32, width: f32) {
+        let padding = Vec2::splat(radius + width + 1.0);
+        self.cover(center - padding, center + padding, |p| {
+            return width * 0.5 + 0.5 - (p.distance(center) - radius).abs();
+        });
+    }
+    /// 用颜色按mask的覆盖率混合到画布上并清空mask
+    fn composite(&mut self, color: Color) {
+        let Some((min_x, min_y, max_x, max_y)) = self.dirty.take() else {
+            return;
+        };
+        let source = color.as_rgba_f32();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let index = (y * self.width + x) as usize;
+                let coverage = std::mem::take(&mut self.mask[index]);
+                if coverage <= 0.0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[index * 4..index * 4 + 4];
+                let alpha = source[3] * coverage;
+                let dest_alpha = pixel[3] as f32 / 255.0;
+                let out_alpha = alpha + dest_alpha * (1.0 - alpha);
+                if out_alpha <= 0.0 {
+                    continue;
+                }
+                for c in 0..3 {
+                    let dest = pixel[c] as f32 / 255.0;
+                    let value = (source[c] * alpha + dest * dest_alpha * (1.0 - alpha)) / out_alpha;
+                    pixel[c] = (value * 255.0).round() as u8;
+                }
+                pixel[3] = (out_alpha * 255.0).round() as u8;
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use bevy::math::IVec2;
+    use serde_json::Map;
+
+    use super::*;
+    use crate::mvt_tile::MvtLayer;
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * width + x) * 4) as usize;
+        return [
+            pixels[index],
+            pixels[index + 1],
+            pixels[index + 2],
+            pixels[index + 3],
+        ];
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let square = |min: i32, max: i32| {
+            vec![
+                IVec2::new(min, min),
+                IVec2::new(max, min),
+                IVec2::new(max, max),
+                IVec2::new(min, max),
+            ]
+        };
+        let mut park = Map::new();
+        park.insert("class".into(), Value::from("park"));
+        let tile = MvtTile {
+            layers: vec![MvtLayer {
+                name: "landuse".into(),
+                extent: 4096,
+                features: vec![
+                    MvtFeature {
+                        geometry_type: MvtGeometryType::Polygon,
+                        properties: park,
+                        // 带洞的正方形
+                        geometry: vec![square(0, 2048), square(512, 1536)],
+                        ..Default::default()
+                    },
+                    MvtFeature {
+                        geometry_type: MvtGeometryType::Polygon,
+                        geometry: vec![square(2048, 4096)],
+                        ..Default::default()
+                    },
+                ],
+            }],
+        };
+        let style = MvtStyle {
+            background: Color::WHITE,
+            layers: vec![MvtStyleLayer::new(
+                "landuse",
+                MvtPaint::Fill {
+                    color: Color::rgb(0.0, 1.0, 0.0),
+                    outline_color: None,
+                },
+            )
+            .with_filter(MvtFilter::eq("class", "park"))],
+        };
+        let pixels = style.rasterize(&tile, 64, 64);
+        assert_eq!(pixels.len(), 64 * 64 * 4);
+        assert_eq!(pixel(&pixels, 64, 4, 4), [0, 255, 0, 255]);
+        // 洞内和被过滤掉的要素保持背景色
+        assert_eq!(pixel(&pixels, 64, 16, 16), [255, 255, 255, 255]);
+        assert_eq!(pixel(&pixels, 64, 48, 48), [255, 255, 255, 255]);
+    }
+    #[test]
+    fn test_fill_anti_aliasing() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.fill_polygon(&[vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.5, 0.0),
+            Vec2::new(2.5, 4.0),
+            Vec2::new(0.0, 4.0),
+        ]]);
+        // 完全覆盖的像素为1，边缘按覆盖的面积
+        assert_eq!(canvas.mask[1], 1.0);
+        assert!((canvas.mask[2] - 0.5).abs() < 1e-6);
+        assert_eq!(canvas.mask[3], 0.0);
+    }
+    #[test]
+    fn test_filter() {
+        let mut properties = Map::new();
+        properties.insert("rank".into(), Value::from(3));
+        properties.insert("class".into(), Value::from("river"));
+        let feature = MvtFeature {
+            geometry_type: MvtGeometryType::LineString,
+            properties,
+            ..Default::default()
+        };
+        assert!(MvtFilter::eq("rank", 3.0).evaluate(&feature));
+        assert!(MvtFilter::All(vec![
+            MvtFilter::GeometryType(MvtGeometryType::LineString),
+            MvtFilter::In("class".into(), vec!["river".into(), "canal".into()]),
+            MvtFilter::Lt("rank".into(), 5.0),
+        ])
+        .evaluate(&feature));
+        assert!(!MvtFilter::Any(vec![
+            MvtFilter::Has("name".into()),
+            MvtFilter::Gt("rank".into(), 3.0),
+        ])
+        .evaluate(&feature));
+        assert!(MvtFilter::Not(Box::new(MvtFilter::Has("name".into()))).evaluate(&feature));
+    }
+}
@@ -0,0 +1,391 @@
+use std::io::Read;
+
+use bevy::math::IVec2;
+use serde_json::{Map, Value};
+
+/// Mapbox Vector Tile的解码，只依赖protobuf的编码格式，不需要proto文件
+/// https://github.com/mapbox/vector-tile-spec/tree/master/2.1
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MvtError {
+    #[error("unexpected end of buffer")]
+    UnexpectedEof,
+    #[error("unsupported wire type: {0}")]
+    UnsupportedWireType(u8),
+    #[error("invalid utf8 string")]
+    InvalidUtf8,
+    #[error("invalid geometry command: {0}")]
+    InvalidCommand(u32),
+    #[error("invalid tag index: {0}")]
+    InvalidTag(u32),
+    #[error("gzip: {0}")]
+    Gzip(String),
+}
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_BYTES: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+struct PbfReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> PbfReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+    fn is_empty(&self) -> bool {
+        return self.pos >= self.bytes.len();
+    }
+    fn read_varint(&mut self) -> Result<u64, MvtError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = *self.bytes.get(self.pos).ok_or(MvtError::UnexpectedEof)?;
+            self.pos += 1;
+            if shift < 64 {
+                value |= ((byte & 0x7f) as u64) << shift;
+            }
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], MvtError> {
+        let end = self.pos.checked_add(len).ok_or(MvtError::UnexpectedEof)?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(MvtError::UnexpectedEof)?;
+        self.pos = end;
+        return Ok(slice);
+    }
+    /// 返回字段编号和编码类型
+    fn read_key(&mut self) -> Result<(u32, u8), MvtError> {
+        let key = self.read_varint()?;
+        return Ok(((key >> 3) as u32, (key & 0x7) as u8));
+    }
+    fn read_bytes(&mut self) -> Result<&'a [u8], MvtError> {
+        let len = self.read_varint()? as usize;
+        return self.read_slice(len);
+    }
+    fn read_string(&mut self) -> Result<String, MvtError> {
+        let bytes = self.read_bytes()?;
+        return String::from_utf8(bytes.to_vec()).map_err(|_| MvtError::InvalidUtf8);
+    }
+    fn read_fixed32(&mut self) -> Result<[u8; 4], MvtError> {
+        let slice = self.read_slice(4)?;
+        return Ok([slice[0], slice[1], slice[2], slice[3]]);
+    }
+    fn read_fixed64(&mut self) -> Result<[u8; 8], MvtError> {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(self.read_slice(8)?);
+        return Ok(value);
+    }
+    fn read_packed(&mut self) -> Result<Vec<u32>, MvtError> {
+        let mut reader = PbfReader::new(self.read_bytes()?);
+        let mut values = vec![];
+        while !reader.is_empty() {
+            values.push(reader.read_varint()? as u32);
+        }
+        return Ok(values);
+    }
+    fn skip(&mut self, wire_type: u8) -> Result<(), MvtError> {
+        match wire_type {
+            WIRE_VARINT => {
+                self.read_varint()?;
+            }
+            WIRE_FIXED64 => {
+                self.read_slice(8)?;
+            }
+            WIRE_BYTES => {
+                self.read_bytes()?;
+            }
+            WIRE_FIXED32 => {
+                self.read_slice(4)?;
+            }
+            v => return Err(MvtError::UnsupportedWireType(v)),
+        }
+        return Ok(());
+    }
+}
+fn zigzag(value: u64) -> i64 {
+    return (value >> 1) as i64 ^ -((value & 1) as i64);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MvtGeometryType {
+    #[default]
+    Unknown,
+    Point,
+    LineString,
+    Polygon,
+}
+impl MvtGeometryType {
+    fn from_u64(value: u64) -> Self {
+        match value {
+            1 => Self::Point,
+            2 => Self::LineString,
+            3 => Self::Polygon,
+            _ => Self::Unknown,
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MvtFeature {
+    pub id: Option<u64>,
+    pub geometry_type: MvtGeometryType,
+    pub properties: Map<String, Value>,
+    /// 瓦片坐标，y轴向下。点的每个点是一部分，线的每条线是一部分，面的每个环是一部分(不重复首点)
+    pub geometry: Vec<Vec<IVec2>>,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct MvtLayer {
+    pub name: String,
+    /// 瓦片坐标的范围，默认为4096
+    pub extent: u32,
+    pub features: Vec<MvtFeature>,
+}
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MvtTile {
+    pub layers: Vec<MvtLayer>,
+}
+impl MvtTile {
+    /// 解码瓦片，gzip压缩的瓦片先解压
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, MvtError> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut decompressed = vec![];
+            flate2::read::GzDecoder::new(bytes)
+                .read_to_end(&mut decompressed)
+                .map_err(|error| MvtError::Gzip(error.to_string()))?;
+            return Self::decode(&decompressed);
+        }
+        return Self::decode(bytes);
+    }
+    fn decode(bytes: &[u8]) -> Result<Self, MvtError> {
+        let mut reader = PbfReader::new(bytes);
+        let mut layers = vec![];
+        while !reader.is_empty() {
+            match reader.read_key()? {
+                (3, WIRE_BYTES) => layers.push(decode_layer(reader.read_bytes()?)?),
+                (_, wire_type) => reader.skip(wire_type)?,
+            }
+        }
+        return Ok(Self { layers });
+    }
+    pub fn get_layer(&self, name: &str) -> Option<&MvtLayer> {
+        return self.layers.iter().find(|v| v.name == name);
+    }
+}
+fn decode_layer(bytes: &[u8]) -> Result<MvtLayer, MvtError> {
+    let mut reader = PbfReader::new(bytes);
+    let mut name = String::new();
+    let mut extent = 4096;
+    let mut keys = vec![];
+    let mut values = vec![];
+    let mut features = vec![];
+    while !reader.is_empty() {
+        match reader.read_key()? {
+            (1, WIRE_BYTES) => name = reader.read_string()?,
+            // 属性在键和值都读完后才能解析
+            (2, WIRE_BYTES) => features.push(reader.read_bytes()?),
+            (3, WIRE_BYTES) => keys.push(reader.read_string()?),
+            (4, WIRE_BYTES) => values.push(decode_value(reader.read_bytes()?)?),
+            (5, WIRE_VARINT) => extent = reader.read_varint()? as u32,
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+    let features = features
+        .into_iter()
+        .map(|v| decode_feature(v, &keys, &values))
+        .collect::<Result<Vec<_>, _>>()?;
+    return Ok(MvtLayer {
+        name,
+        extent,
+        features,
+    });
+}
+fn decode_value(bytes: &[u8]) -> Result<Value, MvtError> {
+    let mut reader = PbfReader::new(bytes);
+    let mut value = Value::Null;
+    while !reader.is_empty() {
+        value = match reader.read_key()? {
+            (1, WIRE_BYTES) => Value::String(reader.read_string()?),
+            (2, WIRE_FIXED32) => Value::from(f32::from_le_bytes(reader.read_fixed32()?) as f64),
+            (3, WIRE_FIXED64) => Value::from(f64::from_le_bytes(reader.read_fixed64()?)),
+            (4, WIRE_VARINT) => Value::from(reader.read_varint()? as i64),
+            (5, WIRE_VARINT) => Value::from(reader.read_varint()?),
+            (6, WIRE_VARINT) => Value::from(zigzag(reader.read_varint()?)),
+            (7, WIRE_VARINT) => Value::Bool(reader.read_varint()? != 0),
+            (_, wire_type) => {
+                reader.skip(wire_type)?;
+                continue;
+            }
+        };
+    }
+    return Ok(value);
+}
+fn decode_feature(bytes: &[u8], keys: &[String], values: &[Value]) -> Result<MvtFeature, MvtError> {
+    let mut reader = PbfReader::new(bytes);
+    let mut feature = MvtFeature::default();
+    let mut tags = vec![];
+    let mut commands = vec![];
+    while !reader.is_empty() {
+        match reader.read_key()? {
+            (1, WIRE_VARINT) => feature.id = Some(reader.read_varint()?),
+            (2, WIRE_BYTES) => tags = reader.read_packed()?,
+            (3, WIRE_VARINT) => {
+                feature.geometry_type = MvtGeometryType::from_u64(reader.read_varint()?)
+            }
+            (4, WIRE_BYTES) => commands = reader.read_packed()?,
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+    for pair in tags.chunks(2) {
+        let [key, value] = pair else {
+            return Err(MvtError::InvalidTag(pair[0]));
+        };
+        let key = keys.get(*key as usize).ok_or(MvtError::InvalidTag(*key))?;
+        let value = values
+            .get(*value as usize)
+            .ok_or(MvtError::InvalidTag(*value))?;
+        feature.properties.insert(key.clone(), value.clone());
+    }
+    feature.geometry = decode_geometry(&commands, feature.geometry_type)?;
+    return Ok(feature);
+}
+/// 命令的低3位是类型，高位是重复次数，参数是zigzag编码的增量
+fn decode_geometry(
+    commands: &[u32],
+    geometry_type: MvtGeometryType,
+) -> Result<Vec<Vec<IVec2>>, MvtError> {
+    const MOVE_TO: u32 = 1;
+    const LINE_TO: u32 = 2;
+    const CLOSE_PATH: u32 = 7;
+    let mut parts: Vec<Vec<IVec2>> = vec![];
+    let mut cursor = IVec2::ZERO;
+    let mut i = 0;
+    while i < commands.len() {
+        let command = commands[i];
+        let (id, count) = (command & 0x7, command >> 3);
+        i += 1;
+        match id {
+            MOVE_TO | LINE_TO => {
+                for _ in 0..count {
+                    let (Some(dx), Some(dy)) = (commands.get(i), commands.get(i + 1)) else {
+                        return Err(MvtError::InvalidCommand(command));
+                    };
+                    i += 2;
+                    cursor += IVec2::new(zigzag(*dx as u64) as i32, zigzag(*dy as u64) as i32);
+                    if id == MOVE_TO {
+                        parts.push(vec![cursor]);
+                    } else if let Some(part) = parts.last_mut() {
+                        part.push(cursor);
+                    } else {
+                        return Err(MvtError::InvalidCommand(command));
+                    }
+                }
+            }
+            CLOSE_PATH => {}
+            _ => return Err(MvtError::InvalidCommand(command)),
+        }
+    }
+    if geometry_type == MvtGeometryType::Point {
+        // MultiPoint只有一个MoveTo，拆成多个点
+        return Ok(parts.into_iter().flatten().map(|v| vec![v]).collect());
+    }
+    return Ok(parts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buffer.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        buffer.push(value as u8);
+    }
+    fn write_bytes(buffer: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+        write_varint(buffer, field << 3 | WIRE_BYTES as u64);
+        write_varint(buffer, bytes.len() as u64);
+        buffer.extend_from_slice(bytes);
+    }
+    fn write_packed(buffer: &mut Vec<u8>, field: u64, values: &[u32]) {
+        let mut packed = vec![];
+        for v in values {
+            write_varint(&mut packed, *v as u64);
+        }
+        write_bytes(buffer, field, &packed);
+    }
+
+    #[test]
+    fn test_decode_tile() {
+        // 规范中的例子：一个正方形和一个点
+        let mut polygon = vec![];
+        write_varint(&mut polygon, 1 << 3 | WIRE_VARINT as u64);
+        write_varint(&mut polygon, 7);
+        write_packed(&mut polygon, 2, &[0, 0]);
+        write_varint(&mut polygon, 3 << 3 | WIRE_VARINT as u64);
+        write_varint(&mut polygon, 3);
+        write_packed(&mut polygon, 4, &[9, 6, 12, 18, 10, 12, 24, 44, 15]);
+        let mut point = vec![];
+        write_packed(&mut point, 2, &[1, 1]);
+        write_varint(&mut point, 3 << 3 | WIRE_VARINT as u64);
+        write_varint(&mut point, 1);
+        write_packed(&mut point, 4, &[17, 10, 14, 3, 9]);
+
+        let mut value = vec![];
+        write_bytes(&mut value, 1, b"park");
+        let mut number = vec![];
+        write_varint(&mut number, 6 << 3 | WIRE_VARINT as u64);
+        write_varint(&mut number, 3); // zigzag(-2)
+
+        let mut layer = vec![];
+        write_varint(&mut layer, 15 << 3 | WIRE_VARINT as u64);
+        write_varint(&mut layer, 2);
+        write_bytes(&mut layer, 1, b"landuse");
+        write_bytes(&mut layer, 2, &polygon);
+        write_bytes(&mut layer, 2, &point);
+        write_bytes(&mut layer, 3, b"class");
+        write_bytes(&mut layer, 3, b"rank");
+        write_bytes(&mut layer, 4, &value);
+        write_bytes(&mut layer, 4, &number);
+        let mut tile = vec![];
+        write_bytes(&mut tile, 3, &layer);
+
+        let tile = MvtTile::from_slice(&tile).unwrap();
+        let layer = tile.get_layer("landuse").unwrap();
+        assert_eq!(layer.extent, 4096);
+        assert_eq!(layer.features.len(), 2);
+
+        let polygon = &layer.features[0];
+        assert_eq!(polygon.id, Some(7));
+        assert_eq!(polygon.geometry_type, MvtGeometryType::Polygon);
+        assert_eq!(polygon.properties.get("class"), Some(&Value::from("park")));
+        assert_eq!(
+            polygon.geometry,
+            vec![vec![
+                IVec2::new(3, 6),
+                IVec2::new(8, 12),
+                IVec2::new(20, 34)
+            ]]
+        );
+
+        let point = &layer.features[1];
+        assert_eq!(point.properties.get("rank"), Some(&Value::from(-2)));
+        assert_eq!(
+            point.geometry,
+            vec![vec![IVec2::new(5, 7)], vec![IVec2::new(3, 2)]]
+        );
+    }
+    #[test]
+    fn test_truncated_tile() {
+        let mut tile = vec![];
+        write_bytes(&mut tile, 3, &[1 << 3 | WIRE_BYTES, 10, b'a']);
+        assert_eq!(MvtTile::from_slice(&tile), Err(MvtError::UnexpectedEof));
+    }
+}
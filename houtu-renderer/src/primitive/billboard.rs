@@ -0,0 +1,551 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    math::DVec3,
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::{
+        mesh::{Indices, MeshVertexAttribute, MeshVertexBufferLayout},
+        render_resource::{
+            AsBindGroup, CompareFunction, PrimitiveTopology, RenderPipelineDescriptor, ShaderRef,
+            ShaderType, SpecializedMeshPipelineError, VertexFormat,
+        },
+        view::NoFrustumCulling,
+    },
+    window::PrimaryWindow,
+};
+use houtu_scene::{BoundingSphere, Cartographic, Ellipsoid, EllipsoidalOccluder, NearFarScalar};
+
+use crate::{
+    camera::GlobeCamera, quadtree::quadtree_primitive::QuadtreePrimitive, RenderEntityType,
+};
+
+use super::{selected_scale, RtcCamera, CLAMP_INTERVAL};
+
+/// 屏幕上的圆点，大小以像素为单位
+#[derive(Component, Debug, Clone)]
+pub struct PointPrimitive {
+    pub position: Cartographic,
+    pub pixel_size: f32,
+    pub color: Color,
+    pub outline_color: Color,
+    pub outline_width: f32,
+    /// 屏幕上的偏移(像素)，x向右，y向下
+    pub pixel_offset: Vec2,
+    pub scale_by_distance: Option<NearFarScalar>,
+    /// 高度相对于已加载的地形
+    pub clamp_to_ground: bool,
+}
+impl Default for PointPrimitive {
+    fn default() -> Self {
+        Self {
+            position: Cartographic::default(),
+            pixel_size: 10.0,
+            color: Color::WHITE,
+            outline_color: Color::BLACK,
+            outline_width: 0.0,
+            pixel_offset: Vec2::ZERO,
+            scale_by_distance: None,
+            clamp_to_ground: false,
+        }
+    }
+}
+impl PointPrimitive {
+    pub fn new(position: Cartographic) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+}
+/// 始终面向屏幕的图片，中心对准position
+#[derive(Component, Debug, Clone)]
+pub struct Billboard {
+    pub position: Cartographic,
+    pub image: Handle<Image>,
+    /// 显示的大小(像素)，为None时使用图片的大小
+    pub size: Option<Vec2>,
+    pub scale: f32,
+    /// 与图片的颜色相乘
+    pub color: Color,
+    /// 屏幕上的偏移(像素)，x向右，y向下
+    pub pixel_offset: Vec2,
+    pub scale_by_distance: Option<NearFarScalar>,
+    /// 高度相对于已加载的地形
+    pub clamp_to_ground: bool,
+}
+impl Billboard {
+    pub fn new(position: Cartographic, image: Handle<Image>) -> Self {
+        Self {
+            position,
+            image,
+            size: None,
+            scale: 1.0,
+            color: Color::WHITE,
+            pixel_offset: Vec2::ZERO,
+            scale_by_distance: None,
+            clamp_to_ground: false,
+        }
+    }
+}
+/// 合批前的一个点或图片
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkerInstance {
+    pub position: DVec3,
+    /// 宽高(像素)，点的大小包含轮廓线
+    pub size: Vec2,
+    pub pixel_offset: Vec2,
+    pub color: Vec4,
+    pub outline_color: Vec4,
+    pub outline_width: f32,
+    pub is_point: bool,
+    pub scale_by_distance: Option<NearFarScalar>,
+}
+#[derive(ShaderType, Debug, Clone, Copy, Default)]
+pub struct BillboardUniform {
+    pub model_view: Mat4,
+    pub projection: Mat4,
+    pub viewport: Vec2,
+}
+/// 同一张图片的所有图片共用一个材质，点使用没有图片的材质
+#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone)]
+#[uuid = "c4e2b7a9-1d3f-4b6e-8a5c-0f9d2e7b4a63"]
+pub struct BillboardMaterial {
+    #[uniform(0)]
+    pub uniform: BillboardUniform,
+    #[texture(1)]
+    #[sampler(2)]
+    pub image: Option<Handle<Image>>,
+}
+impl BillboardMaterial {
+    /// xy为角点(-0.5到0.5)，zw为像素偏移
+    pub const ATTRIBUTE_CORNER_AND_OFFSET: MeshVertexAttribute =
+        MeshVertexAttribute::new("ATTRIBUTE_CORNER_AND_OFFSET", 1110, VertexFormat::Float32x4);
+    /// xy为宽高，z为轮廓线宽度，w为1时是点
+    pub const ATTRIBUTE_SIZE_AND_OUTLINE: MeshVertexAttribute =
+        MeshVertexAttribute::new("ATTRIBUTE_SIZE_AND_OUTLINE", 1111, VertexFormat::Float32x4);
+    pub const ATTRIBUTE_OUTLINE_COLOR: MeshVertexAttribute =
+        MeshVertexAttribute::new("ATTRIBUTE_OUTLINE_COLOR", 1112, VertexFormat::Float32x4);
+    /// 与NearFarScalar相同的顺序，far不大于near时不缩放
+    pub const ATTRIBUTE_SCALE_BY_DISTANCE: MeshVertexAttribute =
+        MeshVertexAttribute::new("ATTRIBUTE_SCALE_BY_DISTANCE", 1113, VertexFormat::Float32x4);
+}
+impl Material for BillboardMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "billboard_material.wgsl".into()
+    }
+    fn fragment_shader() -> ShaderRef {
+        "billboard_material.wgsl".into()
+    }
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            BillboardMaterial::ATTRIBUTE_CORNER_AND_OFFSET.at_shader_location(1),
+            BillboardMaterial::ATTRIBUTE_SIZE_AND_OUTLINE.at_shader_location(2),
+            Mesh::ATTRIBUTE_COLOR.at_shader_location(3),
+            BillboardMaterial::ATTRIBUTE_OUTLINE_COLOR.at_shader_location(4),
+            BillboardMaterial::ATTRIBUTE_SCALE_BY_DISTANCE.at_shader_location(5),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        descriptor.primitive.cull_mode = None;
+        // 地平线以下的已经在CPU上剔除，其余的不被地形遮挡
+        if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+            depth_stencil.depth_compare = CompareFunction::Always;
+            depth_stencil.depth_write_enabled = false;
+        }
+        Ok(())
+    }
+}
+/// 一个批次，对应一次绘制
+#[derive(Component, Debug, Clone)]
+pub struct MarkerBatch {
+    pub image: Option<Handle<Image>>,
+    pub entities: Vec<Entity>,
+    pub center: DVec3,
+}
+/// 每张图片对应的批次实体，点的键为None
+#[derive(Resource, Debug, Default)]
+pub struct MarkerBatches(pub HashMap<Option<Handle<Image>>, Entity>);
+
+/// 每个实例生成一个四边形，顶点坐标相对于包围球中心
+pub fn build_marker_mesh(instances: &[MarkerInstance]) -> Option<(DVec3, Mesh)> {
+    if instances.is_empty() {
+        return None;
+    }
+    let points: Vec<DVec3> = instances.iter().map(|v| v.position).collect();
+    let center = BoundingSphere::from_points(&points).center;
+    let vertex_count = instances.len() * 4;
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(vertex_count);
+    let mut corner_and_offset: Vec<[f32; 4]> = Vec::with_capacity(vertex_count);
+    let mut size_and_outline: Vec<[f32; 4]> = Vec::with_capacity(vertex_count);
+    let mut colors: Vec<[f32; 4]> = Vec::with_capacity(vertex_count);
+    let mut outline_colors: Vec<[f32; 4]> = Vec::with_capacity(vertex_count);
+    let mut scale_by_distance: Vec<[f32; 4]> = Vec::with_capacity(vertex_count);
+    let mut indices: Vec<u32> = Vec::with_capacity(instances.len() * 6);
+    for (i, instance) in instances.iter().enumerate() {
+        let position = (instance.position - center).as_vec3().to_array();
+        let scalar = instance
+            .scale_by_distance
+            .map_or([0.0, 1.0, 0.0, 1.0], |v| {
+                [
+                    v.near as f32,
+                    v.near_value as f32,
+                    v.far as f32,
+                    v.far_value as f32,
+                ]
+            });
+        let kind = if instance.is_point { 1.0 } else { 0.0 };
+        for corner in [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]] {
+            positions.push(position);
+            corner_and_offset.push([
+                corner[0],
+                corner[1],
+                instance.pixel_offset.x,
+                instance.pixel_offset.y,
+            ]);
+            size_and_outline.push([
+                instance.size.x,
+                instance.size.y,
+                instance.outline_width,
+                kind,
+            ]);
+            colors.push(instance.color.to_array());
+            outline_colors.push(instance.outline_color.to_array());
+            scale_by_distance.push(scalar);
+        }
+        let base = (i * 4) as u32;
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(
+        BillboardMaterial::ATTRIBUTE_CORNER_AND_OFFSET,
+        corner_and_offset,
+    );
+    mesh.insert_attribute(
+        BillboardMaterial::ATTRIBUTE_SIZE_AND_OUTLINE,
+        size_and_outline,
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(BillboardMaterial::ATTRIBUTE_OUTLINE_COLOR, outline_colors);
+    mesh.insert_attribute(
+        BillboardMaterial::ATTRIBUTE_SCALE_BY_DISTANCE,
+        scale_by_distance,
+    );
+    mesh.set_indices(Some(Indices::U32(indices)));
+    return Some((center, mesh));
+}
+/// 加上地形高度，高度缓存到下次重新采样，返回坐标和高度是否变化
+fn clamp_marker_position(
+    primitive: &QuadtreePrimitive,
+    ground_heights: &mut HashMap<Entity, f64>,
+    entity: Entity,
+    position: &Cartographic,
+    resample: bool,
+) -> (DVec3, bool) {
+    let cached = ground_heights.get(&entity).copied();
+    let height = match cached {
+        Some(height) if !resample => height,
+        _ => primitive.get_height(position).unwrap_or(0.0),
+    };
+    ground_heights.insert(entity, height);
+    let mut position = *position;
+    position.height += height;
+    let changed = cached.map_or(true, |v| (v - height).abs() > 0.01);
+    return (
+        Ellipsoid::WGS84.cartographic_to_cartesian(&position),
+        changed,
+    );
+}
+/// 按图片合批，地平线以下的被剔除，只有可见集合或属性变化时才重新生成网格
+pub fn marker_batch_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BillboardMaterial>>,
+    images: Res<Assets<Image>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut removed_points: RemovedComponents<PointPrimitive>,
+    mut removed_billboards: RemovedComponents<Billboard>,
+    mut cameras: Query<&mut GlobeCamera>,
+    primitive: Option<Res<QuadtreePrimitive>>,
+    time: Res<Time>,
+    mut last_clamp_time: Local<f64>,
+    mut ground_heights: Local<HashMap<Entity, f64>>,
+    mut marker_batches: ResMut<MarkerBatches>,
+    batches: Query<&MarkerBatch>,
+    points: Query<(
+        Entity,
+        Ref<PointPrimitive>,
+        Option<Ref<Visibility>>,
+        Option<Ref<RenderEntityType>>,
+    )>,
+    billboards: Query<(
+        Entity,
+        Ref<Billboard>,
+        Option<Ref<Visibility>>,
+        Option<Ref<RenderEntityType>>,
+    )>,
+) {
+    let Ok(mut globe_camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let ellipsoid = Ellipsoid::WGS84;
+    let mut occluder = EllipsoidalOccluder::new(&ellipsoid);
+    occluder.set_camera_position(globe_camera.get_position_wc());
+    // 贴地的点定时重新采样地形高度，高度变化时才重新生成网格
+    let reclamp = time.elapsed_seconds_f64() - *last_clamp_time > CLAMP_INTERVAL;
+    if reclamp {
+        *last_clamp_time = time.elapsed_seconds_f64();
+    }
+    let mut to_cartesian =
+        |entity: Entity, position: &Cartographic, clamp_to_ground: bool, resample: bool| {
+            return match (&primitive, clamp_to_ground) {
+                (Some(primitive), true) => clamp_marker_position(
+                    primitive,
+                    &mut ground_heights,
+                    entity,
+                    position,
+                    reclamp || resample,
+                ),
+                _ => (ellipsoid.cartographic_to_cartesian(position), false),
+            };
+        };
+
+    let changed_images: HashSet<Handle<Image>> = image_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                Some(handle.clone_weak())
+            }
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect();
+    let removed_entities: Vec<Entity> = removed_points
+        .iter()
+        .chain(removed_billboards.iter())
+        .collect();
+    for entity in removed_entities.iter() {
+        ground_heights.remove(entity);
+    }
+    let removed = !removed_entities.is_empty();
+    let is_changed = |visibility: &Option<Ref<Visibility>>,
+                      render_type: &Option<Ref<RenderEntityType>>|
+     -> bool {
+        return visibility.as_ref().map_or(false, |v| v.is_changed())
+            || render_type.as_ref().map_or(false, |v| v.is_changed());
+    };
+    let is_hidden = |visibility: &Option<Ref<Visibility>>| -> bool {
+        return visibility
+            .as_ref()
+            .map_or(false, |v| **v == Visibility::Hidden);
+    };
+
+    let mut groups: HashMap<Option<Handle<Image>>, (bool, Vec<Entity>, Vec<MarkerInstance>)> =
+        HashMap::new();
+    for (entity, point, visibility, render_type) in points.iter() {
+        let group = groups.entry(None).or_default();
+        let (position, height_changed) = to_cartesian(
+            entity,
+            &point.position,
+            point.clamp_to_ground,
+            point.is_changed(),
+        );
+        group.0 |= removed
+            || height_changed
+            || point.is_changed()
+            || is_changed(&visibility, &render_type);
+        if is_hidden(&visibility) || !occluder.is_point_visible(position) {
+            continue;
+        }
+        let scale = selected_scale(render_type.as_deref());
+        let size = (point.pixel_size + point.outline_width * 2.0) * scale;
+        group.1.push(entity);
+        group.2.push(MarkerInstance {
+            position,
+            size: Vec2::splat(size),
+            pixel_offset: point.pixel_offset,
+            color: point.color.as_linear_rgba_f32().into(),
+            outline_color: point.outline_color.as_linear_rgba_f32().into(),
+            outline_width: point.outline_width * scale,
+            is_point: true,
+            scale_by_distance: point.scale_by_distance,
+        });
+    }
+    for (entity, billboard, visibility, render_type) in billboards.iter() {
+        let key = Some(billboard.image.clone_weak());
+        let group = groups.entry(key).or_default();
+        let (position, height_changed) = to_cartesian(
+            entity,
+            &billboard.position,
+            billboard.clamp_to_ground,
+            billboard.is_changed(),
+        );
+        group.0 |= removed
+            || height_changed
+            || billboard.is_changed()
+            || is_changed(&visibility, &render_type)
+            || changed_images.contains(&billboard.image);
+        if is_hidden(&visibility) || !occluder.is_point_visible(position) {
+            continue;
+        }
+        let image_size = images
+            .get(&billboard.image)
+            .map_or(Vec2::splat(32.0), |v| v.size());
+        let size = billboard.size.unwrap_or(image_size)
+            * billboard.scale
+            * selected_scale(render_type.as_deref());
+        group.1.push(entity);
+        group.2.push(MarkerInstance {
+            position,
+            size,
+            pixel_offset: billboard.pixel_offset,
+            color: billboard.color.as_linear_rgba_f32().into(),
+            outline_color: Vec4::ZERO,
+            outline_width: 0.0,
+            is_point: false,
+            scale_by_distance: billboard.scale_by_distance,
+        });
+    }
+
+    // 没有任何点或图片的批次
+    marker_batches.0.retain(|key, batch_entity| {
+        if groups.get(key).map_or(false, |v| !v.1.is_empty()) {
+            return true;
+        }
+        commands.entity(*batch_entity).despawn();
+        return false;
+    });
+    for (key, (dirty, entities, instances)) in groups.into_iter() {
+        let batch_entity = marker_batches.0.get(&key).copied();
+        if let Some(batch_entity) = batch_entity {
+            let unchanged = batches
+                .get(batch_entity)
+                .map_or(false, |v| v.entities == entities);
+            if !dirty && unchanged {
+                continue;
+            }
+        }
+        let Some((center, mesh)) = build_marker_mesh(&instances) else {
+            continue;
+        };
+        let batch = MarkerBatch {
+            image: key.clone(),
+            entities,
+            center,
+        };
+        match batch_entity {
+            Some(batch_entity) => {
+                commands
+                    .entity(batch_entity)
+                    .insert((meshes.add(mesh), batch));
+            }
+            None => {
+                // 弱引用作为键，材质中需要强引用
+                let image = key.as_ref().map(|v| {
+                    let mut handle = v.clone();
+                    handle.make_strong(&images);
+                    handle
+                });
+                let material = materials.add(BillboardMaterial {
+                    uniform: BillboardUniform::default(),
+                    image,
+                });
+                let batch_entity = commands
+                    .spawn((
+                        MaterialMeshBundle {
+                            mesh: meshes.add(mesh),
+                            material,
+                            ..Default::default()
+                        },
+                        batch,
+                        NoFrustumCulling,
+                    ))
+                    .id();
+                marker_batches.0.insert(key, batch_entity);
+            }
+        }
+    }
+}
+/// 每帧更新相对中心点的矩阵和视口大小
+pub fn marker_uniform_system(
+    primary_query: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut GlobeCamera>,
+    mut materials: ResMut<Assets<BillboardMaterial>>,
+    batches: Query<(&MarkerBatch, &Handle<BillboardMaterial>)>,
+) {
+    let Ok(window) = primary_query.get_single() else {
+        return;
+    };
+    let Ok(mut globe_camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let rtc_camera = RtcCamera::new(&mut globe_camera);
+    for (batch, handle) in batches.iter() {
+        let Some(material) = materials.get_mut(handle) else {
+            continue;
+        };
+        let uniform = &mut material.uniform;
+        uniform.model_view = rtc_camera.model_view(batch.center);
+        uniform.projection = rtc_camera.projection;
+        uniform.viewport = Vec2::new(window.width(), window.height());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+
+    #[test]
+    fn test_build_marker_mesh() {
+        let ellipsoid = Ellipsoid::WGS84;
+        let instance = MarkerInstance {
+            position: ellipsoid
+                .cartographic_to_cartesian(&Cartographic::from_degrees(120.0, 30.0, 0.0)),
+            size: Vec2::new(32.0, 16.0),
+            pixel_offset: Vec2::new(0.0, -10.0),
+            color: Vec4::ONE,
+            outline_color: Vec4::ZERO,
+            outline_width: 0.0,
+            is_point: false,
+            scale_by_distance: Some(NearFarScalar::new(100.0, 1.0, 1000.0, 0.5)),
+        };
+        let other = MarkerInstance {
+            position: ellipsoid
+                .cartographic_to_cartesian(&Cartographic::from_degrees(120.001, 30.0, 0.0)),
+            is_point: true,
+            scale_by_distance: None,
+            ..instance
+        };
+        let (center, mesh) = build_marker_mesh(&[instance, other]).unwrap();
+        assert_eq!(mesh.count_vertices(), 8);
+        assert_eq!(mesh.indices().unwrap().len(), 12);
+        assert!(center.distance(instance.position) < 100.0);
+        let Some(VertexAttributeValues::Float32x4(size_and_outline)) =
+            mesh.attribute(BillboardMaterial::ATTRIBUTE_SIZE_AND_OUTLINE)
+        else {
+            panic!("expect size and outline");
+        };
+        assert_eq!(size_and_outline[0], [32.0, 16.0, 0.0, 0.0]);
+        assert_eq!(size_and_outline[4][3], 1.0);
+        let Some(VertexAttributeValues::Float32x4(scale_by_distance)) =
+            mesh.attribute(BillboardMaterial::ATTRIBUTE_SCALE_BY_DISTANCE)
+        else {
+            panic!("expect scale by distance");
+        };
+        assert_eq!(scale_by_distance[0], [100.0, 1.0, 1000.0, 0.5]);
+        assert_eq!(scale_by_distance[4], [0.0, 1.0, 0.0, 1.0]);
+
+        assert!(build_marker_mesh(&[]).is_none());
+    }
+}
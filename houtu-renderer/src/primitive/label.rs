@@ -0,0 +1,207 @@
+use bevy::{
+    math::{DMat4, DVec3, DVec4},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use houtu_scene::{Cartographic, Ellipsoid, EllipsoidalOccluder, NearFarScalar};
+
+use crate::{camera::GlobeCamera, RenderEntityType};
+
+use super::selected_scale;
+
+/// 文字标注，用bevy_ui的文字绘制，同一字体的字形在一个图集中合批
+#[derive(Component, Debug, Clone)]
+pub struct Label {
+    pub position: Cartographic,
+    pub text: String,
+    /// 默认使用bevy自带的字体
+    pub font: Handle<Font>,
+    pub font_size: f32,
+    pub color: Color,
+    pub background_color: Option<Color>,
+    /// 屏幕上的偏移(像素)，x向右，y向下
+    pub pixel_offset: Vec2,
+    pub scale_by_distance: Option<NearFarScalar>,
+}
+impl Default for Label {
+    fn default() -> Self {
+        Self {
+            position: Cartographic::default(),
+            text: String::new(),
+            font: Handle::default(),
+            font_size: 16.0,
+            color: Color::WHITE,
+            background_color: None,
+            pixel_offset: Vec2::ZERO,
+            scale_by_distance: None,
+        }
+    }
+}
+impl Label {
+    pub fn new(position: Cartographic, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+    fn text_style(&self) -> TextStyle {
+        TextStyle {
+            font: self.font.clone(),
+            font_size: self.font_size,
+            color: self.color,
+        }
+    }
+}
+/// 标注对应的ui节点
+#[derive(Component, Debug, Clone, Copy)]
+pub struct LabelNode {
+    pub owner: Entity,
+}
+#[derive(Component, Debug, Clone, Copy)]
+pub struct LabelNodeEntity(Entity);
+
+/// 世界坐标转为窗口坐标(像素，左上角为原点)，在相机后面时返回None
+pub fn world_to_window_coordinates(
+    view_projection: &DMat4,
+    position: &DVec3,
+    window_size: Vec2,
+) -> Option<Vec2> {
+    let clip = *view_projection * DVec4::new(position.x, position.y, position.z, 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let x = (clip.x / clip.w + 1.0) * 0.5 * window_size.x as f64;
+    let y = (1.0 - clip.y / clip.w) * 0.5 * window_size.y as f64;
+    return Some(Vec2::new(x as f32, y as f32));
+}
+/// 新建或更新标注的ui节点，标注删除后同时删除节点
+pub fn label_node_system(
+    mut commands: Commands,
+    labels: Query<(Entity, &Label, Option<&LabelNodeEntity>), Changed<Label>>,
+    all_labels: Query<(), With<Label>>,
+    nodes: Query<(Entity, &LabelNode)>,
+) {
+    for (entity, label, node) in labels.iter() {
+        let background_color = BackgroundColor(label.background_color.unwrap_or(Color::NONE));
+        let text = Text::from_section(label.text.clone(), label.text_style());
+        if let Some(LabelNodeEntity(node)) = node {
+            commands.entity(*node).insert((text, background_color));
+            continue;
+        }
+        let node = commands
+            .spawn((
+                TextBundle {
+                    text,
+                    background_color,
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        display: Display::None,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                LabelNode { owner: entity },
+            ))
+            .id();
+        commands.entity(entity).insert(LabelNodeEntity(node));
+    }
+    for (node, LabelNode { owner }) in nodes.iter() {
+        if all_labels.get(*owner).is_err() {
+            commands.entity(node).despawn_recursive();
+        }
+    }
+}
+/// 每帧把标注投影到屏幕上，地平线以下的隐藏
+pub fn label_position_system(
+    primary_query: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut GlobeCamera>,
+    labels: Query<(
+        &Label,
+        &LabelNodeEntity,
+        Option<&Visibility>,
+        Option<&RenderEntityType>,
+    )>,
+    mut nodes: Query<(&mut Style, &mut Transform, &Node), With<LabelNode>>,
+) {
+    let Ok(window) = primary_query.get_single() else {
+        return;
+    };
+    let Ok(mut globe_camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let ellipsoid = Ellipsoid::WGS84;
+    let camera_position = globe_camera.get_position_wc();
+    let mut occluder = EllipsoidalOccluder::new(&ellipsoid);
+    occluder.set_camera_position(camera_position);
+    let projection = *globe_camera.frustum.get_projection_matrix();
+    let view_projection = projection * globe_camera.get_view_matrix();
+    let window_size = Vec2::new(window.width(), window.height());
+
+    for (label, LabelNodeEntity(node), visibility, render_type) in labels.iter() {
+        let Ok((mut style, mut transform, node)) = nodes.get_mut(*node) else {
+            continue;
+        };
+        let position = ellipsoid.cartographic_to_cartesian(&label.position);
+        let window_position = if visibility.map_or(false, |v| *v == Visibility::Hidden)
+            || !occluder.is_point_visible(position)
+        {
+            None
+        } else {
+            world_to_window_coordinates(&view_projection, &position, window_size)
+        };
+        let Some(window_position) = window_position else {
+            if style.display != Display::None {
+                style.display = Display::None;
+            }
+            continue;
+        };
+        let scale = label.scale_by_distance.map_or(1.0, |v| {
+            v.evaluate(camera_position.distance(position)) as f32
+        }) * selected_scale(render_type);
+        // 缩放以节点中心为原点，节点中心对准标注位置
+        let top_left = window_position + label.pixel_offset - node.size() * 0.5;
+        if style.display != Display::Flex {
+            style.display = Display::Flex;
+        }
+        if style.left != Val::Px(top_left.x) || style.top != Val::Px(top_left.y) {
+            style.left = Val::Px(top_left.x);
+            style.top = Val::Px(top_left.y);
+        }
+        if transform.scale.x != scale {
+            transform.scale = Vec3::splat(scale);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_to_window_coordinates() {
+        let window_size = Vec2::new(800.0, 600.0);
+        let view_projection = DMat4::perspective_infinite_reverse_rh(1.0, 800.0 / 600.0, 1.0);
+        let center = world_to_window_coordinates(
+            &view_projection,
+            &DVec3::new(0.0, 0.0, -10.0),
+            window_size,
+        )
+        .unwrap();
+        assert!((center - Vec2::new(400.0, 300.0)).length() < 1e-3);
+        // 向上的点在窗口中y更小
+        let up = world_to_window_coordinates(
+            &view_projection,
+            &DVec3::new(0.0, 1.0, -10.0),
+            window_size,
+        )
+        .unwrap();
+        assert!(up.y < 300.0);
+        assert!(world_to_window_coordinates(
+            &view_projection,
+            &DVec3::new(0.0, 0.0, 10.0),
+            window_size
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,128 @@
+use bevy::{
+    math::{DMat4, DVec3},
+    prelude::*,
+    ui::UiSystem,
+};
+
+use houtu_scene::{Cartographic, Ellipsoid};
+
+use crate::{
+    camera::GlobeCamera, quadtree::quadtree_primitive::QuadtreePrimitive, RenderEntityType,
+};
+
+mod billboard;
+mod label;
+mod polygon;
+mod polyline;
+pub use billboard::{Billboard, BillboardMaterial, PointPrimitive};
+pub(crate) use label::world_to_window_coordinates;
+pub use label::Label;
+pub use polygon::{Polygon, PolygonMaterial};
+pub use polyline::{Polyline, PolylineDash, PolylineMaterial};
+
+/// 选中的点、图片和标注放大显示
+const SELECTED_SCALE: f32 = 1.25;
+
+fn selected_scale(render_type: Option<&RenderEntityType>) -> f32 {
+    if render_type == Some(&RenderEntityType::SelectedPoint) {
+        return SELECTED_SCALE;
+    }
+    return 1.0;
+}
+
+/// 图元共用的相机矩阵，顶点坐标都相对于各自的中心点(RTC)
+struct RtcCamera {
+    view_matrix: DMat4,
+    projection: Mat4,
+}
+impl RtcCamera {
+    fn new(globe_camera: &mut GlobeCamera) -> Self {
+        return Self {
+            view_matrix: globe_camera.get_view_matrix(),
+            projection: globe_camera.get_reverse_z_projection_matrix().as_mat4(),
+        };
+    }
+    /// 相对中心点的模型视图矩阵，在CPU上用f64计算，避免大坐标在f32下抖动
+    fn model_view(&self, center: DVec3) -> Mat4 {
+        return (self.view_matrix * DMat4::from_translation(center)).as_mat4();
+    }
+}
+
+/// 贴地的图元每隔多久重新采样一次地形高度(秒)，地形是逐步加载的
+const CLAMP_INTERVAL: f64 = 1.0;
+
+/// 贴地图元加密的间距(米)，和高精度地形的格网间距相当，间距太大时会穿过山体或者悬空
+const GROUND_SPACING: f64 = 30.0;
+/// 贴地图元加密后的最多点数，很长的线和很大的面放宽间距
+const MAX_GROUND_POINTS: f64 = 10000.0;
+/// 贴地图元抬高的高度(米)，避免和地形深度冲突
+const GROUND_HEIGHT_OFFSET: f64 = 1.0;
+
+/// 已加载地形上的高度加上GROUND_HEIGHT_OFFSET，没有加载的地方当作0
+fn sample_ground_heights(primitive: &QuadtreePrimitive, positions: &[Cartographic]) -> Vec<f64> {
+    return positions
+        .iter()
+        .map(|v| primitive.get_height(v).unwrap_or(0.0) + GROUND_HEIGHT_OFFSET)
+        .collect();
+}
+/// 贴地图元加密用的最大圆心角(弧度)，spacing为按最多点数平均分配的间距(米)
+fn ground_granularity(spacing: f64, granularity: f64) -> f64 {
+    let spacing = spacing.max(GROUND_SPACING);
+    return (spacing / Ellipsoid::WGS84.maximum_radius).min(granularity);
+}
+fn ground_heights_unchanged(old: &[f64], new: &[f64]) -> bool {
+    return old.len() == new.len()
+        && old
+            .iter()
+            .zip(new.iter())
+            .all(|(a, b)| (a - b).abs() < 0.01);
+}
+
+/// 点、线、面等基础几何图元
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MaterialPlugin::<PolylineMaterial> {
+                prepass_enabled: false,
+                shadows_enabled: false,
+                ..Default::default()
+            },
+            MaterialPlugin::<PolygonMaterial> {
+                prepass_enabled: false,
+                shadows_enabled: false,
+                ..Default::default()
+            },
+            MaterialPlugin::<BillboardMaterial> {
+                prepass_enabled: false,
+                shadows_enabled: false,
+                ..Default::default()
+            },
+        ))
+        .init_resource::<billboard::MarkerBatches>()
+        .add_systems(
+            PostUpdate,
+            (
+                polygon::polygon_mesh_system,
+                polygon::polygon_uniform_system,
+                polyline::polyline_mesh_system,
+                polyline::polyline_uniform_system,
+            )
+                .chain(),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                billboard::marker_batch_system,
+                billboard::marker_uniform_system,
+            )
+                .chain(),
+        )
+        .add_systems(
+            PostUpdate,
+            (label::label_node_system, label::label_position_system)
+                .chain()
+                .before(UiSystem::Layout),
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use bevy::{
+    math::DVec3,
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::{
+        mesh::{Indices, MeshVertexBufferLayout},
+        render_resource::{
+            AsBindGroup, PrimitiveTopology, RenderPipelineDescriptor, ShaderRef, ShaderType,
+            SpecializedMeshPipelineError,
+        },
+        view::NoFrustumCulling,
+    },
+};
+use houtu_scene::{
+    geodesic_polygon_area, ArcType, BoundingSphere, Cartographic, Ellipsoid, PolygonGeometry,
+    PolygonGeometryOptions, PolygonHierarchy, PolygonPipeline, RADIANS_PER_DEGREE,
+};
+
+use crate::{camera::GlobeCamera, quadtree::quadtree_primitive::QuadtreePrimitive};
+
+use super::{
+    ground_granularity, ground_heights_unchanged, polyline::Polyline, sample_ground_heights,
+    RtcCamera, CLAMP_INTERVAL, MAX_GROUND_POINTS,
+};
+
+/// 由经纬度定义的多边形，可以有洞，轮廓线用Polyline绘制
+#[derive(Component, Debug, Clone)]
+pub struct Polygon {
+    pub hierarchy: PolygonHierarchy,
+    pub height: f64,
+    pub extruded_height: Option<f64>,
+    pub per_position_height: bool,
+    /// 贴地，所有高度都相对于已加载的地形
+    pub clamp_to_ground: bool,
+    /// 细分后相邻两点的最大圆心角(弧度)
+    pub granularity: f64,
+    pub fill: bool,
+    pub fill_color: Color,
+    pub outline: bool,
+    pub outline_color: Color,
+    /// 轮廓线宽度(像素)
+    pub outline_width: f32,
+}
+impl Default for Polygon {
+    fn default() -> Self {
+        Self {
+            hierarchy: PolygonHierarchy::default(),
+            height: 0.0,
+            extruded_height: None,
+            per_position_height: false,
+            clamp_to_ground: false,
+            granularity: RADIANS_PER_DEGREE,
+            fill: true,
+            fill_color: Color::WHITE,
+            outline: false,
+            outline_color: Color::BLACK,
+            outline_width: 1.0,
+        }
+    }
+}
+impl Polygon {
+    pub fn new(hierarchy: PolygonHierarchy) -> Self {
+        Self {
+            hierarchy,
+            ..Default::default()
+        }
+    }
+    /// 贴地时按地形分辨率细分，否则三角形会穿过山体
+    pub fn geometry_options(&self) -> PolygonGeometryOptions {
+        let granularity = if self.clamp_to_ground {
+            let area = geodesic_polygon_area(&self.hierarchy.positions);
+            ground_granularity((area / MAX_GROUND_POINTS).sqrt(), self.granularity)
+        } else {
+            self.granularity
+        };
+        PolygonGeometryOptions {
+            height: self.height,
+            extruded_height: self.extruded_height,
+            per_position_height: self.per_position_height,
+            granularity,
+            ellipsoid: Ellipsoid::WGS84,
+        }
+    }
+}
+#[derive(ShaderType, Debug, Clone, Copy, Default)]
+pub struct PolygonUniform {
+    pub model_view: Mat4,
+    pub projection: Mat4,
+    pub color: Vec4,
+}
+#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone)]
+#[uuid = "9a1c5d27-6b3e-4c8f-a0d4-7e2f3b5c9d10"]
+pub struct PolygonMaterial {
+    #[uniform(0)]
+    pub uniform: PolygonUniform,
+}
+impl Material for PolygonMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "polygon_material.wgsl".into()
+    }
+    fn fragment_shader() -> ShaderRef {
+        "polygon_material.wgsl".into()
+    }
+    fn alpha_mode(&self) -> AlphaMode {
+        if self.uniform.color.w < 1.0 {
+            return AlphaMode::Blend;
+        }
+        return AlphaMode::Opaque;
+    }
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+/// 生成的几何和当前使用的地形高度
+#[derive(Component, Debug, Clone)]
+pub struct PolygonRenderState {
+    pub geometry: PolygonGeometry,
+    pub ground_heights: Vec<f64>,
+    pub center: DVec3,
+    pub radius: f64,
+}
+
+/// 顶点坐标相对于包围球中心，ground_heights为空时不加地形高度
+pub fn build_polygon_mesh(
+    geometry: &PolygonGeometry,
+    ground_heights: &[f64],
+) -> (DVec3, f64, Mesh) {
+    let ellipsoid = Ellipsoid::WGS84;
+    let cartesians: Vec<DVec3> = geometry
+        .positions
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let mut v = *v;
+            v.height += ground_heights.get(i).copied().unwrap_or(0.0);
+            return ellipsoid.cartographic_to_cartesian(&v);
+        })
+        .collect();
+    let bounding_sphere = BoundingSphere::from_points(&cartesians);
+    let center = bounding_sphere.center;
+    let positions: Vec<[f32; 3]> = cartesians
+        .iter()
+        .map(|v| (*v - center).as_vec3().to_array())
+        .collect();
+    let normals: Vec<[f32; 3]> = geometry
+        .normals
+        .iter()
+        .map(|v| v.as_vec3().to_array())
+        .collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_indices(Some(Indices::U32(geometry.indices.clone())));
+    return (center, bounding_sphere.radius, mesh);
+}
+/// 多边形改变时重新生成，贴地的多边形在地形高度变化后更新
+pub fn polygon_mesh_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<PolygonMaterial>>,
+    primitive: Option<Res<QuadtreePrimitive>>,
+    time: Res<Time>,
+    mut last_clamp_time: Local<f64>,
+    polygons: Query<(
+        Entity,
+        Ref<Polygon>,
+        Option<&PolygonRenderState>,
+        Option<&Visibility>,
+    )>,
+) {
+    let reclamp = time.elapsed_seconds_f64() - *last_clamp_time > CLAMP_INTERVAL;
+    if reclamp {
+        *last_clamp_time = time.elapsed_seconds_f64();
+    }
+    for (entity, polygon, state, visibility) in polygons.iter() {
+        let geometry = if polygon.is_changed() {
+            PolygonPipeline::create_geometry(&polygon.hierarchy, &polygon.geometry_options())
+        } else if polygon.clamp_to_ground && reclamp && state.is_some() {
+            state.map(|v| v.geometry.clone())
+        } else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        if visibility.is_none() {
+            entity_commands.insert(SpatialBundle::INHERITED_IDENTITY);
+        }
+        let Some(geometry) = geometry else {
+            entity_commands.despawn_descendants();
+            entity_commands.remove::<(Handle<Mesh>, Handle<PolygonMaterial>, PolygonRenderState)>();
+            continue;
+        };
+        let ground_heights = match (&primitive, polygon.clamp_to_ground) {
+            (Some(primitive), true) => sample_ground_heights(primitive, &geometry.positions),
+            _ => vec![],
+        };
+        if !polygon.is_changed() {
+            let unchanged = state.map_or(false, |v| {
+                ground_heights_unchanged(&v.ground_heights, &ground_heights)
+            });
+            if unchanged {
+                continue;
+            }
+        }
+
+        // 贴地的轮廓线由Polyline自己加密并采样地形高度
+        if polygon.is_changed() {
+            entity_commands.despawn_descendants();
+        }
+        if polygon.is_changed() && polygon.outline {
+            for outline in geometry.outlines.iter() {
+                entity_commands.with_children(|parent| {
+                    parent.spawn((
+                        Polyline {
+                            positions: outline.clone(),
+                            width: polygon.outline_width,
+                            color: polygon.outline_color,
+                            arc_type: ArcType::None,
+                            clamp_to_ground: polygon.clamp_to_ground,
+                            ..Default::default()
+                        },
+                        SpatialBundle::INHERITED_IDENTITY,
+                    ));
+                });
+            }
+        }
+        let (center, radius, mesh) = build_polygon_mesh(&geometry, &ground_heights);
+        if polygon.fill {
+            let material = PolygonMaterial {
+                uniform: PolygonUniform {
+                    color: polygon.fill_color.as_linear_rgba_f32().into(),
+                    ..Default::default()
+                },
+            };
+            entity_commands.insert((meshes.add(mesh), materials.add(material), NoFrustumCulling));
+        } else {
+            entity_commands.remove::<(Handle<Mesh>, Handle<PolygonMaterial>)>();
+        }
+        entity_commands.insert(PolygonRenderState {
+            geometry,
+            ground_heights,
+            center,
+            radius,
+        });
+    }
+}
+/// 每帧更新相对中心点的矩阵
+pub fn polygon_uniform_system(
+    mut cameras: Query<&mut GlobeCamera>,
+    mut materials: ResMut<Assets<PolygonMaterial>>,
+    polygons: Query<(&PolygonRenderState, &Handle<PolygonMaterial>)>,
+) {
+    let Ok(mut globe_camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let rtc_camera = RtcCamera::new(&mut globe_camera);
+    for (state, handle) in polygons.iter() {
+        let Some(material) = materials.get_mut(handle) else {
+            continue;
+        };
+        let uniform = &mut material.uniform;
+        uniform.model_view = rtc_camera.model_view(state.center);
+        uniform.projection = rtc_camera.projection;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+
+    #[test]
+    fn test_build_polygon_mesh() {
+        let polygon = Polygon {
+            extruded_height: Some(100.0),
+            ..Polygon::new(PolygonHierarchy::new(vec![
+                Cartographic::from_degrees(120.0, 30.0, 0.0),
+                Cartographic::from_degrees(120.01, 30.0, 0.0),
+                Cartographic::from_degrees(120.01, 30.01, 0.0),
+                Cartographic::from_degrees(120.0, 30.01, 0.0),
+            ]))
+        };
+        let geometry =
+            PolygonPipeline::create_geometry(&polygon.hierarchy, &polygon.geometry_options())
+                .unwrap();
+        let (center, radius, mesh) = build_polygon_mesh(&geometry, &[]);
+        assert_eq!(mesh.count_vertices(), geometry.positions.len());
+        assert!(radius < 2000.0);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expect positions");
+        };
+        assert!(positions
+            .iter()
+            .all(|v| Vec3::from(*v).length() as f64 <= radius + 1.0));
+
+        // 加上地形高度后整体抬高
+        let ground_heights = vec![500.0; geometry.positions.len()];
+        let (raised, _, _) = build_polygon_mesh(&geometry, &ground_heights);
+        assert!((raised.length() - center.length() - 500.0).abs() < 1.0);
+    }
+    #[test]
+    fn test_ground_geometry_options() {
+        let mut polygon = Polygon::new(PolygonHierarchy::new(vec![
+            Cartographic::from_degrees(120.0, 30.0, 0.0),
+            Cartographic::from_degrees(120.01, 30.0, 0.0),
+            Cartographic::from_degrees(120.01, 30.01, 0.0),
+        ]));
+        assert_eq!(polygon.geometry_options().granularity, RADIANS_PER_DEGREE);
+        // 贴地时按地形分辨率细分
+        polygon.clamp_to_ground = true;
+        let spacing = polygon.geometry_options().granularity * Ellipsoid::WGS84.maximum_radius;
+        assert!((spacing - 30.0).abs() < 1e-6);
+    }
+}
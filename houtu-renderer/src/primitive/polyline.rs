@@ -0,0 +1,424 @@
+use bevy::{
+    math::DVec3,
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::{
+        mesh::{Indices, MeshVertexAttribute, MeshVertexBufferLayout},
+        render_resource::{
+            AsBindGroup, PrimitiveTopology, RenderPipelineDescriptor, ShaderRef, ShaderType,
+            SpecializedMeshPipelineError, VertexFormat,
+        },
+        view::NoFrustumCulling,
+    },
+    window::PrimaryWindow,
+};
+use houtu_scene::{
+    polyline_length, ArcType, BoundingSphere, Cartographic, Ellipsoid, PolylinePipeline,
+    RADIANS_PER_DEGREE,
+};
+
+use crate::{camera::GlobeCamera, quadtree::quadtree_primitive::QuadtreePrimitive};
+
+use super::{
+    ground_granularity, ground_heights_unchanged, sample_ground_heights, RtcCamera, CLAMP_INTERVAL,
+    GROUND_HEIGHT_OFFSET, MAX_GROUND_POINTS,
+};
+
+/// 虚线样式，与Cesium的PolylineDashMaterial相同
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolylineDash {
+    /// 一个完整的虚线周期的长度(像素)
+    pub length: f32,
+    /// 16位的掩码，每一位对应周期中的1/16，为1的部分绘制
+    pub pattern: u16,
+}
+impl Default for PolylineDash {
+    fn default() -> Self {
+        Self {
+            length: 16.0,
+            pattern: 255,
+        }
+    }
+}
+/// 由经纬度定义的折线，宽度以像素为单位
+#[derive(Component, Debug, Clone)]
+pub struct Polyline {
+    pub positions: Vec<Cartographic>,
+    pub width: f32,
+    pub color: Color,
+    pub dash: Option<PolylineDash>,
+    pub arc_type: ArcType,
+    /// 加密时相邻两点的最大圆心角(弧度)
+    pub granularity: f64,
+    /// 贴地，加密后的每个点的高度都相对于已加载的地形，直线连接时按测地线加密
+    pub clamp_to_ground: bool,
+}
+impl Default for Polyline {
+    fn default() -> Self {
+        Self {
+            positions: vec![],
+            width: 2.0,
+            color: Color::WHITE,
+            dash: None,
+            arc_type: ArcType::Geodesic,
+            granularity: RADIANS_PER_DEGREE,
+            clamp_to_ground: false,
+        }
+    }
+}
+impl Polyline {
+    pub fn new(positions: Vec<Cartographic>) -> Self {
+        Self {
+            positions,
+            ..Default::default()
+        }
+    }
+    /// 贴地时按地形分辨率加密，否则地形高度只在很稀疏的点上采样
+    pub fn ground_granularity(&self) -> f64 {
+        let length = polyline_length(&self.positions).unwrap_or(0.0);
+        return ground_granularity(length / MAX_GROUND_POINTS, self.granularity);
+    }
+}
+#[derive(ShaderType, Debug, Clone, Copy, Default)]
+pub struct PolylineUniform {
+    pub model_view: Mat4,
+    pub projection: Mat4,
+    pub color: Vec4,
+    pub viewport: Vec2,
+    pub width: f32,
+    pub dash_length: f32,
+    pub dash_pattern: u32,
+    pub meters_per_pixel: f32,
+}
+#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone)]
+#[uuid = "3f3b0c3e-7d4a-4f7e-9c55-2a1d6f0b8e41"]
+pub struct PolylineMaterial {
+    #[uniform(0)]
+    pub uniform: PolylineUniform,
+}
+impl PolylineMaterial {
+    /// 线段另一端的位置
+    pub const ATTRIBUTE_OTHER_POSITION: MeshVertexAttribute =
+        MeshVertexAttribute::new("ATTRIBUTE_OTHER_POSITION", 1100, VertexFormat::Float32x3);
+    /// x为向哪一侧扩展，y为另一端是下一个点(1)还是上一个点(-1)，z为到起点的距离(米)
+    pub const ATTRIBUTE_EXPAND_AND_DISTANCE: MeshVertexAttribute = MeshVertexAttribute::new(
+        "ATTRIBUTE_EXPAND_AND_DISTANCE",
+        1101,
+        VertexFormat::Float32x3,
+    );
+}
+impl Material for PolylineMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "line_material.wgsl".into()
+    }
+    fn fragment_shader() -> ShaderRef {
+        "line_material.wgsl".into()
+    }
+    fn alpha_mode(&self) -> AlphaMode {
+        if self.uniform.color.w < 1.0 || self.uniform.dash_length > 0.0 {
+            return AlphaMode::Blend;
+        }
+        return AlphaMode::Opaque;
+    }
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            PolylineMaterial::ATTRIBUTE_OTHER_POSITION.at_shader_location(1),
+            PolylineMaterial::ATTRIBUTE_EXPAND_AND_DISTANCE.at_shader_location(2),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+/// 渲染用的中心点和包围球半径
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PolylineRenderState {
+    pub center: DVec3,
+    pub radius: f64,
+}
+/// 贴地折线加密后的点和上次采样的地形高度，地形加载后据此判断是否需要更新
+#[derive(Component, Debug, Clone)]
+pub struct PolylineGroundState {
+    pub positions: Vec<Cartographic>,
+    pub ground_heights: Vec<f64>,
+}
+
+/// 每段生成一个四边形，顶点坐标相对于包围球中心
+pub fn build_polyline_mesh(points: &[DVec3]) -> Option<(PolylineRenderState, Mesh)> {
+    if points.len() < 2 {
+        return None;
+    }
+    let bounding_sphere = BoundingSphere::from_points(&points.to_vec());
+    let center = bounding_sphere.center;
+    let segment_count = points.len() - 1;
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(segment_count * 4);
+    let mut other_positions: Vec<[f32; 3]> = Vec::with_capacity(segment_count * 4);
+    let mut expand_and_distance: Vec<[f32; 3]> = Vec::with_capacity(segment_count * 4);
+    let mut indices: Vec<u32> = Vec::with_capacity(segment_count * 6);
+    let mut distance = 0.0;
+    for (i, pair) in points.windows(2).enumerate() {
+        let start = (pair[0] - center).as_vec3().to_array();
+        let end = (pair[1] - center).as_vec3().to_array();
+        let next_distance = distance + pair[0].distance(pair[1]);
+        for side in [1.0, -1.0] {
+            positions.push(start);
+            other_positions.push(end);
+            expand_and_distance.push([side, 1.0, distance as f32]);
+        }
+        for side in [1.0, -1.0] {
+            positions.push(end);
+            other_positions.push(start);
+            expand_and_distance.push([side, -1.0, next_distance as f32]);
+        }
+        let base = (i * 4) as u32;
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 1, base + 3, base + 2]);
+        distance = next_distance;
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(PolylineMaterial::ATTRIBUTE_OTHER_POSITION, other_positions);
+    mesh.insert_attribute(
+        PolylineMaterial::ATTRIBUTE_EXPAND_AND_DISTANCE,
+        expand_and_distance,
+    );
+    mesh.set_indices(Some(Indices::U32(indices)));
+    let state = PolylineRenderState {
+        center,
+        radius: bounding_sphere.radius,
+    };
+    return Some((state, mesh));
+}
+/// 折线改变时重新加密并生成网格，贴地的折线在地形高度变化后更新
+pub fn polyline_mesh_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<PolylineMaterial>>,
+    primitive: Option<Res<QuadtreePrimitive>>,
+    time: Res<Time>,
+    mut last_clamp_time: Local<f64>,
+    polylines: Query<(
+        Entity,
+        Ref<Polyline>,
+        Option<&PolylineGroundState>,
+        Option<&Visibility>,
+    )>,
+) {
+    let reclamp = time.elapsed_seconds_f64() - *last_clamp_time > CLAMP_INTERVAL;
+    if reclamp {
+        *last_clamp_time = time.elapsed_seconds_f64();
+    }
+    for (entity, polyline, ground_state, visibility) in polylines.iter() {
+        let points = if polyline.clamp_to_ground {
+            let positions = if polyline.is_changed() {
+                let arc_type = match polyline.arc_type {
+                    ArcType::None => ArcType::Geodesic,
+                    v => v,
+                };
+                PolylinePipeline::generate_cartographic_arc(
+                    &polyline.positions,
+                    arc_type,
+                    polyline.ground_granularity(),
+                    None,
+                )
+            } else if let (true, Some(ground_state)) = (reclamp, ground_state) {
+                ground_state.positions.clone()
+            } else {
+                continue;
+            };
+            let ground_heights = match &primitive {
+                Some(primitive) => sample_ground_heights(primitive, &positions),
+                None => vec![GROUND_HEIGHT_OFFSET; positions.len()],
+            };
+            if !polyline.is_changed()
+                && ground_state.map_or(false, |v| {
+                    ground_heights_unchanged(&v.ground_heights, &ground_heights)
+                })
+            {
+                continue;
+            }
+            let points = clamp_positions(&positions, &ground_heights);
+            commands.entity(entity).insert(PolylineGroundState {
+                positions,
+                ground_heights,
+            });
+            points
+        } else if polyline.is_changed() {
+            if ground_state.is_some() {
+                commands.entity(entity).remove::<PolylineGroundState>();
+            }
+            PolylinePipeline::generate_arc(
+                &polyline.positions,
+                polyline.arc_type,
+                polyline.granularity,
+                None,
+            )
+        } else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        if visibility.is_none() {
+            entity_commands.insert(SpatialBundle::INHERITED_IDENTITY);
+        }
+        let Some((state, mesh)) = build_polyline_mesh(&points) else {
+            entity_commands.remove::<(Handle<Mesh>, Handle<PolylineMaterial>)>();
+            continue;
+        };
+        let material = PolylineMaterial {
+            uniform: PolylineUniform {
+                color: polyline.color.as_linear_rgba_f32().into(),
+                width: polyline.width,
+                dash_length: polyline.dash.map_or(0.0, |v| v.length),
+                dash_pattern: polyline.dash.map_or(0, |v| v.pattern as u32),
+                ..Default::default()
+            },
+        };
+        entity_commands.insert((
+            meshes.add(mesh),
+            materials.add(material),
+            state,
+            NoFrustumCulling,
+        ));
+    }
+}
+/// 加密后的点加上地形高度
+fn clamp_positions(positions: &[Cartographic], ground_heights: &[f64]) -> Vec<DVec3> {
+    let ellipsoid = Ellipsoid::WGS84;
+    return positions
+        .iter()
+        .zip(ground_heights.iter())
+        .map(|(position, height)| {
+            let mut position = *position;
+            position.height += height;
+            ellipsoid.cartographic_to_cartesian(&position)
+        })
+        .collect();
+}
+/// 每帧更新相对中心点的矩阵和像素大小
+pub fn polyline_uniform_system(
+    primary_query: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut GlobeCamera>,
+    mut materials: ResMut<Assets<PolylineMaterial>>,
+    polylines: Query<(&PolylineRenderState, &Handle<PolylineMaterial>)>,
+) {
+    let Ok(window) = primary_query.get_single() else {
+        return;
+    };
+    let Ok(mut globe_camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let viewport = Vec2::new(window.width(), window.height());
+    let rtc_camera = RtcCamera::new(&mut globe_camera);
+    let pixel_size_factor = 2.0 * (globe_camera.frustum.get_fovy() * 0.5).tan() / viewport.y as f64;
+    let near = globe_camera.frustum.near;
+    let camera_position = globe_camera.get_position_wc();
+    for (state, handle) in polylines.iter() {
+        let Some(material) = materials.get_mut(handle) else {
+            continue;
+        };
+        let distance = (camera_position.distance(state.center) - state.radius).max(near);
+        let uniform = &mut material.uniform;
+        uniform.model_view = rtc_camera.model_view(state.center);
+        uniform.projection = rtc_camera.projection;
+        uniform.viewport = viewport;
+        uniform.meters_per_pixel = (distance * pixel_size_factor) as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+
+    #[test]
+    fn test_build_polyline_mesh() {
+        let points = vec![
+            DVec3::new(6378137.0, 0.0, 0.0),
+            DVec3::new(6378137.0, 100.0, 0.0),
+            DVec3::new(6378137.0, 100.0, 50.0),
+        ];
+        let (state, mesh) = build_polyline_mesh(&points).unwrap();
+        assert!(state.center.distance(DVec3::new(6378137.0, 50.0, 25.0)) < 100.0);
+        assert_eq!(mesh.count_vertices(), 8);
+        assert_eq!(mesh.indices().unwrap().len(), 12);
+        // 顶点坐标相对于中心点，保持较小的数值
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expect positions");
+        };
+        assert!(positions.iter().all(|v| Vec3::from(*v).length() < 200.0));
+        let Some(VertexAttributeValues::Float32x3(expand)) =
+            mesh.attribute(PolylineMaterial::ATTRIBUTE_EXPAND_AND_DISTANCE)
+        else {
+            panic!("expect expand and distance");
+        };
+        assert_eq!(expand[0], [1.0, 1.0, 0.0]);
+        assert_eq!(expand[3], [-1.0, -1.0, 100.0]);
+        assert_eq!(expand[7][2], 150.0);
+
+        assert!(build_polyline_mesh(&points[..1]).is_none());
+    }
+    #[test]
+    fn test_far_side_fails_depth_test() {
+        let radius = Ellipsoid::WGS84.maximum_radius;
+        let mut globe_camera = GlobeCamera::default();
+        globe_camera.position = DVec3::new(radius * 3.0, 0.0, 0.0);
+        globe_camera.direction = DVec3::NEG_X;
+        globe_camera.up = DVec3::Z;
+        globe_camera.right = globe_camera.direction.cross(globe_camera.up);
+        let rtc_camera = RtcCamera::new(&mut globe_camera);
+        // 和着色器一样，顶点相对中心点，先乘模型视图矩阵再乘投影矩阵
+        let depth = |center: DVec3| -> f32 {
+            let clip = rtc_camera.projection * rtc_camera.model_view(center) * Vec4::W;
+            return clip.z / clip.w;
+        };
+        let near_side = depth(DVec3::new(radius, 0.0, 0.0));
+        let far_side = depth(DVec3::new(-radius, 0.0, 0.0));
+        assert!(near_side > 0.0 && near_side <= 1.0);
+        assert!(far_side > 0.0 && far_side <= 1.0);
+        // bevy的主渲染通道使用GreaterEqual，被地球挡住的点深度更小，不会画出来
+        assert!(far_side < near_side);
+    }
+    #[test]
+    fn test_clamp_positions() {
+        let positions = vec![
+            Cartographic::from_degrees(0.0, 0.0, 10.0),
+            Cartographic::from_degrees(1.0, 0.0, 0.0),
+        ];
+        let points = clamp_positions(&positions, &[100.0, 50.0]);
+        let ellipsoid = Ellipsoid::WGS84;
+        let first = ellipsoid.cartesian_to_cartographic(&points[0]).unwrap();
+        let second = ellipsoid.cartesian_to_cartographic(&points[1]).unwrap();
+        assert!((first.height - 110.0).abs() < 1e-6);
+        assert!((second.height - 50.0).abs() < 1e-6);
+    }
+    #[test]
+    fn test_ground_granularity() {
+        let mut polyline = Polyline::new(vec![
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(0.01, 0.0, 0.0),
+        ]);
+        polyline.clamp_to_ground = true;
+        let spacing = polyline.ground_granularity() * Ellipsoid::WGS84.maximum_radius;
+        assert!((spacing - 30.0).abs() < 1e-6);
+        // 很长的线放宽间距，点数不超过上限
+        polyline.positions[1] = Cartographic::from_degrees(90.0, 0.0, 0.0);
+        let count = PolylinePipeline::generate_cartographic_arc(
+            &polyline.positions,
+            ArcType::Geodesic,
+            polyline.ground_granularity(),
+            None,
+        )
+        .len();
+        assert!(count <= MAX_GROUND_POINTS as usize + 2);
+        assert!(count > 1000);
+    }
+}
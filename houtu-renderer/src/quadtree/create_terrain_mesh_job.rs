@@ -3,17 +3,17 @@ use houtu_jobs::{
     AsyncReturn, Context, Job,
 };
 use houtu_scene::{
-    GeographicTilingScheme, HeightmapTerrainData, IndicesAndEdgesCache,
+    GeographicTilingScheme, IndicesAndEdgesCache,
 };
 use std::{
     io,
     sync::{Arc, Mutex},
 };
 
-use super::tile_key::TileKey;
+use super::{terrain_data::TerrainData, tile_key::TileKey};
 
 pub struct CreateTileJob {
-    pub terrain_data: Arc<Mutex<HeightmapTerrainData>>,
+    pub terrain_data: Arc<Mutex<TerrainData>>,
     pub indices_and_edges_cache: Arc<Mutex<IndicesAndEdgesCache>>,
     pub tiling_scheme: GeographicTilingScheme,
     pub key: TileKey,
@@ -40,7 +40,7 @@ impl Job for CreateTileJob {
                 self.terrain_data
                     .lock()
                     .expect("terrain_data.lock")
-                    .createMesh(
+                    .create_mesh(
                         &self.tiling_scheme,
                         self.key.x,
                         self.key.y,
@@ -1,20 +1,26 @@
 use bevy::render::define_atomic_id;
 
 define_atomic_id!(CreditId);
+#[derive(Clone)]
 pub struct Credit {
     pub id: CreditId,
-    pub html: &'static str,
+    pub html: String,
     pub show: bool,
 }
 impl Default for Credit {
     fn default() -> Self {
         Self {
             id: CreditId::new(),
-            html: "",
+            html: "".to_string(),
             show: true,
         }
     }
 }
 impl Credit {
-    
+    pub fn new(html: &str) -> Self {
+        Self {
+            html: html.to_string(),
+            ..Default::default()
+        }
+    }
 }
@@ -1,16 +1,25 @@
 use std::f64::consts::PI;
 
+use houtu_jobs::AsyncReturn;
 use houtu_scene::{
     Ellipsoid, GeographicTilingScheme, HeightmapTerrainData, Rectangle, TilingScheme,
 };
 
-use super::{terrain_provider::TerrainProvider, tile_key::TileKey};
+use super::{
+    terrain_provider::{
+        RequestTileGeometry, RequestTileGeometryError, RequestTileGeometryJob, TerrainProvider,
+        TerrainTileRequest, TerrainTileResponse,
+    },
+    tile_key::TileKey,
+};
 
 pub struct EllipsoidTerrainProvider {
     pub tiling_scheme: GeographicTilingScheme,
     _level_zero_maximum_geometric_error: f64,
     pub ready: bool,
     pub rectangle: Rectangle,
+    /// 生成顶点法线，开启光照时需要
+    pub request_vertex_normals: bool,
 }
 impl EllipsoidTerrainProvider {
     pub fn new() -> Self {
@@ -23,6 +32,7 @@ impl EllipsoidTerrainProvider {
             _level_zero_maximum_geometric_error: _level_zero_maximum_geometric_error,
             ready: true,
             rectangle: Rectangle::MAX_VALUE.clone(),
+            request_vertex_normals: false,
         }
     }
 }
@@ -39,27 +49,23 @@ impl TerrainProvider for EllipsoidTerrainProvider {
     fn get_level_maximum_geometric_error(&self, level: u32) -> f64 {
         return self._level_zero_maximum_geometric_error / (1 << level) as f64;
     }
-    fn request_tile_geometry(&self) -> Option<HeightmapTerrainData> {
-        let width = 16;
-        let height = 16;
-        return Some(HeightmapTerrainData::new(
-            vec![0.; width * height],
-            width as u32,
-            height as u32,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-        ));
+    fn request_tile_geometry(&self, key: &TileKey) -> RequestTileGeometry {
+        return RequestTileGeometry::Requested(RequestTileGeometryJob {
+            key: key.clone(),
+            request: Box::new(EllipsoidTerrainTileRequest {
+                width: 16,
+                height: 16,
+                include_vertex_normals: self.request_vertex_normals,
+            }),
+        });
     }
+    fn handle_tile_metadata(&mut self, _key: &TileKey, _metadata: &str) {}
     fn get_has_water_mask(&self) -> bool {
         false
     }
+    /// 请求法线时高度图生成网格会根据相邻格网点计算法线
     fn get_has_vertex_normals(&self) -> bool {
-        false
+        self.request_vertex_normals
     }
     fn get_availability(&mut self) -> Option<&mut super::tile_availability::TileAvailability> {
         return None;
@@ -68,6 +74,37 @@ impl TerrainProvider for EllipsoidTerrainProvider {
         self.ready
     }
 }
+/// 椭球面上的瓦片高度都为0
+struct EllipsoidTerrainTileRequest {
+    width: u32,
+    height: u32,
+    include_vertex_normals: bool,
+}
+impl TerrainTileRequest for EllipsoidTerrainTileRequest {
+    fn perform(
+        self: Box<Self>,
+    ) -> AsyncReturn<Result<TerrainTileResponse, RequestTileGeometryError>> {
+        Box::pin(async move {
+            let mut data = HeightmapTerrainData::new(
+                vec![0.; (self.width * self.height) as usize],
+                self.width,
+                self.height,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            );
+            data._include_vertex_normals = self.include_vertex_normals;
+            Ok(TerrainTileResponse {
+                data: data.into(),
+                metadata: None,
+            })
+        })
+    }
+}
 fn get_level_zero_maximum_geometric_error(tiling_scheme: &GeographicTilingScheme) -> f64 {
     return get_estimated_level_zero_geometric_error_for_a_heightmap(
         &tiling_scheme.ellipsoid,
@@ -6,7 +6,7 @@ use bevy::{
     render::renderer::RenderDevice,
 };
 use houtu_jobs::{FinishedJobs, JobSpawner};
-use houtu_scene::{HeightmapTerrainData, TileBoundingRegion};
+use houtu_scene::{Ray, TileBoundingRegion};
 
 use crate::camera::GlobeCamera;
 
@@ -20,11 +20,19 @@ use super::{
     quadtree_tile::QuadtreeTileLoadState,
     quadtree_tile_storage::QuadtreeTileStorage,
     reproject_texture::ReprojectTextureTaskQueue,
-    terrain_provider::TerrainProvider,
+    terrain_data::TerrainData,
+    terrain_provider::{
+        RequestTileGeometry, RequestTileGeometryError, RequestTileGeometryJob, TerrainProvider,
+    },
     tile_imagery::TileImagery,
     tile_key::TileKey,
     upsample_job::UpsampleJob,
+    water_mask::{
+        compute_water_mask_translation_and_scale, create_water_mask_image, ALL_WATER_TEXTURE_HANDLE,
+    },
 };
+/// 地形请求失败(网络错误等)后的最大重试次数，超过后从父瓦片上采样
+const MAXIMUM_TERRAIN_REQUEST_RETRIES: u32 = 3;
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum TerrainState {
     FAILED = 0,
@@ -49,8 +57,11 @@ pub struct GlobeSurfaceTile {
     pub bounding_volume_source_tile: Option<TileKey>,
     pub vertex_array: Option<bool>, //TODO 暂时不知道放什么数据结构，先放个bool值
     pub imagery: Vec<TileImagery>,
-    pub terrain_data: Option<Arc<Mutex<HeightmapTerrainData>>>,
+    pub terrain_data: Option<Arc<Mutex<TerrainData>>>,
     pub water_mask_texture: Option<Handle<Image>>,
+    /// 瓦片纹理坐标到水面掩膜纹理坐标的平移(xy)和缩放(zw)
+    pub water_mask_translation_and_scale: DVec4,
+    pub terrain_request_retries: u32,
 }
 impl GlobeSurfaceTile {
     pub fn new() -> Self {
@@ -65,6 +76,8 @@ impl GlobeSurfaceTile {
             imagery: Vec::new(),
             terrain_data: None,
             water_mask_texture: None,
+            water_mask_translation_and_scale: DVec4::new(0.0, 0.0, 1.0, 1.0),
+            terrain_request_retries: 0,
         }
     }
     ///新增一个TileImagery
@@ -113,7 +126,7 @@ impl GlobeSurfaceTile {
         }
         return should_removeTile;
     }
-    pub fn get_cloned_terrain_data(&self) -> Arc<Mutex<HeightmapTerrainData>> {
+    pub fn get_cloned_terrain_data(&self) -> Arc<Mutex<TerrainData>> {
         self.terrain_data.as_ref().unwrap().clone()
     }
     pub fn has_mesh(&self) -> bool {
@@ -123,7 +136,12 @@ impl GlobeSurfaceTile {
             false
         }
     }
-    pub fn set_terrain_data(&mut self, new_terrain_data: HeightmapTerrainData) {
+    /// 射线与瓦片地形网格的最近交点，没有网格时返回None
+    pub fn pick(&self, ray: &Ray, cull_back_faces: bool) -> Option<DVec3> {
+        let terrain_data = self.terrain_data.as_ref()?.lock().unwrap();
+        return terrain_data.get_mesh()?.pick(ray, cull_back_faces);
+    }
+    pub fn set_terrain_data(&mut self, new_terrain_data: TerrainData) {
         self.terrain_data = Some(Arc::new(Mutex::new(new_terrain_data)));
         self.terrain_state = TerrainState::RECEIVED;
     }
@@ -319,10 +337,11 @@ fn processTerrainStateMachine(
                 .lock()
                 .unwrap()
                 .can_upsample();
-        if parent_ready {
+        if !parent_ready {
+            // 父瓦片的地形还没准备好，先加载父瓦片
             GlobeSurfaceTile::process_state_machine(
                 storage,
-                tile_key,
+                parent_key,
                 terrain_provider,
                 imagery_layer_storage,
                 true,
@@ -348,7 +367,9 @@ fn processTerrainStateMachine(
                     tiling_scheme: terrain_provider.get_tiling_scheme().clone(),
                     parent_key: parent_key,
                     key: tile_key,
-                })
+                });
+                let tile = storage.get_mut(&tile_key).unwrap();
+                tile.data.terrain_state = TerrainState::RECEIVING;
             }
         } else {
             tile.state = QuadtreeTileLoadState::FAILED;
@@ -356,11 +377,17 @@ fn processTerrainStateMachine(
     }
     let tile = storage.get_mut(&tile_key).unwrap();
     if tile.data.terrain_state == TerrainState::UNLOADED {
-        tile.data.terrain_state = TerrainState::RECEIVING;
-        let value = terrain_provider
-            .request_tile_geometry()
-            .expect("terrain_datasource.request_tile_geometry");
-        tile.data.set_terrain_data(value);
+        match terrain_provider.request_tile_geometry(&tile_key) {
+            RequestTileGeometry::Requested(job) => {
+                tile.data.terrain_state = TerrainState::RECEIVING;
+                job_spawner.spawn(job);
+            }
+            // 请求数量达到上限，保持UNLOADED下一帧再请求
+            RequestTileGeometry::Throttled => {}
+            RequestTileGeometry::NotAvailable => {
+                tile.data.terrain_state = TerrainState::FAILED;
+            }
+        }
     }
     let tile = storage.get_mut(&tile_key).unwrap();
     if tile.data.terrain_state == TerrainState::RECEIVING {}
@@ -382,21 +409,115 @@ fn processTerrainStateMachine(
         // bevy::log::info!("terrain state of tile {:?} is ready", tile.key);
     }
     if tile.data.terrain_state == TerrainState::READY {}
+    if tile.data.terrain_state >= TerrainState::RECEIVED
+        && tile.data.water_mask_texture.is_none()
+        && terrain_provider.get_has_water_mask()
+    {
+        update_water_mask(storage, tile_key, images);
+    }
+}
+/// 瓦片自带水面掩膜时直接创建纹理，否则沿用最近的非上采样祖先瓦片的掩膜
+fn update_water_mask(
+    storage: &mut QuadtreeTileStorage,
+    tile_key: TileKey,
+    images: &mut Assets<Image>,
+) {
+    let tile = storage.get_mut(&tile_key).unwrap();
+    let water_mask = tile
+        .data
+        .terrain_data
+        .as_ref()
+        .and_then(|v| v.lock().unwrap().get_water_mask().cloned());
+    if let Some(water_mask) = water_mask {
+        tile.data.water_mask_translation_and_scale = DVec4::new(0.0, 0.0, 1.0, 1.0);
+        if water_mask.len() == 1 {
+            // 只有一个字节时表示整个瓦片都是陆地或者都是水面
+            if water_mask[0] != 0 {
+                tile.data.water_mask_texture = Some(ALL_WATER_TEXTURE_HANDLE.typed());
+            }
+        } else if let Some(image) = create_water_mask_image(&water_mask) {
+            tile.data.water_mask_texture = Some(images.add(image));
+        }
+        return;
+    }
+    let tile_rectangle = tile.rectangle.clone();
+    let mut source_key = tile.parent;
+    while let Some(key) = source_key {
+        let source = storage.get(&key).unwrap();
+        let is_source = source
+            .data
+            .terrain_data
+            .as_ref()
+            .map_or(false, |v| !v.lock().unwrap().was_created_by_upsampling());
+        if is_source {
+            break;
+        }
+        source_key = source.parent;
+    }
+    let Some(source_key) = source_key else {
+        return;
+    };
+    let source = storage.get(&source_key).unwrap();
+    let Some(texture) = source.data.water_mask_texture.clone() else {
+        return;
+    };
+    let translation_and_scale =
+        compute_water_mask_translation_and_scale(&tile_rectangle, &source.rectangle);
+    let tile = storage.get_mut(&tile_key).unwrap();
+    tile.data.water_mask_texture = Some(texture);
+    tile.data.water_mask_translation_and_scale = translation_and_scale;
 }
 pub fn process_terrain_state_machine_system(
     mut finished_jobs: FinishedJobs,
     mut primitive: ResMut<QuadtreePrimitive>,
 ) {
+    let primitive = &mut *primitive;
+    while let Some(res) = finished_jobs.take_next::<RequestTileGeometryJob>() {
+        let response = match res.result {
+            Ok(response) => response,
+            Err(RequestTileGeometryError::NotAvailable) => {
+                if let Some(tile) = primitive.storage.get_mut(&res.key) {
+                    tile.data.terrain_state = TerrainState::FAILED;
+                }
+                continue;
+            }
+            Err(RequestTileGeometryError::Failed(error)) => {
+                bevy::log::warn!("failed to request terrain tile {:?}: {}", res.key, error);
+                if let Some(tile) = primitive.storage.get_mut(&res.key) {
+                    tile.data.terrain_request_retries += 1;
+                    tile.data.terrain_state =
+                        if tile.data.terrain_request_retries < MAXIMUM_TERRAIN_REQUEST_RETRIES {
+                            TerrainState::UNLOADED
+                        } else {
+                            TerrainState::FAILED
+                        };
+                }
+                continue;
+            }
+        };
+        // 即使瓦片已经被卸载，metadata中的可用范围也要记录下来
+        if let Some(metadata) = response.metadata.as_ref() {
+            primitive
+                .tile_provider
+                .get_terrain_provider_mut()
+                .handle_tile_metadata(&res.key, metadata);
+        }
+        if let Some(tile) = primitive.storage.get_mut(&res.key) {
+            tile.data.set_terrain_data(response.data);
+        }
+    }
     while let Some(result) = finished_jobs.take_next::<CreateTileJob>() {
         if let Ok(res) = result {
-            let tile = primitive.storage.get_mut(&res.key).unwrap();
-            tile.data.terrain_state = TerrainState::TRANSFORMED;
+            if let Some(tile) = primitive.storage.get_mut(&res.key) {
+                tile.data.terrain_state = TerrainState::TRANSFORMED;
+            }
         }
     }
     while let Some(result) = finished_jobs.take_next::<UpsampleJob>() {
         if let Ok(res) = result {
-            let tile = primitive.storage.get_mut(&res.key).unwrap();
-
+            let Some(tile) = primitive.storage.get_mut(&res.key) else {
+                continue;
+            };
             if let Some(new_terrain_data) = res.terrain_data {
                 tile.data.set_terrain_data(new_terrain_data);
             } else {
@@ -119,6 +119,12 @@ impl GlobeSurfaceTileProvider {
     pub fn get_tiling_scheme(&self) -> &GeographicTilingScheme {
         return self.terrain_provider.get_tiling_scheme();
     }
+    pub fn get_terrain_provider(&self) -> &Box<dyn TerrainProvider> {
+        return &self.terrain_provider;
+    }
+    pub fn get_terrain_provider_mut(&mut self) -> &mut Box<dyn TerrainProvider> {
+        return &mut self.terrain_provider;
+    }
     pub fn compute_tile_load_priority(
         &mut self,
         tile: &mut QuadtreeTile,
@@ -439,7 +445,7 @@ pub fn update_tile_bounding_region(
     if tile.data.has_mesh() {
         let cloned = tile.data.get_cloned_terrain_data();
         let terrain_data = cloned.lock().unwrap();
-        let mesh = terrain_data._mesh.as_ref().unwrap();
+        let mesh = terrain_data.get_mesh().unwrap();
         if let (Some(min), Some(max)) = (mesh.minimum_height, mesh.maximum_height) {
             let tile_bounding_region = tile.data.tile_bounding_region.as_mut().unwrap();
             tile_bounding_region.minimum_height = min;
@@ -460,7 +466,7 @@ pub fn update_tile_bounding_region(
             if ancestor_surface_tile.has_mesh() {
                 let cloned = ancestor_surface_tile.get_cloned_terrain_data();
                 let terrain_data = cloned.lock().unwrap();
-                let mesh = terrain_data._mesh.as_ref().unwrap();
+                let mesh = terrain_data.get_mesh().unwrap();
 
                 map.insert(
                     tile_key,
@@ -492,7 +498,7 @@ pub fn update_tile_bounding_region(
                 if !tile.data.bounding_volume_is_from_mesh {
                     let cloned = tile.data.get_cloned_terrain_data();
                     let terrain_data = cloned.lock().unwrap();
-                    let mesh = terrain_data._mesh.as_ref().unwrap();
+                    let mesh = terrain_data.get_mesh().unwrap();
                     let tile_bounding_region = tile.data.tile_bounding_region.as_mut().unwrap();
 
                     tile_bounding_region.oriented_bounding_box =
@@ -550,7 +556,7 @@ pub fn update_tile_bounding_region(
             if !tile.data.bounding_volume_is_from_mesh {
                 let cloned = tile.data.get_cloned_terrain_data();
                 let terrain_data = cloned.lock().unwrap();
-                let mesh = terrain_data._mesh.as_ref().unwrap();
+                let mesh = terrain_data.get_mesh().unwrap();
                 let tile_bounding_region = tile.data.tile_bounding_region.as_mut().unwrap();
                 tile_bounding_region.oriented_bounding_box =
                     Some(mesh.oriented_bounding_box.clone());
@@ -1,12 +1,19 @@
-use bevy::prelude::{AssetServer, Handle, Image};
-use houtu_scene::{GeographicTilingScheme, Rectangle, TilingScheme};
+use bevy::prelude::{AssetServer, Event, Handle, Image};
+use houtu_jobs::{AsyncReturn, Context, Job};
+use houtu_scene::{Cartographic, Rectangle, TilingScheme};
 
 use super::credit::Credit;
 use super::tile_key::TileKey;
 pub trait ImageryProvider: Send + Sync {
     fn get_tile_credits(&self, key: &TileKey) -> Option<Vec<Credit>>;
     fn request_image(&self, key: &TileKey, asset_server: &AssetServer) -> Option<Handle<Image>>;
-    fn pick_features(&self, key: &TileKey, longitude: f64, latitude: f64);
+    /// 拾取瓦片中某个位置(弧度)的要素，返回的任务需要通过JobSpawner执行，不支持拾取时返回None
+    fn pick_features(
+        &self,
+        key: &TileKey,
+        longitude: f64,
+        latitude: f64,
+    ) -> Option<PickFeaturesJob>;
     fn load_image(&self, url: String);
     fn get_tile_width(&self) -> u32;
     fn get_tile_height(&self) -> u32;
@@ -16,3 +23,54 @@ pub trait ImageryProvider: Send + Sync {
     fn get_minimum_level(&self) -> u32;
     fn get_tiling_scheme(&self) -> &Box<dyn TilingScheme>;
 }
+/// 从影像服务拾取到的要素
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageryLayerFeatureInfo {
+    pub name: Option<String>,
+    /// html格式的描述
+    pub description: Option<String>,
+    pub position: Option<Cartographic>,
+    /// 要素的原始属性
+    pub data: serde_json::Value,
+}
+/// 各个ImageryProvider的具体拾取请求，在任务中执行
+pub trait PickFeaturesRequest: Send + Sync {
+    fn perform(self: Box<Self>) -> AsyncReturn<Result<Vec<ImageryLayerFeatureInfo>, String>>;
+}
+pub struct PickFeaturesJob {
+    pub key: TileKey,
+    pub request: Box<dyn PickFeaturesRequest>,
+}
+/// 左键点击地形拾取到影像要素时发送，key是影像瓦片
+#[derive(Event, Debug, Clone)]
+pub struct ImageryPickEvent {
+    pub key: TileKey,
+    pub features: Vec<ImageryLayerFeatureInfo>,
+}
+pub struct PickFeaturesJobOutcome {
+    pub key: TileKey,
+    pub result: Result<Vec<ImageryLayerFeatureInfo>, String>,
+}
+impl Job for PickFeaturesJob {
+    type Outcome = PickFeaturesJobOutcome;
+    fn name(&self) -> String {
+        format!("pick features {:?}", self.key)
+    }
+    fn perform(self, _context: Context) -> AsyncReturn<Self::Outcome> {
+        Box::pin(async move {
+            let key = self.key;
+            let fetch = self.request.perform();
+            #[cfg(not(target_arch = "wasm32"))]
+            let result = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime.block_on(fetch),
+                Err(error) => Err(error.to_string()),
+            };
+            #[cfg(target_arch = "wasm32")]
+            let result = fetch.await;
+            PickFeaturesJobOutcome { key, result }
+        })
+    }
+}
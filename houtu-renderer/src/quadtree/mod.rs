@@ -1,5 +1,5 @@
 use bevy::{core::FrameCount, prelude::*, render::renderer::RenderDevice, window::PrimaryWindow};
-use houtu_jobs::JobSpawner;
+use houtu_jobs::{FinishedJobs, JobSpawner};
 use houtu_scene::GeographicTilingScheme;
 use rand::Rng;
 
@@ -9,6 +9,7 @@ use self::{
     globe_surface_tile::process_terrain_state_machine_system,
     imagery_layer::ImageryLayer,
     imagery_layer_storage::ImageryLayerStorage,
+    imagery_provider::{ImageryPickEvent, PickFeaturesJob},
     imagery_storage::ImageryStorage,
     indices_and_edges_cache::IndicesAndEdgesCacheArc,
     quadtree_primitive::QuadtreePrimitive,
@@ -19,7 +20,7 @@ use self::{
 };
 
 use super::{
-    camera::GlobeCamera,
+    camera::{GlobeCamera, GlobeClickEvent},
     wmts_imagery_provider::{WMTSImageryProvider, WMTSImageryProviderOptions},
 };
 
@@ -40,6 +41,7 @@ pub mod quadtree_tile;
 pub mod quadtree_tile_storage;
 pub mod reproject_texture;
 // pub mod terrain_datasource;
+pub mod terrain_data;
 pub mod terrain_provider;
 pub mod texture_minification_filter;
 pub mod tile_availability;
@@ -49,6 +51,7 @@ pub mod tile_replacement_queue;
 pub mod tile_selection_result;
 pub mod traversal_details;
 pub mod upsample_job;
+pub mod water_mask;
 pub struct Plugin;
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
@@ -59,9 +62,12 @@ impl bevy::prelude::Plugin for Plugin {
         app.insert_resource(AllTraversalQuadDetails::new());
         app.insert_resource(IndicesAndEdgesCacheArc::new());
         app.insert_resource(ImageryStorage::new());
+        app.add_systems(Startup, water_mask::setup_water_mask_textures);
         app.add_systems(Update,render_system);
         app.add_systems(Update,process_terrain_state_machine_system.after(render_system));
         app.add_systems(Update,imagery_layer::finish_reproject_texture_system);
+        app.add_event::<ImageryPickEvent>();
+        app.add_systems(Update,(imagery_pick_system, imagery_pick_outcome_system));
     }
 }
 
@@ -113,3 +119,35 @@ fn render_system(
         &mut imagery_storage,
     );
 }
+/// 左键点击地形时拾取各影像图层的要素
+fn imagery_pick_system(
+    primitive: Res<QuadtreePrimitive>,
+    imagery_layer_storage: Res<ImageryLayerStorage>,
+    mut click_reader: EventReader<GlobeClickEvent>,
+    mut job_spawner: JobSpawner,
+) {
+    for event in click_reader.iter() {
+        if event.button != MouseButton::Left {
+            continue;
+        }
+        let jobs = primitive.pick_imagery_layer_features(&event.result, &imagery_layer_storage);
+        for job in jobs {
+            job_spawner.spawn(job);
+        }
+    }
+}
+fn imagery_pick_outcome_system(
+    mut finished_jobs: FinishedJobs,
+    mut pick_writer: EventWriter<ImageryPickEvent>,
+) {
+    while let Some(outcome) = finished_jobs.take_next::<PickFeaturesJob>() {
+        match outcome.result {
+            Ok(features) if !features.is_empty() => pick_writer.send(ImageryPickEvent {
+                key: outcome.key,
+                features,
+            }),
+            Ok(_) => {}
+            Err(error) => warn!("pick features {:?} failed: {}", outcome.key, error),
+        }
+    }
+}
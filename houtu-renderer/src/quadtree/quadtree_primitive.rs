@@ -2,6 +2,7 @@ use std::cmp::Ordering;
 
 use bevy::{
     core::FrameCount,
+    math::DVec3,
     prelude::{AssetServer, Assets, Image, Res, Resource},
     render::renderer::RenderDevice,
     time::Time,
@@ -9,14 +10,17 @@ use bevy::{
 };
 use houtu_jobs::JobSpawner;
 use houtu_scene::{
-    Cartographic, Ellipsoid, EllipsoidalOccluder, GeographicTilingScheme, Matrix4, Rectangle,
+    Cartographic, Ellipsoid, EllipsoidalOccluder, GeographicTilingScheme, IntersectionTests,
+    Matrix4, Ray, Rectangle,
 };
 
 use crate::camera::GlobeCamera;
 
 use super::{
     globe_surface_tile_provider::{GlobeSurfaceTileProvider, TileVisibility},
+    imagery_layer::{ImageryLayer, ImageryLayerId},
     imagery_layer_storage::ImageryLayerStorage,
+    imagery_provider::PickFeaturesJob,
     imagery_storage::ImageryStorage,
     indices_and_edges_cache::IndicesAndEdgesCacheArc,
     quadtree_primitive_debug::QuadtreePrimitiveDebug,
@@ -29,6 +33,14 @@ use super::{
     tile_selection_result::TileSelectionResult,
     traversal_details::{AllTraversalQuadDetails, RootTraversalDetails, TraversalDetails},
 };
+/// 拾取到的地形位置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobePickResult {
+    /// 地心地固坐标
+    pub position: DVec3,
+    pub cartographic: Cartographic,
+    pub tile_key: TileKey,
+}
 #[derive(Resource)]
 pub struct QuadtreePrimitive {
     pub tile_cache_size: u32,
@@ -91,6 +103,86 @@ impl QuadtreePrimitive {
     pub fn get_tiling_scheme(&self) -> &GeographicTilingScheme {
         return self.tile_provider.get_tiling_scheme();
     }
+    /// 运行中添加影像图层，已经加载的瓦片也创建这个图层的影像，对应Cesium的GlobeSurfaceTileProvider._onLayerAdded
+    pub fn add_imagery_layer(
+        &mut self,
+        mut imagery_layer: ImageryLayer,
+        imagery_layer_storage: &mut ImageryLayerStorage,
+        imagery_storage: &mut ImageryStorage,
+    ) -> ImageryLayerId {
+        if imagery_layer.show {
+            self.create_tile_imagery_skeletons(&mut imagery_layer, imagery_storage);
+        }
+        let id = imagery_layer.id.clone();
+        imagery_layer_storage.add(imagery_layer);
+        return id;
+    }
+    /// 删除影像图层，瓦片上属于这个图层的影像一起释放，对应Cesium的GlobeSurfaceTileProvider._onLayerRemoved
+    pub fn remove_imagery_layer(
+        &mut self,
+        id: &ImageryLayerId,
+        imagery_layer_storage: &mut ImageryLayerStorage,
+        imagery_storage: &mut ImageryStorage,
+    ) {
+        self.free_tile_imagery(id, imagery_storage);
+        imagery_layer_storage.remove(id);
+    }
+    /// 显示或隐藏影像图层，隐藏时释放瓦片上的影像，显示时重新创建，对应Cesium的_onLayerShownOrHidden
+    pub fn set_imagery_layer_show(
+        &mut self,
+        id: &ImageryLayerId,
+        show: bool,
+        imagery_layer_storage: &mut ImageryLayerStorage,
+        imagery_storage: &mut ImageryStorage,
+    ) {
+        let Some(imagery_layer) = imagery_layer_storage.get_mut(id) else {
+            return;
+        };
+        if imagery_layer.show == show {
+            return;
+        }
+        imagery_layer.show = show;
+        if show {
+            self.create_tile_imagery_skeletons(imagery_layer, imagery_storage);
+        } else {
+            self.free_tile_imagery(id, imagery_storage);
+        }
+    }
+    fn create_tile_imagery_skeletons(
+        &mut self,
+        imagery_layer: &mut ImageryLayer,
+        imagery_storage: &mut ImageryStorage,
+    ) {
+        let terrain_provider = self.tile_provider.get_terrain_provider();
+        for tile in self.storage.iter_mut() {
+            if tile.state == QuadtreeTileLoadState::START {
+                continue;
+            }
+            if imagery_layer._create_tile_imagery_skeletons(
+                tile,
+                terrain_provider,
+                imagery_storage,
+                None,
+            ) {
+                tile.state = QuadtreeTileLoadState::LOADING;
+            }
+        }
+    }
+    fn free_tile_imagery(&mut self, id: &ImageryLayerId, imagery_storage: &mut ImageryStorage) {
+        for tile in self.storage.iter_mut() {
+            let mut i = tile.data.imagery.len();
+            while i > 0 {
+                i -= 1;
+                let tile_imagery = &tile.data.imagery[i];
+                let imagery_key = tile_imagery
+                    .loading_imagery
+                    .or(tile_imagery.ready_imagery);
+                if imagery_key.map_or(false, |v| v.layer_id == *id) {
+                    tile.data.remove_imagery(i).free_resources(imagery_storage);
+                }
+            }
+        }
+    }
     fn clear_tile_load_queue(&mut self) {
         self.tile_load_queue_high.clear();
         self.tile_load_queue_medium.clear();
@@ -231,6 +323,132 @@ impl QuadtreePrimitive {
         );
         update_tile_load_progress_system(self);
     }
+    /// 射线与当前渲染瓦片地形的最近交点，先用瓦片网格的包围球筛选
+    pub fn pick(&self, ray: &Ray) -> Option<GlobePickResult> {
+        let mut candidates: Vec<(f64, TileKey)> = vec![];
+        for key in self.tiles_to_render.iter() {
+            let Some(tile) = self.storage.get(key) else {
+                continue;
+            };
+            let Some(terrain_data) = tile.data.terrain_data.as_ref() else {
+                continue;
+            };
+            let terrain_data = terrain_data.lock().unwrap();
+            let Some(mesh) = terrain_data.get_mesh() else {
+                continue;
+            };
+            if let Some(interval) = IntersectionTests::ray_sphere(ray, &mesh.bounding_sphere_3d) {
+                candidates.push((interval.start, *key));
+            }
+        }
+        candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        let mut result: Option<(f64, DVec3, TileKey)> = None;
+        for (start, key) in candidates {
+            // 包围球比已有的交点还远，不可能更近
+            if let Some((distance, _, _)) = result {
+                if start > distance {
+                    break;
+                }
+            }
+            let tile = self.storage.get(&key).unwrap();
+            let Some(position) = tile.data.pick(ray, true) else {
+                continue;
+            };
+            let distance = position.distance(ray.origin);
+            if result.map_or(true, |v| distance < v.0) {
+                result = Some((distance, position, key));
+            }
+        }
+        let (_, position, tile_key) = result?;
+        let cartographic = Ellipsoid::WGS84.cartesian_to_cartographic(&position)?;
+        return Some(GlobePickResult {
+            position,
+            cartographic,
+            tile_key,
+        });
+    }
+    /// 已加载地形在该经纬度处的高度，对应Cesium的Globe.getHeight
+    pub fn get_height(&self, cartographic: &Cartographic) -> Option<f64> {
+        let mut tile = self
+            .storage
+            .root
+            .iter()
+            .filter_map(|key| self.storage.get(key))
+            .find(|tile| tile.rectangle.contains(cartographic))?;
+        // 找到包含该位置的最深一级渲染瓦片
+        while TileSelectionResult::originalResult(&tile.last_selection_result)
+            == TileSelectionResult::REFINED as u8
+        {
+            let child = [
+                &tile.southwest,
+                &tile.southeast,
+                &tile.northwest,
+                &tile.northeast,
+            ]
+            .into_iter()
+            .filter_map(|key| key.as_ref().and_then(|key| self.storage.get(key)))
+            .find(|child| child.rectangle.contains(cartographic));
+            match child {
+                Some(v) => tile = v,
+                None => break,
+            }
+        }
+        let ellipsoid = Ellipsoid::WGS84;
+        let surface = Cartographic::new(cartographic.longitude, cartographic.latitude, 0.0);
+        let cartesian = ellipsoid.cartographic_to_cartesian(&surface);
+        let normal = ellipsoid.geodetic_surface_normal_cartographic(&surface);
+        // 从最低的地形以下沿法线向上求交
+        let minimum_height = tile
+            .data
+            .tile_bounding_region
+            .as_ref()
+            .map(|v| v.minimum_height)
+            .unwrap_or(0.0)
+            .min(-11500.0);
+        let origin = cartesian - normal * (minimum_height.abs() + 1.0);
+        let intersection = tile.data.pick(&Ray::new(origin, normal), false)?;
+        return ellipsoid
+            .cartesian_to_cartographic(&intersection)
+            .map(|v| v.height);
+    }
+    /// 拾取地形位置处的影像要素，对应Cesium的ImageryLayerCollection.pickImageryLayerFeatures，
+    /// 从上层到下层返回各个图层的拾取任务，需要通过JobSpawner执行
+    pub fn pick_imagery_layer_features(
+        &self,
+        pick: &GlobePickResult,
+        imagery_layer_storage: &ImageryLayerStorage,
+    ) -> Vec<PickFeaturesJob> {
+        let Some(tile) = self.storage.get(&pick.tile_key) else {
+            return vec![];
+        };
+        let position = &pick.cartographic;
+        let mut jobs = vec![];
+        for tile_imagery in tile.data.imagery.iter().rev() {
+            let Some(imagery_key) = tile_imagery.ready_imagery.as_ref() else {
+                continue;
+            };
+            let Some(imagery_layer) = imagery_layer_storage.get(&imagery_key.layer_id) else {
+                continue;
+            };
+            if !imagery_layer.show || imagery_layer.alpha == 0.0 {
+                continue;
+            }
+            // 一个地形瓦片对应多个影像瓦片，只拾取包含该位置的那个
+            let key = &imagery_key.key;
+            let provider = &imagery_layer.imagery_provider;
+            let rectangle = provider
+                .get_tiling_scheme()
+                .tile_x_y_to_rectange(key.x, key.y, key.level);
+            if !rectangle.contains(position) {
+                continue;
+            }
+            if let Some(job) = provider.pick_features(key, position.longitude, position.latitude) {
+                jobs.push(job);
+            }
+        }
+        return jobs;
+    }
 }
 
 fn process_tile_load_queue(
@@ -42,6 +42,9 @@ impl QuadtreeTileStorage {
     pub fn get_mut(&mut self, k: &TileKey) -> Option<&mut QuadtreeTile> {
         return self.map.get_mut(k);
     }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut QuadtreeTile> {
+        return self.map.values_mut();
+    }
     pub fn get_children_mut(
         &mut self,
         parent_key: &TileKey,
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+
+use houtu_scene::{GeographicTilingScheme, HeightmapTerrainData, IndicesAndEdgesCache, TerrainMesh};
+
+use crate::quantized_mesh_terrain_data::QuantizedMeshTerrainData;
+
+/// 地形瓦片数据，可以是高度图也可以是quantized-mesh
+pub enum TerrainData {
+    Heightmap(HeightmapTerrainData),
+    QuantizedMesh(QuantizedMeshTerrainData),
+}
+impl From<HeightmapTerrainData> for TerrainData {
+    fn from(value: HeightmapTerrainData) -> Self {
+        Self::Heightmap(value)
+    }
+}
+impl From<QuantizedMeshTerrainData> for TerrainData {
+    fn from(value: QuantizedMeshTerrainData) -> Self {
+        Self::QuantizedMesh(value)
+    }
+}
+impl TerrainData {
+    pub fn get_mesh(&self) -> Option<&TerrainMesh> {
+        match self {
+            Self::Heightmap(data) => data.get_mesh(),
+            Self::QuantizedMesh(data) => data.get_mesh(),
+        }
+    }
+    pub fn has_mesh(&self) -> bool {
+        match self {
+            Self::Heightmap(data) => data.has_mesh(),
+            Self::QuantizedMesh(data) => data.has_mesh(),
+        }
+    }
+    pub fn can_upsample(&self) -> bool {
+        match self {
+            Self::Heightmap(data) => data.can_upsample(),
+            Self::QuantizedMesh(data) => data.can_upsample(),
+        }
+    }
+    pub fn is_child_available(&self, this_x: u32, this_y: u32, child_x: u32, child_y: u32) -> bool {
+        match self {
+            Self::Heightmap(data) => data.is_child_available(this_x, this_y, child_x, child_y),
+            Self::QuantizedMesh(data) => {
+                data.is_child_available(this_x, this_y, child_x, child_y)
+            }
+        }
+    }
+    pub fn was_created_by_upsampling(&self) -> bool {
+        match self {
+            Self::Heightmap(data) => data.was_created_by_upsampling(),
+            Self::QuantizedMesh(data) => data.was_created_by_upsampling(),
+        }
+    }
+    pub fn get_water_mask(&self) -> Option<&Vec<u8>> {
+        match self {
+            Self::Heightmap(data) => data._water_mask.as_ref(),
+            Self::QuantizedMesh(data) => data.get_water_mask(),
+        }
+    }
+    pub async fn create_mesh(
+        &mut self,
+        tiling_scheme: &GeographicTilingScheme,
+        x: u32,
+        y: u32,
+        level: u32,
+        exaggeration: Option<f64>,
+        exaggeration_relative_height: Option<f64>,
+        indices_and_edges_cache: Arc<Mutex<IndicesAndEdgesCache>>,
+    ) {
+        match self {
+            Self::Heightmap(data) => {
+                data.createMesh(
+                    tiling_scheme,
+                    x,
+                    y,
+                    level,
+                    exaggeration,
+                    exaggeration_relative_height,
+                    indices_and_edges_cache,
+                )
+                .await
+            }
+            Self::QuantizedMesh(data) => data.create_mesh(
+                tiling_scheme,
+                x,
+                y,
+                level,
+                exaggeration,
+                exaggeration_relative_height,
+            ),
+        }
+    }
+    pub async fn upsample(
+        &self,
+        tiling_scheme: &GeographicTilingScheme,
+        this_x: u32,
+        this_y: u32,
+        this_level: u32,
+        descendant_x: u32,
+        descendant_y: u32,
+        descendant_level: u32,
+    ) -> Option<TerrainData> {
+        match self {
+            Self::Heightmap(data) => data
+                .upsample(
+                    tiling_scheme,
+                    this_x,
+                    this_y,
+                    this_level,
+                    descendant_x,
+                    descendant_y,
+                    descendant_level,
+                )
+                .await
+                .map(TerrainData::from),
+            Self::QuantizedMesh(data) => data
+                .upsample(
+                    tiling_scheme,
+                    this_x,
+                    this_y,
+                    this_level,
+                    descendant_x,
+                    descendant_y,
+                    descendant_level,
+                )
+                .map(TerrainData::from),
+        }
+    }
+}
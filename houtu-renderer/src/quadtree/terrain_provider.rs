@@ -1,10 +1,7 @@
+use houtu_jobs::{AsyncReturn, Context, Job};
+use houtu_scene::GeographicTilingScheme;
 
-
-use houtu_scene::{
-    GeographicTilingScheme, HeightmapTerrainData,
-};
-
-use super::{tile_availability::TileAvailability, tile_key::TileKey};
+use super::{terrain_data::TerrainData, tile_availability::TileAvailability, tile_key::TileKey};
 pub trait TerrainProvider: Send + Sync {
     fn get_tiling_scheme(&self) -> &GeographicTilingScheme;
     fn get_ready(&self) -> bool;
@@ -13,8 +10,65 @@ pub trait TerrainProvider: Send + Sync {
     fn get_availability(&mut self) -> Option<&mut TileAvailability>;
     // fn get_regular_grid_indices(&self, width: u32, height: u32);
     // fn get_regular_grid_indices_and_edge_indices(&self, width: u32, height: u32);
-    fn request_tile_geometry(&self) -> Option<HeightmapTerrainData>;
+    /// 请求瓦片的地形数据，返回的任务需要通过JobSpawner执行
+    fn request_tile_geometry(&self, key: &TileKey) -> RequestTileGeometry;
+    /// 瓦片数据中附带的metadata，一般用来补充可用范围
+    fn handle_tile_metadata(&mut self, key: &TileKey, metadata: &str);
     fn get_level_maximum_geometric_error(&self, level: u32) -> f64;
     fn load_tile_data_availability(&self, key: &TileKey) -> Option<bool>;
     fn get_tile_data_available(&self, key: &TileKey) -> Option<bool>;
 }
+pub enum RequestTileGeometry {
+    Requested(RequestTileGeometryJob),
+    /// 同时进行的请求太多，下一帧再请求
+    Throttled,
+    /// 这个瓦片没有地形数据，需要从父瓦片上采样
+    NotAvailable,
+}
+#[derive(thiserror::Error, Debug)]
+pub enum RequestTileGeometryError {
+    #[error("terrain tile is not available")]
+    NotAvailable,
+    /// 网络错误等，可以重试
+    #[error("{0}")]
+    Failed(String),
+}
+pub struct TerrainTileResponse {
+    pub data: TerrainData,
+    pub metadata: Option<String>,
+}
+/// 各个TerrainProvider的具体请求，在任务中执行
+pub trait TerrainTileRequest: Send + Sync {
+    fn perform(self: Box<Self>) -> AsyncReturn<Result<TerrainTileResponse, RequestTileGeometryError>>;
+}
+pub struct RequestTileGeometryJob {
+    pub key: TileKey,
+    pub request: Box<dyn TerrainTileRequest>,
+}
+pub struct RequestTileGeometryJobOutcome {
+    pub key: TileKey,
+    pub result: Result<TerrainTileResponse, RequestTileGeometryError>,
+}
+impl Job for RequestTileGeometryJob {
+    type Outcome = RequestTileGeometryJobOutcome;
+    fn name(&self) -> String {
+        format!("request tile geometry {:?}", self.key)
+    }
+    fn perform(self, _context: Context) -> AsyncReturn<Self::Outcome> {
+        Box::pin(async move {
+            let key = self.key;
+            let fetch = self.request.perform();
+            #[cfg(not(target_arch = "wasm32"))]
+            let result = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime.block_on(fetch),
+                Err(error) => Err(RequestTileGeometryError::Failed(error.to_string())),
+            };
+            #[cfg(target_arch = "wasm32")]
+            let result = fetch.await;
+            RequestTileGeometryJobOutcome { key, result }
+        })
+    }
+}
@@ -20,7 +20,7 @@ impl TileAvailability {
             storage: QuadtreeNodeStorage::new(),
         };
     }
-    pub fn compute_child_mask_for_tile(&mut self, level: u32, x: u32, y: u32) -> u32 {
+    pub fn compute_child_mask_for_tile(&self, level: u32, x: u32, y: u32) -> u32 {
         let child_level = level + 1;
         if child_level >= self.maximum_level {
             return 0;
@@ -48,12 +48,12 @@ impl TileAvailability {
         };
         return mask;
     }
-    pub fn is_tile_available(&mut self, level: u32, x: u32, y: u32) -> bool {
+    pub fn is_tile_available(&self, level: u32, x: u32, y: u32) -> bool {
         let retangle = self.tiling_scheme.tile_x_y_to_rectange(x, y, level);
         let center = retangle.center();
         return self.compute_maximum_level_at_position(&center) >= level as i32;
     }
-    pub fn compute_maximum_level_at_position(&mut self, position: &Cartographic) -> i32 {
+    pub fn compute_maximum_level_at_position(&self, position: &Cartographic) -> i32 {
         let mut node = None;
         for root_node_key in self.storage.root_nodes.iter() {
             let root_node = self.storage.map.get(root_node_key).unwrap();
@@ -304,7 +304,7 @@ impl QuadtreeNodeStorage {
             }
         }
     }
-    fn find_node(&mut self, level: u32, x: u32, y: u32) -> bool {
+    fn find_node(&self, level: u32, x: u32, y: u32) -> bool {
         for i in 0..self.root_nodes.len() {
             let node = self.root_nodes[i];
             if node.x == x && node.y == y && node.level == level {
@@ -314,7 +314,7 @@ impl QuadtreeNodeStorage {
         return false;
     }
     fn find_max_level_from_node(
-        &mut self,
+        &self,
         stop_node_key: Option<TileKey>,
         node_key: TileKey,
         position: &Cartographic,
@@ -3,23 +3,23 @@ use houtu_jobs::{
     AsyncReturn, Context, Job,
 };
 use houtu_scene::{
-    GeographicTilingScheme, HeightmapTerrainData,
+    GeographicTilingScheme,
 };
 use std::{
     io,
     sync::{Arc, Mutex},
 };
 
-use super::tile_key::TileKey;
+use super::{terrain_data::TerrainData, tile_key::TileKey};
 
 pub struct UpsampleJob {
-    pub terrain_data: Arc<Mutex<HeightmapTerrainData>>,
+    pub terrain_data: Arc<Mutex<TerrainData>>,
     pub tiling_scheme: GeographicTilingScheme,
     pub key: TileKey,
     pub parent_key: TileKey,
 }
 pub struct UpsampleJobOutcome {
-    pub terrain_data: Option<HeightmapTerrainData>,
+    pub terrain_data: Option<TerrainData>,
     pub key: TileKey,
 }
 
@@ -0,0 +1,77 @@
+use bevy::{
+    math::DVec4,
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use houtu_scene::Rectangle;
+
+/// 整个瓦片都是水面时共用的1x1纹理
+pub const ALL_WATER_TEXTURE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x5f3a_8c1e_92d4_47b6);
+
+pub fn setup_water_mask_textures(mut images: ResMut<Assets<Image>>) {
+    images.set_untracked(
+        ALL_WATER_TEXTURE_HANDLE,
+        create_water_mask_image(&[255]).expect("all water texture"),
+    );
+}
+/// quantized-mesh中的水面掩膜是边长为2的幂的正方形格网，按从北到南的顺序存储，
+/// 和瓦片的纹理坐标一致，直接作为纹理数据
+pub fn create_water_mask_image(water_mask: &[u8]) -> Option<Image> {
+    let size = (water_mask.len() as f64).sqrt() as usize;
+    if size == 0 || size * size != water_mask.len() {
+        return None;
+    }
+    return Some(Image::new(
+        Extent3d {
+            width: size as u32,
+            height: size as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        water_mask.to_vec(),
+        TextureFormat::R8Unorm,
+    ));
+}
+/// 上采样得到的瓦片沿用祖先瓦片的水面掩膜，计算瓦片纹理坐标到祖先掩膜纹理坐标的平移和缩放
+pub fn compute_water_mask_translation_and_scale(
+    tile_rectangle: &Rectangle,
+    source_rectangle: &Rectangle,
+) -> DVec4 {
+    let source_width = source_rectangle.compute_width();
+    let source_height = source_rectangle.compute_height();
+    return DVec4::new(
+        (tile_rectangle.west - source_rectangle.west) / source_width,
+        (source_rectangle.north - tile_rectangle.north) / source_height,
+        tile_rectangle.compute_width() / source_width,
+        tile_rectangle.compute_height() / source_height,
+    );
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_water_mask_image() {
+        let image = create_water_mask_image(&[0, 0, 255, 255]).unwrap();
+        assert!(image.texture_descriptor.size.width == 2);
+        assert!(image.texture_descriptor.format == TextureFormat::R8Unorm);
+        // 第一行在北边，保持原来的顺序
+        assert!(image.data == vec![0, 0, 255, 255]);
+        let image = create_water_mask_image(&[255]).unwrap();
+        assert!(image.data == vec![255]);
+        assert!(create_water_mask_image(&[0, 255, 0]).is_none());
+        assert!(create_water_mask_image(&[]).is_none());
+    }
+    #[test]
+    fn test_water_mask_translation_and_scale() {
+        let source = Rectangle::new(0.0, 0.0, 1.0, 1.0);
+        let north_east = Rectangle::new(0.5, 0.5, 1.0, 1.0);
+        let result = compute_water_mask_translation_and_scale(&north_east, &source);
+        assert!(result == DVec4::new(0.5, 0.0, 0.5, 0.5));
+        let south_west = Rectangle::new(0.0, 0.0, 0.25, 0.25);
+        let result = compute_water_mask_translation_and_scale(&south_west, &source);
+        assert!(result == DVec4::new(0.0, 0.75, 0.25, 0.25));
+    }
+}
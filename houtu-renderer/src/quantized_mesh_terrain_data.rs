@@ -1,22 +1,56 @@
-use bevy::math::DVec3;
-use houtu_scene::{BoundingSphere, OrientedBoundingBox, TilingScheme};
-use quantized_mesh_decoder::{from_reader, Indices};
+use std::{collections::HashMap, f64::consts::TAU};
 
-use crate::quadtree::{terrain_provider::TerrainProvider, tile_key::TileKey};
+use bevy::math::{DVec2, DVec3};
+use houtu_scene::{
+    add_skirt_indices, eastNorthUpToFixedFrame, lerp, oct_decode, oct_encode,
+    AxisAlignedBoundingBox, BoundingSphere, Cartesian3, Cartographic, EllipsoidalOccluder,
+    GeographicTilingScheme, Intersections2D, Matrix4, OrientedBoundingBox, Rectangle,
+    TerrainEncoding, TerrainMesh, TilingScheme, WebMercatorProjection,
+};
+use quantized_mesh_decoder::Indices;
 
+use crate::quadtree::tile_key::TileKey;
+
+const MAX_SHORT: f64 = 32767.0;
+const HALF_MAX_SHORT: f64 = 16383.0;
+/// 裙边顶点向外偏移瓦片宽高的比例，避免和相邻瓦片的裙边重叠
+const SKIRT_OFFSET_PERCENTAGE: f64 = 0.0001;
+
+pub struct QuantizedMeshTerrainDataOptions {
+    /// 依次存放所有顶点的u、v、height，取值范围都是[0,32767]
+    pub quantized_vertices: Vec<u16>,
+    pub encoded_normals: Option<Vec<u8>>,
+    pub indices: Vec<u32>,
+    pub minimum_height: f64,
+    pub maximum_height: f64,
+    pub bounding_sphere: BoundingSphere,
+    pub oriented_bounding_box: OrientedBoundingBox,
+    pub horizon_occlusion_point: Option<DVec3>,
+    pub west_indices: Vec<u32>,
+    pub south_indices: Vec<u32>,
+    pub east_indices: Vec<u32>,
+    pub north_indices: Vec<u32>,
+    pub west_skirt_height: f64,
+    pub south_skirt_height: f64,
+    pub east_skirt_height: f64,
+    pub north_skirt_height: f64,
+    pub child_tile_mask: u32,
+    pub created_by_upsampling: bool,
+    pub water_mask: Option<Vec<u8>>,
+}
 pub struct QuantizedMeshTerrainData {
     quantized_vertices: Vec<u16>,
     encoded_normals: Option<Vec<u8>>,
-    indices: Indices,
-    minimum_height: f32,
-    maximum_height: f32,
+    indices: Vec<u32>,
+    minimum_height: f64,
+    maximum_height: f64,
     bounding_sphere: BoundingSphere,
     oriented_bounding_box: OrientedBoundingBox,
-    horizon_occlusion_point: DVec3,
-    west_indices: Indices,
-    south_indices: Indices,
-    east_indices: Indices,
-    north_indices: Indices,
+    horizon_occlusion_point: Option<DVec3>,
+    west_indices: Vec<u32>,
+    south_indices: Vec<u32>,
+    east_indices: Vec<u32>,
+    north_indices: Vec<u32>,
     west_skirt_height: f64,
     south_skirt_height: f64,
     east_skirt_height: f64,
@@ -24,12 +58,39 @@ pub struct QuantizedMeshTerrainData {
     child_tile_mask: u32,
     created_by_upsampling: bool,
     water_mask: Option<Vec<u8>>,
+    mesh: Option<TerrainMesh>,
 }
 impl QuantizedMeshTerrainData {
-    fn from_data(
+    pub fn new(options: QuantizedMeshTerrainDataOptions) -> Self {
+        Self {
+            quantized_vertices: options.quantized_vertices,
+            encoded_normals: options.encoded_normals,
+            indices: options.indices,
+            minimum_height: options.minimum_height,
+            maximum_height: options.maximum_height,
+            bounding_sphere: options.bounding_sphere,
+            oriented_bounding_box: options.oriented_bounding_box,
+            horizon_occlusion_point: options.horizon_occlusion_point,
+            west_indices: options.west_indices,
+            south_indices: options.south_indices,
+            east_indices: options.east_indices,
+            north_indices: options.north_indices,
+            west_skirt_height: options.west_skirt_height,
+            south_skirt_height: options.south_skirt_height,
+            east_skirt_height: options.east_skirt_height,
+            north_skirt_height: options.north_skirt_height,
+            child_tile_mask: options.child_tile_mask,
+            created_by_upsampling: options.created_by_upsampling,
+            water_mask: options.water_mask,
+            mesh: None,
+        }
+    }
+    pub fn from_data(
         value: quantized_mesh_decoder::QuantizedMeshTerrainData,
         tile_key: TileKey,
-        terrain_provider: &mut Box<dyn TerrainProvider>,
+        tiling_scheme: &GeographicTilingScheme,
+        skirt_height: f64,
+        child_tile_mask: u32,
     ) -> Self {
         let h = &value.header;
         let bounding_sphere = BoundingSphere::new(
@@ -40,8 +101,7 @@ impl QuantizedMeshTerrainData {
             },
             h.bounding_sphere_radius,
         );
-        let skirt_height = terrain_provider.get_level_maximum_geometric_error(tile_key.level) * 5.0;
-        let rectangle = terrain_provider.get_tiling_scheme().tile_x_y_to_rectange(
+        let rectangle = tiling_scheme.tile_x_y_to_rectange(
             tile_key.x,
             tile_key.y,
             tile_key.level,
@@ -50,35 +110,839 @@ impl QuantizedMeshTerrainData {
             &rectangle,
             Some(h.minimum_height as f64),
             Some(h.maximum_height as f64),
-            Some(&terrain_provider.get_tiling_scheme().ellipsoid),
+            Some(&tiling_scheme.ellipsoid),
         );
-        Self {
+        let horizon_occlusion_point = DVec3 {
+            x: h.horizon_occlusion_point_x,
+            y: h.horizon_occlusion_point_y,
+            z: h.horizon_occlusion_point_z,
+        };
+        let minimum_height = h.minimum_height as f64;
+        let maximum_height = h.maximum_height as f64;
+        Self::new(QuantizedMeshTerrainDataOptions {
             quantized_vertices: value.vertex_data,
             encoded_normals: value.extension.vertex_normals,
-            indices: value.triangle_indices,
-            minimum_height: value.header.minimum_height,
-            maximum_height: value.header.maximum_height,
+            indices: indices_to_vec(value.triangle_indices),
+            minimum_height,
+            maximum_height,
             bounding_sphere: bounding_sphere,
             oriented_bounding_box: oriented_bounding_box,
-            horizon_occlusion_point: DVec3 {
-                x: h.horizon_occlusion_point_x,
-                y: h.horizon_occlusion_point_y,
-                z: h.horizon_occlusion_point_z,
-            },
-            west_indices: value.west_indices,
-            south_indices: value.south_indices,
-            east_indices: value.east_indices,
-            north_indices: value.north_indices,
+            horizon_occlusion_point: Some(horizon_occlusion_point),
+            west_indices: indices_to_vec(value.west_indices),
+            south_indices: indices_to_vec(value.south_indices),
+            east_indices: indices_to_vec(value.east_indices),
+            north_indices: indices_to_vec(value.north_indices),
             west_skirt_height: skirt_height,
             south_skirt_height: skirt_height,
             east_skirt_height: skirt_height,
             north_skirt_height: skirt_height,
-            child_tile_mask: terrain_provider
-                .get_availability()
-                .unwrap()
-                .compute_child_mask_for_tile(tile_key.level, tile_key.x, tile_key.y),
+            child_tile_mask,
             created_by_upsampling: false,
             water_mask: value.extension.water_mask,
+        })
+    }
+    pub fn get_mesh(&self) -> Option<&TerrainMesh> {
+        return self.mesh.as_ref();
+    }
+    pub fn has_mesh(&self) -> bool {
+        return self.mesh.is_some();
+    }
+    /// 上采样直接使用量化后的顶点，不需要先创建网格
+    pub fn can_upsample(&self) -> bool {
+        return true;
+    }
+    pub fn get_water_mask(&self) -> Option<&Vec<u8>> {
+        return self.water_mask.as_ref();
+    }
+    pub fn get_minimum_height(&self) -> f64 {
+        return self.minimum_height;
+    }
+    pub fn get_maximum_height(&self) -> f64 {
+        return self.maximum_height;
+    }
+    pub fn is_child_available(&self, this_x: u32, this_y: u32, child_x: u32, child_y: u32) -> bool {
+        let mut bit_number = 2; // northwest child
+        if child_x != this_x * 2 {
+            bit_number += 1; // east child
+        }
+        if child_y != this_y * 2 {
+            bit_number -= 2; // south child
+        }
+        return (self.child_tile_mask & (1 << bit_number)) != 0;
+    }
+    pub fn was_created_by_upsampling(&self) -> bool {
+        return self.created_by_upsampling;
+    }
+    fn vertex_count(&self) -> usize {
+        return self.quantized_vertices.len() / 3;
+    }
+    /// 顶点的经纬度(弧度)和高度
+    fn vertex_cartographic(&self, rectangle: &Rectangle, east: f64, index: usize) -> Cartographic {
+        let vertex_count = self.vertex_count();
+        let u = self.quantized_vertices[index] as f64 / MAX_SHORT;
+        let v = self.quantized_vertices[index + vertex_count] as f64 / MAX_SHORT;
+        let height = lerp(
+            self.minimum_height,
+            self.maximum_height,
+            self.quantized_vertices[index + vertex_count * 2] as f64 / MAX_SHORT,
+        );
+        return Cartographic::new(
+            lerp(rectangle.west, east, u),
+            lerp(rectangle.south, rectangle.north, v),
+            height,
+        );
+    }
+    fn encoded_normal(&self, index: usize) -> Option<DVec2> {
+        return self.encoded_normals.as_ref().map(|normals| {
+            DVec2::new(normals[index * 2] as f64, normals[index * 2 + 1] as f64)
+        });
+    }
+    pub fn create_mesh<T: TilingScheme>(
+        &mut self,
+        tiling_scheme: &T,
+        x: u32,
+        y: u32,
+        level: u32,
+        exaggeration: Option<f64>,
+        exaggeration_relative_height: Option<f64>,
+    ) {
+        let exaggeration = exaggeration.unwrap_or(1.0);
+        let exaggeration_relative_height = exaggeration_relative_height.unwrap_or(0.0);
+        let include_geodetic_surface_normals = exaggeration != 1.0;
+        let ellipsoid = tiling_scheme.get_ellipsoid();
+        let rectangle = tiling_scheme.tile_x_y_to_rectange(x, y, level);
+        let mut east = rectangle.east;
+        if east < rectangle.west {
+            east += TAU;
         }
+        let center = self.bounding_sphere.center;
+        let from_enu = eastNorthUpToFixedFrame(&center, Some(ellipsoid));
+        let to_enu = from_enu.inverse_transformation();
+
+        let south_mercator_y = WebMercatorProjection::geodetic_latitude_to_mercator_angle(rectangle.south);
+        let north_mercator_y = WebMercatorProjection::geodetic_latitude_to_mercator_angle(rectangle.north);
+        let one_over_mercator_height = 1.0 / (north_mercator_y - south_mercator_y);
+
+        // 裙边按照固定的方向排列，和高度图生成的网格保持一致
+        let vertex_count = self.vertex_count();
+        let quantized = &self.quantized_vertices;
+        let mut west_indices_south_to_north = self.west_indices.clone();
+        west_indices_south_to_north
+            .sort_by_key(|i| quantized[*i as usize + vertex_count]);
+        let mut south_indices_east_to_west = self.south_indices.clone();
+        south_indices_east_to_west.sort_by_key(|i| std::cmp::Reverse(quantized[*i as usize]));
+        let mut east_indices_north_to_south = self.east_indices.clone();
+        east_indices_north_to_south
+            .sort_by_key(|i| std::cmp::Reverse(quantized[*i as usize + vertex_count]));
+        let mut north_indices_west_to_east = self.north_indices.clone();
+        north_indices_west_to_east.sort_by_key(|i| quantized[*i as usize]);
+
+        let edge_vertex_count = west_indices_south_to_north.len()
+            + south_indices_east_to_west.len()
+            + east_indices_north_to_south.len()
+            + north_indices_west_to_east.len();
+        let total_vertex_count = vertex_count + edge_vertex_count;
+
+        let mut cartographics: Vec<Cartographic> = Vec::with_capacity(total_vertex_count);
+        let mut uvs: Vec<DVec2> = Vec::with_capacity(total_vertex_count);
+        let mut normals: Vec<Option<DVec2>> = Vec::with_capacity(total_vertex_count);
+        for i in 0..vertex_count {
+            let cartographic = self.vertex_cartographic(&rectangle, east, i);
+            uvs.push(DVec2::new(
+                quantized[i] as f64 / MAX_SHORT,
+                1.0 - quantized[i + vertex_count] as f64 / MAX_SHORT,
+            ));
+            cartographics.push(cartographic);
+            normals.push(self.encoded_normal(i));
+        }
+        let longitude_offset = (east - rectangle.west) * SKIRT_OFFSET_PERCENTAGE;
+        let latitude_offset = (rectangle.north - rectangle.south) * SKIRT_OFFSET_PERCENTAGE;
+        let skirts = [
+            (&west_indices_south_to_north, self.west_skirt_height, -longitude_offset, 0.0),
+            (&south_indices_east_to_west, self.south_skirt_height, 0.0, -latitude_offset),
+            (&east_indices_north_to_south, self.east_skirt_height, longitude_offset, 0.0),
+            (&north_indices_west_to_east, self.north_skirt_height, 0.0, latitude_offset),
+        ];
+        for (edge_indices, skirt_height, longitude_offset, latitude_offset) in skirts.iter() {
+            for index in edge_indices.iter() {
+                let index = *index as usize;
+                let cartographic = cartographics[index];
+                cartographics.push(Cartographic::new(
+                    cartographic.longitude + longitude_offset,
+                    cartographic.latitude + latitude_offset,
+                    cartographic.height - skirt_height,
+                ));
+                uvs.push(uvs[index]);
+                normals.push(normals[index]);
+            }
+        }
+
+        let mut positions: Vec<DVec3> = Vec::with_capacity(total_vertex_count);
+        let mut minimum = DVec3::splat(f64::MAX);
+        let mut maximum = DVec3::splat(f64::MIN);
+        let mut h_min = self.minimum_height;
+        for cartographic in cartographics.iter() {
+            let position = ellipsoid.cartographic_to_cartesian(cartographic);
+            let enu = to_enu.multiply_by_point(&position);
+            minimum = enu.minimum_by_component(minimum);
+            maximum = enu.maximum_by_component(maximum);
+            h_min = h_min.min(cartographic.height);
+            positions.push(position);
+        }
+
+        let encoding = TerrainEncoding::new(
+            center,
+            Some(AxisAlignedBoundingBox::new(minimum, maximum, center)),
+            Some(h_min),
+            Some(self.maximum_height),
+            Some(from_enu),
+            self.encoded_normals.is_some(),
+            Some(true),
+            Some(include_geodetic_surface_normals),
+            Some(exaggeration),
+            Some(exaggeration_relative_height),
+        );
+        let mut vertices = vec![0.0; total_vertex_count * encoding.stride as usize];
+        let mut buffer_index: i64 = 0;
+        for i in 0..total_vertex_count {
+            let latitude = cartographics[i].latitude;
+            let web_mercator_t = (north_mercator_y
+                - WebMercatorProjection::geodetic_latitude_to_mercator_angle(latitude))
+                * one_over_mercator_height;
+            let geodetic_surface_normal = if include_geodetic_surface_normals {
+                ellipsoid.geodetic_surface_normal(&positions[i])
+            } else {
+                None
+            };
+            buffer_index = encoding.encode(
+                &mut vertices,
+                buffer_index,
+                &mut positions[i],
+                &uvs[i],
+                cartographics[i].height,
+                normals[i],
+                Some(web_mercator_t),
+                geodetic_surface_normal.as_ref(),
+            );
+        }
+
+        let index_count_without_skirts = self.indices.len();
+        let has_skirts = [
+            &west_indices_south_to_north,
+            &south_indices_east_to_west,
+            &east_indices_north_to_south,
+            &north_indices_west_to_east,
+        ]
+        .iter()
+        .all(|x| !x.is_empty());
+        let skirt_index_count = if has_skirts {
+            (edge_vertex_count - 4) * 6
+        } else {
+            0
+        };
+        let mut indices = self.indices.clone();
+        indices.resize(index_count_without_skirts + skirt_index_count, 0);
+        if skirt_index_count > 0 {
+            add_skirt_indices(
+                &west_indices_south_to_north,
+                &south_indices_east_to_west,
+                &east_indices_north_to_south,
+                &north_indices_west_to_east,
+                vertex_count as u32,
+                &mut indices,
+                index_count_without_skirts as u32,
+            );
+        }
+
+        self.mesh = Some(TerrainMesh::new(
+            center,
+            vertices,
+            indices,
+            Some(index_count_without_skirts as u32),
+            vertex_count as u32,
+            Some(self.minimum_height),
+            Some(self.maximum_height),
+            self.bounding_sphere,
+            self.horizon_occlusion_point,
+            encoding.stride,
+            self.oriented_bounding_box,
+            encoding,
+            west_indices_south_to_north,
+            south_indices_east_to_west,
+            east_indices_north_to_south,
+            north_indices_west_to_east,
+        ));
+    }
+    /// 按照子瓦片的范围裁剪三角形，得到子瓦片的地形数据，只支持相邻层级
+    pub fn upsample(
+        &self,
+        tiling_scheme: &GeographicTilingScheme,
+        this_x: u32,
+        this_y: u32,
+        this_level: u32,
+        descendant_x: u32,
+        descendant_y: u32,
+        descendant_level: u32,
+    ) -> Option<QuantizedMeshTerrainData> {
+        if descendant_level != this_level + 1 {
+            return None;
+        }
+        let is_east_child = this_x * 2 != descendant_x;
+        let is_north_child = this_y * 2 == descendant_y;
+        let (min_u, max_u) = if is_east_child {
+            (HALF_MAX_SHORT, MAX_SHORT)
+        } else {
+            (0.0, HALF_MAX_SHORT)
+        };
+        let (min_v, max_v) = if is_north_child {
+            (HALF_MAX_SHORT, MAX_SHORT)
+        } else {
+            (0.0, HALF_MAX_SHORT)
+        };
+
+        let vertex_count = self.vertex_count();
+        let has_vertex_normals = self.encoded_normals.is_some();
+        let parent = ParentBuffers {
+            u: self.quantized_vertices[0..vertex_count]
+                .iter()
+                .map(|x| *x as f64)
+                .collect(),
+            v: self.quantized_vertices[vertex_count..vertex_count * 2]
+                .iter()
+                .map(|x| *x as f64)
+                .collect(),
+            height: self.quantized_vertices[vertex_count * 2..vertex_count * 3]
+                .iter()
+                .map(|x| *x as f64)
+                .collect(),
+            normals: self
+                .encoded_normals
+                .as_ref()
+                .map(|normals| normals.iter().map(|x| *x as f64).collect()),
+        };
+
+        let mut child = ChildBuffers::default();
+        let mut vertex_map: Vec<Option<u32>> = vec![None; vertex_count];
+        for i in 0..vertex_count {
+            let u = parent.u[i];
+            let v = parent.v[i];
+            if ((is_east_child && u >= HALF_MAX_SHORT) || (!is_east_child && u <= HALF_MAX_SHORT))
+                && ((is_north_child && v >= HALF_MAX_SHORT)
+                    || (!is_north_child && v <= HALF_MAX_SHORT))
+            {
+                vertex_map[i] = Some(child.u.len() as u32);
+                child.push(u, v, parent.height[i], parent.normal(i));
+            }
+        }
+
+        let mut interpolated_map: HashMap<String, u32> = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let triangle_vertices = [
+                ClipVertex::Indexed(triangle[0] as usize),
+                ClipVertex::Indexed(triangle[1] as usize),
+                ClipVertex::Indexed(triangle[2] as usize),
+            ];
+            // 先沿东西方向的分界线裁剪
+            let clipped = Intersections2D::clip_triangle_at_axis_aligned_threshold(
+                HALF_MAX_SHORT,
+                is_east_child,
+                triangle_vertices[0].get_u(&parent),
+                triangle_vertices[1].get_u(&parent),
+                triangle_vertices[2].get_u(&parent),
+            );
+            if clipped.is_empty() {
+                continue;
+            }
+            let mut clipped_index = 0;
+            let mut clipped_triangle_vertices = Vec::with_capacity(3);
+            while clipped_triangle_vertices.len() < 3 && clipped_index < clipped.len() {
+                let (vertex, next_index) =
+                    ClipVertex::from_clip_result(&clipped, clipped_index, &triangle_vertices);
+                clipped_triangle_vertices.push(vertex);
+                clipped_index = next_index;
+            }
+            if clipped_triangle_vertices.len() < 3 {
+                continue;
+            }
+            // 再沿南北方向的分界线裁剪
+            let clipped2 = Intersections2D::clip_triangle_at_axis_aligned_threshold(
+                HALF_MAX_SHORT,
+                is_north_child,
+                clipped_triangle_vertices[0].get_v(&parent),
+                clipped_triangle_vertices[1].get_v(&parent),
+                clipped_triangle_vertices[2].get_v(&parent),
+            );
+            add_clipped_polygon(
+                &mut child,
+                &mut vertex_map,
+                &mut interpolated_map,
+                &parent,
+                &clipped2,
+                &clipped_triangle_vertices,
+            );
+            // 第一次裁剪得到四边形时，剩下的顶点和后两个顶点组成第二个三角形
+            if clipped_index < clipped.len() {
+                clipped_triangle_vertices[1] = clipped_triangle_vertices[2].clone();
+                let (vertex, _) =
+                    ClipVertex::from_clip_result(&clipped, clipped_index, &triangle_vertices);
+                clipped_triangle_vertices[2] = vertex;
+                let clipped2 = Intersections2D::clip_triangle_at_axis_aligned_threshold(
+                    HALF_MAX_SHORT,
+                    is_north_child,
+                    clipped_triangle_vertices[0].get_v(&parent),
+                    clipped_triangle_vertices[1].get_v(&parent),
+                    clipped_triangle_vertices[2].get_v(&parent),
+                );
+                add_clipped_polygon(
+                    &mut child,
+                    &mut vertex_map,
+                    &mut interpolated_map,
+                    &parent,
+                    &clipped2,
+                    &clipped_triangle_vertices,
+                );
+            }
+        }
+
+        let u_offset = if is_east_child { -MAX_SHORT } else { 0.0 };
+        let v_offset = if is_north_child { -MAX_SHORT } else { 0.0 };
+        let mut west_indices = vec![];
+        let mut south_indices = vec![];
+        let mut east_indices = vec![];
+        let mut north_indices = vec![];
+        let mut minimum_height = f64::MAX;
+        let mut maximum_height = f64::MIN;
+
+        let ellipsoid = tiling_scheme.ellipsoid;
+        let rectangle =
+            tiling_scheme.tile_x_y_to_rectange(descendant_x, descendant_y, descendant_level);
+        let mut east = rectangle.east;
+        if east < rectangle.west {
+            east += TAU;
+        }
+        let child_vertex_count = child.u.len();
+        let mut positions: Vec<DVec3> = Vec::with_capacity(child_vertex_count);
+        for i in 0..child_vertex_count {
+            let mut u = child.u[i].round();
+            if u <= min_u {
+                west_indices.push(i as u32);
+                u = 0.0;
+            } else if u >= max_u {
+                east_indices.push(i as u32);
+                u = MAX_SHORT;
+            } else {
+                u = u * 2.0 + u_offset;
+            }
+            child.u[i] = u;
+
+            let mut v = child.v[i].round();
+            if v <= min_v {
+                south_indices.push(i as u32);
+                v = 0.0;
+            } else if v >= max_v {
+                north_indices.push(i as u32);
+                v = MAX_SHORT;
+            } else {
+                v = v * 2.0 + v_offset;
+            }
+            child.v[i] = v;
+
+            let height = lerp(
+                self.minimum_height,
+                self.maximum_height,
+                child.height[i] / MAX_SHORT,
+            );
+            minimum_height = minimum_height.min(height);
+            maximum_height = maximum_height.max(height);
+            child.height[i] = height;
+
+            positions.push(ellipsoid.cartographic_to_cartesian(&Cartographic::new(
+                lerp(rectangle.west, east, u / MAX_SHORT),
+                lerp(rectangle.south, rectangle.north, v / MAX_SHORT),
+                height,
+            )));
+        }
+        if child_vertex_count == 0 {
+            return None;
+        }
+
+        let bounding_sphere = BoundingSphere::from_points(&positions);
+        let oriented_bounding_box = OrientedBoundingBox::from_rectangle(
+            &rectangle,
+            Some(minimum_height),
+            Some(maximum_height),
+            Some(&ellipsoid),
+        );
+        let occluder = EllipsoidalOccluder::new(&ellipsoid);
+        let horizon_occlusion_point = occluder
+            .compute_horizon_culling_point_possibly_under_ellipsoid(
+                &bounding_sphere.center,
+                &positions,
+                minimum_height,
+            );
+
+        let height_range = maximum_height - minimum_height;
+        let mut quantized_vertices: Vec<u16> = Vec::with_capacity(child_vertex_count * 3);
+        quantized_vertices.extend(child.u.iter().map(|x| *x as u16));
+        quantized_vertices.extend(child.v.iter().map(|x| *x as u16));
+        quantized_vertices.extend(child.height.iter().map(|x| {
+            if height_range > 0.0 {
+                (MAX_SHORT * (x - minimum_height) / height_range).round() as u16
+            } else {
+                0
+            }
+        }));
+
+        let mut shortest_skirt = self.west_skirt_height.min(self.east_skirt_height);
+        shortest_skirt = shortest_skirt.min(self.south_skirt_height);
+        shortest_skirt = shortest_skirt.min(self.north_skirt_height);
+        let west_skirt_height = if is_east_child {
+            shortest_skirt * 0.5
+        } else {
+            self.west_skirt_height
+        };
+        let south_skirt_height = if is_north_child {
+            shortest_skirt * 0.5
+        } else {
+            self.south_skirt_height
+        };
+        let east_skirt_height = if is_east_child {
+            self.east_skirt_height
+        } else {
+            shortest_skirt * 0.5
+        };
+        let north_skirt_height = if is_north_child {
+            self.north_skirt_height
+        } else {
+            shortest_skirt * 0.5
+        };
+
+        return Some(QuantizedMeshTerrainData::new(
+            QuantizedMeshTerrainDataOptions {
+                quantized_vertices,
+                encoded_normals: if has_vertex_normals {
+                    Some(child.normals.iter().map(|x| *x as u8).collect())
+                } else {
+                    None
+                },
+                indices: child.indices,
+                minimum_height,
+                maximum_height,
+                bounding_sphere,
+                oriented_bounding_box,
+                horizon_occlusion_point,
+                west_indices,
+                south_indices,
+                east_indices,
+                north_indices,
+                west_skirt_height,
+                south_skirt_height,
+                east_skirt_height,
+                north_skirt_height,
+                child_tile_mask: 0,
+                created_by_upsampling: true,
+                water_mask: None,
+            },
+        ));
+    }
+    /// 计算瓦片范围内某个经纬度(弧度)处的高度，不在任何三角形内时返回None
+    pub fn interpolate_height(
+        &self,
+        rectangle: &Rectangle,
+        longitude: f64,
+        latitude: f64,
+    ) -> Option<f64> {
+        let u = ((longitude - rectangle.west) / rectangle.compute_width()).clamp(0.0, 1.0)
+            * MAX_SHORT;
+        let v = ((latitude - rectangle.south) / rectangle.compute_height()).clamp(0.0, 1.0)
+            * MAX_SHORT;
+        let vertex_count = self.vertex_count();
+        let quantized = &self.quantized_vertices;
+        let u_at = |i: u32| quantized[i as usize] as f64;
+        let v_at = |i: u32| quantized[i as usize + vertex_count] as f64;
+        let h_at = |i: u32| quantized[i as usize + vertex_count * 2] as f64;
+        for triangle in self.indices.chunks_exact(3) {
+            let (i0, i1, i2) = (triangle[0], triangle[1], triangle[2]);
+            let barycentric = Intersections2D::compute_barycentric_coordinates(
+                u,
+                v,
+                u_at(i0),
+                v_at(i0),
+                u_at(i1),
+                v_at(i1),
+                u_at(i2),
+                v_at(i2),
+            );
+            if barycentric.x >= -1e-15 && barycentric.y >= -1e-15 && barycentric.z >= -1e-15 {
+                let quantized_height = barycentric.x * h_at(i0)
+                    + barycentric.y * h_at(i1)
+                    + barycentric.z * h_at(i2);
+                return Some(lerp(
+                    self.minimum_height,
+                    self.maximum_height,
+                    quantized_height / MAX_SHORT,
+                ));
+            }
+        }
+        return None;
+    }
+}
+fn indices_to_vec(indices: Indices) -> Vec<u32> {
+    match indices {
+        Indices::IndexData16(v) => v.into_iter().map(|x| x as u32).collect(),
+        Indices::IndexData32(v) => v,
+    }
+}
+/// 父瓦片量化后的顶点数据，法线为oct编码
+struct ParentBuffers {
+    u: Vec<f64>,
+    v: Vec<f64>,
+    height: Vec<f64>,
+    normals: Option<Vec<f64>>,
+}
+impl ParentBuffers {
+    fn normal(&self, index: usize) -> Option<DVec2> {
+        return self
+            .normals
+            .as_ref()
+            .map(|normals| DVec2::new(normals[index * 2], normals[index * 2 + 1]));
+    }
+}
+#[derive(Default)]
+struct ChildBuffers {
+    u: Vec<f64>,
+    v: Vec<f64>,
+    height: Vec<f64>,
+    normals: Vec<f64>,
+    indices: Vec<u32>,
+}
+impl ChildBuffers {
+    fn push(&mut self, u: f64, v: f64, height: f64, normal: Option<DVec2>) {
+        self.u.push(u);
+        self.v.push(v);
+        self.height.push(height);
+        if let Some(normal) = normal {
+            self.normals.push(normal.x);
+            self.normals.push(normal.y);
+        }
+    }
+}
+/// 裁剪过程中的顶点，可能是父瓦片的顶点，也可能是两个顶点之间插值得到的新顶点
+#[derive(Clone, Debug)]
+enum ClipVertex {
+    Indexed(usize),
+    Interpolated {
+        first: Box<ClipVertex>,
+        second: Box<ClipVertex>,
+        ratio: f64,
+    },
+}
+impl ClipVertex {
+    fn from_clip_result(clip_result: &[f64], index: usize, vertices: &[ClipVertex]) -> (Self, usize) {
+        if clip_result[index] != -1.0 {
+            return (vertices[clip_result[index] as usize].clone(), index + 1);
+        }
+        let vertex = ClipVertex::Interpolated {
+            first: Box::new(vertices[clip_result[index + 1] as usize].clone()),
+            second: Box::new(vertices[clip_result[index + 2] as usize].clone()),
+            ratio: clip_result[index + 3],
+        };
+        return (vertex, index + 4);
+    }
+    fn key(&self) -> String {
+        match self {
+            ClipVertex::Indexed(index) => index.to_string(),
+            ClipVertex::Interpolated {
+                first,
+                second,
+                ratio,
+            } => format!("({},{},{})", first.key(), second.key(), ratio),
+        }
+    }
+    fn get_u(&self, parent: &ParentBuffers) -> f64 {
+        match self {
+            ClipVertex::Indexed(index) => parent.u[*index],
+            ClipVertex::Interpolated {
+                first,
+                second,
+                ratio,
+            } => lerp(first.get_u(parent), second.get_u(parent), *ratio),
+        }
+    }
+    fn get_v(&self, parent: &ParentBuffers) -> f64 {
+        match self {
+            ClipVertex::Indexed(index) => parent.v[*index],
+            ClipVertex::Interpolated {
+                first,
+                second,
+                ratio,
+            } => lerp(first.get_v(parent), second.get_v(parent), *ratio),
+        }
+    }
+    fn get_h(&self, parent: &ParentBuffers) -> f64 {
+        match self {
+            ClipVertex::Indexed(index) => parent.height[*index],
+            ClipVertex::Interpolated {
+                first,
+                second,
+                ratio,
+            } => lerp(first.get_h(parent), second.get_h(parent), *ratio),
+        }
+    }
+    /// 插值得到的顶点先解码两端的法线，插值后重新编码
+    fn get_normal(&self, parent: &ParentBuffers) -> Option<DVec2> {
+        match self {
+            ClipVertex::Indexed(index) => parent.normal(*index),
+            ClipVertex::Interpolated {
+                first,
+                second,
+                ratio,
+            } => {
+                let first = first.get_normal(parent)?;
+                let second = second.get_normal(parent)?;
+                let first = oct_decode(first.x, first.y);
+                let second = oct_decode(second.x, second.y);
+                let normal = first.lerp(second, *ratio).normalize();
+                Some(oct_encode(&normal))
+            }
+        }
+    }
+}
+fn add_clipped_polygon(
+    child: &mut ChildBuffers,
+    vertex_map: &mut Vec<Option<u32>>,
+    interpolated_map: &mut HashMap<String, u32>,
+    parent: &ParentBuffers,
+    clipped: &[f64],
+    triangle_vertices: &[ClipVertex],
+) {
+    let mut polygon_indices = Vec::with_capacity(4);
+    let mut clipped_index = 0;
+    while clipped_index < clipped.len() {
+        let (vertex, next_index) =
+            ClipVertex::from_clip_result(clipped, clipped_index, triangle_vertices);
+        clipped_index = next_index;
+        let new_index = match &vertex {
+            ClipVertex::Indexed(index) if vertex_map[*index].is_some() => vertex_map[*index],
+            _ => interpolated_map.get(&vertex.key()).copied(),
+        };
+        let new_index = new_index.unwrap_or_else(|| {
+            let new_index = child.u.len() as u32;
+            child.push(
+                vertex.get_u(parent),
+                vertex.get_v(parent),
+                vertex.get_h(parent),
+                vertex.get_normal(parent),
+            );
+            interpolated_map.insert(vertex.key(), new_index);
+            new_index
+        });
+        polygon_indices.push(new_index);
+    }
+    if polygon_indices.len() == 3 {
+        child.indices.extend(polygon_indices);
+    } else if polygon_indices.len() == 4 {
+        child.indices.extend([
+            polygon_indices[0],
+            polygon_indices[1],
+            polygon_indices[2],
+            polygon_indices[0],
+            polygon_indices[2],
+            polygon_indices[3],
+        ]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 两个三角形组成的瓦片，高度从南到北由0升到100
+    fn create_terrain_data() -> QuantizedMeshTerrainData {
+        QuantizedMeshTerrainData::new(QuantizedMeshTerrainDataOptions {
+            quantized_vertices: vec![
+                0, 32767, 32767, 0, // u
+                0, 0, 32767, 32767, // v
+                0, 0, 32767, 32767, // height
+            ],
+            encoded_normals: None,
+            indices: vec![0, 1, 2, 0, 2, 3],
+            minimum_height: 0.0,
+            maximum_height: 100.0,
+            bounding_sphere: BoundingSphere::default(),
+            oriented_bounding_box: OrientedBoundingBox::default(),
+            horizon_occlusion_point: None,
+            west_indices: vec![0, 3],
+            south_indices: vec![0, 1],
+            east_indices: vec![1, 2],
+            north_indices: vec![2, 3],
+            west_skirt_height: 1.0,
+            south_skirt_height: 1.0,
+            east_skirt_height: 1.0,
+            north_skirt_height: 1.0,
+            child_tile_mask: 15,
+            created_by_upsampling: false,
+            water_mask: None,
+        })
+    }
+    #[test]
+    fn test_interpolate_height() {
+        let data = create_terrain_data();
+        let tiling_scheme = GeographicTilingScheme::default();
+        let rectangle = tiling_scheme.tile_x_y_to_rectange(0, 0, 1);
+        let height = data
+            .interpolate_height(
+                &rectangle,
+                lerp(rectangle.west, rectangle.east, 0.3),
+                lerp(rectangle.south, rectangle.north, 0.6),
+            )
+            .unwrap();
+        assert!((height - 60.0).abs() < 0.01);
+        let height = data
+            .interpolate_height(&rectangle, rectangle.west, rectangle.north)
+            .unwrap();
+        assert!((height - 100.0).abs() < 0.01);
+    }
+    #[test]
+    fn test_upsample_north_east_child() {
+        let data = create_terrain_data();
+        let tiling_scheme = GeographicTilingScheme::default();
+        let child = data
+            .upsample(&tiling_scheme, 0, 0, 1, 1, 0, 2)
+            .unwrap();
+        assert!(child.was_created_by_upsampling());
+        assert!(!child.indices.is_empty());
+        assert!(child.minimum_height >= 49.0 && child.maximum_height <= 100.0);
+        let rectangle = tiling_scheme.tile_x_y_to_rectange(1, 0, 2);
+        // 对应父瓦片中v=0.85的位置
+        let height = child
+            .interpolate_height(
+                &rectangle,
+                lerp(rectangle.west, rectangle.east, 0.8),
+                lerp(rectangle.south, rectangle.north, 0.7),
+            )
+            .unwrap();
+        assert!((height - 85.0).abs() < 0.1);
+        assert!(data.upsample(&tiling_scheme, 0, 0, 1, 2, 0, 3).is_none());
+    }
+    #[test]
+    fn test_create_mesh_with_skirts() {
+        let mut data = create_terrain_data();
+        let tiling_scheme = GeographicTilingScheme::default();
+        let rectangle = tiling_scheme.tile_x_y_to_rectange(0, 0, 1);
+        let center = tiling_scheme
+            .ellipsoid
+            .cartographic_to_cartesian(&rectangle.center());
+        data.bounding_sphere = BoundingSphere::new(center, 1.0e7);
+        data.create_mesh(&tiling_scheme, 0, 0, 1, None, None);
+        let mesh = data.get_mesh().unwrap();
+        // 4个顶点加上每条边2个裙边顶点
+        assert!(mesh.vertices.len() == 12 * mesh.encoding.stride as usize);
+        assert!(mesh.index_count_without_skirts == Some(6));
+        assert!(mesh.indices.len() == 6 + 4 * 6);
+        assert!(mesh.west_indices_south_to_north == vec![0, 3]);
+        assert!(mesh.south_indices_east_to_west == vec![1, 0]);
+        assert!(mesh.east_indices_north_to_south == vec![2, 1]);
+        assert!(mesh.north_indices_west_to_east == vec![3, 2]);
     }
 }
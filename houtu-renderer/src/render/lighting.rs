@@ -0,0 +1,51 @@
+use bevy::{prelude::*, render::extract_resource::ExtractResource};
+use houtu_scene::compute_sun_direction_in_fixed_frame;
+
+/// 模拟时间，太阳的位置由它决定
+#[derive(Resource)]
+pub struct SimulationClock {
+    /// 自1970-01-01T00:00:00Z以来的秒数(UTC)
+    pub current_time: f64,
+    /// 时间流逝的倍数，0表示暂停
+    pub multiplier: f64,
+}
+impl Default for SimulationClock {
+    fn default() -> Self {
+        let current_time = instant::SystemTime::now()
+            .duration_since(instant::SystemTime::UNIX_EPOCH)
+            .map(|x| x.as_secs_f64())
+            .unwrap_or(0.0);
+        Self {
+            current_time,
+            multiplier: 1.0,
+        }
+    }
+}
+/// 地形光照的参数，会提取到渲染世界中
+#[derive(Resource, Clone, ExtractResource)]
+pub struct SceneLighting {
+    pub enable_lighting: bool,
+    /// 地固坐标系中指向太阳的单位向量
+    pub sun_direction_wc: Vec3,
+    pub lambert_diffuse_multiplier: f32,
+    /// 背光面的最低亮度
+    pub vertex_shadow_darkness: f32,
+}
+impl Default for SceneLighting {
+    fn default() -> Self {
+        Self {
+            enable_lighting: false,
+            sun_direction_wc: Vec3::X,
+            lambert_diffuse_multiplier: 0.9,
+            vertex_shadow_darkness: 0.3,
+        }
+    }
+}
+pub fn update_scene_lighting_system(
+    time: Res<Time>,
+    mut clock: ResMut<SimulationClock>,
+    mut lighting: ResMut<SceneLighting>,
+) {
+    clock.current_time += time.delta_seconds_f64() * clock.multiplier;
+    lighting.sun_direction_wc = compute_sun_direction_in_fixed_frame(clock.current_time).as_vec3();
+}
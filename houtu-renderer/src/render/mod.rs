@@ -3,7 +3,7 @@ use bevy::{
     math::{DMat4, DVec2, DVec3, Vec4Swizzles},
     prelude::*,
     reflect::List,
-    render::renderer::RenderDevice,
+    render::{extract_resource::ExtractResourcePlugin, renderer::RenderDevice},
     window::PrimaryWindow,
 };
 use houtu_jobs::JobSpawner;
@@ -31,6 +31,7 @@ use super::quadtree::{
     tile_key::TileKey,
     traversal_details::{AllTraversalQuadDetails, RootTraversalDetails},
 };
+pub mod lighting;
 mod node_atlas;
 mod node_atlas_render;
 mod terrain_data;
@@ -47,6 +48,10 @@ pub struct Plugin;
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(terrain_render_pipeline::TerrainRenderPlugin);
+        app.init_resource::<lighting::SimulationClock>();
+        app.init_resource::<lighting::SceneLighting>();
+        app.add_plugins(ExtractResourcePlugin::<lighting::SceneLighting>::default());
+        app.add_systems(Update, lighting::update_scene_lighting_system);
         app.add_systems(Startup, setup);
         app.add_systems(
             Update,
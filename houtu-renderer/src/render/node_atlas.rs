@@ -31,4 +31,6 @@ pub struct ShaderDefines {
     pub apply_color_to_alpha: bool,
     pub apply_quantization_bits12: bool,
     pub apply_webmercator_t: bool,
+    pub show_reflective_ocean: bool,
+    pub enable_lighting: bool,
 }
@@ -38,6 +38,10 @@ pub struct TerrainConfig {
     pub mvp: Mat4,
     pub attachments: Vec<AtlasAttachment>,
     pub tile_key: TileKey,
+    /// 水面掩膜，None表示整个瓦片都是陆地
+    pub water_mask: Option<Handle<Image>>,
+    pub water_mask_translation_and_scale: Vec4,
+    pub camera_position: Vec3,
 }
 impl TerrainConfig {
     pub fn count(&self) -> u32 {
@@ -121,7 +125,7 @@ impl TerrainBundle {
                 continue;
             }
             let imagery_layer = imagery_layer_opt.expect("expect imagery layer of imagery"); //
-            if imagery_layer.alpha == 0.0 {
+            if !imagery_layer.show || imagery_layer.alpha == 0.0 {
                 continue;
             }
             let imagery = imagery_opt.expect("expect a imagery");
@@ -176,6 +180,9 @@ impl TerrainBundle {
             has_web_mercator_t: terrain_mesh.encoding.has_web_mercator_t,
             attachments: attachments,
             tile_key: tile.key.clone(),
+            water_mask: tile.data.water_mask_texture.clone(),
+            water_mask_translation_and_scale: tile.data.water_mask_translation_and_scale.as_vec4(),
+            camera_position: globe_camera.get_position_wc().as_vec3(),
         };
         return Self {
             config: terrain_config,
@@ -188,7 +195,7 @@ impl TerrainBundle {
 }
 fn get_mvp(globe_camera: &mut GlobeCamera, rtc: &DVec3) -> DMat4 {
     let view_matrix = globe_camera.get_view_matrix();
-    let projection_matrix = globe_camera.frustum.get_projection_matrix().clone();
+    let projection_matrix = globe_camera.get_reverse_z_projection_matrix();
     // let center_eye = view_matrix.multiply_by_point(rtc);
     let mut mvp = view_matrix.clone();
     // mvp.set_translation(&center_eye);
@@ -12,12 +12,14 @@ use bevy::{
         render_asset::RenderAssets,
         render_resource::*,
         renderer::{RenderDevice, RenderQueue},
+        texture::FallbackImage,
         view::NoFrustumCulling,
         Extract, RenderApp, RenderSet,
     },
 };
 
 use super::{
+    lighting::SceneLighting,
     node_atlas::ShaderDefines, node_atlas_render::GpuNodeAtlas, terrain_data::TerrainConfig,
     terrain_render_pipeline::TerrainRenderPipeline,
 };
@@ -40,6 +42,24 @@ impl From<&TerrainConfig> for TerrainConfigUniform {
         }
     }
 }
+/// 海面高光需要的数据
+#[derive(Clone, Component)]
+pub struct TerrainOcean {
+    pub water_mask: Option<Handle<Image>>,
+    pub water_mask_translation_and_scale: Vec4,
+    pub camera_position: Vec3,
+    pub time: f32,
+}
+#[derive(Clone, Default, ShaderType)]
+struct StateUniform {
+    texture_num: i32,
+    time: f32,
+    water_mask_translation_and_scale: Vec4,
+    camera_position: Vec3,
+    lambert_diffuse_multiplier: f32,
+    sun_direction_wc: Vec3,
+    vertex_shadow_darkness: f32,
+}
 #[derive(Component)]
 pub struct TerrainBindGroup {
     pub bind_group: BindGroup,
@@ -50,6 +70,7 @@ pub fn extract_terrain_config(
     device: Res<RenderDevice>,
     mut images: ResMut<RenderAssets<Image>>,
     query: Extract<Query<(Entity, &TerrainConfig)>>,
+    time: Extract<Res<Time>>,
 ) {
     for (entity, terrain_config) in query.iter() {
         let terrain_config_uniform: TerrainConfigUniform = terrain_config.into();
@@ -62,22 +83,35 @@ pub fn extract_terrain_config(
             quantization_bits12: terrain_config.quantization_bits12,
             has_web_mercator_t: terrain_config.has_web_mercator_t,
         };
+        let terrain_ocean = TerrainOcean {
+            water_mask: terrain_config.water_mask.clone(),
+            water_mask_translation_and_scale: terrain_config.water_mask_translation_and_scale,
+            camera_position: terrain_config.camera_position,
+            time: time.elapsed_seconds_wrapped(),
+        };
         command
             .get_or_spawn(entity)
-            .insert((terrain_config_uniform, gpu_node_atlas));
+            .insert((terrain_config_uniform, gpu_node_atlas, terrain_ocean));
     }
 }
 pub fn prepare_terrain(
     mut commands: Commands,
     images: Res<RenderAssets<Image>>,
     mut queue: ResMut<RenderQueue>,
-    mut query: Query<(Entity, &TerrainConfigUniform, &mut GpuNodeAtlas)>,
+    mut query: Query<(
+        Entity,
+        &TerrainConfigUniform,
+        &mut GpuNodeAtlas,
+        &TerrainOcean,
+    )>,
     render_device: Res<RenderDevice>,
     pipeline: Res<TerrainRenderPipeline>,
+    fallback_image: Res<FallbackImage>,
+    lighting: Res<SceneLighting>,
 ) {
     let mut command_encoder =
         render_device.create_command_encoder(&CommandEncoderDescriptor::default());
-    for (entity, terrain_config_uniform, mut gpu_node_atlas) in &mut query {
+    for (entity, terrain_config_uniform, mut gpu_node_atlas, terrain_ocean) in &mut query {
         gpu_node_atlas.update(&mut command_encoder, &mut queue, &images);
         let mut buffer = encase::UniformBuffer::new(Vec::new());
         buffer.write(terrain_config_uniform).unwrap();
@@ -87,8 +121,22 @@ pub fn prepare_terrain(
             contents: &buffer.into_inner(),
         });
         let sampler = render_device.create_sampler(&SamplerDescriptor::default());
-        let (uniform_buffer, state_uniform_buffer, shader_defines) =
-            other_uniform(&gpu_node_atlas, &render_device);
+        // 水面掩膜还没有上传到GPU时先不显示海面
+        let water_mask = terrain_ocean
+            .water_mask
+            .as_ref()
+            .and_then(|handle| images.get(handle));
+        let (uniform_buffer, state_uniform_buffer, shader_defines) = other_uniform(
+            &gpu_node_atlas,
+            terrain_ocean,
+            water_mask.is_some(),
+            &lighting,
+            &render_device,
+        );
+        let water_mask_view = match water_mask {
+            Some(image) => &image.texture_view,
+            None => &fallback_image.d2.texture_view,
+        };
         let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
             label: Some("terrain_material"),
             layout: &pipeline.layout,
@@ -115,6 +163,10 @@ pub fn prepare_terrain(
                     binding: 4,
                     resource: vertex_uniform_buffer.as_entire_binding(),
                 },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(water_mask_view),
+                },
             ],
         });
         commands.entity(entity).insert(TerrainBindGroup {
@@ -126,6 +178,9 @@ pub fn prepare_terrain(
 }
 fn other_uniform(
     gpu_node_atlas: &GpuNodeAtlas,
+    terrain_ocean: &TerrainOcean,
+    show_reflective_ocean: bool,
+    lighting: &SceneLighting,
     render_device: &RenderDevice,
 ) -> (Buffer, Buffer, ShaderDefines) {
     let mut buffer_data = vec![];
@@ -162,8 +217,8 @@ fn other_uniform(
 
         // 耗费了四天时间，查这个渲染的bug，原来是这里多传个f32
         // 导致传入的数据比着色器中的TerrainMaterialUniform多了4个字节，以至于第二次及其之后的循环的数据都不对
-        // TODO alpha暂时在着色器中用不到，先不管
-        // buffer_data.push(attachment.day_alpha);
+        // 现在着色器中加了day_alpha，结构体末尾要补齐到16字节的倍数，见下面的padding
+        buffer_data.push(attachment.day_alpha);
         apply_day_night_alpha = apply_day_night_alpha || attachment.day_alpha != 1.0; //
 
         buffer_data.push(attachment.brightness);
@@ -180,18 +235,30 @@ fn other_uniform(
 
         buffer_data.push(attachment.one_over_gamma);
         apply_gamma = apply_gamma || attachment.one_over_gamma != 1.0;
+
+        // 17个f32，数组元素的步长是80字节，补齐3个f32
+        buffer_data.extend_from_slice(&[0.0; 3]);
     }
     let uniform_buffer = render_device.create_buffer_with_data(&wgpu::util::BufferInitDescriptor {
         label: Some("uniform_buffer"),
         contents: bytemuck::cast_slice(&buffer_data), //
         usage: wgpu::BufferUsages::STORAGE,           //
     });
-    let state_uniform_buffer_data = vec![gpu_node_atlas.attachments.len() as i32];
-
+    let state_uniform = StateUniform {
+        texture_num: gpu_node_atlas.attachments.len() as i32,
+        time: terrain_ocean.time,
+        water_mask_translation_and_scale: terrain_ocean.water_mask_translation_and_scale,
+        camera_position: terrain_ocean.camera_position,
+        lambert_diffuse_multiplier: lighting.lambert_diffuse_multiplier,
+        sun_direction_wc: lighting.sun_direction_wc,
+        vertex_shadow_darkness: lighting.vertex_shadow_darkness,
+    };
+    let mut state_uniform_buffer_data = encase::UniformBuffer::new(Vec::new());
+    state_uniform_buffer_data.write(&state_uniform).unwrap();
     let state_uniform_buffer =
         render_device.create_buffer_with_data(&wgpu::util::BufferInitDescriptor {
             label: Some("state_uniform_buffer"), //
-            contents: bytemuck::cast_slice(&state_uniform_buffer_data),
+            contents: &state_uniform_buffer_data.into_inner(),
             usage: wgpu::BufferUsages::UNIFORM,
         });
     let shader_defines = ShaderDefines {
@@ -207,6 +274,8 @@ fn other_uniform(
         apply_color_to_alpha,
         apply_quantization_bits12,
         apply_webmercator_t,
+        show_reflective_ocean,
+        enable_lighting: lighting.enable_lighting,
     };
     return (uniform_buffer, state_uniform_buffer, shader_defines);
 }
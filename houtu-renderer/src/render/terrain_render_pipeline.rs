@@ -228,6 +228,16 @@ fn terrain_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
                 },
                 count: None,
             },
+            BindGroupLayoutEntry {
+                binding: 5,
+                visibility: ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
         ],
     });
     return layout;
@@ -271,23 +281,32 @@ fn update_pipeline_layout(
     if data.apply_day_night_alpha {
         fragment_shader_defs.push("APPLY_DAY_NIGHT_ALPHA".into());
     }
+    if data.show_reflective_ocean {
+        fragment_shader_defs.push("SHOW_REFLECTIVE_OCEAN".into());
+    }
+    if data.enable_lighting {
+        fragment_shader_defs.push("ENABLE_LIGHTING".into());
+    }
+    let has_vertex_normals = _layout.contains(Mesh::ATTRIBUTE_NORMAL);
+    if has_vertex_normals {
+        fragment_shader_defs.push("HAS_VERTEX_NORMALS".into());
+    }
     let vertex_shader_defs = &mut _descriptor.vertex.shader_defs;
-    if data.apply_quantization_bits12 {
+    let mut attributes = if data.apply_quantization_bits12 {
         vertex_shader_defs.push("QUANTIZATION_BITS12".into());
-        let attributes = vec![
-            TerrainMeshMaterial::COMPRESSED_0.at_shader_location(0),
-            // TerrainMeshMaterial::COMPRESSED_1.at_shader_location(1),
-        ];
-        let attribute_real = _layout.get_layout(&attributes).unwrap();
-        _descriptor.vertex.buffers = vec![attribute_real];
+        vec![TerrainMeshMaterial::COMPRESSED_0.at_shader_location(0)]
     } else {
-        let mut attributes = vec![
+        vec![
             TerrainMeshMaterial::ATTRIBUTE_POSITION_HEIGHT.at_shader_location(0),
             Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
             TerrainMeshMaterial::ATTRIBUTE_WEB_MERCATOR_T.at_shader_location(2),
-        ];
-        let attribute_real = _layout.get_layout(&attributes).unwrap();
-        _descriptor.vertex.buffers = vec![attribute_real];
+        ]
+    };
+    if has_vertex_normals {
+        vertex_shader_defs.push("HAS_VERTEX_NORMALS".into());
+        attributes.push(Mesh::ATTRIBUTE_NORMAL.at_shader_location(3));
     }
+    let attribute_real = _layout.get_layout(&attributes).unwrap();
+    _descriptor.vertex.buffers = vec![attribute_real];
     return _descriptor;
 }
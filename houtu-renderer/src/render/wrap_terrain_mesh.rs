@@ -7,7 +7,9 @@ use bevy::{
         render_resource::VertexFormat,
     },
 };
-use houtu_scene::{decompress_texture_coordinates, Matrix4, TerrainMesh, TerrainQuantization};
+use houtu_scene::{
+    decompress_texture_coordinates, oct_decode_float, Matrix4, TerrainMesh, TerrainQuantization,
+};
 
 use super::terrian_material::TerrainMeshMaterial;
 
@@ -19,6 +21,16 @@ impl<'a> From<WrapTerrainMesh<'a>> for Mesh {
         let terrain_mesh = wrap_terrain_mesh.0;
         let mut mesh = Mesh::new(bevy::render::mesh::PrimitiveTopology::TriangleList);
         mesh.set_indices(Some(Indices::U32(terrain_mesh.indices.clone())));
+        // 顶点法线是oct编码后打包成的一个浮点数，解码后作为ATTRIBUTE_NORMAL
+        if terrain_mesh.encoding.has_vertex_normals {
+            let offset = terrain_mesh.encoding._offset_vertex_normal as usize;
+            let normals: Vec<[f32; 3]> = terrain_mesh
+                .vertices
+                .chunks_exact(terrain_mesh.encoding.stride as usize)
+                .map(|x| oct_decode_float(x[offset] as f64).as_vec3().to_array())
+                .collect();
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        }
         let mut positions: Vec<[f32; 4]> = Vec::new();
         let mut uvs: Vec<[f32; 2]> = Vec::new();
         let mut web_mercator_t: Vec<f32> = Vec::new();
@@ -44,7 +56,6 @@ impl<'a> From<WrapTerrainMesh<'a>> for Mesh {
                     }
                 });
             mesh.insert_attribute(TerrainMeshMaterial::ATTRIBUTE_POSITION_HEIGHT, positions);
-            // mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
             mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
             if terrain_mesh.encoding.has_web_mercator_t {
                 mesh.insert_attribute(
@@ -55,7 +66,6 @@ impl<'a> From<WrapTerrainMesh<'a>> for Mesh {
             mesh
         } else {
             let mut compressed0: Vec<[f32; 4]> = Vec::new();
-            let mut compressed1: Vec<f32> = Vec::new();
             terrain_mesh
                 .vertices
                 .iter()
@@ -72,12 +82,8 @@ impl<'a> From<WrapTerrainMesh<'a>> for Mesh {
                             0f32
                         },
                     ]);
-                    if terrain_mesh.encoding.has_vertex_normals {
-                        compressed1.push(terrain_mesh.vertices[i + 4] as f32);
-                    }
                 });
             mesh.insert_attribute(TerrainMeshMaterial::COMPRESSED_0, compressed0);
-            // mesh.insert_attribute(TerrainMeshMaterial::COMPRESSED_1, compressed1);
             mesh
         }
     }
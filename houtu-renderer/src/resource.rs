@@ -0,0 +1,186 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("request {0} failed with status {1}")]
+    Status(String, u16),
+    #[error("file urls are not supported in the browser: {0}")]
+    Unsupported(String),
+}
+
+/// `file://`开头或者没有协议的url都当作本地文件
+pub fn is_local_url(url: &str) -> bool {
+    return url.starts_with("file://") || !url.contains("://");
+}
+/// 去掉`file://`前缀以及查询参数和片段，比如瓦片地址中的`?v=1.2.0`
+pub fn to_local_path(url: &str) -> &str {
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    let end = path.find(['?', '#']).unwrap_or(path.len());
+    return &path[..end];
+}
+pub fn append_forward_slash(url: &str) -> String {
+    if url.is_empty() || url.ends_with('/') {
+        return url.to_string();
+    }
+    return format!("{}/", url);
+}
+/// 把相对地址拼接到目录地址`base`后面，`relative`本身是绝对地址时原样返回
+pub fn join_url(base: &str, relative: &str) -> String {
+    if relative.contains("://") || base.is_empty() {
+        return relative.to_string();
+    }
+    return format!(
+        "{}{}",
+        append_forward_slash(base),
+        relative.trim_start_matches("./")
+    );
+}
+/// 把参数编码后拼接到url的查询字符串后面
+pub fn append_query(url: &str, query: &[(&str, &str)]) -> String {
+    let query = query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, encode_query_value(v)))
+        .collect::<Vec<String>>()
+        .join("&");
+    let separator = if !url.contains('?') {
+        "?"
+    } else if url.ends_with('?') || url.ends_with('&') {
+        ""
+    } else {
+        "&"
+    };
+    return format!("{}{}{}", url, separator, query);
+}
+pub fn encode_query_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return result;
+}
+/// 图片MIME类型对应的扩展名，bevy根据扩展名选择AssetLoader
+pub fn format_to_extension(format: &str) -> &str {
+    let subtype = format.rsplit('/').next().unwrap_or("");
+    return match subtype {
+        "jpeg" | "jpg" => "jpg",
+        "" => "png",
+        other => other,
+    };
+}
+/// 读取url对应的资源，支持http(s)和本地文件
+pub async fn fetch_bytes(url: &str, headers: &[(&str, &str)]) -> Result<Vec<u8>, Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if is_local_url(url) {
+            return Ok(std::fs::read(to_local_path(url))?);
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        if url.starts_with("file://") {
+            return Err(Error::Unsupported(url.to_string()));
+        }
+    }
+    let client = reqwest::Client::new();
+    let mut request = client.get(url);
+    for (key, value) in headers.iter() {
+        request = request.header(*key, *value);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Status(url.to_string(), status.as_u16()));
+    }
+    let bytes = response.bytes().await?;
+    return Ok(bytes.to_vec());
+}
+pub async fn fetch_text(url: &str, headers: &[(&str, &str)]) -> Result<String, Error> {
+    let bytes = fetch_bytes(url, headers).await?;
+    return Ok(String::from_utf8_lossy(&bytes).into_owned());
+}
+/// 限制同一个服务同时进行的请求数量
+#[derive(Clone, Debug)]
+pub struct RequestThrottle {
+    active: Arc<AtomicU32>,
+    maximum: u32,
+}
+impl RequestThrottle {
+    pub fn new(maximum: u32) -> Self {
+        Self {
+            active: Arc::new(AtomicU32::new(0)),
+            maximum,
+        }
+    }
+    /// 请求数量已满时返回None，返回的许可在请求结束后drop
+    pub fn try_acquire(&self) -> Option<RequestPermit> {
+        let maximum = self.maximum;
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                if x < maximum {
+                    Some(x + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| RequestPermit {
+                active: self.active.clone(),
+            })
+    }
+    pub fn get_active_requests(&self) -> u32 {
+        return self.active.load(Ordering::SeqCst);
+    }
+}
+#[derive(Debug)]
+pub struct RequestPermit {
+    active: Arc<AtomicU32>,
+}
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_url() {
+        assert!(join_url("http://localhost/terrain", "./layer.json") == "http://localhost/terrain/layer.json");
+        assert!(join_url("/data/terrain/", "0/0/0.terrain") == "/data/terrain/0/0/0.terrain");
+        assert!(join_url("http://localhost/terrain", "http://other/a.png") == "http://other/a.png");
+        assert!(is_local_url("/data/terrain"));
+        assert!(is_local_url("file:///data/terrain"));
+        assert!(!is_local_url("https://localhost/terrain"));
+        assert!(to_local_path("file:///data/terrain/0/0/0.terrain?v=1.2.0") == "/data/terrain/0/0/0.terrain");
+        assert!(to_local_path("/data/terrain/layer.json#extensions") == "/data/terrain/layer.json");
+        assert!(to_local_path("/data/terrain/layer.json") == "/data/terrain/layer.json");
+    }
+    #[test]
+    fn test_request_throttle() {
+        let throttle = RequestThrottle::new(2);
+        let first = throttle.try_acquire();
+        let second = throttle.try_acquire();
+        assert!(first.is_some() && second.is_some());
+        assert!(throttle.try_acquire().is_none());
+        drop(first);
+        assert!(throttle.get_active_requests() == 1);
+        assert!(throttle.try_acquire().is_some());
+    }
+}
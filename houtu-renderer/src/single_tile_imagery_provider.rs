@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+use houtu_scene::{GeographicTilingScheme, Rectangle, TilingScheme};
+
+use crate::quadtree::{
+    credit::Credit,
+    imagery_provider::{ImageryProvider, PickFeaturesJob},
+    tile_key::TileKey,
+};
+
+/// 覆盖一个矩形范围的单张图片，比如KML的GroundOverlay
+pub struct SingleTileImageryProvider {
+    pub tiling_scheme: Box<dyn TilingScheme>,
+    pub rectangle: Rectangle,
+    pub image: Handle<Image>,
+    pub ready: bool,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+impl SingleTileImageryProvider {
+    pub fn new(image: Handle<Image>, rectangle: Rectangle) -> Self {
+        // 只有一层，0级只有一个瓦片，瓦片范围就是图片范围
+        let tiling_scheme = GeographicTilingScheme {
+            rectangle,
+            number_of_level_zero_tiles_x: 1,
+            number_of_level_zero_tiles_y: 1,
+            ..Default::default()
+        };
+        Self {
+            tiling_scheme: Box::new(tiling_scheme),
+            rectangle,
+            image,
+            ready: true,
+            tile_width: 256,
+            tile_height: 256,
+        }
+    }
+}
+impl ImageryProvider for SingleTileImageryProvider {
+    fn get_maximum_level(&self) -> u32 {
+        0
+    }
+    fn get_minimum_level(&self) -> u32 {
+        0
+    }
+    fn get_ready(&self) -> bool {
+        self.ready
+    }
+    fn get_rectangle(&self) -> &Rectangle {
+        &self.rectangle
+    }
+    fn get_tile_credits(&self, _key: &TileKey) -> Option<Vec<Credit>> {
+        None
+    }
+    fn get_tile_height(&self) -> u32 {
+        self.tile_height
+    }
+    fn get_tile_width(&self) -> u32 {
+        self.tile_width
+    }
+    fn get_tiling_scheme(&self) -> &Box<dyn TilingScheme> {
+        &self.tiling_scheme
+    }
+    fn load_image(&self, _url: String) {}
+    fn pick_features(
+        &self,
+        _key: &TileKey,
+        _longitude: f64,
+        _latitude: f64,
+    ) -> Option<PickFeaturesJob> {
+        None
+    }
+    fn request_image(&self, key: &TileKey, _asset_server: &AssetServer) -> Option<Handle<Image>> {
+        if key.level != 0 || key.x != 0 || key.y != 0 {
+            return None;
+        }
+        return Some(self.image.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_tile() {
+        let rectangle = Rectangle::new(
+            110f64.to_radians(),
+            20f64.to_radians(),
+            112f64.to_radians(),
+            21f64.to_radians(),
+        );
+        let provider = SingleTileImageryProvider::new(Handle::default(), rectangle);
+        let tiling_scheme = provider.get_tiling_scheme();
+        assert_eq!(tiling_scheme.get_number_of_tiles_at_level(0), 1);
+        let tile_rectangle = tiling_scheme.tile_x_y_to_rectange(0, 0, 0);
+        assert!((tile_rectangle.west - rectangle.west).abs() < 1e-12);
+        assert!((tile_rectangle.east - rectangle.east).abs() < 1e-12);
+        assert!((tile_rectangle.south - rectangle.south).abs() < 1e-12);
+        assert!((tile_rectangle.north - rectangle.north).abs() < 1e-12);
+    }
+}
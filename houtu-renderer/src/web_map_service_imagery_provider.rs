@@ -0,0 +1,515 @@
+use bevy::prelude::*;
+use houtu_jobs::AsyncReturn;
+use houtu_scene::{Cartographic, GeographicTilingScheme, Rectangle, TilingScheme};
+
+use crate::{
+    quadtree::{
+        credit::Credit,
+        imagery_provider::{
+            ImageryLayerFeatureInfo, ImageryProvider, PickFeaturesJob, PickFeaturesRequest,
+        },
+        tile_key::TileKey,
+    },
+    resource::{append_query, fetch_text, format_to_extension},
+};
+
+pub struct WebMapServiceImageryProviderOptions {
+    pub url: String,
+    /// 逗号分隔的图层名
+    pub layers: String,
+    pub styles: String,
+    /// `1.1.1`或`1.3.0`，1.3.0中EPSG:4326的坐标轴顺序是纬度在前
+    pub version: String,
+    pub format: String,
+    pub transparent: bool,
+    /// 决定请求的坐标系，WebMercatorTilingScheme使用EPSG:3857，其它使用EPSG:4326
+    pub tiling_scheme: Option<Box<dyn TilingScheme>>,
+    pub rectangle: Option<Rectangle>,
+    pub minimum_level: u32,
+    pub maximum_level: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// GetMap和GetFeatureInfo请求的额外参数，和默认参数同名时覆盖默认值
+    pub parameters: Vec<(String, String)>,
+    /// 只用于GetFeatureInfo请求的额外参数
+    pub get_feature_info_parameters: Vec<(String, String)>,
+    pub enable_pick_features: bool,
+    /// GetFeatureInfo请求的INFO_FORMAT
+    pub info_format: String,
+}
+impl Default for WebMapServiceImageryProviderOptions {
+    fn default() -> Self {
+        Self {
+            url: "".to_string(),
+            layers: "".to_string(),
+            styles: "".to_string(),
+            version: "1.1.1".to_string(),
+            format: "image/png".to_string(),
+            transparent: true,
+            tiling_scheme: None,
+            rectangle: None,
+            minimum_level: 0,
+            maximum_level: 19,
+            tile_width: 256,
+            tile_height: 256,
+            parameters: vec![],
+            get_feature_info_parameters: vec![],
+            enable_pick_features: true,
+            info_format: "application/json".to_string(),
+        }
+    }
+}
+/// [WMS](https://www.ogc.org/standard/wms/)影像服务，每个瓦片用一次GetMap请求，
+/// 拾取要素使用GetFeatureInfo请求
+pub struct WebMapServiceImageryProvider {
+    pub url: String,
+    pub layers: String,
+    pub styles: String,
+    pub version: String,
+    pub format: String,
+    pub transparent: bool,
+    pub tiling_scheme: Box<dyn TilingScheme>,
+    pub rectangle: Rectangle,
+    pub minimum_level: u32,
+    pub maximum_level: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub parameters: Vec<(String, String)>,
+    pub get_feature_info_parameters: Vec<(String, String)>,
+    pub enable_pick_features: bool,
+    pub info_format: String,
+}
+impl WebMapServiceImageryProvider {
+    pub fn new(options: WebMapServiceImageryProviderOptions) -> Self {
+        let tiling_scheme = options
+            .tiling_scheme
+            .unwrap_or(Box::new(GeographicTilingScheme::default()));
+        Self {
+            url: options.url,
+            layers: options.layers,
+            styles: options.styles,
+            version: options.version,
+            format: options.format,
+            transparent: options.transparent,
+            rectangle: options.rectangle.unwrap_or(tiling_scheme.get_rectangle()),
+            tiling_scheme: tiling_scheme,
+            minimum_level: options.minimum_level,
+            maximum_level: options.maximum_level,
+            tile_width: options.tile_width,
+            tile_height: options.tile_height,
+            parameters: options.parameters,
+            get_feature_info_parameters: options.get_feature_info_parameters,
+            enable_pick_features: options.enable_pick_features,
+            info_format: options.info_format,
+        }
+    }
+    /// 版本号不低于1.3.0
+    pub fn is_version_1_3(&self) -> bool {
+        let mut numbers = self
+            .version
+            .split('.')
+            .map(|x| x.parse::<u32>().unwrap_or(0));
+        let major = numbers.next().unwrap_or(0);
+        let minor = numbers.next().unwrap_or(0);
+        return major > 1 || (major == 1 && minor >= 3);
+    }
+    pub fn is_web_mercator(&self) -> bool {
+        return self.tiling_scheme.get_name() == "WebMercatorTilingScheme";
+    }
+    pub fn get_crs(&self) -> &str {
+        return if self.is_web_mercator() {
+            "EPSG:3857"
+        } else {
+            "EPSG:4326"
+        };
+    }
+    /// 瓦片范围在请求坐标系中的BBOX，WMS 1.3.0的EPSG:4326是`南,西,北,东`
+    pub fn get_bbox(&self, key: &TileKey) -> String {
+        let rectangle = self
+            .tiling_scheme
+            .tile_x_y_to_native_rectange(key.x, key.y, key.level);
+        if self.is_version_1_3() && !self.is_web_mercator() {
+            return format!(
+                "{},{},{},{}",
+                rectangle.south, rectangle.west, rectangle.north, rectangle.east
+            );
+        }
+        return format!(
+            "{},{},{},{}",
+            rectangle.west, rectangle.south, rectangle.east, rectangle.north
+        );
+    }
+    fn get_common_parameters(&self, key: &TileKey, request: &str) -> Vec<(String, String)> {
+        let crs_name = if self.is_version_1_3() { "crs" } else { "srs" };
+        return vec![
+            ("service", "WMS".to_string()),
+            ("version", self.version.clone()),
+            ("request", request.to_string()),
+            ("layers", self.layers.clone()),
+            ("styles", self.styles.clone()),
+            ("format", self.format.clone()),
+            ("transparent", self.transparent.to_string()),
+            ("bbox", self.get_bbox(key)),
+            ("width", self.tile_width.to_string()),
+            ("height", self.tile_height.to_string()),
+            (crs_name, self.get_crs().to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    }
+    pub fn build_get_map_url(&self, key: &TileKey) -> String {
+        let mut parameters = self.get_common_parameters(key, "GetMap");
+        merge_parameters(&mut parameters, &self.parameters);
+        return append_query(&self.url, &to_query(&parameters));
+    }
+    /// 拾取位置(弧度)在瓦片图片中的像素坐标，原点在左上角
+    pub fn get_pixel(&self, key: &TileKey, longitude: f64, latitude: f64) -> (u32, u32) {
+        let rectangle = self
+            .tiling_scheme
+            .tile_x_y_to_native_rectange(key.x, key.y, key.level);
+        let position = self
+            .tiling_scheme
+            .rectangle_to_native_rectangle(&Rectangle::new(
+                longitude, latitude, longitude, latitude,
+            ));
+        let i = (position.west - rectangle.west) / (rectangle.east - rectangle.west)
+            * self.tile_width as f64;
+        let j = (rectangle.north - position.south) / (rectangle.north - rectangle.south)
+            * self.tile_height as f64;
+        return (
+            (i.floor().max(0.0) as u32).min(self.tile_width - 1),
+            (j.floor().max(0.0) as u32).min(self.tile_height - 1),
+        );
+    }
+    pub fn build_get_feature_info_url(
+        &self,
+        key: &TileKey,
+        longitude: f64,
+        latitude: f64,
+    ) -> String {
+        let (i, j) = self.get_pixel(key, longitude, latitude);
+        let mut parameters = self.get_common_parameters(key, "GetFeatureInfo");
+        let (i_name, j_name) = if self.is_version_1_3() {
+            ("i", "j")
+        } else {
+            ("x", "y")
+        };
+        parameters.push(("query_layers".to_string(), self.layers.clone()));
+        parameters.push(("info_format".to_string(), self.info_format.clone()));
+        parameters.push((i_name.to_string(), i.to_string()));
+        parameters.push((j_name.to_string(), j.to_string()));
+        merge_parameters(&mut parameters, &self.parameters);
+        merge_parameters(&mut parameters, &self.get_feature_info_parameters);
+        return append_query(&self.url, &to_query(&parameters));
+    }
+}
+/// 参数名不区分大小写，同名参数覆盖原来的值
+fn merge_parameters(parameters: &mut Vec<(String, String)>, custom: &[(String, String)]) {
+    for (key, value) in custom.iter() {
+        match parameters
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        {
+            Some(parameter) => parameter.1 = value.clone(),
+            None => parameters.push((key.clone(), value.clone())),
+        }
+    }
+}
+fn to_query(parameters: &[(String, String)]) -> Vec<(&str, &str)> {
+    return parameters
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+}
+impl ImageryProvider for WebMapServiceImageryProvider {
+    fn get_maximum_level(&self) -> u32 {
+        self.maximum_level
+    }
+    fn get_minimum_level(&self) -> u32 {
+        self.minimum_level
+    }
+    fn get_ready(&self) -> bool {
+        true
+    }
+    fn get_rectangle(&self) -> &Rectangle {
+        &self.rectangle
+    }
+    fn get_tile_credits(&self, _key: &TileKey) -> Option<Vec<Credit>> {
+        None
+    }
+    fn get_tile_height(&self) -> u32 {
+        self.tile_height
+    }
+    fn get_tile_width(&self) -> u32 {
+        self.tile_width
+    }
+    fn get_tiling_scheme(&self) -> &Box<dyn TilingScheme> {
+        &self.tiling_scheme
+    }
+    fn load_image(&self, _url: String) {}
+    fn pick_features(
+        &self,
+        key: &TileKey,
+        longitude: f64,
+        latitude: f64,
+    ) -> Option<PickFeaturesJob> {
+        if !self.enable_pick_features {
+            return None;
+        }
+        return Some(PickFeaturesJob {
+            key: *key,
+            request: Box::new(GetFeatureInfoRequest {
+                url: self.build_get_feature_info_url(key, longitude, latitude),
+                info_format: self.info_format.clone(),
+                position: Cartographic::from_radians(longitude, latitude, 0.0),
+            }),
+        });
+    }
+    fn request_image(&self, key: &TileKey, asset_server: &AssetServer) -> Option<Handle<Image>> {
+        // bevy根据扩展名选择AssetLoader，GetMap请求的地址没有扩展名，在末尾加一个服务会忽略的参数
+        let url = format!(
+            "{}&.{}",
+            self.build_get_map_url(key),
+            format_to_extension(&self.format)
+        );
+        return Some(asset_server.load(url));
+    }
+}
+struct GetFeatureInfoRequest {
+    url: String,
+    info_format: String,
+    position: Cartographic,
+}
+impl PickFeaturesRequest for GetFeatureInfoRequest {
+    fn perform(self: Box<Self>) -> AsyncReturn<Result<Vec<ImageryLayerFeatureInfo>, String>> {
+        Box::pin(async move {
+            let text = fetch_text(&self.url, &[])
+                .await
+                .map_err(|error| error.to_string())?;
+            parse_feature_info(&self.info_format, &text, self.position)
+        })
+    }
+}
+/// 解析GetFeatureInfo的响应，支持GeoJSON、html和纯文本
+pub fn parse_feature_info(
+    info_format: &str,
+    text: &str,
+    position: Cartographic,
+) -> Result<Vec<ImageryLayerFeatureInfo>, String> {
+    if info_format.contains("json") {
+        let value: serde_json::Value =
+            serde_json::from_str(text).map_err(|error| error.to_string())?;
+        let features = match value.get("features").and_then(|x| x.as_array()) {
+            Some(features) => features.clone(),
+            None if value.get("type").and_then(|x| x.as_str()) == Some("Feature") => {
+                vec![value.clone()]
+            }
+            None => vec![],
+        };
+        return Ok(features
+            .iter()
+            .map(|feature| geojson_feature_to_info(feature, position))
+            .collect());
+    }
+    let text = text.trim();
+    // 没有要素时服务一般返回空的body
+    let compact: String = text.split_whitespace().collect();
+    if text.is_empty() || compact.to_lowercase().contains("<body></body>") {
+        return Ok(vec![]);
+    }
+    return Ok(vec![ImageryLayerFeatureInfo {
+        description: Some(text.to_string()),
+        position: Some(position),
+        ..Default::default()
+    }]);
+}
+fn geojson_feature_to_info(
+    feature: &serde_json::Value,
+    position: Cartographic,
+) -> ImageryLayerFeatureInfo {
+    let properties = feature
+        .get("properties")
+        .cloned()
+        .unwrap_or(serde_json::Value::Null);
+    let name = ["name", "title", "NAME", "TITLE"]
+        .iter()
+        .filter_map(|key| properties.get(*key))
+        .chain(feature.get("id"))
+        .find_map(value_to_string);
+    let description = properties.as_object().map(|object| {
+        let rows: String = object
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    key,
+                    value_to_string(value).unwrap_or_default()
+                )
+            })
+            .collect();
+        format!(
+            "<table class=\"cesium-infoBox-defaultTable\">{}</table>",
+            rows
+        )
+    });
+    return ImageryLayerFeatureInfo {
+        name,
+        description,
+        position: Some(position),
+        data: properties,
+    };
+}
+fn value_to_string(value: &serde_json::Value) -> Option<String> {
+    return match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(x) => Some(x.clone()),
+        other => Some(other.to_string()),
+    };
+}
+#[cfg(test)]
+mod tests {
+    use houtu_scene::WebMercatorTilingScheme;
+
+    use super::*;
+
+    fn get_query_value(url: &str, name: &str) -> Option<String> {
+        let query = url.split_once('?')?.1;
+        return query
+            .split('&')
+            .filter_map(|x| x.split_once('='))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.replace("%2C", ",").replace("%3A", ":"));
+    }
+    fn parse_bbox(url: &str) -> Vec<f64> {
+        return get_query_value(url, "bbox")
+            .unwrap()
+            .split(',')
+            .map(|x| x.parse().unwrap())
+            .collect();
+    }
+    #[test]
+    fn test_get_map_url_1_1_1() {
+        let provider = WebMapServiceImageryProvider::new(WebMapServiceImageryProviderOptions {
+            url: "https://example.com/wms?map=world".to_string(),
+            layers: "roads,rivers".to_string(),
+            ..Default::default()
+        });
+        let url = provider.build_get_map_url(&TileKey::new(1, 0, 0));
+        assert!(url.starts_with(
+            "https://example.com/wms?map=world&service=WMS&version=1.1.1&request=GetMap"
+        ));
+        assert!(get_query_value(&url, "layers").as_deref() == Some("roads,rivers"));
+        assert!(get_query_value(&url, "srs").as_deref() == Some("EPSG:4326"));
+        assert!(get_query_value(&url, "crs").is_none());
+        assert!(parse_bbox(&url) == vec![0.0, -90.0, 180.0, 90.0]);
+    }
+    #[test]
+    fn test_get_map_url_1_3_0() {
+        let provider = WebMapServiceImageryProvider::new(WebMapServiceImageryProviderOptions {
+            url: "https://example.com/wms".to_string(),
+            layers: "roads".to_string(),
+            version: "1.3.0".to_string(),
+            ..Default::default()
+        });
+        let url = provider.build_get_map_url(&TileKey::new(1, 0, 0));
+        assert!(get_query_value(&url, "crs").as_deref() == Some("EPSG:4326"));
+        // 纬度在前
+        assert!(parse_bbox(&url) == vec![-90.0, 0.0, 90.0, 180.0]);
+    }
+    #[test]
+    fn test_get_map_url_web_mercator() {
+        let provider = WebMapServiceImageryProvider::new(WebMapServiceImageryProviderOptions {
+            url: "https://example.com/wms".to_string(),
+            layers: "roads".to_string(),
+            version: "1.3.0".to_string(),
+            tiling_scheme: Some(Box::new(WebMercatorTilingScheme::default())),
+            ..Default::default()
+        });
+        let url = provider.build_get_map_url(&TileKey::new(1, 0, 1));
+        assert!(get_query_value(&url, "crs").as_deref() == Some("EPSG:3857"));
+        // 投影坐标不交换坐标轴
+        let bbox = parse_bbox(&url);
+        let half = 20037508.342789244;
+        assert!(bbox[0].abs() < 1e-6);
+        assert!((bbox[1]).abs() < 1e-6);
+        assert!((bbox[2] - half).abs() < 1e-6);
+        assert!((bbox[3] - half).abs() < 1e-6);
+    }
+    #[test]
+    fn test_custom_parameters() {
+        let provider = WebMapServiceImageryProvider::new(WebMapServiceImageryProviderOptions {
+            url: "https://example.com/wms".to_string(),
+            layers: "roads".to_string(),
+            parameters: vec![
+                ("TRANSPARENT".to_string(), "false".to_string()),
+                ("time".to_string(), "2023-01-01".to_string()),
+            ],
+            get_feature_info_parameters: vec![("feature_count".to_string(), "10".to_string())],
+            ..Default::default()
+        });
+        let url = provider.build_get_map_url(&TileKey::new(0, 0, 0));
+        assert!(get_query_value(&url, "transparent").as_deref() == Some("false"));
+        assert!(get_query_value(&url, "TRANSPARENT").is_none());
+        assert!(url.ends_with("&time=2023-01-01"));
+        assert!(get_query_value(&url, "feature_count").is_none());
+        let url = provider.build_get_feature_info_url(&TileKey::new(0, 0, 0), 0.0, 0.0);
+        assert!(get_query_value(&url, "feature_count").as_deref() == Some("10"));
+        assert!(get_query_value(&url, "time").as_deref() == Some("2023-01-01"));
+    }
+    #[test]
+    fn test_get_feature_info_url() {
+        let provider = WebMapServiceImageryProvider::new(WebMapServiceImageryProviderOptions {
+            url: "https://example.com/wms".to_string(),
+            layers: "roads".to_string(),
+            ..Default::default()
+        });
+        // 瓦片(1,0,0)是经度0到180度，经度45.5、纬度44.5在图片的(64,64)
+        let longitude = 45.5_f64.to_radians();
+        let latitude = 44.5_f64.to_radians();
+        let url = provider.build_get_feature_info_url(&TileKey::new(1, 0, 0), longitude, latitude);
+        assert!(get_query_value(&url, "request").as_deref() == Some("GetFeatureInfo"));
+        assert!(get_query_value(&url, "query_layers").as_deref() == Some("roads"));
+        assert!(get_query_value(&url, "x").as_deref() == Some("64"));
+        assert!(get_query_value(&url, "y").as_deref() == Some("64"));
+        let provider = WebMapServiceImageryProvider::new(WebMapServiceImageryProviderOptions {
+            url: "https://example.com/wms".to_string(),
+            layers: "roads".to_string(),
+            version: "1.3.0".to_string(),
+            enable_pick_features: false,
+            ..Default::default()
+        });
+        let url = provider.build_get_feature_info_url(&TileKey::new(1, 0, 0), longitude, latitude);
+        assert!(get_query_value(&url, "i").as_deref() == Some("64"));
+        assert!(get_query_value(&url, "j").as_deref() == Some("64"));
+        assert!(provider
+            .pick_features(&TileKey::new(1, 0, 0), longitude, latitude)
+            .is_none());
+    }
+    #[test]
+    fn test_parse_feature_info() {
+        let position = Cartographic::from_degrees(10.0, 20.0, 0.0);
+        let json = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","id":"roads.1","properties":{"name":"G1","lanes":4}},
+            {"type":"Feature","id":"roads.2","properties":{"lanes":2}}
+        ]}"#;
+        let features = parse_feature_info("application/json", json, position).unwrap();
+        assert!(features.len() == 2);
+        assert!(features[0].name.as_deref() == Some("G1"));
+        assert!(features[0].data["lanes"] == 4);
+        assert!(features[0]
+            .description
+            .as_ref()
+            .unwrap()
+            .contains("<tr><th>lanes</th><td>4</td></tr>"));
+        assert!(features[1].name.as_deref() == Some("roads.2"));
+        assert!(features[1].position == Some(position));
+        assert!(parse_feature_info("application/json", "{", position).is_err());
+        let html = "<html><body>\n</body></html>";
+        assert!(parse_feature_info("text/html", html, position)
+            .unwrap()
+            .is_empty());
+        let features = parse_feature_info("text/plain", "roads: G1", position).unwrap();
+        assert!(features[0].description.as_deref() == Some("roads: G1"));
+    }
+}
@@ -0,0 +1,431 @@
+use std::io;
+
+use houtu_jobs::{AsyncReturn, Context, Job};
+use houtu_scene::{GeographicTilingScheme, Rectangle, TilingScheme, WebMercatorTilingScheme};
+use roxmltree::Node;
+
+use crate::{
+    resource::{self, fetch_text},
+    wmts_imagery_provider::WMTSImageryProvider,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Resource(#[from] resource::Error),
+    #[error("{0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("layer {0} is not found in the capabilities")]
+    LayerNotFound(String),
+    #[error("tile matrix set {0} is not found in the capabilities")]
+    TileMatrixSetNotFound(String),
+    #[error("layer {0} has no tile matrix set in a supported crs")]
+    UnsupportedCrs(String),
+    #[error("layer {0} has no tile url")]
+    NoTileUrl(String),
+}
+/// WMTS GetCapabilities文档中和创建影像服务有关的内容
+#[derive(Debug, Clone)]
+pub struct WMTSCapabilities {
+    pub layers: Vec<WMTSLayer>,
+    pub tile_matrix_sets: Vec<WMTSTileMatrixSet>,
+    /// OperationsMetadata中GetTile的KVP地址
+    pub get_tile_url: Option<String>,
+}
+#[derive(Debug, Clone)]
+pub struct WMTSLayer {
+    pub identifier: String,
+    pub title: Option<String>,
+    pub styles: Vec<String>,
+    pub default_style: Option<String>,
+    pub formats: Vec<String>,
+    pub tile_matrix_set_links: Vec<String>,
+    pub resource_urls: Vec<WMTSResourceUrl>,
+    /// WGS84BoundingBox，单位是弧度
+    pub rectangle: Option<Rectangle>,
+}
+#[derive(Debug, Clone)]
+pub struct WMTSResourceUrl {
+    pub format: String,
+    pub resource_type: String,
+    pub template: String,
+}
+#[derive(Debug, Clone)]
+pub struct WMTSTileMatrixSet {
+    pub identifier: String,
+    pub supported_crs: String,
+    pub tile_matrices: Vec<WMTSTileMatrix>,
+}
+#[derive(Debug, Clone)]
+pub struct WMTSTileMatrix {
+    pub identifier: String,
+    pub scale_denominator: f64,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub matrix_width: u32,
+    pub matrix_height: u32,
+}
+/// 创建影像服务时的可选项，没有指定时从文档中选择
+#[derive(Default)]
+pub struct CreateWMTSProviderOptions<'a> {
+    pub tile_matrix_set: Option<&'a str>,
+    pub style: Option<&'a str>,
+    pub format: Option<&'a str>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WMTSCrs {
+    Geographic,
+    WebMercator,
+}
+impl WMTSCrs {
+    /// 支持`EPSG:4326`、`urn:ogc:def:crs:EPSG::3857`、`urn:ogc:def:crs:OGC:1.3:CRS84`等写法
+    pub fn from_crs(crs: &str) -> Option<Self> {
+        let code = crs.rsplit(':').next().unwrap_or("").trim().to_uppercase();
+        return match code.as_str() {
+            "4326" | "4490" | "CRS84" => Some(Self::Geographic),
+            "3857" | "900913" | "102100" | "102113" | "3785" => Some(Self::WebMercator),
+            _ => None,
+        };
+    }
+}
+impl WMTSCapabilities {
+    pub fn from_str(xml: &str) -> Result<Self, Error> {
+        let document = roxmltree::Document::parse(xml)?;
+        let root = document.root_element();
+        let mut capabilities = Self {
+            layers: vec![],
+            tile_matrix_sets: vec![],
+            get_tile_url: parse_get_tile_url(root),
+        };
+        if let Some(contents) = child(root, "Contents") {
+            for layer in children(contents, "Layer") {
+                capabilities.layers.push(parse_layer(layer));
+            }
+            for tile_matrix_set in children(contents, "TileMatrixSet") {
+                capabilities
+                    .tile_matrix_sets
+                    .push(parse_tile_matrix_set(tile_matrix_set));
+            }
+        }
+        return Ok(capabilities);
+    }
+    pub async fn from_url(url: &str) -> Result<Self, Error> {
+        let xml = fetch_text(url, &[]).await?;
+        return Self::from_str(&xml);
+    }
+    pub fn get_layer(&self, identifier: &str) -> Option<&WMTSLayer> {
+        return self.layers.iter().find(|x| x.identifier == identifier);
+    }
+    pub fn get_tile_matrix_set(&self, identifier: &str) -> Option<&WMTSTileMatrixSet> {
+        return self
+            .tile_matrix_sets
+            .iter()
+            .find(|x| x.identifier == identifier);
+    }
+    /// 根据图层的TileMatrixSet选择切片方案，生成可以直接使用的影像服务
+    pub fn create_provider(
+        &self,
+        layer: &str,
+        options: CreateWMTSProviderOptions,
+    ) -> Result<WMTSImageryProvider, Error> {
+        let layer = self
+            .get_layer(layer)
+            .ok_or(Error::LayerNotFound(layer.to_string()))?;
+        let tile_matrix_set = match options.tile_matrix_set {
+            Some(identifier) => self
+                .get_tile_matrix_set(identifier)
+                .ok_or(Error::TileMatrixSetNotFound(identifier.to_string()))?,
+            None => layer
+                .tile_matrix_set_links
+                .iter()
+                .filter_map(|x| self.get_tile_matrix_set(x))
+                .find(|x| WMTSCrs::from_crs(&x.supported_crs).is_some())
+                .ok_or(Error::UnsupportedCrs(layer.identifier.clone()))?,
+        };
+        let crs = WMTSCrs::from_crs(&tile_matrix_set.supported_crs)
+            .ok_or(Error::UnsupportedCrs(layer.identifier.clone()))?;
+        let first_matrix = tile_matrix_set
+            .tile_matrices
+            .first()
+            .ok_or(Error::TileMatrixSetNotFound(tile_matrix_set.identifier.clone()))?;
+        // 第一个TileMatrix就是切片方案的第0级
+        let tiling_scheme: Box<dyn TilingScheme> = match crs {
+            WMTSCrs::Geographic => {
+                let mut tiling_scheme = GeographicTilingScheme::default();
+                tiling_scheme.number_of_level_zero_tiles_x = first_matrix.matrix_width;
+                tiling_scheme.number_of_level_zero_tiles_y = first_matrix.matrix_height;
+                Box::new(tiling_scheme)
+            }
+            WMTSCrs::WebMercator => {
+                let mut tiling_scheme = WebMercatorTilingScheme::default();
+                tiling_scheme.number_of_level_zero_tiles_x = first_matrix.matrix_width;
+                tiling_scheme.number_of_level_zero_tiles_y = first_matrix.matrix_height;
+                Box::new(tiling_scheme)
+            }
+        };
+        let style = options
+            .style
+            .map(|x| x.to_string())
+            .or(layer.default_style.clone())
+            .or(layer.styles.first().cloned())
+            .unwrap_or("default".to_string());
+        // 没有指定格式时优先使用png
+        let format = options
+            .format
+            .map(|x| x.to_string())
+            .or(layer.formats.iter().find(|x| x.as_str() == "image/png").cloned())
+            .or(layer.formats.first().cloned())
+            .unwrap_or("image/png".to_string());
+        let url = layer
+            .resource_urls
+            .iter()
+            .filter(|x| x.resource_type.eq_ignore_ascii_case("tile"))
+            .find(|x| x.format == format)
+            .or(layer
+                .resource_urls
+                .iter()
+                .find(|x| x.resource_type.eq_ignore_ascii_case("tile")))
+            .map(|x| x.template.clone())
+            .or(self.get_tile_url.clone())
+            .ok_or(Error::NoTileUrl(layer.identifier.clone()))?;
+        let rectangle = layer.rectangle.unwrap_or(tiling_scheme.get_rectangle());
+        return Ok(WMTSImageryProvider {
+            name: layer.title.clone().unwrap_or(layer.identifier.clone()),
+            url: url,
+            layer: layer.identifier.clone(),
+            style: style,
+            format: format,
+            tile_matrix_set_id: tile_matrix_set.identifier.clone(),
+            minimum_level: 0,
+            maximum_level: tile_matrix_set.tile_matrices.len() as u32 - 1,
+            tile_width: first_matrix.tile_width,
+            tile_height: first_matrix.tile_height,
+            tile_matrix_labels: Some(
+                tile_matrix_set
+                    .tile_matrices
+                    .iter()
+                    .map(|x| x.identifier.clone())
+                    .collect(),
+            ),
+            tiling_scheme: tiling_scheme,
+            subdomains: vec![],
+            rectangle: rectangle,
+        });
+    }
+}
+fn is_element(node: &Node, name: &str) -> bool {
+    return node.is_element() && node.tag_name().name() == name;
+}
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    return node.children().find(|x| is_element(x, name));
+}
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    return node.children().filter(move |x| is_element(x, name));
+}
+fn child_text(node: Node, name: &str) -> Option<String> {
+    return child(node, name)
+        .and_then(|x| x.text())
+        .map(|x| x.trim().to_string());
+}
+fn child_number<T: std::str::FromStr>(node: Node, name: &str) -> Option<T> {
+    return child_text(node, name).and_then(|x| x.parse().ok());
+}
+/// 不区分命名空间读取属性，比如`xlink:href`
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    return node
+        .attributes()
+        .find(|x| x.name() == name)
+        .map(|x| x.value());
+}
+fn parse_corner(node: Node, name: &str) -> Option<(f64, f64)> {
+    let text = child_text(node, name)?;
+    let mut values = text.split_whitespace().map(|x| x.parse::<f64>());
+    let x = values.next()?.ok()?;
+    let y = values.next()?.ok()?;
+    return Some((x, y));
+}
+fn parse_get_tile_url(root: Node) -> Option<String> {
+    let operations = child(root, "OperationsMetadata")?;
+    let operation = children(operations, "Operation")
+        .find(|x| attribute(*x, "name") == Some("GetTile"))?;
+    let http = child(child(operation, "DCP")?, "HTTP")?;
+    // 有多个地址时选择KVP编码的那个
+    let get = children(http, "Get")
+        .find(|get| {
+            get.descendants()
+                .filter(|x| is_element(x, "Value"))
+                .any(|x| x.text().map(|x| x.trim()) == Some("KVP"))
+        })
+        .or(child(http, "Get"))?;
+    return attribute(get, "href").map(|x| x.to_string());
+}
+fn parse_layer(node: Node) -> WMTSLayer {
+    let mut styles = vec![];
+    let mut default_style = None;
+    for style in children(node, "Style") {
+        if let Some(identifier) = child_text(style, "Identifier") {
+            if attribute(style, "isDefault") == Some("true") {
+                default_style = Some(identifier.clone());
+            }
+            styles.push(identifier);
+        }
+    }
+    let rectangle = child(node, "WGS84BoundingBox").and_then(|x| {
+        let (west, south) = parse_corner(x, "LowerCorner")?;
+        let (east, north) = parse_corner(x, "UpperCorner")?;
+        Some(Rectangle::new(
+            west.to_radians(),
+            south.to_radians(),
+            east.to_radians(),
+            north.to_radians(),
+        ))
+    });
+    return WMTSLayer {
+        identifier: child_text(node, "Identifier").unwrap_or_default(),
+        title: child_text(node, "Title"),
+        styles,
+        default_style,
+        formats: children(node, "Format")
+            .filter_map(|x| x.text())
+            .map(|x| x.trim().to_string())
+            .collect(),
+        tile_matrix_set_links: children(node, "TileMatrixSetLink")
+            .filter_map(|x| child_text(x, "TileMatrixSet"))
+            .collect(),
+        resource_urls: children(node, "ResourceURL")
+            .filter_map(|x| {
+                Some(WMTSResourceUrl {
+                    format: attribute(x, "format").unwrap_or("").to_string(),
+                    resource_type: attribute(x, "resourceType").unwrap_or("tile").to_string(),
+                    template: attribute(x, "template")?.to_string(),
+                })
+            })
+            .collect(),
+        rectangle,
+    };
+}
+fn parse_tile_matrix_set(node: Node) -> WMTSTileMatrixSet {
+    return WMTSTileMatrixSet {
+        identifier: child_text(node, "Identifier").unwrap_or_default(),
+        supported_crs: child_text(node, "SupportedCRS").unwrap_or_default(),
+        tile_matrices: children(node, "TileMatrix")
+            .map(|x| WMTSTileMatrix {
+                identifier: child_text(x, "Identifier").unwrap_or_default(),
+                scale_denominator: child_number(x, "ScaleDenominator").unwrap_or(0.0),
+                tile_width: child_number(x, "TileWidth").unwrap_or(256),
+                tile_height: child_number(x, "TileHeight").unwrap_or(256),
+                matrix_width: child_number(x, "MatrixWidth").unwrap_or(1),
+                matrix_height: child_number(x, "MatrixHeight").unwrap_or(1),
+            })
+            .collect(),
+    };
+}
+pub struct LoadWMTSCapabilitiesJob {
+    pub url: String,
+}
+impl Job for LoadWMTSCapabilitiesJob {
+    type Outcome = Result<WMTSCapabilities, Error>;
+    fn name(&self) -> String {
+        format!("load wmts capabilities {}", self.url)
+    }
+    fn perform(self, _context: Context) -> AsyncReturn<Self::Outcome> {
+        Box::pin(async move {
+            let fetch = WMTSCapabilities::from_url(&self.url);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(fetch)
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                fetch.await
+            }
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::quadtree::tile_key::TileKey;
+
+    fn load_capabilities() -> WMTSCapabilities {
+        WMTSCapabilities::from_str(include_str!("../assets/wmts-capabilities.xml")).unwrap()
+    }
+    #[test]
+    fn test_parse_capabilities() {
+        let capabilities = load_capabilities();
+        assert!(capabilities.get_tile_url.as_deref() == Some("https://example.com/wmts?"));
+        assert!(capabilities.layers.len() == 2);
+        // Layer中TileMatrixSetLink下的TileMatrixSet不应该被当作TileMatrixSet
+        assert!(capabilities.tile_matrix_sets.len() == 2);
+        let layer = capabilities.get_layer("roads").unwrap();
+        assert!(layer.styles == vec!["light", "dark"]);
+        assert!(layer.default_style.as_deref() == Some("dark"));
+        assert!(layer.formats == vec!["image/png"]);
+        assert!(layer.resource_urls.len() == 1);
+        let rectangle = layer.rectangle.unwrap();
+        assert!((rectangle.west - 70.0_f64.to_radians()).abs() < 1e-12);
+        assert!((rectangle.north - 60.0_f64.to_radians()).abs() < 1e-12);
+        let tile_matrix_set = capabilities.get_tile_matrix_set("c").unwrap();
+        assert!(WMTSCrs::from_crs(&tile_matrix_set.supported_crs) == Some(WMTSCrs::Geographic));
+        assert!(tile_matrix_set.tile_matrices.len() == 3);
+        assert!(tile_matrix_set.tile_matrices[2].matrix_width == 8);
+    }
+    #[test]
+    fn test_crs() {
+        assert!(WMTSCrs::from_crs("EPSG:4326") == Some(WMTSCrs::Geographic));
+        assert!(WMTSCrs::from_crs("urn:ogc:def:crs:OGC:1.3:CRS84") == Some(WMTSCrs::Geographic));
+        assert!(WMTSCrs::from_crs("urn:ogc:def:crs:EPSG:6.18.3:3857") == Some(WMTSCrs::WebMercator));
+        assert!(WMTSCrs::from_crs("EPSG:2000").is_none());
+    }
+    #[test]
+    fn test_create_geographic_provider() {
+        let capabilities = load_capabilities();
+        let provider = capabilities
+            .create_provider("img", CreateWMTSProviderOptions::default())
+            .unwrap();
+        assert!(provider.tiling_scheme.get_name() == "GeographicTilingScheme");
+        assert!(provider.tiling_scheme.get_number_of_x_tiles_at_level(0) == 2);
+        assert!(provider.tile_matrix_set_id == "c");
+        assert!(provider.maximum_level == 2);
+        assert!(provider.style == "default");
+        assert!(provider.format == "image/png");
+        assert!(!provider.is_restful());
+        let url = provider.build_url(&TileKey::new(3, 1, 1));
+        assert!(url.contains("tilematrix=2&tilerow=1&tilecol=3"));
+        assert!(url.starts_with("https://example.com/wmts?service=WMTS"));
+        assert!((provider.rectangle.east - PI).abs() < 1e-12);
+    }
+    #[test]
+    fn test_create_web_mercator_provider() {
+        let capabilities = load_capabilities();
+        let provider = capabilities
+            .create_provider("roads", CreateWMTSProviderOptions::default())
+            .unwrap();
+        assert!(provider.tiling_scheme.get_name() == "WebMercatorTilingScheme");
+        assert!(provider.is_restful());
+        let url = provider.build_url(&TileKey::new(1, 0, 1));
+        assert!(url == "https://example.com/roads/dark/w/1/0/1.png");
+        let provider = capabilities.create_provider(
+            "img",
+            CreateWMTSProviderOptions {
+                tile_matrix_set: Some("w"),
+                ..Default::default()
+            },
+        );
+        assert!(provider.unwrap().tiling_scheme.get_name() == "WebMercatorTilingScheme");
+        assert!(matches!(
+            capabilities.create_provider("none", CreateWMTSProviderOptions::default()),
+            Err(Error::LayerNotFound(_))
+        ));
+    }
+}
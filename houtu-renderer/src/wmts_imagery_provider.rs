@@ -1,12 +1,18 @@
 use bevy::prelude::*;
-use std::collections::HashMap;
 
-use houtu_scene::{GeographicTilingScheme, Rectangle, TileKey, TilingScheme};
+use houtu_scene::{GeographicTilingScheme, Rectangle, TilingScheme};
 
-use crate::quadtree::imagery_provider::ImageryProvider;
+use crate::{
+    quadtree::{
+        imagery_provider::{ImageryProvider, PickFeaturesJob},
+        tile_key::TileKey,
+    },
+    resource::{append_query, format_to_extension},
+};
 #[derive(Default)]
 pub struct WMTSImageryProviderOptions {
     pub name: Option<&'static str>,
+    /// KVP方式是服务地址，RESTful方式是包含`{TileMatrix}`、`{TileRow}`、`{TileCol}`等占位符的模板
     pub url: &'static str,
     pub layer: &'static str,
     pub style: &'static str,
@@ -22,6 +28,7 @@ pub struct WMTSImageryProviderOptions {
     pub subdomains: Option<Vec<&'static str>>,
     pub rectangle: Option<Rectangle>,
 }
+/// [WMTS](https://www.ogc.org/standard/wmts/)影像服务，支持KVP和RESTful两种请求方式
 pub struct WMTSImageryProvider {
     pub name: String,
     pub url: String,
@@ -50,7 +57,7 @@ impl ImageryProvider for WMTSImageryProvider {
     }
     fn get_tile_credits(
         &self,
-        key: &crate::quadtree::tile_key::TileKey,
+        _key: &TileKey,
     ) -> Option<Vec<crate::quadtree::credit::Credit>> {
         None
     }
@@ -60,21 +67,22 @@ impl ImageryProvider for WMTSImageryProvider {
     fn get_tile_width(&self) -> u32 {
         self.tile_width
     }
-    fn request_image(
-        &self,
-        key: &crate::quadtree::tile_key::TileKey,
-        asset_server: &AssetServer,
-    ) -> Option<Handle<Image>> {
-        // asset_server.load()
-        None
+    fn request_image(&self, key: &TileKey, asset_server: &AssetServer) -> Option<Handle<Image>> {
+        let mut url = self.build_url(key);
+        if !self.is_restful() {
+            // bevy根据扩展名选择AssetLoader，KVP请求的地址没有扩展名，在末尾加一个服务会忽略的参数
+            url = format!("{}&.{}", url, self.get_format_extension());
+        }
+        return Some(asset_server.load(url));
     }
-    fn load_image(&self, url: String) {}
+    fn load_image(&self, _url: String) {}
     fn pick_features(
         &self,
-        key: &crate::quadtree::tile_key::TileKey,
-        longitude: f64,
-        latitude: f64,
-    ) {
+        _key: &TileKey,
+        _longitude: f64,
+        _latitude: f64,
+    ) -> Option<PickFeaturesJob> {
+        None
     }
     fn get_tiling_scheme(&self) -> &Box<dyn TilingScheme> {
         &self.tiling_scheme
@@ -114,8 +122,6 @@ impl WMTSImageryProvider {
                 }
             },
             rectangle: options.rectangle.unwrap_or(tiling_scheme.get_rectangle()),
-            // matrix_set: options.matrix_set,
-            // crs: options.crs,
             tile_matrix_set_id: options.tile_matrix_set_id.to_string(),
             minimum_level: options.minimum_level.unwrap_or(0),
             maximum_level: options.maximum_level.unwrap_or(19),
@@ -128,42 +134,136 @@ impl WMTSImageryProvider {
                     None
                 }
             },
-            // tile_matrix_set: options.tile_matrix_set,
             tiling_scheme: tiling_scheme,
             subdomains: subdomains,
         }
     }
-    pub fn getParams(&self, col: u32, row: u32, level: u32) -> HashMap<String, String> {
-        let tile_matrix;
-        if let Some(real_labels) = &self.tile_matrix_labels {
-            tile_matrix = real_labels[level as usize].clone();
-        } else {
-            tile_matrix = level.to_string();
+    /// url中包含`{TileMatrix}`、`{TileRow}`或`{TileCol}`时使用RESTful方式，否则使用KVP方式
+    pub fn is_restful(&self) -> bool {
+        let url = self.url.to_lowercase();
+        return ["{tilematrix}", "{tilerow}", "{tilecol}"]
+            .iter()
+            .any(|x| url.contains(x));
+    }
+    /// 层级对应的TileMatrix标识符，没有`tile_matrix_labels`时就是层级本身
+    pub fn get_tile_matrix(&self, level: u32) -> String {
+        return self
+            .tile_matrix_labels
+            .as_ref()
+            .and_then(|labels| labels.get(level as usize))
+            .cloned()
+            .unwrap_or(level.to_string());
+    }
+    pub fn get_subdomain(&self, key: &TileKey) -> Option<&str> {
+        if self.subdomains.is_empty() {
+            return None;
         }
-        let mut query: HashMap<String, String> = HashMap::new();
-        query.insert("tilematrix".to_string(), tile_matrix);
-        query.insert("layer".to_string(), self.layer.clone());
-        query.insert("style".to_string(), self.style.clone());
-        query.insert("tilerow".to_string(), row.to_string());
-        query.insert("tilecol".to_string(), col.to_string());
-        query.insert("tilematrixset".to_string(), self.tile_matrix_set_id.clone());
-        query.insert("format".to_string(), self.format.clone());
-        return query;
-    }
-    pub fn getParamsByTileKey(&self, tile_key: &TileKey) -> HashMap<String, String> {
-        return self.getParams(tile_key.column, tile_key.row, tile_key.level);
-    }
-    pub fn build_url(&self, tile_key: &TileKey) -> String {
-        let params = self.getParamsByTileKey(tile_key);
-        let mut params_str = String::new();
-        params.iter().for_each(|(k, v)| {
-            let param = format!("{}={}", k, v);
-            if params_str == "" {
-                params_str = format!("{}", param);
-            } else {
-                params_str = format!("{}&{}", params_str, param);
-            }
+        let index = (key.x + key.y + key.level) as usize % self.subdomains.len();
+        return Some(self.subdomains[index].as_str());
+    }
+    /// `format`对应的图片扩展名
+    pub fn get_format_extension(&self) -> &str {
+        return format_to_extension(&self.format);
+    }
+    pub fn build_url(&self, key: &TileKey) -> String {
+        let tile_matrix = self.get_tile_matrix(key.level);
+        let row = key.y.to_string();
+        let col = key.x.to_string();
+        let subdomain = self.get_subdomain(key).unwrap_or("");
+        if self.is_restful() {
+            return fill_template(
+                &self.url,
+                &[
+                    ("TileMatrix", tile_matrix.as_str()),
+                    ("TileRow", row.as_str()),
+                    ("TileCol", col.as_str()),
+                    ("Style", self.style.as_str()),
+                    ("TileMatrixSet", self.tile_matrix_set_id.as_str()),
+                    ("Layer", self.layer.as_str()),
+                    ("s", subdomain),
+                ],
+            );
+        }
+        let base = fill_template(&self.url, &[("s", subdomain)]);
+        return append_query(
+            &base,
+            &[
+                ("service", "WMTS"),
+                ("request", "GetTile"),
+                ("version", "1.0.0"),
+                ("layer", self.layer.as_str()),
+                ("style", self.style.as_str()),
+                ("tilematrixset", self.tile_matrix_set_id.as_str()),
+                ("tilematrix", tile_matrix.as_str()),
+                ("tilerow", row.as_str()),
+                ("tilecol", col.as_str()),
+                ("format", self.format.as_str()),
+            ],
+        );
+    }
+}
+/// 替换模板中的`{name}`占位符，名称不区分大小写，没有对应值的占位符保持不变
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        let end = start + length;
+        let name = &rest[start + 1..end];
+        result.push_str(&rest[..start]);
+        match values.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)) {
+            Some((_, v)) => result.push_str(v),
+            None => result.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    return result;
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kvp_url() {
+        let provider = WMTSImageryProvider::new(WMTSImageryProviderOptions {
+            url: "https://{s}.example.com/wmts?token=abc",
+            layer: "img",
+            style: "default",
+            format: Some("image/png"),
+            tile_matrix_set_id: "w",
+            subdomains: Some(vec!["t0", "t1"]),
+            ..Default::default()
         });
-        return format!("{}&{}", self.url, params_str);
+        assert!(!provider.is_restful());
+        let url = provider.build_url(&TileKey::new(1, 2, 3));
+        assert!(
+            url == "https://t0.example.com/wmts?token=abc&service=WMTS&request=GetTile&version=1.0.0&layer=img&style=default&tilematrixset=w&tilematrix=3&tilerow=2&tilecol=1&format=image%2Fpng"
+        );
+        assert!(provider.get_format_extension() == "png");
+    }
+    #[test]
+    fn test_restful_url() {
+        let provider = WMTSImageryProvider::new(WMTSImageryProviderOptions {
+            url: "https://{s}.example.com/{Style}/{TileMatrixSet}/{TileMatrix}/{tilerow}/{TileCol}.jpg",
+            layer: "img",
+            style: "default",
+            tile_matrix_set_id: "EPSG:4326",
+            tile_matrix_labels: Some(vec!["EPSG:4326:0", "EPSG:4326:1"]),
+            ..Default::default()
+        });
+        assert!(provider.is_restful());
+        let url = provider.build_url(&TileKey::new(1, 0, 1));
+        assert!(url == "https://c.example.com/default/EPSG:4326/EPSG:4326:1/0/1.jpg");
+        // 超出tile_matrix_labels范围时使用层级
+        assert!(provider.get_tile_matrix(5) == "5");
+        assert!(provider.get_format_extension() == "jpg");
+    }
+    #[test]
+    fn test_fill_template() {
+        assert!(fill_template("{a}/{B}/{c}", &[("A", "1"), ("b", "2")]) == "1/2/{c}");
+        assert!(fill_template("a/{b", &[("b", "2")]) == "a/{b");
     }
 }
@@ -4,7 +4,7 @@ use bevy::prelude::warn;
 use houtu_scene::{GeographicTilingScheme, Rectangle, TilingScheme, WebMercatorTilingScheme};
 use new_string_template::template::Template;
 
-use crate::quadtree::imagery_provider::ImageryProvider;
+use crate::quadtree::imagery_provider::{ImageryProvider, PickFeaturesJob};
 
 pub struct XYZImageryProvider {
     pub tiling_scheme: Box<dyn TilingScheme>,
@@ -75,7 +75,8 @@ impl ImageryProvider for XYZImageryProvider {
         key: &crate::quadtree::tile_key::TileKey,
         longitude: f64,
         latitude: f64,
-    ) {
+    ) -> Option<PickFeaturesJob> {
+        None
     }
     fn request_image(
         &self,
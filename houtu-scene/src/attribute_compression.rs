@@ -1,4 +1,4 @@
-use bevy::math::DVec2;
+use bevy::math::{DVec2, DVec3};
 
 pub fn compress_texture_coordinates(texture_coordinates: &DVec2) -> f64 {
     // let x = bit_or(texture_coordinates.x * 4095.0, 0b0);
@@ -17,3 +17,85 @@ pub fn decompress_texture_coordinates(compressed: f64) -> DVec2 {
 pub fn oct_pack_float(encoded: &DVec2) -> f64 {
     return 256.0 * encoded.x + encoded.y;
 }
+fn sign_not_zero(value: f64) -> f64 {
+    return if value < 0.0 { -1.0 } else { 1.0 };
+}
+pub fn to_snorm(value: f64, range_maximum: f64) -> f64 {
+    return ((value.clamp(-1.0, 1.0) * 0.5 + 0.5) * range_maximum).round();
+}
+pub fn from_snorm(value: f64, range_maximum: f64) -> f64 {
+    return (value.clamp(0.0, range_maximum) / range_maximum) * 2.0 - 1.0;
+}
+/// 把单位向量编码成两个`[0,range_maximum]`之间的数，见 http://jcgt.org/published/0003/02/01/
+pub fn oct_encode_in_range(vector: &DVec3, range_maximum: f64) -> DVec2 {
+    let sum = vector.x.abs() + vector.y.abs() + vector.z.abs();
+    let mut result = DVec2::new(vector.x / sum, vector.y / sum);
+    if vector.z < 0.0 {
+        let x = result.x;
+        let y = result.y;
+        result.x = (1.0 - y.abs()) * sign_not_zero(x);
+        result.y = (1.0 - x.abs()) * sign_not_zero(y);
+    }
+    result.x = to_snorm(result.x, range_maximum);
+    result.y = to_snorm(result.y, range_maximum);
+    return result;
+}
+pub fn oct_encode(vector: &DVec3) -> DVec2 {
+    return oct_encode_in_range(vector, 255.0);
+}
+pub fn oct_decode_in_range(x: f64, y: f64, range_maximum: f64) -> DVec3 {
+    let mut result = DVec3::new(
+        from_snorm(x, range_maximum),
+        from_snorm(y, range_maximum),
+        0.0,
+    );
+    result.z = 1.0 - (result.x.abs() + result.y.abs());
+    if result.z < 0.0 {
+        let old_x = result.x;
+        result.x = (1.0 - result.y.abs()) * sign_not_zero(old_x);
+        result.y = (1.0 - old_x.abs()) * sign_not_zero(result.y);
+    }
+    return result.normalize();
+}
+pub fn oct_decode(x: f64, y: f64) -> DVec3 {
+    return oct_decode_in_range(x, y, 255.0);
+}
+/// [`oct_pack_float`]的逆运算
+pub fn oct_decode_float(value: f64) -> DVec3 {
+    let temp = value / 256.0;
+    let x = temp.floor();
+    let y = (temp - x) * 256.0;
+    return oct_decode(x, y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const EPSILON: f64 = 1.0 / 127.0;
+
+    #[test]
+    fn oct_encode_and_decode() {
+        let vectors = [
+            DVec3::X,
+            DVec3::NEG_X,
+            DVec3::Y,
+            DVec3::NEG_Y,
+            DVec3::Z,
+            DVec3::NEG_Z,
+            DVec3::new(1.0, 1.0, 1.0).normalize(),
+            DVec3::new(-1.0, 2.0, -3.0).normalize(),
+        ];
+        for vector in vectors.iter() {
+            let encoded = oct_encode(vector);
+            let decoded = oct_decode(encoded.x, encoded.y);
+            assert!((decoded - *vector).abs().max_element() < EPSILON);
+        }
+    }
+    #[test]
+    fn oct_pack_float_round_trip() {
+        let vector = DVec3::new(0.3, -0.4, 0.8).normalize();
+        let encoded = oct_encode(&vector);
+        let decoded = oct_decode_float(oct_pack_float(&encoded));
+        assert!((decoded - vector).abs().max_element() < EPSILON);
+    }
+}
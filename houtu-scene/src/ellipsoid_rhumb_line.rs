@@ -0,0 +1,381 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use crate::{ellipsoid::Ellipsoid, nagetive_pi_to_pi, Cartographic, EPSILON12, EPSILON8};
+
+/// 恒向线(等角航线)，沿线的方位角保持不变，对应Cesium的EllipsoidRhumbLine
+#[derive(Debug, Clone)]
+pub struct EllipsoidRhumbLine {
+    start: Cartographic,
+    end: Cartographic,
+    heading: f64,
+    distance: f64,
+    ellipsoid: Ellipsoid,
+    ellipticity: f64,
+    ellipticity_squared: f64,
+}
+impl Default for EllipsoidRhumbLine {
+    fn default() -> Self {
+        Self::new(Cartographic::default(), Cartographic::default(), None)
+    }
+}
+impl EllipsoidRhumbLine {
+    pub fn new(start: Cartographic, end: Cartographic, ellipsoid: Option<Ellipsoid>) -> Self {
+        let ellipsoid = ellipsoid.unwrap_or(Ellipsoid::WGS84);
+        let ellipticity_squared = ellipticity_squared(&ellipsoid);
+        let mut rhumb_line = Self {
+            start,
+            end,
+            heading: 0.0,
+            distance: 0.0,
+            ellipsoid,
+            ellipticity: ellipticity_squared.sqrt(),
+            ellipticity_squared,
+        };
+        rhumb_line.set_end_points(start, end);
+        return rhumb_line;
+    }
+    /// 从起点沿heading方向走distance米得到终点
+    pub fn from_start_heading_distance(
+        start: Cartographic,
+        heading: f64,
+        distance: f64,
+        ellipsoid: Option<Ellipsoid>,
+    ) -> Self {
+        let ellipsoid = ellipsoid.unwrap_or(Ellipsoid::WGS84);
+        let end = interpolate_using_surface_distance(
+            &ellipsoid,
+            &start,
+            nagetive_pi_to_pi(heading),
+            distance,
+        );
+        return Self::new(start, end, Some(ellipsoid));
+    }
+    pub fn set_end_points(&mut self, start: Cartographic, end: Cartographic) {
+        self.start = start;
+        self.end = end;
+        self.start.height = 0.0;
+        self.end.height = 0.0;
+        self.heading = calculate_heading(self.ellipticity, &self.start, &self.end);
+        self.distance = self.calculate_arc_length();
+    }
+    pub fn start(&self) -> &Cartographic {
+        return &self.start;
+    }
+    pub fn end(&self) -> &Cartographic {
+        return &self.end;
+    }
+    /// 方位角(弧度)，从正北顺时针，范围[-π,π]
+    pub fn heading(&self) -> f64 {
+        return self.heading;
+    }
+    /// 起点到终点的表面距离(米)
+    pub fn surface_distance(&self) -> f64 {
+        return self.distance;
+    }
+    pub fn ellipsoid(&self) -> &Ellipsoid {
+        return &self.ellipsoid;
+    }
+    pub fn interpolate_using_fraction(&self, fraction: f64) -> Cartographic {
+        return self.interpolate_using_surface_distance(fraction * self.distance);
+    }
+    pub fn interpolate_using_surface_distance(&self, distance: f64) -> Cartographic {
+        return interpolate_using_surface_distance(
+            &self.ellipsoid,
+            &self.start,
+            self.heading,
+            distance,
+        );
+    }
+    /// 与经线的交点，恒向线沿经线方向时没有唯一交点
+    pub fn find_intersection_with_longitude(
+        &self,
+        intersection_longitude: f64,
+    ) -> Option<Cartographic> {
+        let ellipticity = self.ellipticity;
+        let heading = self.heading;
+        let abs_heading = heading.abs();
+        let start = &self.start;
+
+        let mut intersection_longitude = nagetive_pi_to_pi(intersection_longitude);
+        if (intersection_longitude.abs() - PI).abs() < EPSILON12 {
+            intersection_longitude = start.longitude.signum() * PI;
+        }
+        // 东西方向
+        if (FRAC_PI_2 - abs_heading).abs() <= EPSILON8 {
+            return Some(Cartographic::new(
+                intersection_longitude,
+                start.latitude,
+                0.0,
+            ));
+        }
+        // 南北方向
+        if ((FRAC_PI_2 - abs_heading).abs() - FRAC_PI_2).abs() <= EPSILON8 {
+            if (intersection_longitude - start.longitude).abs() < EPSILON12 {
+                return Some(Cartographic::new(
+                    intersection_longitude,
+                    start.latitude,
+                    0.0,
+                ));
+            }
+            return None;
+        }
+
+        let phi1 = start.latitude;
+        let e_sin_phi1 = ellipticity * phi1.sin();
+        let left_component = (0.5 * (FRAC_PI_2 + phi1)).tan()
+            * ((intersection_longitude - start.longitude) / heading.tan()).exp();
+        let denominator = (1.0 + e_sin_phi1) / (1.0 - e_sin_phi1);
+
+        let mut new_phi = start.latitude;
+        for _ in 0..100 {
+            let phi = new_phi;
+            let e_sin_phi = ellipticity * phi.sin();
+            let numerator = (1.0 + e_sin_phi) / (1.0 - e_sin_phi);
+            new_phi = 2.0
+                * (left_component * (numerator / denominator).powf(ellipticity / 2.0)).atan()
+                - FRAC_PI_2;
+            if (new_phi - phi).abs() < EPSILON12 {
+                break;
+            }
+        }
+        return Some(Cartographic::new(intersection_longitude, new_phi, 0.0));
+    }
+    /// 与纬线的交点，恒向线沿纬线方向时没有唯一交点
+    pub fn find_intersection_with_latitude(
+        &self,
+        intersection_latitude: f64,
+    ) -> Option<Cartographic> {
+        let heading = self.heading;
+        if (heading.abs() - FRAC_PI_2).abs() <= EPSILON8 {
+            return None;
+        }
+        let sigma1 = calculate_sigma(self.ellipticity, self.start.latitude);
+        let sigma2 = calculate_sigma(self.ellipticity, intersection_latitude);
+        let delta_longitude = heading.tan() * (sigma2 - sigma1);
+        let longitude = nagetive_pi_to_pi(self.start.longitude + delta_longitude);
+        return Some(Cartographic::new(longitude, intersection_latitude, 0.0));
+    }
+    fn calculate_arc_length(&self) -> f64 {
+        let major = self.ellipsoid.maximum_radius;
+        let heading = self.heading;
+        let start = &self.start;
+        let end = &self.end;
+        let distance;
+        if (heading.abs() - FRAC_PI_2).abs() <= EPSILON8 {
+            // 沿纬线
+            let sin_phi = start.latitude.sin();
+            distance =
+                (major * start.latitude.cos() * nagetive_pi_to_pi(end.longitude - start.longitude))
+                    / (1.0 - self.ellipticity_squared * sin_phi * sin_phi).sqrt();
+        } else {
+            let m1 = meridian_distance(&self.ellipsoid, start.latitude);
+            let m2 = meridian_distance(&self.ellipsoid, end.latitude);
+            distance = (m2 - m1) / heading.cos();
+        }
+        return distance.abs();
+    }
+}
+fn ellipticity_squared(ellipsoid: &Ellipsoid) -> f64 {
+    let major_squared = ellipsoid.maximum_radius * ellipsoid.maximum_radius;
+    let minor_squared = ellipsoid.minimum_radius * ellipsoid.minimum_radius;
+    return (major_squared - minor_squared) / major_squared;
+}
+/// 等角纬度
+fn calculate_sigma(ellipticity: f64, latitude: f64) -> f64 {
+    let e_sin_l = ellipticity * latitude.sin();
+    return (0.5 * (FRAC_PI_2 + latitude)).tan().ln()
+        - (ellipticity / 2.0) * ((1.0 + e_sin_l) / (1.0 - e_sin_l)).ln();
+}
+fn calculate_heading(ellipticity: f64, start: &Cartographic, end: &Cartographic) -> f64 {
+    let sigma1 = calculate_sigma(ellipticity, start.latitude);
+    let sigma2 = calculate_sigma(ellipticity, end.latitude);
+    return nagetive_pi_to_pi(end.longitude - start.longitude).atan2(sigma2 - sigma1);
+}
+/// 赤道到纬度latitude的子午线弧长
+fn meridian_distance(ellipsoid: &Ellipsoid, latitude: f64) -> f64 {
+    let a = ellipsoid.maximum_radius;
+    let b = ellipsoid.minimum_radius;
+    let n = (a - b) / (a + b);
+    let n2 = n * n;
+    let n3 = n2 * n;
+    let n4 = n3 * n;
+    let n5 = n4 * n;
+    return (a / (1.0 + n))
+        * ((1.0 + n2 / 4.0 + n4 / 64.0) * latitude
+            - (3.0 / 2.0) * (n - n3 / 8.0 + n5 / 64.0) * (2.0 * latitude).sin()
+            + (15.0 / 16.0) * (n2 - n4 / 4.0) * (4.0 * latitude).sin()
+            - (35.0 / 48.0) * (n3 - (5.0 / 16.0) * n5) * (6.0 * latitude).sin()
+            + (315.0 / 512.0) * n4 * (8.0 * latitude).sin());
+}
+/// 由子午线弧长反求纬度，牛顿迭代
+fn inverse_meridian_distance(ellipsoid: &Ellipsoid, distance: f64) -> f64 {
+    let major = ellipsoid.maximum_radius;
+    let e2 = ellipticity_squared(ellipsoid);
+    let quarter = meridian_distance(ellipsoid, FRAC_PI_2);
+    let mut latitude = (distance / quarter * FRAC_PI_2).clamp(-FRAC_PI_2, FRAC_PI_2);
+    for _ in 0..20 {
+        let sin_phi = latitude.sin();
+        // 子午线曲率半径
+        let radius = major * (1.0 - e2) / (1.0 - e2 * sin_phi * sin_phi).powf(1.5);
+        let delta = (meridian_distance(ellipsoid, latitude) - distance) / radius;
+        latitude = (latitude - delta).clamp(-FRAC_PI_2, FRAC_PI_2);
+        if delta.abs() < EPSILON12 {
+            break;
+        }
+    }
+    return latitude;
+}
+fn interpolate_using_surface_distance(
+    ellipsoid: &Ellipsoid,
+    start: &Cartographic,
+    heading: f64,
+    distance: f64,
+) -> Cartographic {
+    if distance == 0.0 {
+        return Cartographic::new(start.longitude, start.latitude, 0.0);
+    }
+    let major = ellipsoid.maximum_radius;
+    let ellipticity_squared = ellipticity_squared(ellipsoid);
+    let ellipticity = ellipticity_squared.sqrt();
+    let longitude;
+    let latitude;
+    if (FRAC_PI_2 - heading.abs()).abs() > EPSILON8 {
+        let m1 = meridian_distance(ellipsoid, start.latitude);
+        let m2 = m1 + distance * heading.cos();
+        latitude = inverse_meridian_distance(ellipsoid, m2);
+        let sigma1 = calculate_sigma(ellipticity, start.latitude);
+        let sigma2 = calculate_sigma(ellipticity, latitude);
+        let delta_longitude = heading.tan() * (sigma2 - sigma1);
+        longitude = nagetive_pi_to_pi(start.longitude + delta_longitude);
+    } else {
+        // 沿纬线
+        latitude = start.latitude;
+        let sin_phi = start.latitude.sin();
+        let local_radius =
+            (major * start.latitude.cos()) / (1.0 - ellipticity_squared * sin_phi * sin_phi).sqrt();
+        let delta_longitude = distance / local_radius;
+        if heading > 0.0 {
+            longitude = nagetive_pi_to_pi(start.longitude + delta_longitude);
+        } else {
+            longitude = nagetive_pi_to_pi(start.longitude - delta_longitude);
+        }
+    }
+    return Cartographic::new(longitude, latitude, 0.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EPSILON10, EPSILON6};
+
+    #[test]
+    fn test_heading_and_distance() {
+        // 沿赤道向东
+        let start = Cartographic::from_degrees(0.0, 0.0, 0.0);
+        let end = Cartographic::from_degrees(90.0, 0.0, 0.0);
+        let rhumb_line = EllipsoidRhumbLine::new(start, end, None);
+        assert!((rhumb_line.heading() - FRAC_PI_2).abs() < EPSILON12);
+        assert!(
+            (rhumb_line.surface_distance() - Ellipsoid::WGS84.maximum_radius * FRAC_PI_2).abs()
+                < EPSILON6
+        );
+        // 沿经线向北到极点，子午线四分之一弧长
+        let end = Cartographic::from_degrees(0.0, 90.0, 0.0);
+        let rhumb_line = EllipsoidRhumbLine::new(start, end, None);
+        assert!(rhumb_line.heading().abs() < EPSILON12);
+        assert!((rhumb_line.surface_distance() - 10001965.729).abs() < 1e-3);
+        // 向西南
+        let end = Cartographic::from_degrees(-1.0, -1.0, 0.0);
+        let rhumb_line = EllipsoidRhumbLine::new(start, end, None);
+        assert!(rhumb_line.heading() < -FRAC_PI_2 && rhumb_line.heading() > -PI);
+    }
+    #[test]
+    fn test_from_start_heading_distance() {
+        let start = Cartographic::from_degrees(-70.0, 40.0, 0.0);
+        let end = Cartographic::from_degrees(0.0, 50.0, 0.0);
+        let rhumb_line = EllipsoidRhumbLine::new(start, end, None);
+        let other = EllipsoidRhumbLine::from_start_heading_distance(
+            start,
+            rhumb_line.heading(),
+            rhumb_line.surface_distance(),
+            None,
+        );
+        assert!(other.end().equals_epsilon(end, EPSILON10));
+        assert!((other.heading() - rhumb_line.heading()).abs() < EPSILON10);
+
+        // 单位球上沿纬线向西，经度变化为距离/cos(纬度)
+        let other = EllipsoidRhumbLine::from_start_heading_distance(
+            Cartographic::from_degrees(10.0, 20.0, 0.0),
+            -FRAC_PI_2,
+            0.1,
+            Some(Ellipsoid::UNIT_SPHERE),
+        );
+        let expected = 10.0_f64.to_radians() - 0.1 / 20.0_f64.to_radians().cos();
+        assert!((other.end().longitude - expected).abs() < EPSILON12);
+        assert!((other.end().latitude - 20.0_f64.to_radians()).abs() < EPSILON12);
+        assert!((other.surface_distance() - 0.1).abs() < EPSILON12);
+    }
+    #[test]
+    fn test_interpolate() {
+        let start = Cartographic::from_degrees(-70.0, 40.0, 0.0);
+        let end = Cartographic::from_degrees(0.0, 50.0, 0.0);
+        let rhumb_line = EllipsoidRhumbLine::new(start, end, None);
+        assert!(rhumb_line
+            .interpolate_using_fraction(0.0)
+            .equals_epsilon(start, EPSILON12));
+        assert!(rhumb_line
+            .interpolate_using_fraction(1.0)
+            .equals_epsilon(end, EPSILON10));
+        // 中点的方位角不变
+        let middle = rhumb_line.interpolate_using_fraction(0.5);
+        let first_half = EllipsoidRhumbLine::new(start, middle, None);
+        let second_half = EllipsoidRhumbLine::new(middle, end, None);
+        assert!((first_half.heading() - rhumb_line.heading()).abs() < EPSILON10);
+        assert!((second_half.heading() - rhumb_line.heading()).abs() < EPSILON10);
+        assert!(
+            (first_half.surface_distance() - rhumb_line.surface_distance() / 2.0).abs() < EPSILON6
+        );
+        let quarter =
+            rhumb_line.interpolate_using_surface_distance(rhumb_line.surface_distance() / 4.0);
+        assert!(quarter.latitude > start.latitude && quarter.latitude < middle.latitude);
+    }
+    #[test]
+    fn test_find_intersection() {
+        let start = Cartographic::from_degrees(-70.0, 40.0, 0.0);
+        let end = Cartographic::from_degrees(0.0, 50.0, 0.0);
+        let rhumb_line = EllipsoidRhumbLine::new(start, end, None);
+        let middle = rhumb_line.interpolate_using_fraction(0.5);
+
+        let point = rhumb_line
+            .find_intersection_with_longitude(middle.longitude)
+            .unwrap();
+        assert!(point.equals_epsilon(middle, EPSILON10));
+        let point = rhumb_line
+            .find_intersection_with_latitude(middle.latitude)
+            .unwrap();
+        assert!(point.equals_epsilon(middle, EPSILON10));
+        let point = rhumb_line
+            .find_intersection_with_longitude(end.longitude)
+            .unwrap();
+        assert!(point.equals_epsilon(end, EPSILON10));
+
+        // 沿纬线的恒向线与纬线没有唯一交点
+        let east = EllipsoidRhumbLine::new(
+            Cartographic::from_degrees(0.0, 30.0, 0.0),
+            Cartographic::from_degrees(10.0, 30.0, 0.0),
+            None,
+        );
+        assert!(east.find_intersection_with_latitude(0.5).is_none());
+        let point = east
+            .find_intersection_with_longitude(5.0_f64.to_radians())
+            .unwrap();
+        assert!((point.latitude - 30.0_f64.to_radians()).abs() < EPSILON12);
+        // 沿经线的恒向线与其他经线没有交点
+        let north = EllipsoidRhumbLine::new(
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(0.0, 30.0, 0.0),
+            None,
+        );
+        assert!(north.find_intersection_with_longitude(0.5).is_none());
+    }
+}
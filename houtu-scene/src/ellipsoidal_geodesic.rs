@@ -62,6 +62,18 @@ impl EllipsoidGeodesic {
     pub fn setEndPoints(&mut self, start: Cartographic, end: Cartographic) {
         self.computeProperties(&start, &end);
     }
+    /// 两个端点之间的表面距离(米)，需要先调用setEndPoints
+    pub fn surface_distance(&self) -> f64 {
+        return self.distance;
+    }
+    /// 起点处的方位角(弧度)，从正北顺时针
+    pub fn start_heading(&self) -> f64 {
+        return self.startHeading;
+    }
+    /// 终点处的方位角(弧度)
+    pub fn end_heading(&self) -> f64 {
+        return self.endHeading;
+    }
     pub fn interpolateUsingFraction(&mut self, fraction: f64) -> Cartographic {
         return self.interpolateUsingSurfaceDistance(self.distance * fraction);
     }
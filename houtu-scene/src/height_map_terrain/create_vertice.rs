@@ -9,6 +9,7 @@ use crate::{
     ellipsoidal_occluder::EllipsoidalOccluder,
     geometry::{AxisAlignedBoundingBox, BoundingSphere, OrientedBoundingBox},
     math::{eastNorthUpToFixedFrame, Cartesian3, Matrix4},
+    oct_encode,
     terrain_encoding::TerrainEncoding,
     web_mercator_projection::WebMercatorProjection,
 };
@@ -49,6 +50,8 @@ pub struct CreateVerticeOptions<'a> {
     pub ellipsoid: Option<Ellipsoid>,
     pub structure: Option<HeightmapTerrainStructure>,
     pub includeWebMercatorT: Option<bool>,
+    /// 根据相邻格网点计算顶点法线，用于地形光照
+    pub includeVertexNormals: Option<bool>,
 }
 pub struct CreateVerticeReturn {
     pub vertices: Vec<f32>,
@@ -110,6 +113,7 @@ pub fn create_vertice(options: CreateVerticeOptions) -> CreateVerticeReturn {
     let relativeToCenter = options.relativeToCenter.unwrap_or(DVec3::ZERO);
     let hasRelativeToCenter = options.relativeToCenter.is_some();
     let includeWebMercatorT = options.includeWebMercatorT.unwrap_or(false);
+    let includeVertexNormals = options.includeVertexNormals.unwrap_or(false);
     let exaggeration = options.exaggeration.unwrap_or(1.0);
     let exaggeration_relative_height = options.exaggeration_relative_height.unwrap_or(0.0);
     let hasExaggeration = exaggeration != 1.0;
@@ -205,6 +209,8 @@ pub fn create_vertice(options: CreateVerticeOptions) -> CreateVerticeReturn {
             None
         }
     };
+    // 裙边顶点对应的格网顶点，裙边的法线和格网顶点相同
+    let mut skirtSources: Vec<u32> = vec![0; edge_vertex_count as usize];
     let mut startRow: i32 = 0;
     let mut endRow: i32 = height as i32;
     let mut startCol: i32 = 0;
@@ -334,6 +340,8 @@ pub fn create_vertice(options: CreateVerticeOptions) -> CreateVerticeReturn {
                         // Add after west, south, and east indices. North indices are ordered west to east.
                         index = grid_vertex_count + height + width + height + (col as u32);
                     }
+                    skirtSources[(index - grid_vertex_count) as usize] =
+                        (row as u32) * width + (col as u32);
                 }
             }
 
@@ -377,6 +385,33 @@ pub fn create_vertice(options: CreateVerticeOptions) -> CreateVerticeReturn {
         }
     }
 
+    let vertexNormalsOption = if includeVertexNormals {
+        let mut normals = vec![DVec2::ZERO; vertex_count as usize];
+        for row in 0..height {
+            for col in 0..width {
+                let index = (row * width + col) as usize;
+                let west = positions[(row * width + col.saturating_sub(1)) as usize];
+                let east = positions[(row * width + (col + 1).min(width - 1)) as usize];
+                let north = positions[(row.saturating_sub(1) * width + col) as usize];
+                let south = positions[((row + 1).min(height - 1) * width + col) as usize];
+                let mut normal = (east - west).cross(north - south).normalize_or_zero();
+                // 两极处同一行的点重合，退化成椭球面法线
+                if normal == DVec3::ZERO {
+                    normal = ellipsoid
+                        .geodetic_surface_normal(&positions[index])
+                        .unwrap_or(DVec3::Z);
+                }
+                normals[index] = oct_encode(&normal);
+            }
+        }
+        for (i, source) in skirtSources.iter().enumerate() {
+            normals[grid_vertex_count as usize + i] = normals[*source as usize];
+        }
+        Some(normals)
+    } else {
+        None
+    };
+
     let bounding_sphere_3d = BoundingSphere::from_points(&positions);
     let mut oriented_bounding_box = OrientedBoundingBox::default();
     if let Some(rectangle) = rectangleOption {
@@ -406,7 +441,7 @@ pub fn create_vertice(options: CreateVerticeOptions) -> CreateVerticeReturn {
         Some(hMin),
         Some(maximum_height),
         Some(from_enu),
-        false,
+        includeVertexNormals,
         Some(includeWebMercatorT),
         Some(includeGeodeticSurfaceNormals),
         Some(exaggeration),
@@ -425,7 +460,7 @@ pub fn create_vertice(options: CreateVerticeOptions) -> CreateVerticeReturn {
             &mut positions[jj],
             &uvs[jj],
             heights[jj],
-            None,
+            vertexNormalsOption.as_ref().and_then(|x| Some(x[jj])),
             webMercatorTsOption.as_ref().and_then(|x| Some(x[jj])),
             geodeticSurfaceNormalsOption
                 .as_ref()
@@ -447,7 +482,7 @@ pub fn create_vertice(options: CreateVerticeOptions) -> CreateVerticeReturn {
 
 #[cfg(test)]
 mod tests {
-    use crate::{equals_epsilon, lerp, Cartographic, EPSILON7};
+    use crate::{equals_epsilon, lerp, oct_decode_float, Cartographic, EPSILON7};
     use std::f64::consts::PI;
 
     use super::*;
@@ -487,6 +522,7 @@ mod tests {
             structure: Some(structure.clone()),
             isGeographic: None,
             includeWebMercatorT: None,
+            includeVertexNormals: None,
             exaggeration: None,
             exaggeration_relative_height: None,
             relativeToCenter: None,
@@ -546,6 +582,50 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn test_vertex_normals() {
+        let width = 3;
+        let height = 3;
+        let mut heightmap: Vec<f32> = vec![0.0; 9];
+        let rectangle = Rectangle::new(
+            10.0.to_radians(),
+            30.0.to_radians(),
+            20.0.to_radians(),
+            40.0.to_radians(),
+        );
+        let options = CreateVerticeOptions {
+            heightmap: &mut heightmap,
+            width: width,
+            height: height,
+            skirt_height: 10.0,
+            native_rectangle: Rectangle::new(10.0, 30.0, 20.0, 40.0),
+            rectangle: Some(rectangle.clone()),
+            structure: None,
+            isGeographic: None,
+            includeWebMercatorT: None,
+            includeVertexNormals: Some(true),
+            exaggeration: None,
+            exaggeration_relative_height: None,
+            relativeToCenter: Some(Ellipsoid::WGS84.cartographic_to_cartesian(&rectangle.center())),
+            ellipsoid: None,
+        };
+        let results = create_vertice(options);
+        let encoding = results.encoding;
+        assert!(encoding.has_vertex_normals);
+        let stride = encoding.stride as usize;
+        let offset = encoding._offset_vertex_normal as usize;
+        // 平坦的地形，中心点的法线就是椭球面法线
+        let expected = Ellipsoid::WGS84
+            .geodetic_surface_normal_cartographic(&rectangle.center());
+        let normal = oct_decode_float(results.vertices[4 * stride + offset] as f64);
+        assert!(normal.equals_epsilon(expected, Some(2.0 / 127.0), None));
+        // 裙边顶点沿用格网顶点的法线
+        let vertex_count = results.vertices.len() / stride;
+        assert!(vertex_count == 9 + 12);
+        let west_skirt = results.vertices[9 * stride + offset];
+        let south_west = results.vertices[6 * stride + offset];
+        assert!(west_skirt == south_west);
+    }
     fn compute(index: i32, num: i32) -> f64 {
         return index as f64 / (num as f64);
     }
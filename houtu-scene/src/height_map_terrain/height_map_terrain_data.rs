@@ -33,6 +33,8 @@ pub struct HeightmapTerrainData {
     pub _water_mask: Option<Vec<u8>>,
     pub _skirt_height: Option<f64>,
     pub _mesh: Option<TerrainMesh>,
+    /// 生成网格时计算顶点法线，只有开启光照时才需要，由地形提供者设置
+    pub _include_vertex_normals: bool,
 }
 
 impl HeightmapTerrainData {
@@ -59,6 +61,7 @@ impl HeightmapTerrainData {
             _water_mask: water_mask,
             _skirt_height: skirt_height,
             _mesh: mesh,
+            _include_vertex_normals: false,
         }
     }
     pub fn get_mesh(&self) -> Option<&TerrainMesh> {
@@ -171,6 +174,7 @@ impl HeightmapTerrainData {
             heightmap: &mut self._buffer,
             structure: Some(structure),
             includeWebMercatorT: Some(true),
+            includeVertexNormals: Some(self._include_vertex_normals),
             width: self._width,
             height: self._height,
             native_rectangle: native_rectangle,
@@ -230,13 +234,13 @@ impl HeightmapTerrainData {
             let latitude = lerp(
                 destination_rectangle.north,
                 destination_rectangle.south,
-                (j / (height - 1)) as f64,
+                j as f64 / (height - 1) as f64,
             );
             for i in 0..width {
                 let longitude = lerp(
                     destination_rectangle.west,
                     destination_rectangle.east,
-                    (i / (width - 1)) as f64,
+                    i as f64 / (width - 1) as f64,
                 );
                 let mut heightSample = interpolateMeshHeight(
                     &buffer,
@@ -275,7 +279,7 @@ impl HeightmapTerrainData {
                 );
             }
         }
-        return Some(HeightmapTerrainData::new(
+        let mut result = HeightmapTerrainData::new(
             heights,
             width,
             height,
@@ -286,7 +290,9 @@ impl HeightmapTerrainData {
             None,
             None,
             None,
-        ));
+        );
+        result._include_vertex_normals = self._include_vertex_normals;
+        return Some(result);
     }
 }
 
@@ -1,10 +1,6 @@
-use bevy::{
-    math::{DVec3},
-};
+use bevy::math::DVec3;
 
-use crate::{
-    BoundingSphere, OrientedBoundingBox, TerrainEncoding,
-};
+use crate::{BoundingSphere, IntersectionTests, OrientedBoundingBox, Ray, TerrainEncoding};
 use bevy::prelude::*;
 #[derive(Default, Clone, Debug, Component)]
 pub struct TerrainMesh {
@@ -63,4 +59,99 @@ impl TerrainMesh {
             north_indices_west_to_east,
         }
     }
+    pub fn get_position(&self, index: u32) -> DVec3 {
+        return self
+            .encoding
+            .decode_position(&self.vertices, index as usize);
+    }
+    /// 射线与网格(不含裙边)最近的交点
+    pub fn pick(&self, ray: &Ray, cull_back_faces: bool) -> Option<DVec3> {
+        let index_count = self
+            .index_count_without_skirts
+            .map(|v| v as usize)
+            .unwrap_or(self.indices.len())
+            .min(self.indices.len());
+        let mut min_t: Option<f64> = None;
+        for triangle in self.indices[..index_count].chunks_exact(3) {
+            let p0 = self.get_position(triangle[0]);
+            let p1 = self.get_position(triangle[1]);
+            let p2 = self.get_position(triangle[2]);
+            let Some(t) =
+                IntersectionTests::ray_triangle_parametric(ray, &p0, &p1, &p2, cull_back_faces)
+            else {
+                continue;
+            };
+            if t >= 0.0 && min_t.map_or(true, |v| t < v) {
+                min_t = Some(t);
+            }
+        }
+        return min_t.map(|t| ray.getPoint(t));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{DMat4, DVec2};
+
+    use crate::{Cartesian3, Ellipsoid, Matrix4, Transforms, EPSILON3};
+
+    use super::*;
+
+    /// 以center为中心、边长2000米的正方形，两个三角形，高度分别为0和100
+    fn create_mesh() -> (TerrainMesh, DMat4) {
+        let center = Ellipsoid::WGS84
+            .cartographic_to_cartesian(&crate::Cartographic::from_degrees(120.0, 30.0, 0.0));
+        let enu = Transforms::eastNorthUpToFixedFrame(&center, None);
+        let encoding = TerrainEncoding::new(
+            center, None, None, None, None, false, None, None, None, None,
+        );
+        let corners = [
+            DVec3::new(-1000.0, -1000.0, 0.0),
+            DVec3::new(1000.0, -1000.0, 0.0),
+            DVec3::new(1000.0, 1000.0, 100.0),
+            DVec3::new(-1000.0, 1000.0, 100.0),
+        ];
+        let mut vertices = vec![0.0; encoding.stride as usize * corners.len()];
+        let mut index = 0;
+        for corner in corners.iter() {
+            let mut position = enu.multiply_by_point(corner);
+            index = encoding.encode(
+                &mut vertices,
+                index,
+                &mut position,
+                &DVec2::ZERO,
+                corner.z,
+                None,
+                None,
+                None,
+            );
+        }
+        let mut mesh = TerrainMesh::default();
+        mesh.center = center;
+        mesh.vertices = vertices;
+        mesh.indices = vec![0, 1, 2, 0, 2, 3];
+        mesh.index_count_without_skirts = Some(6);
+        mesh.vertex_stride = encoding.stride;
+        mesh.encoding = encoding;
+        return (mesh, enu);
+    }
+    #[test]
+    fn test_pick() {
+        let (mesh, enu) = create_mesh();
+        let up = enu.multiply_by_point_as_vector(&DVec3::UNIT_Z);
+        // 从上方垂直向下，东北角的三角形在y=500处高度为75
+        let origin = enu.multiply_by_point(&DVec3::new(500.0, 500.0, 1000.0));
+        let ray = Ray::new(origin, up.negate());
+        let actual = mesh.pick(&ray, true).unwrap();
+        let expected = enu.multiply_by_point(&DVec3::new(500.0, 500.0, 75.0));
+        assert!(actual.equals_epsilon(expected, None, Some(EPSILON3)));
+        // 从下方向上时剔除背面
+        let origin = enu.multiply_by_point(&DVec3::new(500.0, 500.0, -1000.0));
+        let ray = Ray::new(origin, up);
+        assert!(mesh.pick(&ray, true).is_none());
+        assert!(mesh.pick(&ray, false).is_some());
+        // 网格外
+        let origin = enu.multiply_by_point(&DVec3::new(1500.0, 0.0, 1000.0));
+        assert!(mesh.pick(&Ray::new(origin, up.negate()), false).is_none());
+    }
 }
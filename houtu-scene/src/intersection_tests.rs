@@ -1,10 +1,10 @@
-use std::f64::{NEG_INFINITY};
+use std::f64::NEG_INFINITY;
 
 use bevy::math::{DMat3, DVec3};
 
 use crate::{
-    Cartesian3, Ellipsoid, Matrix3, Plane, QuadraticRealPolynomial,
-    QuarticRealPolynomial, Ray, EPSILON12, EPSILON15,
+    BoundingSphere, Cartesian3, Ellipsoid, Matrix3, Plane, QuadraticRealPolynomial,
+    QuarticRealPolynomial, Ray, EPSILON12, EPSILON15, EPSILON6,
 };
 #[derive(Default)]
 pub struct Interval {
@@ -37,6 +37,92 @@ impl IntersectionTests {
 
         return Some(origin + direction.multiply_by_scalar(t));
     }
+    /// 射线与三角形相交时返回射线参数t，cull_back_faces为true时忽略背面
+    pub fn ray_triangle_parametric(
+        ray: &Ray,
+        p0: &DVec3,
+        p1: &DVec3,
+        p2: &DVec3,
+        cull_back_faces: bool,
+    ) -> Option<f64> {
+        let origin = ray.origin;
+        let direction = ray.direction;
+
+        let edge0 = *p1 - *p0;
+        let edge1 = *p2 - *p0;
+
+        let p = direction.cross(edge1);
+        let det = edge0.dot(p);
+
+        let t;
+        if cull_back_faces {
+            if det < EPSILON6 {
+                return None;
+            }
+            let tvec = origin - *p0;
+            let u = tvec.dot(p);
+            if u < 0.0 || u > det {
+                return None;
+            }
+            let q = tvec.cross(edge0);
+            let v = direction.dot(q);
+            if v < 0.0 || u + v > det {
+                return None;
+            }
+            t = edge1.dot(q) / det;
+        } else {
+            if det.abs() < EPSILON6 {
+                return None;
+            }
+            let inv_det = 1.0 / det;
+            let tvec = origin - *p0;
+            let u = tvec.dot(p) * inv_det;
+            if u < 0.0 || u > 1.0 {
+                return None;
+            }
+            let q = tvec.cross(edge0);
+            let v = direction.dot(q) * inv_det;
+            if v < 0.0 || u + v > 1.0 {
+                return None;
+            }
+            t = edge1.dot(q) * inv_det;
+        }
+        return Some(t);
+    }
+    pub fn ray_triangle(
+        ray: &Ray,
+        p0: &DVec3,
+        p1: &DVec3,
+        p2: &DVec3,
+        cull_back_faces: bool,
+    ) -> Option<DVec3> {
+        let t = IntersectionTests::ray_triangle_parametric(ray, p0, p1, p2, cull_back_faces)?;
+        if t < 0.0 {
+            return None;
+        }
+        return Some(ray.getPoint(t));
+    }
+    /// 射线与球相交的区间，射线起点在球内时start为0
+    pub fn ray_sphere(ray: &Ray, sphere: &BoundingSphere) -> Option<Interval> {
+        let radius_squared = sphere.radius * sphere.radius;
+        let diff = ray.origin - sphere.center;
+
+        let a = ray.direction.dot(ray.direction);
+        let b = 2.0 * ray.direction.dot(diff);
+        let c = diff.magnitude_squared() - radius_squared;
+
+        let det = b * b - 4.0 * a * c;
+        if det < 0.0 {
+            return None;
+        }
+        let det = det.sqrt();
+        let root0 = (-b - det) / (2.0 * a);
+        let root1 = (-b + det) / (2.0 * a);
+        if root1 < 0.0 {
+            return None;
+        }
+        return Some(Interval::new(root0.max(0.0), root1));
+    }
     pub fn ray_ellipsoid(ray: &Ray, ellipsoid: Option<&Ellipsoid>) -> Option<Interval> {
         let ellipsoid = ellipsoid.unwrap_or(&Ellipsoid::WGS84);
         let inverse_radii = ellipsoid.one_over_radii;
@@ -344,4 +430,50 @@ mod tests {
         let actual = IntersectionTests::grazing_altitude_location(&ray, Some(&ellipsoid));
         assert!(actual.unwrap().eq(&ray.origin));
     }
+    #[test]
+    fn ray_triangle_intersection() {
+        let p0 = DVec3::new(-1.0, -1.0, 0.0);
+        let p1 = DVec3::new(1.0, -1.0, 0.0);
+        let p2 = DVec3::new(0.0, 1.0, 0.0);
+        // 从正面射入
+        let ray = Ray::new(DVec3::new(0.0, 0.0, 5.0), DVec3::new(0.0, 0.0, -1.0));
+        let actual = IntersectionTests::ray_triangle(&ray, &p0, &p1, &p2, true);
+        assert!(actual
+            .unwrap()
+            .equals_epsilon(DVec3::ZERO, Some(EPSILON14), None));
+        assert!(
+            IntersectionTests::ray_triangle_parametric(&ray, &p0, &p1, &p2, false) == Some(5.0)
+        );
+        // 背面
+        let ray = Ray::new(DVec3::new(0.0, 0.0, -5.0), DVec3::UNIT_Z);
+        assert!(IntersectionTests::ray_triangle(&ray, &p0, &p1, &p2, true).is_none());
+        assert!(IntersectionTests::ray_triangle(&ray, &p0, &p1, &p2, false).is_some());
+        // 三角形在射线后方
+        let ray = Ray::new(DVec3::new(0.0, 0.0, 5.0), DVec3::UNIT_Z);
+        assert!(IntersectionTests::ray_triangle(&ray, &p0, &p1, &p2, false).is_none());
+        // 偏出三角形
+        let ray = Ray::new(DVec3::new(2.0, 0.0, 5.0), DVec3::new(0.0, 0.0, -1.0));
+        assert!(IntersectionTests::ray_triangle(&ray, &p0, &p1, &p2, false).is_none());
+    }
+    #[test]
+    fn ray_sphere_intersection() {
+        let sphere = BoundingSphere::new(DVec3::ZERO, 1.0);
+        let ray = Ray::new(DVec3::new(-3.0, 0.0, 0.0), DVec3::UNIT_X);
+        let actual = IntersectionTests::ray_sphere(&ray, &sphere).unwrap();
+        assert!(equals_epsilon(actual.start, 2.0, Some(EPSILON14), None));
+        assert!(equals_epsilon(actual.stop, 4.0, Some(EPSILON14), None));
+        let inside = Ray::new(DVec3::ZERO, DVec3::UNIT_X);
+        let actual = IntersectionTests::ray_sphere(&inside, &sphere).unwrap();
+        assert!(actual.start == 0.0);
+        assert!(IntersectionTests::ray_sphere(
+            &Ray::new(DVec3::new(-3.0, 2.0, 0.0), DVec3::UNIT_X),
+            &sphere
+        )
+        .is_none());
+        assert!(IntersectionTests::ray_sphere(
+            &Ray::new(DVec3::new(3.0, 0.0, 0.0), DVec3::UNIT_X),
+            &sphere
+        )
+        .is_none());
+    }
 }
@@ -0,0 +1,156 @@
+use bevy::math::DVec3;
+
+pub struct Intersections2D;
+impl Intersections2D {
+    /// 用坐标轴对齐的阈值线裁剪三角形，返回裁剪后的多边形。
+    ///
+    /// 结果中非负数表示原三角形的顶点索引(0,1,2)；`-1`表示插值得到的新顶点，
+    /// 后面紧跟三个数：第一个顶点索引、第二个顶点索引以及两者之间的插值比例。
+    pub fn clip_triangle_at_axis_aligned_threshold(
+        threshold: f64,
+        keep_above: bool,
+        u0: f64,
+        u1: f64,
+        u2: f64,
+    ) -> Vec<f64> {
+        let mut result = vec![];
+        let (u0_behind, u1_behind, u2_behind) = if keep_above {
+            (u0 < threshold, u1 < threshold, u2 < threshold)
+        } else {
+            (u0 > threshold, u1 > threshold, u2 > threshold)
+        };
+        let num_behind = u0_behind as u32 + u1_behind as u32 + u2_behind as u32;
+
+        if num_behind == 1 {
+            if u0_behind {
+                let u01_ratio = (threshold - u0) / (u1 - u0);
+                let u02_ratio = (threshold - u0) / (u2 - u0);
+                result.extend([1.0, 2.0]);
+                if u02_ratio != 1.0 {
+                    result.extend([-1.0, 0.0, 2.0, u02_ratio]);
+                }
+                if u01_ratio != 1.0 {
+                    result.extend([-1.0, 0.0, 1.0, u01_ratio]);
+                }
+            } else if u1_behind {
+                let u12_ratio = (threshold - u1) / (u2 - u1);
+                let u10_ratio = (threshold - u1) / (u0 - u1);
+                result.extend([2.0, 0.0]);
+                if u10_ratio != 1.0 {
+                    result.extend([-1.0, 1.0, 0.0, u10_ratio]);
+                }
+                if u12_ratio != 1.0 {
+                    result.extend([-1.0, 1.0, 2.0, u12_ratio]);
+                }
+            } else if u2_behind {
+                let u20_ratio = (threshold - u2) / (u0 - u2);
+                let u21_ratio = (threshold - u2) / (u1 - u2);
+                result.extend([0.0, 1.0]);
+                if u21_ratio != 1.0 {
+                    result.extend([-1.0, 2.0, 1.0, u21_ratio]);
+                }
+                if u20_ratio != 1.0 {
+                    result.extend([-1.0, 2.0, 0.0, u20_ratio]);
+                }
+            }
+        } else if num_behind == 2 {
+            if !u0_behind && u0 != threshold {
+                let u10_ratio = (threshold - u1) / (u0 - u1);
+                let u20_ratio = (threshold - u2) / (u0 - u2);
+                result.extend([0.0, -1.0, 1.0, 0.0, u10_ratio, -1.0, 2.0, 0.0, u20_ratio]);
+            } else if !u1_behind && u1 != threshold {
+                let u21_ratio = (threshold - u2) / (u1 - u2);
+                let u01_ratio = (threshold - u0) / (u1 - u0);
+                result.extend([1.0, -1.0, 2.0, 1.0, u21_ratio, -1.0, 0.0, 1.0, u01_ratio]);
+            } else if !u2_behind && u2 != threshold {
+                let u02_ratio = (threshold - u0) / (u2 - u0);
+                let u12_ratio = (threshold - u1) / (u2 - u1);
+                result.extend([2.0, -1.0, 0.0, 2.0, u02_ratio, -1.0, 1.0, 2.0, u12_ratio]);
+            }
+        } else if num_behind != 3 {
+            // 整个三角形都在保留的一侧
+            result.extend([0.0, 1.0, 2.0]);
+        }
+        // num_behind == 3 时整个三角形都被裁掉
+        return result;
+    }
+    /// 计算点(x,y)相对于三角形(x1,y1),(x2,y2),(x3,y3)的重心坐标
+    pub fn compute_barycentric_coordinates(
+        x: f64,
+        y: f64,
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        x3: f64,
+        y3: f64,
+    ) -> DVec3 {
+        let x1mx3 = x1 - x3;
+        let x3mx2 = x3 - x2;
+        let y2my3 = y2 - y3;
+        let y1my3 = y1 - y3;
+        let inverse_determinant = 1.0 / (y2my3 * x1mx3 + x3mx2 * y1my3);
+        let ymy3 = y - y3;
+        let xmx3 = x - x3;
+        let l1 = (y2my3 * xmx3 + x3mx2 * ymy3) * inverse_determinant;
+        let l2 = (-y1my3 * xmx3 + x1mx3 * ymy3) * inverse_determinant;
+        let l3 = 1.0 - l1 - l2;
+        return DVec3::new(l1, l2, l3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{equals_epsilon, EPSILON15};
+
+    #[test]
+    fn clip_triangle_completely_in_front() {
+        let result =
+            Intersections2D::clip_triangle_at_axis_aligned_threshold(0.5, false, 0.1, 0.2, 0.3);
+        assert!(result == vec![0.0, 1.0, 2.0]);
+    }
+    #[test]
+    fn clip_triangle_completely_behind() {
+        let result =
+            Intersections2D::clip_triangle_at_axis_aligned_threshold(0.5, true, 0.1, 0.2, 0.3);
+        assert!(result.is_empty());
+    }
+    #[test]
+    fn clip_triangle_with_one_vertex_behind() {
+        let result =
+            Intersections2D::clip_triangle_at_axis_aligned_threshold(0.5, false, 1.0, 0.0, -1.0);
+        assert!(result == vec![1.0, 2.0, -1.0, 0.0, 2.0, 0.25, -1.0, 0.0, 1.0, 0.5]);
+    }
+    #[test]
+    fn clip_triangle_with_two_vertices_behind() {
+        let result =
+            Intersections2D::clip_triangle_at_axis_aligned_threshold(0.5, true, 1.0, 0.0, -1.0);
+        assert!(result == vec![0.0, -1.0, 1.0, 0.0, 0.5, -1.0, 2.0, 0.0, 0.75]);
+    }
+    #[test]
+    fn barycentric_coordinates_at_vertices() {
+        let result =
+            Intersections2D::compute_barycentric_coordinates(0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0);
+        assert!(result == DVec3::new(1.0, 0.0, 0.0));
+        let result =
+            Intersections2D::compute_barycentric_coordinates(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0);
+        assert!(result == DVec3::new(0.0, 1.0, 0.0));
+    }
+    #[test]
+    fn barycentric_coordinates_at_centroid() {
+        let result = Intersections2D::compute_barycentric_coordinates(
+            1.0 / 3.0,
+            1.0 / 3.0,
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+            1.0,
+        );
+        assert!(equals_epsilon(result.x, 1.0 / 3.0, Some(EPSILON15), None));
+        assert!(equals_epsilon(result.y, 1.0 / 3.0, Some(EPSILON15), None));
+        assert!(equals_epsilon(result.z, 1.0 / 3.0, Some(EPSILON15), None));
+    }
+}
@@ -8,13 +8,13 @@
 // mod load_file_system;
 // mod tile_boundingR_region;
 // mod tiling_scheme;
-// mod wmts_imagery_layer;
 mod attribute_compression;
 mod bounding_rectangle;
 mod bounding_volume;
 mod cubic_real_polynomial;
 mod culling_volume;
 mod ellipsoid;
+mod ellipsoid_rhumb_line;
 mod ellipsoidal_geodesic;
 mod ellipsoidal_occluder;
 mod frustum_geometry;
@@ -23,13 +23,18 @@ mod geographic_tiling_scheme;
 mod geometry;
 mod height_map_terrain;
 mod intersection_tests;
+mod intersections_2d;
 mod math;
+mod measurement;
 mod perspective_frustum;
 mod perspective_off_center_frustum;
+mod polygon_pipeline;
+mod polyline_pipeline;
 mod projection;
 mod quadratic_real_polynomial;
 mod quartic_real_polynomial;
 mod scene_transform;
+mod sun;
 mod terrain_encoding;
 mod terrain_provider;
 mod terrain_quantization;
@@ -39,13 +44,13 @@ mod tiling_scheme;
 mod visibility;
 mod web_mecator_tiling_scheme;
 mod web_mercator_projection;
-mod wmts_imagery_provider;
 pub use attribute_compression::*;
 pub use bounding_rectangle::*;
 pub use bounding_volume::*;
 pub use cubic_real_polynomial::*;
 pub use culling_volume::*;
 pub use ellipsoid::Ellipsoid;
+pub use ellipsoid_rhumb_line::*;
 pub use ellipsoidal_geodesic::*;
 pub use ellipsoidal_occluder::*;
 pub use frustum_geometry::*;
@@ -54,12 +59,16 @@ pub use geographic_tiling_scheme::*;
 pub use geometry::*;
 pub use height_map_terrain::*;
 pub use math::*;
+pub use measurement::*;
 pub use perspective_frustum::*;
 pub use perspective_off_center_frustum::*;
+pub use polygon_pipeline::*;
+pub use polyline_pipeline::*;
 pub use projection::*;
 pub use quadratic_real_polynomial::*;
 pub use quartic_real_polynomial::*;
 pub use scene_transform::*;
+pub use sun::*;
 pub use terrain_encoding::*;
 pub use terrain_provider::*;
 pub use terrain_quantization::*;
@@ -69,6 +78,6 @@ pub use web_mecator_tiling_scheme::*;
 pub use web_mercator_projection::*;
 
 pub use intersection_tests::*;
+pub use intersections_2d::*;
 pub use tile_bounding_region::*;
 pub use visibility::*;
-// pub use wmts_imagery_provider::*;
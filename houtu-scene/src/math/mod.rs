@@ -16,6 +16,7 @@ pub mod epsilon_f32;
 mod heading_pitch_range;
 mod heading_pitch_roll;
 mod matrix4;
+mod near_far_scalar;
 mod transform;
 pub use cartesian2::*;
 pub use cartesian3::*;
@@ -25,6 +26,7 @@ pub use cartographic::*;
 pub use heading_pitch_range::*;
 pub use heading_pitch_roll::*;
 pub use matrix4::*;
+pub use near_far_scalar::*;
 pub use quaternion::*;
 pub use transform::*;
 
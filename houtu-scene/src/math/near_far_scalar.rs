@@ -0,0 +1,43 @@
+/// 根据到相机的距离在两个值之间插值，对应Cesium的NearFarScalar
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NearFarScalar {
+    pub near: f64,
+    pub near_value: f64,
+    pub far: f64,
+    pub far_value: f64,
+}
+impl NearFarScalar {
+    pub fn new(near: f64, near_value: f64, far: f64, far_value: f64) -> Self {
+        return Self {
+            near,
+            near_value,
+            far,
+            far_value,
+        };
+    }
+    /// 小于near时取near_value，大于far时取far_value，中间线性插值
+    pub fn evaluate(&self, distance: f64) -> f64 {
+        if self.far <= self.near {
+            return self.near_value;
+        }
+        let t = ((distance - self.near) / (self.far - self.near)).clamp(0.0, 1.0);
+        return self.near_value + (self.far_value - self.near_value) * t;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let scalar = NearFarScalar::new(1000.0, 2.0, 11000.0, 0.5);
+        assert_eq!(scalar.evaluate(0.0), 2.0);
+        assert_eq!(scalar.evaluate(6000.0), 1.25);
+        assert_eq!(scalar.evaluate(1e7), 0.5);
+        assert_eq!(
+            NearFarScalar::new(10.0, 3.0, 10.0, 1.0).evaluate(100.0),
+            3.0
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::f64::consts::PI;
+
+use crate::{
+    ellipsoid::Ellipsoid, nagetive_pi_to_pi, zero_to_two_pi, Cartographic, EllipsoidGeodesic,
+    EllipsoidRhumbLine,
+};
+
+/// 两点间的测地线，近似对跖的两点EllipsoidGeodesic无法计算
+fn geodesic(start: &Cartographic, end: &Cartographic) -> Option<EllipsoidGeodesic> {
+    let ellipsoid = Ellipsoid::WGS84;
+    let first = ellipsoid.cartographic_to_cartesian(start).normalize();
+    let last = ellipsoid.cartographic_to_cartesian(end).normalize();
+    if (first.angle_between(last).abs() - PI).abs() < 0.0125 {
+        return None;
+    }
+    let mut geodesic = EllipsoidGeodesic::default();
+    geodesic.setEndPoints(*start, *end);
+    return Some(geodesic);
+}
+/// WGS84椭球面上两点间的测地线距离(米)，忽略高度
+pub fn geodesic_distance(start: &Cartographic, end: &Cartographic) -> Option<f64> {
+    return geodesic(start, end).map(|v| v.surface_distance());
+}
+/// 起点和终点处的方位角(弧度)，从正北顺时针，范围[0,2π)
+pub fn geodesic_bearing(start: &Cartographic, end: &Cartographic) -> Option<(f64, f64)> {
+    return geodesic(start, end).map(|v| {
+        (
+            zero_to_two_pi(v.start_heading()) % (2.0 * PI),
+            zero_to_two_pi(v.end_heading()) % (2.0 * PI),
+        )
+    });
+}
+/// 折线各段测地线距离之和
+pub fn polyline_length(positions: &[Cartographic]) -> Option<f64> {
+    let mut length = 0.0;
+    for pair in positions.windows(2) {
+        length += geodesic_distance(&pair[0], &pair[1])?;
+    }
+    return Some(length);
+}
+/// 多边形周长，首尾不需要重复
+pub fn polygon_perimeter(positions: &[Cartographic]) -> Option<f64> {
+    if positions.len() < 2 {
+        return Some(0.0);
+    }
+    let closing = geodesic_distance(positions.last().unwrap(), &positions[0])?;
+    return Some(polyline_length(positions)? + closing);
+}
+
+fn eccentricity(ellipsoid: &Ellipsoid) -> f64 {
+    let a = ellipsoid.maximum_radius;
+    let b = ellipsoid.minimum_radius;
+    return (1.0 - (b * b) / (a * a)).sqrt();
+}
+/// 恒向线(等角航线)距离(米)，经度差取较短的方向
+pub fn rhumb_distance(start: &Cartographic, end: &Cartographic) -> f64 {
+    return EllipsoidRhumbLine::new(*start, *end, None).surface_distance();
+}
+/// 椭球面上多边形的面积(平方米)，首尾不需要重复
+///
+/// 把顶点转换到等面积球(authalic sphere)上按球面多边形计算，边在球上是大圆弧，
+/// 对于沿赤道和子午线的边结果是精确的。环绕极点的多边形取包含极点的一侧
+pub fn geodesic_polygon_area(positions: &[Cartographic]) -> f64 {
+    if positions.len() < 3 {
+        return 0.0;
+    }
+    let ellipsoid = Ellipsoid::WGS84;
+    let a = ellipsoid.maximum_radius;
+    let e = eccentricity(&ellipsoid);
+    let e2 = e * e;
+    let q = |latitude: f64| -> f64 {
+        let sine = latitude.sin();
+        return (1.0 - e2) * (sine / (1.0 - e2 * sine * sine) + (e * sine).atanh() / e);
+    };
+    let q_pole = q(PI / 2.0);
+    let radius_squared = a * a * q_pole / 2.0;
+    let authalic_latitude =
+        |latitude: f64| -> f64 { (q(latitude) / q_pole).clamp(-1.0, 1.0).asin() };
+
+    // 每条边与赤道之间的带符号面积
+    let mut excess = 0.0;
+    let mut winding = 0.0;
+    for i in 0..positions.len() {
+        let p1 = &positions[i];
+        let p2 = &positions[(i + 1) % positions.len()];
+        let delta_longitude = nagetive_pi_to_pi(p2.longitude - p1.longitude);
+        let t1 = (authalic_latitude(p1.latitude) / 2.0).tan();
+        let t2 = (authalic_latitude(p2.latitude) / 2.0).tan();
+        excess += 2.0 * ((delta_longitude / 2.0).tan() * (t1 + t2)).atan2(1.0 + t1 * t2);
+        winding += delta_longitude;
+    }
+    // 环绕极点时取极点一侧
+    if winding.abs() > PI {
+        return (2.0 * PI - excess.abs()) * radius_squared;
+    }
+    return excess.abs() * radius_squared;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_dms(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+        let value = degrees.abs() + minutes / 60.0 + seconds / 3600.0;
+        return value.copysign(degrees).to_radians();
+    }
+    #[test]
+    fn test_geodesic_distance_and_bearing() {
+        // Vincenty论文中的例子：Flinders Peak到Buninyong
+        let start = Cartographic::new(
+            from_dms(144.0, 25.0, 29.52440),
+            from_dms(-37.0, 57.0, 3.72030),
+            0.0,
+        );
+        let end = Cartographic::new(
+            from_dms(143.0, 55.0, 35.38390),
+            from_dms(-37.0, 39.0, 10.15610),
+            0.0,
+        );
+        let distance = geodesic_distance(&start, &end).unwrap();
+        assert!((distance - 54972.271).abs() < 1e-3);
+        let (initial, last) = geodesic_bearing(&start, &end).unwrap();
+        assert!((initial - from_dms(306.0, 52.0, 5.37)).abs() < 1e-6);
+        assert!((last - from_dms(307.0, 10.0, 25.07)).abs() < 1e-6);
+
+        let antipode = Cartographic::from_degrees(180.0, 0.0, 0.0);
+        assert!(geodesic_distance(&Cartographic::default(), &antipode).is_none());
+    }
+    #[test]
+    fn test_polyline_length() {
+        let positions = [
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(1.0, 0.0, 0.0),
+            Cartographic::from_degrees(2.0, 0.0, 0.0),
+        ];
+        let expected = Ellipsoid::WGS84.maximum_radius * 2.0_f64.to_radians();
+        assert!((polyline_length(&positions).unwrap() - expected).abs() < 1e-6);
+        assert!((polygon_perimeter(&positions).unwrap() - expected * 2.0).abs() < 1e-6);
+        assert_eq!(polyline_length(&positions[..1]), Some(0.0));
+    }
+    #[test]
+    fn test_rhumb_distance() {
+        // 沿赤道
+        let start = Cartographic::from_degrees(-1.0, 0.0, 0.0);
+        let end = Cartographic::from_degrees(1.0, 0.0, 0.0);
+        let expected = Ellipsoid::WGS84.maximum_radius * 2.0_f64.to_radians();
+        assert!((rhumb_distance(&start, &end) - expected).abs() < 1e-6);
+        // 赤道到北极的子午线弧长
+        let start = Cartographic::from_degrees(10.0, 0.0, 0.0);
+        let end = Cartographic::from_degrees(10.0, 90.0, 0.0);
+        assert!((rhumb_distance(&start, &end) - 10001965.729).abs() < 1e-3);
+        // 恒向线比测地线长
+        let start = Cartographic::from_degrees(-70.0, 40.0, 0.0);
+        let end = Cartographic::from_degrees(0.0, 50.0, 0.0);
+        assert!(rhumb_distance(&start, &end) > geodesic_distance(&start, &end).unwrap());
+    }
+    #[test]
+    fn test_polygon_area() {
+        // WGS84椭球的表面积为510065621.7平方千米
+        let total = 510065621.7e6;
+        let octant = [
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(90.0, 0.0, 0.0),
+            Cartographic::from_degrees(0.0, 90.0, 0.0),
+        ];
+        assert!((geodesic_polygon_area(&octant) / (total / 8.0) - 1.0).abs() < 1e-9);
+        let hemisphere = [
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(90.0, 0.0, 0.0),
+            Cartographic::from_degrees(180.0, 0.0, 0.0),
+            Cartographic::from_degrees(-90.0, 0.0, 0.0),
+        ];
+        assert!((geodesic_polygon_area(&hemisphere) / (total / 2.0) - 1.0).abs() < 1e-9);
+        // 顶点顺序不影响结果
+        let mut reversed = octant.to_vec();
+        reversed.reverse();
+        assert!((geodesic_polygon_area(&reversed) - geodesic_polygon_area(&octant)).abs() < 1.0);
+        assert_eq!(geodesic_polygon_area(&octant[..2]), 0.0);
+    }
+}
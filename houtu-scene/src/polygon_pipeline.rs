@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+
+use bevy::math::{DVec2, DVec3};
+
+use crate::{
+    ellipsoid::Ellipsoid, Cartographic, EllipsoidTangentPlane, EPSILON10, RADIANS_PER_DEGREE,
+};
+
+/// 带洞的多边形，洞中还可以有岛，对应Cesium的PolygonHierarchy
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolygonHierarchy {
+    pub positions: Vec<Cartographic>,
+    pub holes: Vec<PolygonHierarchy>,
+}
+impl PolygonHierarchy {
+    pub fn new(positions: Vec<Cartographic>) -> Self {
+        Self {
+            positions,
+            holes: vec![],
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolygonGeometryOptions {
+    /// 多边形所在的高度，per_position_height为true时忽略
+    pub height: f64,
+    /// 拉伸到的高度，为None时只生成一个面
+    pub extruded_height: Option<f64>,
+    /// 使用每个顶点自身的高度
+    pub per_position_height: bool,
+    /// 细分后相邻两点的最大圆心角(弧度)
+    pub granularity: f64,
+    pub ellipsoid: Ellipsoid,
+}
+impl Default for PolygonGeometryOptions {
+    fn default() -> Self {
+        Self {
+            height: 0.0,
+            extruded_height: None,
+            per_position_height: false,
+            granularity: RADIANS_PER_DEGREE,
+            ellipsoid: Ellipsoid::WGS84,
+        }
+    }
+}
+#[derive(Debug, Clone, Default)]
+pub struct PolygonGeometry {
+    pub positions: Vec<Cartographic>,
+    pub normals: Vec<DVec3>,
+    pub indices: Vec<u32>,
+    /// 轮廓线，环是首尾相接的
+    pub outlines: Vec<Vec<Cartographic>>,
+}
+/// 加密后的环，positions在椭球面上
+#[derive(Debug, Clone, Default)]
+struct Ring {
+    positions: Vec<DVec3>,
+    heights: Vec<f64>,
+    /// 是否是原始的顶点，拉伸时在这些点上画竖直的轮廓线
+    original: Vec<bool>,
+}
+impl Ring {
+    fn reverse(&mut self) {
+        self.positions.reverse();
+        self.heights.reverse();
+        self.original.reverse();
+    }
+}
+
+pub struct PolygonPipeline;
+impl PolygonPipeline {
+    /// 有向面积，逆时针为正
+    pub fn compute_area_2d(positions: &[DVec2]) -> f64 {
+        let n = positions.len();
+        let mut area = 0.0;
+        for i in 0..n {
+            let v0 = positions[i];
+            let v1 = positions[(i + 1) % n];
+            area += v0.x * v1.y - v1.x * v0.y;
+        }
+        return area * 0.5;
+    }
+    /// 用earcut三角化，holes为每个洞的起始下标，返回的三角形都是逆时针的
+    pub fn triangulate(positions: &[DVec2], holes: &[usize]) -> Vec<u32> {
+        let data: Vec<f64> = positions.iter().flat_map(|v| [v.x, v.y]).collect();
+        let Ok(triangles) = earcutr::earcut(&data, holes, 2) else {
+            return vec![];
+        };
+        let mut result: Vec<u32> = Vec::with_capacity(triangles.len());
+        for triangle in triangles.chunks_exact(3) {
+            let (i0, mut i1, mut i2) = (triangle[0], triangle[1], triangle[2]);
+            let v0 = positions[i0];
+            if (positions[i1] - v0).perp_dot(positions[i2] - v0) < 0.0 {
+                std::mem::swap(&mut i1, &mut i2);
+            }
+            result.extend_from_slice(&[i0 as u32, i1 as u32, i2 as u32]);
+        }
+        return result;
+    }
+    /// 细分三角形，直到每条边对应的圆心角不超过granularity，结果都在椭球面上，新点的高度取两端的平均值
+    ///
+    /// 共享的边用同一个中点，不会产生裂缝
+    pub fn compute_subdivision(
+        ellipsoid: &Ellipsoid,
+        positions: &[DVec3],
+        heights: &[f64],
+        indices: &[u32],
+        granularity: f64,
+    ) -> (Vec<DVec3>, Vec<f64>, Vec<u32>) {
+        let granularity = granularity.max(RADIANS_PER_DEGREE / 3600.0);
+        let mut positions = positions.to_vec();
+        let mut heights = heights.to_vec();
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut stack: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|v| [v[0], v[1], v[2]])
+            .collect();
+        let mut result: Vec<u32> = Vec::with_capacity(indices.len());
+        while let Some(triangle) = stack.pop() {
+            let angle = |i: usize| -> f64 {
+                let v0 = positions[triangle[i] as usize];
+                let v1 = positions[triangle[(i + 1) % 3] as usize];
+                return v0.angle_between(v1);
+            };
+            let (longest, max) =
+                (0..3)
+                    .map(|i| (i, angle(i)))
+                    .fold((0, 0.0), |a, b| if b.1 > a.1 { b } else { a });
+            if max <= granularity {
+                result.extend_from_slice(&triangle);
+                continue;
+            }
+            let a = triangle[longest];
+            let b = triangle[(longest + 1) % 3];
+            let c = triangle[(longest + 2) % 3];
+            let middle = *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let (a, b) = (a as usize, b as usize);
+                positions.push((positions[a] + positions[b]) * 0.5);
+                heights.push((heights[a] + heights[b]) * 0.5);
+                return (positions.len() - 1) as u32;
+            });
+            stack.push([a, middle, c]);
+            stack.push([middle, b, c]);
+        }
+        for position in positions.iter_mut() {
+            if let Some(v) = ellipsoid.scale_to_geodetic_surface(position) {
+                *position = v;
+            }
+        }
+        return (positions, heights, result);
+    }
+    /// 生成多边形的三角网，外环和洞在EllipsoidTangentPlane上三角化后再细分贴合椭球面
+    pub fn create_geometry(
+        hierarchy: &PolygonHierarchy,
+        options: &PolygonGeometryOptions,
+    ) -> Option<PolygonGeometry> {
+        let mut polygons: Vec<(Vec<Cartographic>, Vec<Vec<Cartographic>>)> = vec![];
+        flatten_hierarchy(hierarchy, &mut polygons);
+        let mut geometry = PolygonGeometry::default();
+        for (outer, holes) in polygons.iter() {
+            add_polygon(&mut geometry, outer, holes, options);
+        }
+        if geometry.indices.is_empty() {
+            return None;
+        }
+        return Some(geometry);
+    }
+}
+/// 去掉重复的点和闭合的尾点
+fn clean_ring(positions: &[Cartographic]) -> Vec<Cartographic> {
+    let mut result: Vec<Cartographic> = Vec::with_capacity(positions.len());
+    for position in positions.iter() {
+        if result
+            .last()
+            .map_or(false, |v| v.equals_epsilon(*position, EPSILON10))
+        {
+            continue;
+        }
+        result.push(*position);
+    }
+    while result.len() > 1 && result[0].equals_epsilon(*result.last().unwrap(), EPSILON10) {
+        result.pop();
+    }
+    return result;
+}
+/// 洞中的岛作为单独的多边形
+fn flatten_hierarchy(
+    hierarchy: &PolygonHierarchy,
+    result: &mut Vec<(Vec<Cartographic>, Vec<Vec<Cartographic>>)>,
+) {
+    let outer = clean_ring(&hierarchy.positions);
+    if outer.len() < 3 {
+        return;
+    }
+    let mut holes: Vec<Vec<Cartographic>> = vec![];
+    for hole in hierarchy.holes.iter() {
+        let ring = clean_ring(&hole.positions);
+        if ring.len() >= 3 {
+            holes.push(ring);
+        }
+        for island in hole.holes.iter() {
+            flatten_hierarchy(island, result);
+        }
+    }
+    result.push((outer, holes));
+}
+/// 沿弦插值后投影到椭球面，每段的圆心角略小于granularity，细分时不会再切分边界
+fn densify_ring(ellipsoid: &Ellipsoid, positions: &[Cartographic], granularity: f64) -> Ring {
+    let granularity = granularity.max(RADIANS_PER_DEGREE / 3600.0) * 0.99;
+    let surface: Vec<DVec3> = positions
+        .iter()
+        .map(|v| {
+            ellipsoid.cartographic_to_cartesian(&Cartographic::new(v.longitude, v.latitude, 0.0))
+        })
+        .collect();
+    let mut ring = Ring::default();
+    let n = positions.len();
+    for i in 0..n {
+        let (a, b) = (surface[i], surface[(i + 1) % n]);
+        let (h0, h1) = (positions[i].height, positions[(i + 1) % n].height);
+        ring.positions.push(a);
+        ring.heights.push(h0);
+        ring.original.push(true);
+        let count = (a.angle_between(b) / granularity).ceil() as usize;
+        for j in 1..count {
+            let t = j as f64 / count as f64;
+            let position = a.lerp(b, t);
+            ring.positions.push(
+                ellipsoid
+                    .scale_to_geodetic_surface(&position)
+                    .unwrap_or(position),
+            );
+            ring.heights.push(h0 + (h1 - h0) * t);
+            ring.original.push(false);
+        }
+    }
+    return ring;
+}
+fn add_polygon(
+    geometry: &mut PolygonGeometry,
+    outer: &[Cartographic],
+    holes: &[Vec<Cartographic>],
+    options: &PolygonGeometryOptions,
+) {
+    let ellipsoid = options.ellipsoid;
+    let mut rings: Vec<Ring> = vec![];
+    let mut points: Vec<DVec2> = vec![];
+    let mut hole_indices: Vec<usize> = vec![];
+    let mut plane: Option<EllipsoidTangentPlane> = None;
+    for (i, ring) in std::iter::once(outer)
+        .chain(holes.iter().map(|v| v.as_slice()))
+        .enumerate()
+    {
+        let mut ring = densify_ring(&ellipsoid, ring, options.granularity);
+        let plane = plane.get_or_insert_with(|| {
+            EllipsoidTangentPlane::from_points(ring.positions.clone(), Some(&ellipsoid))
+        });
+        let mut projected = plane.project_points_to_nearest_on_plane(ring.positions.clone());
+        // 外环逆时针，洞顺时针
+        if (i == 0) == (PolygonPipeline::compute_area_2d(&projected) < 0.0) {
+            ring.reverse();
+            projected.reverse();
+        }
+        if i > 0 {
+            hole_indices.push(points.len());
+        }
+        points.extend(projected);
+        rings.push(ring);
+    }
+    let indices = PolygonPipeline::triangulate(&points, &hole_indices);
+    if indices.is_empty() {
+        return;
+    }
+    let ring_positions: Vec<DVec3> = rings.iter().flat_map(|v| v.positions.clone()).collect();
+    let ring_heights: Vec<f64> = rings.iter().flat_map(|v| v.heights.clone()).collect();
+    let (positions, heights, indices) = PolygonPipeline::compute_subdivision(
+        &ellipsoid,
+        &ring_positions,
+        &ring_heights,
+        &indices,
+        options.granularity,
+    );
+    let heights: Vec<f64> = if options.per_position_height {
+        heights
+    } else {
+        vec![options.height; positions.len()]
+    };
+    let to_cartographic = |position: &DVec3, height: f64| -> Cartographic {
+        let mut result = ellipsoid
+            .cartesian_to_cartographic(position)
+            .unwrap_or_default();
+        result.height = height;
+        return result;
+    };
+    let up = |position: &DVec3| -> DVec3 {
+        return ellipsoid
+            .geodetic_surface_normal(position)
+            .unwrap_or(DVec3::Z);
+    };
+    // 顶面或底面，朝下时翻转三角形
+    let add_cap = |geometry: &mut PolygonGeometry, heights: &[f64], facing_up: bool| {
+        let offset = geometry.positions.len() as u32;
+        let sign = if facing_up { 1.0 } else { -1.0 };
+        for (position, height) in positions.iter().zip(heights.iter()) {
+            geometry.positions.push(to_cartographic(position, *height));
+            geometry.normals.push(up(position) * sign);
+        }
+        for triangle in indices.chunks_exact(3) {
+            if facing_up {
+                geometry.indices.extend_from_slice(&[
+                    triangle[0] + offset,
+                    triangle[1] + offset,
+                    triangle[2] + offset,
+                ]);
+            } else {
+                geometry.indices.extend_from_slice(&[
+                    triangle[0] + offset,
+                    triangle[2] + offset,
+                    triangle[1] + offset,
+                ]);
+            }
+        }
+    };
+    let ring_outline = |start: usize, ring: &Ring, height: Option<f64>| -> Vec<Cartographic> {
+        let mut outline: Vec<Cartographic> = (0..ring.positions.len())
+            .map(|i| to_cartographic(&positions[start + i], height.unwrap_or(heights[start + i])))
+            .collect();
+        outline.push(outline[0]);
+        return outline;
+    };
+    let Some(extruded_height) = options.extruded_height else {
+        add_cap(geometry, &heights, true);
+        let mut start = 0;
+        for ring in rings.iter() {
+            geometry.outlines.push(ring_outline(start, ring, None));
+            start += ring.positions.len();
+        }
+        return;
+    };
+    let average_height = heights.iter().sum::<f64>() / heights.len() as f64;
+    let top_is_upper = average_height >= extruded_height;
+    add_cap(geometry, &heights, top_is_upper);
+    add_cap(
+        geometry,
+        &vec![extruded_height; positions.len()],
+        !top_is_upper,
+    );
+
+    // 侧面，法线朝外
+    let mut start = 0;
+    for ring in rings.iter() {
+        let n = ring.positions.len();
+        for i in 0..n {
+            let (a, b) = (start + i, start + (i + 1) % n);
+            let normal = (positions[b] - positions[a])
+                .cross(up(&positions[a]))
+                .normalize_or_zero();
+            let (a_lower, a_upper, b_lower, b_upper) = if top_is_upper {
+                (extruded_height, heights[a], extruded_height, heights[b])
+            } else {
+                (heights[a], extruded_height, heights[b], extruded_height)
+            };
+            let offset = geometry.positions.len() as u32;
+            geometry.positions.extend_from_slice(&[
+                to_cartographic(&positions[a], a_lower),
+                to_cartographic(&positions[b], b_lower),
+                to_cartographic(&positions[b], b_upper),
+                to_cartographic(&positions[a], a_upper),
+            ]);
+            geometry.normals.extend_from_slice(&[normal; 4]);
+            geometry.indices.extend_from_slice(&[
+                offset,
+                offset + 1,
+                offset + 2,
+                offset,
+                offset + 2,
+                offset + 3,
+            ]);
+        }
+        geometry.outlines.push(ring_outline(start, ring, None));
+        geometry
+            .outlines
+            .push(ring_outline(start, ring, Some(extruded_height)));
+        for i in (0..n).filter(|i| ring.original[*i]) {
+            let position = &positions[start + i];
+            geometry.outlines.push(vec![
+                to_cartographic(position, heights[start + i]),
+                to_cartographic(position, extruded_height),
+            ]);
+        }
+        start += n;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> Vec<DVec2> {
+        return vec![
+            DVec2::new(x, y),
+            DVec2::new(x + size, y),
+            DVec2::new(x + size, y + size),
+            DVec2::new(x, y + size),
+        ];
+    }
+    #[test]
+    fn test_triangulate_with_hole() {
+        let outer = square(0.0, 0.0, 10.0);
+        assert_eq!(PolygonPipeline::compute_area_2d(&outer), 100.0);
+        let mut hole = square(3.0, 3.0, 3.0);
+        hole.reverse();
+        let mut positions = outer.clone();
+        positions.extend(hole);
+        let indices = PolygonPipeline::triangulate(&positions, &[4]);
+        let mut area = 0.0;
+        for triangle in indices.chunks_exact(3) {
+            let triangle: Vec<DVec2> = triangle.iter().map(|v| positions[*v as usize]).collect();
+            let value = PolygonPipeline::compute_area_2d(&triangle);
+            assert!(value > 0.0);
+            area += value;
+        }
+        assert!((area - 91.0).abs() < 1e-9);
+    }
+    #[test]
+    fn test_create_geometry() {
+        let hierarchy = PolygonHierarchy::new(vec![
+            Cartographic::from_degrees(0.0, 0.0, 10.0),
+            Cartographic::from_degrees(2.0, 0.0, 20.0),
+            Cartographic::from_degrees(2.0, 2.0, 30.0),
+            Cartographic::from_degrees(0.0, 2.0, 40.0),
+            Cartographic::from_degrees(0.0, 0.0, 10.0),
+        ]);
+        let options = PolygonGeometryOptions {
+            height: 100.0,
+            granularity: RADIANS_PER_DEGREE * 0.5,
+            ..Default::default()
+        };
+        let geometry = PolygonPipeline::create_geometry(&hierarchy, &options).unwrap();
+        assert_eq!(geometry.positions.len(), geometry.normals.len());
+        let ellipsoid = Ellipsoid::WGS84;
+        for triangle in geometry.indices.chunks_exact(3) {
+            for i in 0..3 {
+                let mut v0 = geometry.positions[triangle[i] as usize];
+                let mut v1 = geometry.positions[triangle[(i + 1) % 3] as usize];
+                v0.height = 0.0;
+                v1.height = 0.0;
+                let angle = ellipsoid
+                    .cartographic_to_cartesian(&v0)
+                    .angle_between(ellipsoid.cartographic_to_cartesian(&v1));
+                assert!(angle <= options.granularity * 1.001);
+            }
+        }
+        assert!(geometry.positions.iter().all(|v| v.height == 100.0));
+        // 闭合的尾点被去掉，轮廓线重新闭合
+        assert_eq!(geometry.outlines.len(), 1);
+        assert_eq!(geometry.outlines[0].first(), geometry.outlines[0].last());
+
+        let options = PolygonGeometryOptions {
+            per_position_height: true,
+            ..options
+        };
+        let geometry = PolygonPipeline::create_geometry(&hierarchy, &options).unwrap();
+        let heights: Vec<f64> = geometry.positions.iter().map(|v| v.height).collect();
+        assert!(heights.iter().all(|v| *v >= 10.0 && *v <= 40.0));
+        assert!(heights.iter().any(|v| *v != 10.0));
+
+        assert!(PolygonPipeline::create_geometry(
+            &PolygonHierarchy::new(hierarchy.positions[..2].to_vec()),
+            &options
+        )
+        .is_none());
+    }
+    #[test]
+    fn test_extruded_with_hole() {
+        let mut hierarchy = PolygonHierarchy::new(vec![
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(0.0, 0.01, 0.0),
+            Cartographic::from_degrees(0.01, 0.01, 0.0),
+            Cartographic::from_degrees(0.01, 0.0, 0.0),
+        ]);
+        hierarchy.holes.push(PolygonHierarchy::new(vec![
+            Cartographic::from_degrees(0.003, 0.003, 0.0),
+            Cartographic::from_degrees(0.006, 0.003, 0.0),
+            Cartographic::from_degrees(0.006, 0.006, 0.0),
+            Cartographic::from_degrees(0.003, 0.006, 0.0),
+        ]));
+        let options = PolygonGeometryOptions {
+            height: 50.0,
+            extruded_height: Some(10.0),
+            ..Default::default()
+        };
+        let geometry = PolygonPipeline::create_geometry(&hierarchy, &options).unwrap();
+        // 两个面各8个点，8条边的侧面各4个点
+        assert_eq!(geometry.positions.len(), 8 * 2 + 8 * 4);
+        assert_eq!(geometry.indices.len(), 8 * 3 * 2 + 8 * 6);
+        // 每个环的顶部、底部和4条竖线
+        assert_eq!(geometry.outlines.len(), 2 * (2 + 4));
+        // 顶面朝上，底面朝下，侧面水平
+        let ellipsoid = Ellipsoid::WGS84;
+        for (position, normal) in geometry.positions.iter().zip(geometry.normals.iter()) {
+            let up = ellipsoid.geodetic_surface_normal_cartographic(position);
+            let dot = up.dot(*normal);
+            assert!((dot.abs() - 1.0).abs() < 1e-9 || dot.abs() < 1e-3);
+            if (dot - 1.0).abs() < 1e-9 {
+                assert_eq!(position.height, 50.0);
+            }
+        }
+        // 外环南边和洞北边的侧面朝南
+        let south = geometry.normals.iter().filter(|v| v.z < -0.999).count();
+        assert_eq!(south, 8);
+    }
+}
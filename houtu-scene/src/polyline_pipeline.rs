@@ -0,0 +1,174 @@
+use std::f64::consts::PI;
+
+use bevy::math::DVec3;
+
+use crate::{
+    ellipsoid::Ellipsoid, Cartographic, EllipsoidGeodesic, EllipsoidRhumbLine, RADIANS_PER_DEGREE,
+};
+
+/// 两点之间的连线方式，对应Cesium的ArcType
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArcType {
+    /// 笛卡尔空间中的直线，不贴合椭球面
+    None,
+    /// 测地线
+    #[default]
+    Geodesic,
+    /// 恒向线
+    Rhumb,
+}
+pub struct PolylinePipeline;
+impl PolylinePipeline {
+    /// 沿给定的连线方式加密，相邻两点对应的圆心角不超过granularity(弧度)，高度线性插值
+    pub fn generate_arc(
+        positions: &[Cartographic],
+        arc_type: ArcType,
+        granularity: f64,
+        ellipsoid: Option<Ellipsoid>,
+    ) -> Vec<DVec3> {
+        let ellipsoid = ellipsoid.unwrap_or(Ellipsoid::WGS84);
+        return Self::generate_cartographic_arc(positions, arc_type, granularity, Some(ellipsoid))
+            .iter()
+            .map(|v| ellipsoid.cartographic_to_cartesian(v))
+            .collect();
+    }
+    /// 与generate_arc相同，返回经纬度，贴地时用来逐点采样地形高度
+    pub fn generate_cartographic_arc(
+        positions: &[Cartographic],
+        arc_type: ArcType,
+        granularity: f64,
+        ellipsoid: Option<Ellipsoid>,
+    ) -> Vec<Cartographic> {
+        let ellipsoid = ellipsoid.unwrap_or(Ellipsoid::WGS84);
+        let mut result: Vec<Cartographic> = vec![];
+        let Some(first) = positions.first() else {
+            return result;
+        };
+        result.push(*first);
+        for pair in positions.windows(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            if start.equals_epsilon(*end, 0.0) {
+                continue;
+            }
+            result.extend(Self::subdivide(
+                &ellipsoid,
+                start,
+                end,
+                arc_type,
+                granularity,
+            ));
+        }
+        return result;
+    }
+    /// 两点之间加密后的点，不包含起点
+    fn subdivide(
+        ellipsoid: &Ellipsoid,
+        start: &Cartographic,
+        end: &Cartographic,
+        arc_type: ArcType,
+        granularity: f64,
+    ) -> Vec<Cartographic> {
+        let count = |distance: f64| -> usize {
+            let granularity = granularity.max(RADIANS_PER_DEGREE / 3600.0);
+            return ((distance / (granularity * ellipsoid.maximum_radius)).ceil() as usize).max(1);
+        };
+        let mut result: Vec<Cartographic> = match arc_type {
+            ArcType::None => vec![],
+            ArcType::Geodesic => {
+                // 近似对跖的两点EllipsoidGeodesic无法计算，退化为直线
+                let first = ellipsoid.cartographic_to_cartesian(start).normalize();
+                let last = ellipsoid.cartographic_to_cartesian(end).normalize();
+                if (first.angle_between(last).abs() - PI).abs() < 0.0125 {
+                    vec![]
+                } else {
+                    let mut geodesic = EllipsoidGeodesic::default();
+                    geodesic.setEndPoints(*start, *end);
+                    let n = count(geodesic.surface_distance());
+                    (1..n)
+                        .map(|i| geodesic.interpolateUsingFraction(i as f64 / n as f64))
+                        .collect()
+                }
+            }
+            ArcType::Rhumb => {
+                let rhumb_line = EllipsoidRhumbLine::new(*start, *end, Some(*ellipsoid));
+                let n = count(rhumb_line.surface_distance());
+                (1..n)
+                    .map(|i| rhumb_line.interpolate_using_fraction(i as f64 / n as f64))
+                    .collect()
+            }
+        };
+        let n = result.len() + 1;
+        for (i, position) in result.iter_mut().enumerate() {
+            let fraction = (i + 1) as f64 / n as f64;
+            position.height = start.height + (end.height - start.height) * fraction;
+        }
+        result.push(*end);
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_arc() {
+        let positions = [
+            Cartographic::from_degrees(0.0, 0.0, 0.0),
+            Cartographic::from_degrees(10.0, 0.0, 100.0),
+        ];
+        let ellipsoid = Ellipsoid::WGS84;
+        let straight = PolylinePipeline::generate_arc(&positions, ArcType::None, 0.0, None);
+        assert_eq!(straight.len(), 2);
+
+        // 每段略大于1度，10度分为10段
+        let granularity = RADIANS_PER_DEGREE * 1.01;
+        let arc = PolylinePipeline::generate_arc(&positions, ArcType::Geodesic, granularity, None);
+        assert_eq!(arc.len(), 11);
+        assert!(arc[0].distance(ellipsoid.cartographic_to_cartesian(&positions[0])) < 1e-6);
+        assert!(arc[10].distance(ellipsoid.cartographic_to_cartesian(&positions[1])) < 1e-6);
+        // 沿赤道，高度线性插值
+        let middle = ellipsoid.cartesian_to_cartographic(&arc[5]).unwrap();
+        assert!((middle.longitude - 5.0 * RADIANS_PER_DEGREE).abs() < 1e-8);
+        assert!(middle.latitude.abs() < 1e-9);
+        assert!((middle.height - 50.0).abs() < 1e-6);
+
+        let rhumb = PolylinePipeline::generate_arc(&positions, ArcType::Rhumb, granularity, None);
+        assert_eq!(rhumb.len(), 11);
+
+        let cartographic_arc = PolylinePipeline::generate_cartographic_arc(
+            &positions,
+            ArcType::Geodesic,
+            granularity,
+            None,
+        );
+        assert_eq!(cartographic_arc.len(), 11);
+        assert!((cartographic_arc[5].height - 50.0).abs() < 1e-6);
+    }
+    #[test]
+    fn test_rhumb_keeps_latitude() {
+        let positions = [
+            Cartographic::from_degrees(-30.0, 45.0, 0.0),
+            Cartographic::from_degrees(30.0, 45.0, 0.0),
+        ];
+        let rhumb =
+            PolylinePipeline::generate_arc(&positions, ArcType::Rhumb, RADIANS_PER_DEGREE, None);
+        let geodesic =
+            PolylinePipeline::generate_arc(&positions, ArcType::Geodesic, RADIANS_PER_DEGREE, None);
+        let ellipsoid = Ellipsoid::WGS84;
+        let middle = rhumb.len() / 2;
+        let rhumb_middle = ellipsoid.cartesian_to_cartographic(&rhumb[middle]).unwrap();
+        let geodesic_middle = ellipsoid
+            .cartesian_to_cartographic(&geodesic[geodesic.len() / 2])
+            .unwrap();
+        assert!((rhumb_middle.latitude - 45.0 * RADIANS_PER_DEGREE).abs() < 1e-9);
+        // 测地线向极点方向弯曲
+        assert!(geodesic_middle.latitude > 45.0 * RADIANS_PER_DEGREE);
+        // 重复的点被跳过
+        let repeated = [positions[0], positions[0], positions[1]];
+        assert_eq!(
+            PolylinePipeline::generate_arc(&repeated, ArcType::None, 0.0, None).len(),
+            2
+        );
+    }
+}
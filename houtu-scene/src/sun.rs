@@ -0,0 +1,72 @@
+use std::f64::consts::TAU;
+
+use bevy::math::DVec3;
+
+/// 1970-01-01T00:00:00Z的儒略日
+const UNIX_EPOCH_JULIAN_DATE: f64 = 2440587.5;
+/// J2000.0历元的儒略日
+const J2000_JULIAN_DATE: f64 = 2451545.0;
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// 计算某一时刻太阳在地固坐标系中的方向(单位向量)。
+///
+/// `unix_seconds`是自1970-01-01T00:00:00Z以来的秒数(UTC)。
+/// 使用天文年历的低精度太阳位置公式，在1950到2050年之间误差约0.01度，足够用于地形光照。
+pub fn compute_sun_direction_in_fixed_frame(unix_seconds: f64) -> DVec3 {
+    let (longitude, latitude) = compute_subsolar_point(unix_seconds);
+    let cos_latitude = latitude.cos();
+    return DVec3::new(
+        cos_latitude * longitude.cos(),
+        cos_latitude * longitude.sin(),
+        latitude.sin(),
+    );
+}
+/// 太阳直射点的经纬度(弧度)，经度范围是`[-PI,PI]`
+pub fn compute_subsolar_point(unix_seconds: f64) -> (f64, f64) {
+    let days = unix_seconds / SECONDS_PER_DAY + UNIX_EPOCH_JULIAN_DATE - J2000_JULIAN_DATE;
+    // 平近点角和平黄经
+    let mean_anomaly = (357.529 + 0.98560028 * days).to_radians();
+    let mean_longitude = 280.459 + 0.98564736 * days;
+    // 黄经和黄赤交角
+    let ecliptic_longitude = (mean_longitude
+        + 1.915 * mean_anomaly.sin()
+        + 0.020 * (2.0 * mean_anomaly).sin())
+    .to_radians();
+    let obliquity = (23.439 - 0.00000036 * days).to_radians();
+    let right_ascension = (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+    // 格林尼治平恒星时
+    let gmst = ((280.46061837 + 360.98564736629 * days) % 360.0).to_radians();
+    let mut longitude = (right_ascension - gmst) % TAU;
+    if longitude > TAU / 2.0 {
+        longitude -= TAU;
+    } else if longitude < -TAU / 2.0 {
+        longitude += TAU;
+    }
+    return (longitude, declination);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const EPSILON: f64 = 0.1;
+
+    #[test]
+    fn test_equinox_and_solstice() {
+        // 2020-03-20T03:50:00Z 春分
+        let (_, latitude) = compute_subsolar_point(1584676200.0);
+        assert!(latitude.to_degrees().abs() < EPSILON);
+        // 2021-06-21T03:32:00Z 夏至
+        let (_, latitude) = compute_subsolar_point(1624246320.0);
+        assert!((latitude.to_degrees() - 23.44).abs() < EPSILON);
+    }
+    #[test]
+    fn test_noon_at_greenwich() {
+        // 2020-03-20T12:00:00Z，均时差约-7.5分钟，太阳直射点在本初子午线以东约1.9度
+        let (longitude, _) = compute_subsolar_point(1584705600.0);
+        assert!((longitude.to_degrees() - 1.9).abs() < 0.5);
+        let direction = compute_sun_direction_in_fixed_frame(1584705600.0);
+        assert!((direction.length() - 1.0).abs() < 1e-12);
+        assert!(direction.x > 0.99);
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::math::{DMat4, DVec2, DVec3};
 
 use crate::{
-    compress_texture_coordinates,
+    compress_texture_coordinates, decompress_texture_coordinates,
     geometry::AxisAlignedBoundingBox,
     math::{Cartesian3, Matrix4, SHIFT_LEFT_12},
     oct_pack_float,
@@ -225,15 +225,41 @@ impl TerrainEncoding {
 
         return new_buffer_index as i64;
     }
+    pub fn decode_position(&self, buffer: &[f32], index: usize) -> DVec3 {
+        let index = index * self.stride as usize;
+        if self.quantization == TerrainQuantization::BITS12 {
+            let xy = decompress_texture_coordinates(buffer[index] as f64);
+            let zh = decompress_texture_coordinates(buffer[index + 1] as f64);
+            return self
+                .from_scaled_enu
+                .multiply_by_point(&DVec3::new(xy.x, xy.y, zh.x));
+        }
+        return DVec3::new(
+            buffer[index] as f64,
+            buffer[index + 1] as f64,
+            buffer[index + 2] as f64,
+        ) + self.center;
+    }
+    pub fn decode_texture_coordinates(&self, buffer: &[f32], index: usize) -> DVec2 {
+        let index = index * self.stride as usize;
+        if self.quantization == TerrainQuantization::BITS12 {
+            return decompress_texture_coordinates(buffer[index + 2] as f64);
+        }
+        return DVec2::new(buffer[index + 4] as f64, buffer[index + 5] as f64);
+    }
     pub fn decode_height(&self, buffer: &Vec<f32>, index: usize) -> f64 {
         let index = index * self.stride as usize;
+        if self.quantization == TerrainQuantization::BITS12 {
+            let zh = decompress_texture_coordinates(buffer[index + 1] as f64);
+            return zh.y * (self.maximum_height - self.minimum_height) + self.minimum_height;
+        }
         return buffer[index + 3] as f64;
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{decompress_texture_coordinates, Cartesian2};
+    use crate::{decompress_texture_coordinates, Cartesian2, EPSILON3};
 
     use super::*;
 
@@ -265,4 +291,74 @@ mod tests {
         let decompressed = decompress_texture_coordinates(compressed);
         assert!(decompressed.equals_epsilon(coords, Some(1.0 / 4095.0), None));
     }
+    #[test]
+    fn test_decode_none() {
+        let center = DVec3::new(6378137.0, 0.0, 0.0);
+        let encoding = TerrainEncoding::new(
+            center, None, None, None, None, false, None, None, None, None,
+        );
+        assert!(encoding.quantization == TerrainQuantization::NONE);
+        let mut buffer = vec![0.0; encoding.stride as usize * 2];
+        let position = DVec3::new(6378237.0, 100.0, -200.0);
+        let index = encoding.encode(
+            &mut buffer,
+            encoding.stride as i64,
+            &mut position.clone(),
+            &DVec2::new(0.25, 0.75),
+            100.0,
+            None,
+            None,
+            None,
+        );
+        assert!(index == encoding.stride as i64 * 2);
+        assert!(encoding.decode_position(&buffer, 1).equals_epsilon(
+            position,
+            None,
+            Some(EPSILON3)
+        ));
+        assert!(encoding.decode_texture_coordinates(&buffer, 1) == DVec2::new(0.25, 0.75));
+        assert!(encoding.decode_height(&buffer, 1) == 100.0);
+    }
+    #[test]
+    fn test_decode_bits12() {
+        let center = DVec3::new(6378137.0, 0.0, 0.0);
+        let from_enu = crate::eastNorthUpToFixedFrame(&center, None);
+        let aabb = AxisAlignedBoundingBox::from_corners(
+            DVec3::new(-1000.0, -1000.0, -100.0),
+            DVec3::new(1000.0, 1000.0, 100.0),
+        );
+        let encoding = TerrainEncoding::new(
+            center,
+            Some(aabb),
+            Some(0.0),
+            Some(500.0),
+            Some(from_enu),
+            false,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(encoding.quantization == TerrainQuantization::BITS12);
+        let mut buffer = vec![0.0; encoding.stride as usize];
+        let position = from_enu.multiply_by_point(&DVec3::new(200.0, -300.0, 50.0));
+        encoding.encode(
+            &mut buffer,
+            0,
+            &mut position.clone(),
+            &DVec2::new(0.25, 0.75),
+            123.0,
+            None,
+            None,
+            None,
+        );
+        // 12位量化，误差在包围盒尺寸的1/4095以内
+        assert!(encoding
+            .decode_position(&buffer, 0)
+            .equals_epsilon(position, None, Some(1.0)));
+        assert!(encoding
+            .decode_texture_coordinates(&buffer, 0)
+            .equals_epsilon(DVec2::new(0.25, 0.75), None, Some(1.0 / 4095.0)));
+        assert!((encoding.decode_height(&buffer, 0) - 123.0).abs() < 500.0 / 4095.0);
+    }
 }
@@ -0,0 +1,37 @@
+struct PolygonUniform {
+    model_view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    color: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> material: PolygonUniform;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) position_ec: vec3<f32>,
+    @location(1) normal_ec: vec3<f32>,
+};
+
+@vertex
+fn vertex(in: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let position_ec = material.model_view * vec4<f32>(in.position, 1.0);
+    out.position = material.projection * position_ec;
+    out.position_ec = position_ec.xyz;
+    out.normal_ec = (material.model_view * vec4<f32>(in.normal, 0.0)).xyz;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // 以相机为光源的简单漫反射，让拉伸后的侧面和顶面区分开
+    let normal = normalize(in.normal_ec);
+    let to_eye = normalize(-in.position_ec);
+    let diffuse = 0.6 + 0.4 * abs(dot(normal, to_eye));
+    return vec4<f32>(material.color.rgb * diffuse, material.color.a);
+}
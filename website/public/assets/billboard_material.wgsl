@@ -0,0 +1,80 @@
+struct BillboardUniform {
+    model_view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    viewport: vec2<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> material: BillboardUniform;
+@group(1) @binding(1)
+var image_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var image_sampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) corner_and_offset: vec4<f32>,
+    @location(2) size_and_outline: vec4<f32>,
+    @location(3) color: vec4<f32>,
+    @location(4) outline_color: vec4<f32>,
+    @location(5) scale_by_distance: vec4<f32>,
+};
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) outline_color: vec4<f32>,
+    // x为缩放后的直径，y为缩放后的轮廓线宽度，z为1时是点
+    @location(3) point: vec3<f32>,
+};
+
+// 与NearFarScalar.evaluate相同
+fn near_far_scale(distance: f32, scalar: vec4<f32>) -> f32 {
+    if (scalar.z <= scalar.x) {
+        return scalar.y;
+    }
+    let t = clamp((distance - scalar.x) / (scalar.z - scalar.x), 0.0, 1.0);
+    return mix(scalar.y, scalar.w, t);
+}
+
+@vertex
+fn vertex(in: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let position_ec = material.model_view * vec4<f32>(in.position, 1.0);
+    let clip = material.projection * position_ec;
+    let scale = near_far_scale(length(position_ec.xyz), in.scale_by_distance);
+    let size = in.size_and_outline.xy * scale;
+    let corner = in.corner_and_offset.xy;
+    // 像素偏移的y向下
+    let pixel = corner * size + vec2<f32>(in.corner_and_offset.z, -in.corner_and_offset.w);
+    let offset = pixel * 2.0 / material.viewport;
+
+    out.position = vec4<f32>(clip.xy + offset * clip.w, clip.zw);
+    out.uv = vec2<f32>(corner.x + 0.5, 0.5 - corner.y);
+    out.color = in.color;
+    out.outline_color = in.outline_color;
+    out.point = vec3<f32>(size.x, in.size_and_outline.z * scale, in.size_and_outline.w);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // 采样需要在统一的控制流中
+    let texel = textureSample(image_texture, image_sampler, in.uv);
+    if (in.point.z < 0.5) {
+        let color = texel * in.color;
+        if (color.a < 0.005) {
+            discard;
+        }
+        return color;
+    }
+    let radius = in.point.x * 0.5;
+    let distance = length(in.uv - vec2<f32>(0.5, 0.5)) * in.point.x;
+    if (distance > radius) {
+        discard;
+    }
+    if (distance > radius - in.point.y) {
+        return in.outline_color;
+    }
+    return in.color;
+}
@@ -1,13 +1,101 @@
-struct LineMaterial {
+struct PolylineUniform {
+    model_view: mat4x4<f32>,
+    projection: mat4x4<f32>,
     color: vec4<f32>,
+    viewport: vec2<f32>,
+    width: f32,
+    dash_length: f32,
+    dash_pattern: u32,
+    meters_per_pixel: f32,
 };
 
 @group(1) @binding(0)
-var<uniform> material: LineMaterial;
+var<uniform> material: PolylineUniform;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) other_position: vec3<f32>,
+    @location(2) expand_and_distance: vec3<f32>,
+};
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) distance: f32,
+};
+
+struct ClippedSegment {
+    start: vec4<f32>,
+    end: vec4<f32>,
+    culled: bool,
+};
+
+// 按近裁剪面裁剪眼坐标系中的线段，对应Cesium的clipLineSegmentToNearPlane，
+// 在近裁剪面后面的一端移动到近裁剪面上，两端都在后面时剔除
+fn clip_segment_to_near(start: vec4<f32>, end: vec4<f32>, near: f32) -> ClippedSegment {
+    var out: ClippedSegment;
+    out.start = start;
+    out.end = end;
+    out.culled = false;
+    let start_behind = start.z > -near;
+    let end_behind = end.z > -near;
+    if (start_behind && end_behind) {
+        out.culled = true;
+        return out;
+    }
+    if (start_behind || end_behind) {
+        let t = (-near - start.z) / (end.z - start.z);
+        let point = mix(start, end, t);
+        if (start_behind) {
+            out.start = point;
+        } else {
+            out.end = point;
+        }
+    }
+    return out;
+}
+
+@vertex
+fn vertex(in: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.distance = in.expand_and_distance.z;
+    // 反向Z的无穷远透视投影中projection[3][2]就是近裁剪面距离
+    let near = material.projection[3][2];
+    let segment = clip_segment_to_near(
+        material.model_view * vec4<f32>(in.position, 1.0),
+        material.model_view * vec4<f32>(in.other_position, 1.0),
+        near
+    );
+    if (segment.culled) {
+        // 退化成裁剪空间外的一个点，不产生片元
+        out.position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+        return out;
+    }
+    // 裁剪后w都大于0，透视除法不会翻转方向
+    let clip = material.projection * segment.start;
+    let other_clip = material.projection * segment.end;
+
+    let half_viewport = material.viewport * 0.5;
+    let screen = clip.xy / clip.w * half_viewport;
+    let other_screen = other_clip.xy / other_clip.w * half_viewport;
+    var direction = (other_screen - screen) * in.expand_and_distance.y;
+    if (length(direction) < 1e-6) {
+        direction = vec2<f32>(1.0, 0.0);
+    }
+    direction = normalize(direction);
+    let normal = vec2<f32>(-direction.y, direction.x);
+    let offset = normal * in.expand_and_distance.x * material.width * 0.5 / half_viewport;
+
+    out.position = vec4<f32>(clip.xy + offset * clip.w, clip.zw);
+    return out;
+}
 
 @fragment
-fn fragment(
-    #import bevy_pbr::mesh_vertex_output
-) -> @location(0) vec4<f32> {
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (material.dash_length > 0.0) {
+        let pixels = in.distance / material.meters_per_pixel;
+        let index = u32(floor(fract(pixels / material.dash_length) * 16.0));
+        if (((material.dash_pattern >> index) & 1u) == 0u) {
+            discard;
+        }
+    }
     return material.color;
 }